@@ -61,6 +61,11 @@ collect() {
         collect_logs tedge-mapper-collectd
     fi
 
+    # Sparkplug mapper log
+    if systemctl list-unit-files tedge-mapper-sparkplug.service  >/dev/null 2>&1; then
+        collect_logs tedge-mapper-sparkplug
+    fi
+
     # Copy tedge.toml
     cp "$TEDGE_CONFIG_DIR"/tedge.toml "$OUTPUT_DIR"/tedge.toml
 
@@ -21,6 +21,7 @@ use cryptoki::RemoteKeyPair;
 
 pub mod device_id;
 pub mod parse_root_certificate;
+pub mod verify;
 
 pub struct PemCertificate {
     pem: x509_parser::pem::Pem,
@@ -396,6 +397,9 @@ pub enum CertificateError {
     #[error("HTTP Connection Problem: {msg} \nHint: {hint}")]
     CertificateValidationFailure { hint: String, msg: String },
 
+    #[error("Invalid device certificate: {msg} \nHint: {hint}")]
+    InvalidClientCertificate { hint: String, msg: String },
+
     #[error("Failed to add the certificate to root store")]
     RootStoreAdd,
 
@@ -151,7 +151,7 @@ pub fn add_certs_from_directory(
     Ok(())
 }
 
-fn new_root_store(cert_path: &Path) -> Result<RootCertStore, CertificateError> {
+pub(crate) fn new_root_store(cert_path: &Path) -> Result<RootCertStore, CertificateError> {
     let mut root_store = RootCertStore::empty();
     rec_add_root_cert(&mut root_store, cert_path);
     Ok(root_store)
@@ -0,0 +1,254 @@
+//! Verification of the device certificates used for TLS client authentication.
+//!
+//! The checks are those a cloud endpoint applies when the device connects,
+//! so misprovisioned devices can be detected before a connection attempt fails with an opaque TLS error.
+
+use crate::parse_root_certificate::new_root_store;
+use crate::parse_root_certificate::read_cert_chain;
+use crate::CertificateError;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::CertificateRevocationListDer;
+use rustls::pki_types::UnixTime;
+use rustls::server::WebPkiClientVerifier;
+use std::path::Path;
+use std::sync::Arc;
+use x509_parser::prelude::FromDer;
+use x509_parser::prelude::X509Certificate;
+
+/// Verify that a device certificate can be used for TLS client authentication
+///
+/// - the certificate chain is built up to one of the trusted root certificates,
+///   using the intermediate certificates stored along the device certificate in the same file
+/// - the key usage and extended key usage of the certificate allow TLS client authentication
+/// - if a certificate revocation list (CRL) is provided, the certificate is not revoked
+pub fn verify_client_certificate(
+    client_certificate: impl AsRef<Path>,
+    root_certificates: impl AsRef<Path>,
+    crl: Option<&Path>,
+) -> Result<(), CertificateError> {
+    let chain = read_cert_chain(client_certificate.as_ref())?;
+    let Some((end_entity, intermediates)) = chain.split_first() else {
+        return Err(CertificateError::InvalidClientCertificate {
+            msg: "no certificate found".to_string(),
+            hint: format!(
+                "{} is expected to contain a PEM encoded certificate",
+                client_certificate.as_ref().display()
+            ),
+        });
+    };
+
+    check_key_usage(end_entity)?;
+
+    let roots = new_root_store(root_certificates.as_ref())?;
+    if roots.is_empty() {
+        return Err(CertificateError::InvalidClientCertificate {
+            msg: "no trusted root certificate".to_string(),
+            hint: format!(
+                "No root certificate found in {}",
+                root_certificates.as_ref().display()
+            ),
+        });
+    }
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+        .allow_unknown_revocation_status();
+    if let Some(crl) = crl {
+        let crls = CertificateRevocationListDer::pem_file_iter(crl)
+            .and_then(|crls| crls.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| CertificateError::CertificateParseFailed {
+                path: crl.to_owned(),
+                source: err.into(),
+            })?;
+        verifier = verifier.with_crls(crls);
+    }
+    let verifier = verifier
+        .build()
+        .map_err(|err| CertificateError::Other(err.into()))?;
+
+    verifier
+        .verify_client_cert(end_entity, intermediates, UnixTime::now())
+        .map_err(client_certificate_error)?;
+    Ok(())
+}
+
+/// When present, the key usage extension must allow the key to sign the TLS handshake
+fn check_key_usage(certificate: &[u8]) -> Result<(), CertificateError> {
+    let (_, x509) = X509Certificate::from_der(certificate)
+        .map_err(|err| CertificateError::X509Error(err.to_string()))?;
+    let key_usage = x509
+        .key_usage()
+        .map_err(|err| CertificateError::X509Error(err.to_string()))?;
+    match key_usage {
+        Some(key_usage) if !key_usage.value.digital_signature() => {
+            Err(CertificateError::InvalidClientCertificate {
+                msg: format!("the key usage is restricted to {}", key_usage.value),
+                hint: "The key usage of a certificate used for TLS client authentication must include digitalSignature".to_string(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+fn client_certificate_error(err: rustls::Error) -> CertificateError {
+    let hint = match &err {
+        rustls::Error::InvalidCertificate(inner) => match inner {
+            rustls::CertificateError::UnknownIssuer => "The certificate chain cannot be built up to a trusted root certificate. Check the root certificates and that the intermediate certificates are stored in the certificate file, after the device certificate.",
+            rustls::CertificateError::BadSignature => "The certificate is not correctly signed by its issuer.",
+            rustls::CertificateError::Expired | rustls::CertificateError::ExpiredContext { .. } => "The certificate has expired and has to be renewed.",
+            rustls::CertificateError::NotValidYet | rustls::CertificateError::NotValidYetContext { .. } => "The certificate is not valid yet. Check the system clock of the device.",
+            rustls::CertificateError::Revoked => "The certificate has been revoked by its CA.",
+            rustls::CertificateError::ExpiredRevocationList | rustls::CertificateError::ExpiredRevocationListContext { .. } => "The certificate revocation list has expired and has to be updated.",
+            rustls::CertificateError::InvalidPurpose | rustls::CertificateError::InvalidPurposeContext { .. } => "The extended key usage of the certificate doesn't allow TLS client authentication.",
+            _ => "The certificate is not valid for TLS client authentication.",
+        },
+        _ => "The certificate is not valid for TLS client authentication.",
+    };
+    CertificateError::InvalidClientCertificate {
+        msg: err.to_string(),
+        hint: hint.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use rcgen::CertificateParams;
+    use rcgen::CertificateRevocationListParams;
+    use rcgen::ExtendedKeyUsagePurpose;
+    use rcgen::IsCa;
+    use rcgen::Issuer;
+    use rcgen::KeyIdMethod;
+    use rcgen::KeyPair;
+    use rcgen::KeyUsagePurpose;
+    use rcgen::RevokedCertParams;
+    use rcgen::SerialNumber;
+    use tempfile::TempDir;
+    use time::Duration;
+    use time::OffsetDateTime;
+
+    struct TestPki {
+        dir: TempDir,
+        ca: Issuer<'static, KeyPair>,
+    }
+
+    impl TestPki {
+        fn new(ca_name: &str) -> Self {
+            let dir = TempDir::new().unwrap();
+            let mut params = CertificateParams::new(vec![]).unwrap();
+            params
+                .distinguished_name
+                .push(rcgen::DnType::CommonName, ca_name);
+            params.is_ca = IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+            params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+            let key = KeyPair::generate().unwrap();
+            let cert = params.self_signed(&key).unwrap();
+            std::fs::write(dir.path().join("ca.pem"), cert.pem()).unwrap();
+            let ca = Issuer::new(params, key);
+            TestPki { dir, ca }
+        }
+
+        fn root_certs(&self) -> std::path::PathBuf {
+            self.dir.path().join("ca.pem")
+        }
+
+        fn device_cert(
+            &self,
+            serial: u64,
+            customize: impl FnOnce(&mut CertificateParams),
+        ) -> std::path::PathBuf {
+            let mut params = CertificateParams::new(vec![]).unwrap();
+            params.serial_number = Some(SerialNumber::from(serial));
+            params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+            customize(&mut params);
+            let key = KeyPair::generate().unwrap();
+            let cert = params.signed_by(&key, &self.ca).unwrap();
+            let path = self.dir.path().join(format!("device-{serial}.pem"));
+            std::fs::write(&path, cert.pem()).unwrap();
+            path
+        }
+
+        fn crl(&self, revoked: &[u64]) -> std::path::PathBuf {
+            let now = OffsetDateTime::now_utc();
+            let params = CertificateRevocationListParams {
+                this_update: now - Duration::hours(1),
+                next_update: now + Duration::days(1),
+                crl_number: SerialNumber::from(1),
+                issuing_distribution_point: None,
+                revoked_certs: revoked
+                    .iter()
+                    .map(|serial| RevokedCertParams {
+                        serial_number: SerialNumber::from(*serial),
+                        revocation_time: now - Duration::minutes(30),
+                        reason_code: None,
+                        invalidity_date: None,
+                    })
+                    .collect(),
+                key_identifier_method: KeyIdMethod::Sha256,
+            };
+            let crl = params.signed_by(&self.ca).unwrap();
+            let path = self.dir.path().join("crl.pem");
+            std::fs::write(&path, crl.pem().unwrap()).unwrap();
+            path
+        }
+    }
+
+    #[test]
+    fn certificate_signed_by_trusted_ca_is_valid() {
+        let pki = TestPki::new("Test CA");
+        let cert = pki.device_cert(1, |_| {});
+
+        assert_matches!(
+            verify_client_certificate(&cert, pki.root_certs(), None),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn certificate_signed_by_unknown_ca_is_rejected() {
+        let pki = TestPki::new("Test CA");
+        let other_pki = TestPki::new("Other CA");
+        let cert = other_pki.device_cert(1, |_| {});
+
+        let err = verify_client_certificate(&cert, pki.root_certs(), None).unwrap_err();
+        assert!(err.to_string().contains("UnknownIssuer"), "{err}");
+    }
+
+    #[test]
+    fn certificate_for_server_authentication_only_is_rejected() {
+        let pki = TestPki::new("Test CA");
+        let cert = pki.device_cert(1, |params| {
+            params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        });
+
+        let err = verify_client_certificate(&cert, pki.root_certs(), None).unwrap_err();
+        assert!(err.to_string().contains("extended key usage"), "{err}");
+    }
+
+    #[test]
+    fn certificate_without_digital_signature_key_usage_is_rejected() {
+        let pki = TestPki::new("Test CA");
+        let cert = pki.device_cert(1, |params| {
+            params.key_usages = vec![KeyUsagePurpose::KeyEncipherment];
+        });
+
+        let err = verify_client_certificate(&cert, pki.root_certs(), None).unwrap_err();
+        assert!(err.to_string().contains("digitalSignature"), "{err}");
+    }
+
+    #[test]
+    fn revoked_certificate_is_rejected() {
+        let pki = TestPki::new("Test CA");
+        let revoked = pki.device_cert(1, |_| {});
+        let valid = pki.device_cert(2, |_| {});
+        let crl = pki.crl(&[1]);
+
+        let err = verify_client_certificate(&revoked, pki.root_certs(), Some(&crl)).unwrap_err();
+        assert!(err.to_string().contains("revoked"), "{err}");
+        assert_matches!(
+            verify_client_certificate(&valid, pki.root_certs(), Some(&crl)),
+            Ok(())
+        );
+    }
+}
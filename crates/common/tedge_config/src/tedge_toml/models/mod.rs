@@ -63,10 +63,15 @@ pub enum CloudType {
     C8y,
     Az,
     Aws,
+    #[serde(rename = "mqtt_cloud")]
+    #[strum(serialize = "mqtt_cloud")]
+    MqttCloud,
 }
 
 #[derive(thiserror::Error, Debug)]
-#[error("Failed to parse cloud type: {input}. Supported values are: 'c8y', 'az' or 'aws'")]
+#[error(
+    "Failed to parse cloud type: {input}. Supported values are: 'c8y', 'az', 'aws' or 'mqtt_cloud'"
+)]
 pub struct InvalidCloudType {
     input: String,
 }
@@ -79,6 +84,7 @@ impl FromStr for CloudType {
             "c8y" => Ok(CloudType::C8y),
             "az" => Ok(CloudType::Az),
             "aws" => Ok(CloudType::Aws),
+            "mqtt_cloud" => Ok(CloudType::MqttCloud),
             _ => Err(InvalidCloudType {
                 input: input.to_string(),
             }),
@@ -33,6 +33,7 @@ use crate::tedge_toml::mapper_config::C8yMapperSpecificConfig;
 use crate::tedge_toml::mapper_config::HasPath as _;
 use crate::tedge_toml::mapper_config::HasUrl;
 use crate::tedge_toml::mapper_config::MapperConfigError;
+use crate::tedge_toml::mapper_config::MqttCloudMapperSpecificConfig;
 use crate::tedge_toml::mapper_config::SpecialisedCloudConfig;
 use anyhow::anyhow;
 use anyhow::Context;
@@ -69,7 +70,9 @@ use tedge_config_macros::*;
 use tracing::error;
 
 mod mqtt_config;
+pub use mqtt_config::MqttAuthClientConfigCloudBroker;
 pub use mqtt_config::MqttAuthConfigCloudBroker;
+pub use mqtt_config::PrivateKeyType;
 pub use mqtt_config::TEdgeMqttClientAuthConfig;
 
 const DEFAULT_ROOT_CERT_PATH: &str = "/etc/ssl/certs";
@@ -77,6 +80,7 @@ const DEFAULT_ROOT_CERT_PATH: &str = "/etc/ssl/certs";
 pub const C8Y_MQTT_PAYLOAD_LIMIT: u32 = 16184; // 16 KB
 pub const AZ_MQTT_PAYLOAD_LIMIT: u32 = 262144; // 256 KB
 pub const AWS_MQTT_PAYLOAD_LIMIT: u32 = 131072; // 128 KB
+pub const MQTT_CLOUD_PAYLOAD_LIMIT: u32 = 262144; // 256 KB
 
 pub trait OptionalConfigError<T> {
     fn or_err(&self) -> Result<&T, ReadError>;
@@ -182,6 +186,11 @@ impl TEdgeConfigDto {
         Self::populate_single_mapper::<C8yMapperSpecificConfig>(&mut self.c8y, location).await?;
         Self::populate_single_mapper::<AzMapperSpecificConfig>(&mut self.az, location).await?;
         Self::populate_single_mapper::<AwsMapperSpecificConfig>(&mut self.aws, location).await?;
+        Self::populate_single_mapper::<MqttCloudMapperSpecificConfig>(
+            &mut self.mqtt_cloud,
+            location,
+        )
+        .await?;
         Ok(())
     }
 }
@@ -221,6 +230,12 @@ impl TEdgeConfig {
     ) -> anyhow::Result<MapperConfig<AwsMapperSpecificConfig>> {
         self.mapper_config(profile)
     }
+    pub fn mqtt_cloud_mapper_config(
+        &self,
+        profile: &Option<impl Borrow<ProfileName>>,
+    ) -> anyhow::Result<MapperConfig<MqttCloudMapperSpecificConfig>> {
+        self.mapper_config(profile)
+    }
 
     pub fn mapper_config<T: SpecialisedCloudConfig>(
         &self,
@@ -242,6 +257,7 @@ impl TEdgeConfig {
             CloudType::C8y => Box::new(self.c8y.keys().map(|p| p.map(<_>::to_owned))),
             CloudType::Az => Box::new(self.az.keys().map(|p| p.map(<_>::to_owned))),
             CloudType::Aws => Box::new(self.aws.keys().map(|p| p.map(<_>::to_owned))),
+            CloudType::MqttCloud => Box::new(self.mqtt_cloud.keys().map(|p| p.map(<_>::to_owned))),
         }
     }
 
@@ -270,6 +286,7 @@ impl TEdgeConfig {
             Cloud::C8y(profile) => self.c8y_mapper_config(&profile).map(Box::new)?,
             Cloud::Az(profile) => self.az_mapper_config(&profile).map(Box::new)?,
             Cloud::Aws(profile) => self.aws_mapper_config(&profile).map(Box::new)?,
+            Cloud::MqttCloud(profile) => self.mqtt_cloud_mapper_config(&profile).map(Box::new)?,
         })
     }
 
@@ -279,6 +296,9 @@ impl TEdgeConfig {
             Some(Cloud::C8y(profile)) => self.c8y_mapper_config(&profile)?.device.id()?,
             Some(Cloud::Az(profile)) => self.az_mapper_config(&profile)?.device.id()?,
             Some(Cloud::Aws(profile)) => self.aws_mapper_config(&profile)?.device.id()?,
+            Some(Cloud::MqttCloud(profile)) => {
+                self.mqtt_cloud_mapper_config(&profile)?.device.id()?
+            }
         })
     }
 
@@ -291,6 +311,11 @@ impl TEdgeConfig {
             Some(Cloud::C8y(profile)) => self.c8y_mapper_config(&profile)?.device.key_path.clone(),
             Some(Cloud::Az(profile)) => self.az_mapper_config(&profile)?.device.key_path.clone(),
             Some(Cloud::Aws(profile)) => self.aws_mapper_config(&profile)?.device.key_path.clone(),
+            Some(Cloud::MqttCloud(profile)) => self
+                .mqtt_cloud_mapper_config(&profile)?
+                .device
+                .key_path
+                .clone(),
         })
     }
 
@@ -303,6 +328,11 @@ impl TEdgeConfig {
             Some(Cloud::C8y(profile)) => self.c8y_mapper_config(&profile)?.device.cert_path.clone(),
             Some(Cloud::Az(profile)) => self.az_mapper_config(&profile)?.device.cert_path.clone(),
             Some(Cloud::Aws(profile)) => self.aws_mapper_config(&profile)?.device.cert_path.clone(),
+            Some(Cloud::MqttCloud(profile)) => self
+                .mqtt_cloud_mapper_config(&profile)?
+                .device
+                .cert_path
+                .clone(),
         })
     }
 
@@ -315,6 +345,11 @@ impl TEdgeConfig {
             Some(Cloud::C8y(profile)) => self.c8y_mapper_config(&profile)?.device.csr_path.clone(),
             Some(Cloud::Az(profile)) => self.az_mapper_config(&profile)?.device.csr_path.clone(),
             Some(Cloud::Aws(profile)) => self.aws_mapper_config(&profile)?.device.csr_path.clone(),
+            Some(Cloud::MqttCloud(profile)) => self
+                .mqtt_cloud_mapper_config(&profile)?
+                .device
+                .csr_path
+                .clone(),
         })
     }
 
@@ -330,10 +365,15 @@ impl TEdgeConfig {
                 let aws_roots =
                     futures::stream::iter(self.all_mapper_configs::<AwsMapperSpecificConfig>())
                         .flat_map(|(mapper, _profile)| stream_trust_store(mapper));
+                let mqtt_cloud_roots = futures::stream::iter(
+                    self.all_mapper_configs::<MqttCloudMapperSpecificConfig>(),
+                )
+                .flat_map(|(mapper, _profile)| stream_trust_store(mapper));
 
                 c8y_roots
                     .chain(az_roots)
                     .chain(aws_roots)
+                    .chain(mqtt_cloud_roots)
                     .collect::<Vec<_>>()
                     .await
                     .into()
@@ -564,6 +604,26 @@ define_tedge_config! {
             minimum_duration: SecondsOrHumanTime,
         },
 
+        renewal: {
+            /// Enable the automatic renewal of the device certificates by tedge-agent
+            #[tedge_config(note = "The certificate is renewed, using the `cert_renew` workflow, when it expires within `certificate.validity.minimum_duration`")]
+            #[tedge_config(example = "true", default(value = false))]
+            enable: bool,
+
+            /// Interval at which tedge-agent checks if the device certificates have to be renewed
+            #[tedge_config(example = "1h", default(from_str = "1h"))]
+            interval: SecondsOrHumanTime,
+
+            /// The clouds for which the device certificate is renewed, a cloud profile being given as `<cloud>@<profile>`
+            #[tedge_config(example = "c8y,c8y@eu,az", default(value = "c8y"))]
+            clouds: TemplatesSet,
+
+            /// The Certificate Authority used by the `cert_renew` workflow to renew the certificates
+            #[tedge_config(note = "This is passed to `tedge cert renew --ca`. Other backends can be used by editing the `renew` step of the `cert_renew` workflow")]
+            #[tedge_config(example = "c8y", default(value = "c8y"))]
+            ca: String,
+        },
+
         /// Organization name used for certificate signing requests
         #[tedge_config(example = "ACME", default(value = "Thin Edge"))]
         organization: Arc<str>,
@@ -800,6 +860,16 @@ define_tedge_config! {
             /// Auto-upload the operation log once it finishes.
             #[tedge_config(example = "always", example = "never", example = "on-failure", default(variable = "AutoLogUpload::OnFailure"))]
             auto_log_upload: AutoLogUpload,
+
+            /// Maximum number of operations of the same type running concurrently on a device or service.
+            /// Additional operations are queued, being kept PENDING in Cumulocity until started.
+            #[tedge_config(example = "1", default(value = 1u32))]
+            max_concurrent: u32,
+
+            /// Operation types (e.g. restart) that run alone on a device or service,
+            /// starting only once all the running operations are completed and delaying the others until done
+            #[tedge_config(example = "restart,firmware_update", default(function = "c8y_exclusive_operations"))]
+            exclusive: TemplatesSet,
         },
 
         availability: {
@@ -886,6 +956,20 @@ define_tedge_config! {
             key_pin: Arc<str>,
         },
 
+        dps: {
+            /// ID scope of the Azure IoT Hub Device Provisioning Service (DPS) instance provisioning the device.
+            ///
+            /// When set, `tedge connect az` registers the device to DPS
+            /// and updates `az.url` and `az.device.id` with the IoT Hub and device id assigned to the device.
+            #[tedge_config(example = "0ne00AB1234")]
+            id_scope: String,
+
+            /// Endpoint of the Azure IoT Hub Device Provisioning Service
+            #[tedge_config(example = "global.azure-devices-provisioning.net")]
+            #[tedge_config(default(from_str = "global.azure-devices-provisioning.net"))]
+            url: HostPort<MQTT_TLS_PORT>,
+        },
+
         mapper: {
             /// Whether the Azure IoT mapper should add a timestamp or not
             #[tedge_config(example = "true")]
@@ -981,6 +1065,27 @@ define_tedge_config! {
             key_pin: Arc<str>,
         },
 
+        provisioning: {
+            /// Name of the AWS IoT fleet provisioning template used to register the device.
+            ///
+            /// When set, and the device certificate doesn't exist yet, `tedge connect aws` provisions the device by claim:
+            /// requesting a certificate for the device CSR and registering the thing with this template.
+            #[tedge_config(example = "tedge-fleet-template")]
+            template_name: String,
+
+            /// Parameters passed to the fleet provisioning template, as a list of `name=value` pairs
+            #[tedge_config(example = "SerialNumber=0042,Model=rpi4")]
+            template_parameters: TemplatesSet,
+
+            /// Path to the claim certificate used to connect AWS IoT when provisioning the device
+            #[tedge_config(example = "/etc/tedge/device-certs/aws-claim-certificate.pem")]
+            claim_cert_path: AbsolutePath,
+
+            /// Path to the private key of the claim certificate
+            #[tedge_config(example = "/etc/tedge/device-certs/aws-claim-private-key.pem")]
+            claim_key_path: AbsolutePath,
+        },
+
         mapper: {
             /// Whether the AWS IoT mapper should add a timestamp or not
             #[tedge_config(example = "true")]
@@ -1019,6 +1124,141 @@ define_tedge_config! {
         topics: TemplatesSet,
     },
 
+    #[tedge_config(multi)]
+    #[tedge_config(reader(private))]
+    mqtt_cloud: {
+        #[tedge_config(reader(skip))]
+        #[serde(skip)]
+        mapper_config_dir: Utf8PathBuf,
+
+        #[tedge_config(reader(skip))]
+        #[serde(skip)]
+        mapper_config_file: Utf8PathBuf,
+
+        /// Host name of the remote MQTT broker
+        #[tedge_config(example = "broker.example.com")]
+        url: ConnectUrl,
+
+        /// Port of the remote MQTT broker
+        #[tedge_config(example = "8883", default(value = 8883u16))]
+        port: u16,
+
+        /// The path where the trusted root certificate(s) of the remote MQTT broker are stored
+        #[tedge_config(note = "The value can be a directory path as well as the path of the certificate file.")]
+        #[tedge_config(example = "/etc/tedge/broker-trusted-root-certificates.pem", default(function = "default_root_cert_path"))]
+        root_cert_path: AbsolutePath,
+
+        /// The authentication method used to connect the remote MQTT broker
+        #[tedge_config(note = "In the auto mode, username/password authentication is used if mqtt_cloud.credentials_path is set")]
+        #[tedge_config(example = "certificate", example = "basic", example = "auto", default(variable = AuthMethod::Certificate))]
+        auth_method: AuthMethod,
+
+        /// The path where the username/password used to connect the remote MQTT broker are stored
+        #[tedge_config(note = "The value must be the path of the credentials file.")]
+        #[tedge_config(example = "/etc/tedge/credentials.toml", default(function = "default_credentials_path"))]
+        credentials_path: AbsolutePath,
+
+        device: {
+            /// Identifier of the device on the remote MQTT broker, used as MQTT client id.
+            /// When not set, it is derived from the device certificate.
+            #[tedge_config(reader(function = "mqtt_cloud_device_id"))]
+            #[tedge_config(default(from_optional_key = "device.id"))]
+            #[tedge_config(example = "Raspberrypi-4d18303a-6d3a-11eb-b1a6-175f6bb72665")]
+            #[doku(as = "String")]
+            id: Result<String, ReadError>,
+
+            /// Path where the device's private key is stored
+            #[tedge_config(example = "/etc/tedge/device-certs/tedge-private-key.pem", default(from_key = "device.key_path"))]
+            key_path: AbsolutePath,
+
+            /// Path where the device's certificate is stored
+            #[tedge_config(example = "/etc/tedge/device-certs/tedge-certificate.pem", default(from_key = "device.cert_path"))]
+            cert_path: AbsolutePath,
+
+            /// Path where the device's certificate signing request is stored
+            #[tedge_config(example = "/etc/tedge/device-certs/tedge.csr", default(from_key = "device.csr_path"))]
+            csr_path: AbsolutePath,
+
+            /// A PKCS#11 URI of the private key.
+            ///
+            /// See RFC #7512.
+            #[tedge_config(example = "pkcs11:model=PKCS%2315%20emulated")]
+            key_uri: Arc<str>,
+
+            /// User PIN value for logging into the PKCS#11 token provided by the consumer.
+            #[tedge_config(example = "123456", example = "my-pin")]
+            key_pin: Arc<str>,
+        },
+
+        topic_templates: {
+            /// Remote topic where measurements are published
+            #[tedge_config(note = "The template can use the {device_id}, {entity} and {type} placeholders.")]
+            #[tedge_config(example = "tedge/{device_id}/{entity}/m/{type}")]
+            #[tedge_config(default(value = "tedge/{device_id}/{entity}/m/{type}"))]
+            measurements: String,
+
+            /// Remote topic where events are published
+            #[tedge_config(note = "The template can use the {device_id}, {entity} and {type} placeholders.")]
+            #[tedge_config(example = "tedge/{device_id}/{entity}/e/{type}")]
+            #[tedge_config(default(value = "tedge/{device_id}/{entity}/e/{type}"))]
+            events: String,
+
+            /// Remote topic where alarms are published
+            #[tedge_config(note = "The template can use the {device_id}, {entity} and {type} placeholders.")]
+            #[tedge_config(example = "tedge/{device_id}/{entity}/a/{type}")]
+            #[tedge_config(default(value = "tedge/{device_id}/{entity}/a/{type}"))]
+            alarms: String,
+
+            /// Remote topic where twin data is published
+            #[tedge_config(note = "The template can use the {device_id}, {entity} and {type} placeholders, {type} being the twin fragment name.")]
+            #[tedge_config(example = "tedge/{device_id}/{entity}/twin/{type}")]
+            #[tedge_config(default(value = "tedge/{device_id}/{entity}/twin/{type}"))]
+            twin: String,
+
+            /// Remote topic on which commands are received
+            #[tedge_config(note = "The template must use the {entity}, {operation} and {cmd_id} placeholders as full topic levels, and can use {device_id}. Command status updates are published on the same topic suffixed by /status.")]
+            #[tedge_config(example = "tedge/{device_id}/{entity}/cmd/{operation}/{cmd_id}")]
+            #[tedge_config(default(value = "tedge/{device_id}/{entity}/cmd/{operation}/{cmd_id}"))]
+            commands: String,
+        },
+
+        mapper: {
+            /// Whether the MQTT cloud mapper should add a timestamp or not
+            #[tedge_config(example = "true")]
+            #[tedge_config(default(value = true))]
+            timestamp: bool,
+
+            /// The format that will be used by the mapper when sending timestamps to the remote MQTT broker
+            #[tedge_config(example = "rfc-3339")]
+            #[tedge_config(example = "unix")]
+            #[tedge_config(default(variable = "TimeFormat::Unix"))]
+            timestamp_format: TimeFormat,
+
+            mqtt: {
+                /// The maximum message payload size that can be mapped to the remote MQTT broker
+                #[tedge_config(example = "262144", default(function = "mqtt_cloud_payload_limit"))]
+                max_payload_size: MqttPayloadLimit,
+            }
+        },
+
+        bridge: {
+            /// The local topic prefix of the messages forwarded to and received from the remote MQTT broker.
+            /// For instance, if this is set to "mqtt-cloud", then messages published locally on `mqtt-cloud/tedge/#`
+            /// are forwarded to the remote broker on the `tedge/#` topic
+            #[tedge_config(example = "mqtt-cloud", default(function = "mqtt_cloud_topic_prefix"))]
+            topic_prefix: TopicPrefix,
+
+            /// The amount of time after which the bridge should send a ping if no other traffic has occurred
+            #[tedge_config(example = "60s", default(from_str = "60s"))]
+            keepalive_interval: SecondsOrHumanTime,
+        },
+
+        /// Set of MQTT topics the MQTT cloud mapper should subscribe to
+        #[tedge_config(example = "te/+/+/+/+/a/+,te/+/+/+/+/m/+,te/+/+/+/+/e/+")]
+        #[tedge_config(default(value = "te/+/+/+/+/m/+,te/+/+/+/+/e/+,te/+/+/+/+/a/+,te/+/+/+/+/twin/+"))]
+        topics: TemplatesSet,
+    },
+
     mqtt: {
         /// MQTT topic root
         #[tedge_config(default(value = "te"))]
@@ -1214,6 +1454,33 @@ define_tedge_config! {
             /// Determines if tedge-agent should enable log_upload operation
             #[tedge_config(example = "true", default(value = true))]
             log_upload: bool,
+
+            /// Determines if tedge-agent should enable the command operation, executing shell commands on the device
+            #[tedge_config(note = "Only the commands matching `agent.command.allowed` are executed.")]
+            #[tedge_config(example = "true", default(value = false))]
+            command: bool,
+        },
+
+        command: {
+            /// The regular expressions a command line must fully match to be executed by the command operation
+            #[tedge_config(example = "uptime,df -h,journalctl -u [a-z-]+ -n [0-9]+", default(function = "TemplatesSet::default"))]
+            allowed: TemplatesSet,
+
+            /// The user running the commands, using sudo. The commands are run as the tedge-agent user if not set.
+            #[tedge_config(example = "nobody")]
+            user: String,
+
+            /// The working directory of the commands
+            #[tedge_config(example = "/tmp", default(from_key = "tmp.path"))]
+            working_dir: AbsolutePath,
+
+            /// The maximum duration of a command, after which the command is killed and the operation fails
+            #[tedge_config(example = "60s", default(from_str = "60s"))]
+            timeout: SecondsOrHumanTime,
+
+            /// The maximum size in bytes of the command output (stdout and stderr), the remaining output being discarded
+            #[tedge_config(example = "65536", default(value = 65536u64))]
+            max_output_size: u64,
         },
 
         entity_store: {
@@ -1226,7 +1493,23 @@ define_tedge_config! {
             clean_start: bool,
         },
 
+        file_cache: {
+            /// The maximum size in bytes of the files cached by the agent for child device operations, 0 meaning no limit
+            #[tedge_config(note = "When the limit is exceeded, the least recently used files that are not used by an operation in progress are removed.")]
+            #[tedge_config(example = "1073741824", default(value = 1073741824u64))]
+            max_size: u64,
+        },
 
+        inventory: {
+            /// Enable the periodic collection of the device hardware, operating system and network facts by tedge-agent
+            #[tedge_config(note = "The facts are published as twin fragments of the main device. Extra facts can be collected by executable scripts stored in `/etc/tedge/device/inventory.d`, each printing a JSON object of twin fragments.")]
+            #[tedge_config(example = "true", default(value = false))]
+            enable: bool,
+
+            /// Interval at which tedge-agent collects the device facts, publishing the fragments that changed
+            #[tedge_config(example = "1h", default(from_str = "1h"))]
+            interval: SecondsOrHumanTime,
+        },
     },
 
     software: {
@@ -1366,6 +1649,21 @@ define_tedge_config! {
         plugin_paths: TemplatesSet,
     },
 
+    remote_access: {
+        /// The targets that can be reached through a remote access tunnel, as `host:port` patterns.
+        /// The host and the port can be `*` to match any, and the port can be a range as in `8000-8080`.
+        #[tedge_config(example = "127.0.0.1:22,localhost:*,192.168.1.10:5900-5910", default(function = "default_remote_access_targets"))]
+        allowed_targets: TemplatesSet,
+
+        /// Close a remote access session when no data has been exchanged for this duration, 0 to disable
+        #[tedge_config(example = "15m", default(from_str = "1h"))]
+        idle_timeout: SecondsOrHumanTime,
+
+        /// Close a remote access session once opened for this duration, 0 to disable
+        #[tedge_config(example = "8h", default(from_str = "24h"))]
+        max_duration: SecondsOrHumanTime,
+    },
+
     flows: {
         stats: {
             /// The interval in seconds between flow statistics dumps
@@ -1382,6 +1680,56 @@ define_tedge_config! {
             on_interval: bool,
         },
     },
+
+    sparkplug: {
+        /// The Sparkplug group the edge node belongs to
+        #[tedge_config(example = "plant-1", default(value = "tedge"))]
+        group_id: String,
+
+        /// The Sparkplug edge node identifier of the device, the device id being used if not set
+        #[tedge_config(example = "gateway-1")]
+        edge_node_id: String,
+
+        broker: {
+            /// The host of the MQTT broker the Sparkplug messages are published to
+            #[tedge_config(example = "127.0.0.1", example = "broker.example.com")]
+            #[tedge_config(default(from_key = "mqtt.client.host"))]
+            host: String,
+
+            /// The port of the MQTT broker the Sparkplug messages are published to
+            #[tedge_config(example = "1883", example = "8883")]
+            #[tedge_config(default(from_key = "mqtt.client.port"))]
+            #[doku(as = "u16")]
+            port: NonZeroU16,
+
+            #[tedge_config(reader(private))]
+            auth: {
+                /// Path to the CA certificate used to authenticate the Sparkplug MQTT broker
+                #[tedge_config(example = "/etc/ssl/certs/ca-certificates.crt")]
+                ca_file: AbsolutePath,
+
+                /// Path to the directory containing the CA certificates used to authenticate the Sparkplug MQTT broker
+                #[tedge_config(example = "/etc/ssl/certs")]
+                ca_dir: AbsolutePath,
+
+                /// Path to the client certificate used to connect the Sparkplug MQTT broker
+                #[tedge_config(example = "/etc/tedge/device-certs/tedge-certificate.pem")]
+                cert_file: AbsolutePath,
+
+                /// Path to the client private key used to connect the Sparkplug MQTT broker
+                #[tedge_config(example = "/etc/tedge/device-certs/tedge-private-key.pem")]
+                key_file: AbsolutePath,
+
+                /// Username used to connect the Sparkplug MQTT broker
+                #[tedge_config(example = "myuser")]
+                username: String,
+
+                /// Path to the password file used to connect the Sparkplug MQTT broker
+                #[tedge_config(example = "/etc/tedge/.sparkplug_password")]
+                password_file: AbsolutePath,
+            },
+        },
+    },
 }
 
 static CLOUD_ROOT_CERTIFICATES: tokio::sync::OnceCell<Arc<[Certificate]>> =
@@ -1424,6 +1772,20 @@ impl TEdgeConfigReader {
         self.aws.entries()
     }
 
+    pub fn mqtt_cloud_keys(&self) -> impl Iterator<Item = Option<&ProfileName>> {
+        self.mqtt_cloud.keys()
+    }
+
+    pub fn mqtt_cloud_keys_str(&self) -> impl Iterator<Item = Option<&str>> {
+        self.mqtt_cloud.keys_str()
+    }
+
+    pub fn mqtt_cloud_entries(
+        &self,
+    ) -> impl Iterator<Item = (Option<&str>, &TEdgeConfigReaderMqttCloud)> {
+        self.mqtt_cloud.entries()
+    }
+
     pub fn cloud_client_tls_config(&self) -> rustls::ClientConfig {
         // TODO do we want to unwrap here?
         client_config_for_ca_certificates(
@@ -1431,7 +1793,12 @@ impl TEdgeConfigReader {
                 .values()
                 .map(|c8y| &c8y.root_cert_path)
                 .chain(self.az.values().map(|az| &az.root_cert_path))
-                .chain(self.aws.values().map(|aws| &aws.root_cert_path)),
+                .chain(self.aws.values().map(|aws| &aws.root_cert_path))
+                .chain(
+                    self.mqtt_cloud
+                        .values()
+                        .map(|mqtt_cloud| &mqtt_cloud.root_cert_path),
+                ),
         )
         .unwrap()
     }
@@ -1442,6 +1809,7 @@ pub enum Cloud<'a> {
     C8y(Option<&'a ProfileName>),
     Az(Option<&'a ProfileName>),
     Aws(Option<&'a ProfileName>),
+    MqttCloud(Option<&'a ProfileName>),
 }
 
 pub trait CloudConfig {
@@ -1483,6 +1851,18 @@ fn c8y_topic_prefix() -> TopicPrefix {
     TopicPrefix::try_new("c8y").unwrap()
 }
 
+fn default_remote_access_targets() -> TemplatesSet {
+    TemplatesSet(vec![
+        "127.0.0.1:*".to_owned(),
+        "localhost:*".to_owned(),
+        "[::1]:*".to_owned(),
+    ])
+}
+
+fn c8y_exclusive_operations() -> TemplatesSet {
+    TemplatesSet(vec!["restart".to_owned()])
+}
+
 fn az_topic_prefix() -> TopicPrefix {
     TopicPrefix::try_new("az").unwrap()
 }
@@ -1491,6 +1871,10 @@ fn aws_topic_prefix() -> TopicPrefix {
     TopicPrefix::try_new("aws").unwrap()
 }
 
+fn mqtt_cloud_topic_prefix() -> TopicPrefix {
+    TopicPrefix::try_new("mqtt-cloud").unwrap()
+}
+
 fn c8y_mqtt_payload_limit() -> MqttPayloadLimit {
     C8Y_MQTT_PAYLOAD_LIMIT.try_into().unwrap()
 }
@@ -1503,6 +1887,10 @@ fn aws_mqtt_payload_limit() -> MqttPayloadLimit {
     AWS_MQTT_PAYLOAD_LIMIT.try_into().unwrap()
 }
 
+fn mqtt_cloud_payload_limit() -> MqttPayloadLimit {
+    MQTT_CLOUD_PAYLOAD_LIMIT.try_into().unwrap()
+}
+
 fn default_http_bind_address(dto: &TEdgeConfigDto) -> IpAddr {
     let external_address = dto.mqtt.external.bind.address;
     external_address
@@ -1572,6 +1960,20 @@ fn aws_device_id(
     }
 }
 
+fn mqtt_cloud_device_id(
+    mqtt_cloud_device: &TEdgeConfigReaderMqttCloudDevice,
+    dto_value: &OptionalConfig<String>,
+) -> Result<String, ReadError> {
+    match (
+        device_id_from_cert(&mqtt_cloud_device.cert_path),
+        dto_value.or_none(),
+    ) {
+        (Ok(common_name), _) => Ok(common_name),
+        (Err(_), Some(dto_value)) => Ok(dto_value.to_string()),
+        (Err(err), None) => Err(err),
+    }
+}
+
 fn cert_error_into_config_error(key: Cow<'static, str>, err: CertificateError) -> ReadError {
     match &err {
         CertificateError::IoError { error, .. } => match error.kind() {
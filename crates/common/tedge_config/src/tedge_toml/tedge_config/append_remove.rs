@@ -54,6 +54,7 @@ impl_append_remove_for_single_value!(
     NonZeroU16,
     SecondsOrHumanTime,
     u32,
+    u64,
     AptConfig,
     MqttPayloadLimit,
     AuthMethod,
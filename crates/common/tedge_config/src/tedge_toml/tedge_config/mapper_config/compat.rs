@@ -14,6 +14,7 @@ use super::*;
 use crate::tedge_toml::tedge_config::TEdgeConfigReaderAws;
 use crate::tedge_toml::tedge_config::TEdgeConfigReaderAz;
 use crate::tedge_toml::tedge_config::TEdgeConfigReaderC8y;
+use crate::tedge_toml::tedge_config::TEdgeConfigReaderMqttCloud;
 use crate::tedge_toml::ReadableKey;
 use crate::TEdgeConfig;
 
@@ -117,6 +118,8 @@ impl FromCloudConfig for C8yMapperSpecificConfig {
             },
             operations: OperationsConfig {
                 auto_log_upload: c8y.operations.auto_log_upload,
+                max_concurrent: c8y.operations.max_concurrent,
+                exclusive: c8y.operations.exclusive.clone(),
             },
             availability: AvailabilityConfig {
                 enable: c8y.availability.enable,
@@ -177,6 +180,10 @@ impl FromCloudConfig for AzMapperSpecificConfig {
                 timestamp: az.mapper.timestamp,
                 timestamp_format: az.mapper.timestamp_format,
             },
+            dps: AzDpsConfig {
+                id_scope: az.dps.id_scope.clone(),
+                url: az.dps.url.clone(),
+            },
         }
     }
 }
@@ -214,6 +221,62 @@ impl FromCloudConfig for AwsMapperSpecificConfig {
                 timestamp: aws.mapper.timestamp,
                 timestamp_format: aws.mapper.timestamp_format,
             },
+            provisioning: AwsProvisioningConfig {
+                template_name: aws.provisioning.template_name.clone(),
+                template_parameters: aws.provisioning.template_parameters.clone(),
+                claim_cert_path: aws.provisioning.claim_cert_path.clone(),
+                claim_key_path: aws.provisioning.claim_key_path.clone(),
+            },
+        }
+    }
+}
+
+impl FromCloudConfig for MqttCloudMapperSpecificConfig {
+    type CloudConfigReader = TEdgeConfigReaderMqttCloud;
+
+    fn load_cloud_mapper_config(
+        profile: Option<&str>,
+        tedge_config: &TEdgeConfig,
+    ) -> Result<MapperConfig<Self>, MapperConfigError> {
+        let mqtt_cloud_config = tedge_config.mqtt_cloud.try_get(profile).map_err(|_| {
+            MapperConfigError::ConfigRead(format!(
+                "MQTT cloud profile '{}' not found",
+                profile.unwrap()
+            ))
+        })?;
+        let location = tedge_config
+            .dto
+            .mqtt_cloud
+            .try_get(profile, "mqtt_cloud")
+            .unwrap()
+            .mapper_config_file
+            .clone()
+            .unwrap_or_else(|| {
+                tedge_config
+                    .location
+                    .tedge_config_root_path()
+                    .join("tedge.toml")
+            });
+
+        build_mapper_config(mqtt_cloud_config.clone(), profile, location)
+    }
+
+    fn from_cloud_config(mqtt_cloud: &Self::CloudConfigReader, _profile: Option<&str>) -> Self {
+        MqttCloudMapperSpecificConfig {
+            port: mqtt_cloud.port,
+            auth_method: mqtt_cloud.auth_method,
+            credentials_path: mqtt_cloud.credentials_path.clone(),
+            topic_templates: MqttCloudTopicTemplates {
+                measurements: mqtt_cloud.topic_templates.measurements.clone(),
+                events: mqtt_cloud.topic_templates.events.clone(),
+                alarms: mqtt_cloud.topic_templates.alarms.clone(),
+                twin: mqtt_cloud.topic_templates.twin.clone(),
+                commands: mqtt_cloud.topic_templates.commands.clone(),
+            },
+            mapper: MqttCloudMapperSettings {
+                timestamp: mqtt_cloud.mapper.timestamp,
+                timestamp_format: mqtt_cloud.mapper.timestamp_format,
+            },
         }
     }
 }
@@ -271,7 +334,7 @@ where
 /// Trait to abstract over different cloud config readers
 ///
 /// This trait provides a uniform interface for accessing common fields
-/// from different cloud configuration readers (C8y, Az, Aws).
+/// from different cloud configuration readers (C8y, Az, Aws, MqttCloud).
 pub trait CloudConfigAccessor {
     fn url(&self) -> &OptionalConfig<ConnectUrl>;
     fn device_id(&self) -> Result<String, ReadError>;
@@ -468,6 +531,66 @@ impl CloudConfigAccessor for TEdgeConfigReaderAws {
     }
 }
 
+impl CloudConfigAccessor for TEdgeConfigReaderMqttCloud {
+    fn url(&self) -> &OptionalConfig<ConnectUrl> {
+        &self.url
+    }
+
+    fn device_id_key(&self, profile: Option<&str>) -> ReadableKey {
+        ReadableKey::MqttCloudDeviceId(profile.map(<_>::to_owned))
+    }
+
+    fn device_id(&self) -> Result<String, ReadError> {
+        Ok(self.device.id()?.clone())
+    }
+
+    fn device_key_path(&self) -> &AbsolutePath {
+        &self.device.key_path
+    }
+
+    fn device_cert_path(&self) -> &AbsolutePath {
+        &self.device.cert_path
+    }
+
+    fn device_csr_path(&self) -> &AbsolutePath {
+        &self.device.csr_path
+    }
+
+    fn device_key_uri(&self) -> Option<Arc<str>> {
+        self.device.key_uri.or_none().cloned()
+    }
+
+    fn device_key_pin(&self) -> Option<Arc<str>> {
+        self.device.key_pin.or_none().cloned()
+    }
+
+    fn bridge_topic_prefix(&self, profile: Option<&str>) -> Keyed<TopicPrefix> {
+        Keyed::new(
+            self.bridge.topic_prefix.clone(),
+            ReadableKey::MqttCloudBridgeTopicPrefix(profile.map(<_>::to_owned)),
+        )
+    }
+
+    fn bridge_keepalive_interval(&self) -> &SecondsOrHumanTime {
+        &self.bridge.keepalive_interval
+    }
+
+    fn topics(&self) -> &TemplatesSet {
+        &self.topics
+    }
+
+    fn root_cert_path(&self, profile: Option<&str>) -> Keyed<AbsolutePath> {
+        Keyed::new(
+            self.root_cert_path.clone(),
+            ReadableKey::MqttCloudRootCertPath(profile.map(<_>::to_owned)),
+        )
+    }
+
+    fn max_payload_size(&self) -> MqttPayloadLimit {
+        self.mapper.mqtt.max_payload_size
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -572,6 +695,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_load_mqtt_cloud_config_named_profile() {
+        let tedge_toml = r#"
+            [mqtt_cloud.profiles.hivemq]
+            url = "broker.example.com"
+            port = 8884
+            auth_method = "basic"
+            topic_templates.measurements = "devices/{device_id}/telemetry"
+        "#;
+
+        let tedge_config = TEdgeConfig::from_dto(
+            toml::from_str(tedge_toml).unwrap(),
+            TEdgeConfigLocation::from_custom_root("/tmp/tedge"),
+        );
+
+        let config: MqttCloudMapperConfig =
+            load_cloud_mapper_config(Some("hivemq"), &tedge_config).unwrap();
+
+        assert_eq!(
+            config.url().or_none().unwrap().as_str(),
+            "broker.example.com"
+        );
+        assert_eq!(config.cloud_specific.port, 8884);
+        assert_eq!(config.cloud_specific.auth_method, AuthMethod::Basic);
+        assert_eq!(config.bridge.topic_prefix.as_str(), "mqtt-cloud");
+        assert_eq!(
+            config.cloud_specific.topic_templates.measurements,
+            "devices/{device_id}/telemetry"
+        );
+        assert_eq!(
+            config.cloud_specific.topic_templates.events,
+            "tedge/{device_id}/{entity}/e/{type}"
+        );
+    }
+
     #[test]
     fn test_missing_url_preserves_key_name() {
         let tedge_toml = r#"
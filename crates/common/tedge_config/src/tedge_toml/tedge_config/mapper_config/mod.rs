@@ -6,6 +6,7 @@ use crate::tedge_toml::ReadableKey;
 use crate::tedge_toml::TEdgeConfigDtoAws;
 use crate::tedge_toml::TEdgeConfigDtoAz;
 use crate::tedge_toml::TEdgeConfigDtoC8y;
+use crate::tedge_toml::TEdgeConfigDtoMqttCloud;
 use crate::TEdgeConfig;
 use crate::TEdgeConfigDto;
 use crate::TEdgeConfigReader;
@@ -197,6 +198,23 @@ impl HasPath for TEdgeConfigDtoAws {
     }
 }
 
+impl HasPath for TEdgeConfigDtoMqttCloud {
+    fn set_mappers_root_dir(&mut self, path: Utf8PathBuf) {
+        self.mapper_config_dir = Some(path)
+    }
+
+    fn config_path(&self) -> Option<MapperConfigPath<'_>> {
+        Some(MapperConfigPath {
+            base_dir: Cow::Borrowed(self.mapper_config_dir.as_deref()?),
+            cloud_type: CloudType::MqttCloud,
+        })
+    }
+
+    fn set_mapper_config_file(&mut self, path: Utf8PathBuf) {
+        self.mapper_config_file = Some(path)
+    }
+}
+
 /// Base mapper configuration with common fields and cloud-specific fields via generics
 pub struct MapperConfig<T: SpecialisedCloudConfig> {
     pub(crate) location: Utf8PathBuf,
@@ -231,6 +249,57 @@ pub struct AwsCloudMapperConfig {
     pub timestamp_format: TimeFormat,
 }
 
+/// AWS IoT fleet provisioning by claim configuration
+pub struct AwsProvisioningConfig {
+    /// Name of the fleet provisioning template used to register the device
+    pub template_name: OptionalConfig<String>,
+
+    /// Parameters passed to the fleet provisioning template, as `name=value` pairs
+    pub template_parameters: OptionalConfig<TemplatesSet>,
+
+    /// Path to the claim certificate
+    pub claim_cert_path: OptionalConfig<AbsolutePath>,
+
+    /// Path to the private key of the claim certificate
+    pub claim_key_path: OptionalConfig<AbsolutePath>,
+}
+
+/// Generic MQTT cloud mapper configuration
+pub struct MqttCloudMapperSettings {
+    /// Whether to add timestamps to messages
+    pub timestamp: bool,
+
+    /// The timestamp format to use
+    pub timestamp_format: TimeFormat,
+}
+
+/// Remote topics used by the generic MQTT cloud mapper
+pub struct MqttCloudTopicTemplates {
+    /// Remote topic of measurements
+    pub measurements: String,
+
+    /// Remote topic of events
+    pub events: String,
+
+    /// Remote topic of alarms
+    pub alarms: String,
+
+    /// Remote topic of twin data
+    pub twin: String,
+
+    /// Remote topic on which commands are received
+    pub commands: String,
+}
+
+/// Azure IoT Hub Device Provisioning Service configuration
+pub struct AzDpsConfig {
+    /// ID scope of the DPS instance provisioning the device
+    pub id_scope: OptionalConfig<String>,
+
+    /// Endpoint of the DPS
+    pub url: HostPort<MQTT_TLS_PORT>,
+}
+
 /// Azure cloud-specific mapper configuration
 pub struct AzCloudMapperConfig {
     /// Whether to add timestamps to messages
@@ -369,6 +438,12 @@ pub struct SoftwareManagementConfig {
 pub struct OperationsConfig {
     /// Auto-upload the operation log once it finishes
     pub auto_log_upload: AutoLogUpload,
+
+    /// Maximum number of operations of the same type running concurrently on an entity
+    pub max_concurrent: u32,
+
+    /// Operation types that run alone on an entity
+    pub exclusive: TemplatesSet,
 }
 
 /// Availability/heartbeat configuration for Cumulocity
@@ -466,11 +541,33 @@ pub struct C8yMapperSpecificConfig {
 /// Azure IoT-specific mapper configuration fields
 pub struct AzMapperSpecificConfig {
     pub mapper: AzCloudMapperConfig,
+
+    /// Device Provisioning Service configuration
+    pub dps: AzDpsConfig,
 }
 
 /// AWS IoT-specific mapper configuration fields
 pub struct AwsMapperSpecificConfig {
     pub mapper: AwsCloudMapperConfig,
+
+    /// Fleet provisioning configuration
+    pub provisioning: AwsProvisioningConfig,
+}
+
+/// Generic MQTT cloud-specific mapper configuration fields
+pub struct MqttCloudMapperSpecificConfig {
+    /// Port of the remote MQTT broker
+    pub port: u16,
+
+    /// The authentication method used to connect the remote broker
+    pub auth_method: AuthMethod,
+
+    /// The path where the username/password are stored
+    pub credentials_path: AbsolutePath,
+
+    pub topic_templates: MqttCloudTopicTemplates,
+
+    pub mapper: MqttCloudMapperSettings,
 }
 
 /// CloudConfig implementation for C8y
@@ -575,6 +672,40 @@ impl SpecialisedCloudConfig for AwsMapperSpecificConfig {
     }
 }
 
+/// CloudConfig implementation for generic MQTT clouds
+impl SpecialisedCloudConfig for MqttCloudMapperSpecificConfig {
+    type CloudDto = TEdgeConfigDtoMqttCloud;
+
+    fn into_config_reader(
+        dto: Self::CloudDto,
+        base_config: &TEdgeConfig,
+        profile: Option<&str>,
+    ) -> Self::CloudConfigReader {
+        let mut multi_dto = MultiDto::default();
+        match profile {
+            Some(profile) => {
+                multi_dto.profiles.insert(profile.parse().unwrap(), dto);
+            }
+            None => multi_dto.non_profile = dto,
+        };
+        let mut reader = TEdgeConfigReader::from_dto(
+            &TEdgeConfigDto {
+                mqtt_cloud: multi_dto,
+                ..base_config.dto.clone()
+            },
+            &base_config.location,
+        );
+        match profile {
+            None => reader.mqtt_cloud.non_profile,
+            Some(profile) => reader
+                .mqtt_cloud
+                .profiles
+                .remove(&profile.parse::<ProfileName>().unwrap())
+                .unwrap(),
+        }
+    }
+}
+
 /// Type alias for Cumulocity mapper configuration
 pub type C8yMapperConfig = MapperConfig<C8yMapperSpecificConfig>;
 
@@ -584,6 +715,9 @@ pub type AzMapperConfig = MapperConfig<AzMapperSpecificConfig>;
 /// Type alias for AWS IoT mapper configuration
 pub type AwsMapperConfig = MapperConfig<AwsMapperSpecificConfig>;
 
+/// Type alias for generic MQTT cloud mapper configuration
+pub type MqttCloudMapperConfig = MapperConfig<MqttCloudMapperSpecificConfig>;
+
 /// Error type for mapper configuration loading
 #[derive(Debug, thiserror::Error)]
 pub enum MapperConfigError {
@@ -634,6 +768,12 @@ impl ExpectedCloudType for AwsMapperSpecificConfig {
     }
 }
 
+impl ExpectedCloudType for MqttCloudMapperSpecificConfig {
+    fn expected_cloud_type() -> CloudType {
+        CloudType::MqttCloud
+    }
+}
+
 pub trait HasUrl {
     // The configured URL field, used to check whether profiles are
     fn configured_url(&self) -> &OptionalConfig<ConnectUrl>;
@@ -669,7 +809,7 @@ fn device_id_from_cert(cert_path: &Utf8Path) -> Result<String, MapperConfigError
     Ok(device_id)
 }
 
-// Allow access to url directly for az and aws, but require c8y dependent crates
+// Allow access to url directly for az, aws and generic MQTT clouds, but require c8y dependent crates
 // to access the url through mqtt/http variables
 impl MapperConfig<AzMapperSpecificConfig> {
     /// Get the cloud URL for Azure
@@ -685,6 +825,13 @@ impl MapperConfig<AwsMapperSpecificConfig> {
     }
 }
 
+impl MapperConfig<MqttCloudMapperSpecificConfig> {
+    /// Get the host name of the remote MQTT broker
+    pub fn url(&self) -> &OptionalConfig<ConnectUrl> {
+        &self.url
+    }
+}
+
 impl MapperConfig<C8yMapperSpecificConfig> {
     /// Get the MQTT endpoint for Cumulocity
     pub fn mqtt(&self) -> &OptionalConfig<HostPort<MQTT_TLS_PORT>> {
@@ -232,6 +232,45 @@ impl TEdgeConfig {
 
         client_auth
     }
+
+    /// Returns the configuration of an MQTT client connecting the broker where Sparkplug messages are published.
+    pub fn sparkplug_mqtt_config(&self) -> Result<mqtt_channel::Config, CertificateError> {
+        let host = self.sparkplug.broker.host.as_str();
+        let port = u16::from(self.sparkplug.broker.port);
+
+        let mut mqtt_config = mqtt_channel::Config::default()
+            .with_host(host)
+            .with_port(port);
+
+        let sparkplug_auth_config = self.sparkplug_auth_config();
+        mqtt_config.with_client_auth(sparkplug_auth_config.try_into()?)?;
+
+        Ok(mqtt_config)
+    }
+
+    /// Returns an authentication configuration for an MQTT client that will connect to the Sparkplug broker.
+    fn sparkplug_auth_config(&self) -> TEdgeMqttClientAuthConfig {
+        let auth = &self.sparkplug.broker.auth;
+        let mut client_auth = TEdgeMqttClientAuthConfig {
+            ca_dir: auth.ca_dir.or_none().cloned().map(Utf8PathBuf::from),
+            ca_file: auth.ca_file.or_none().cloned().map(Utf8PathBuf::from),
+            client_cert: None,
+            username: auth.username.or_none().cloned(),
+            password_file: auth.password_file.or_none().cloned().map(Utf8PathBuf::from),
+        };
+
+        // Both these options have to either be set or not set
+        if let Ok(Some((client_cert, client_key))) =
+            all_or_nothing((auth.cert_file.as_ref(), auth.key_file.as_ref()))
+        {
+            client_auth.client_cert = Some(MqttAuthClientCertConfig {
+                cert_file: client_cert.clone().into(),
+                key_file: client_key.clone().into(),
+            })
+        }
+
+        client_auth
+    }
 }
 
 impl TEdgeConfigReaderDevice {
@@ -0,0 +1,104 @@
+use crate::SessionEnd;
+use crate::SessionStats;
+use serde::Serialize;
+use std::path::PathBuf;
+use tedge_config::TEdgeConfig;
+use time::OffsetDateTime;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+const AUDIT_FILE_NAME: &str = "audit.jsonl";
+
+/// A JSON lines file recording all the remote access sessions
+///
+/// Failing to write the audit log doesn't prevent the sessions to be opened,
+/// but is reported as a warning.
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    lock: Mutex<()>,
+}
+
+/// An entry of the audit log
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum AuditRecord {
+    Started {
+        session_id: String,
+        initiator: String,
+        target: String,
+        #[serde(with = "time::serde::rfc3339")]
+        time: OffsetDateTime,
+    },
+    Ended {
+        session_id: String,
+        initiator: String,
+        target: String,
+        #[serde(with = "time::serde::rfc3339")]
+        started: OffsetDateTime,
+        #[serde(with = "time::serde::rfc3339")]
+        time: OffsetDateTime,
+        reason: SessionEnd,
+        #[serde(flatten)]
+        stats: SessionStats,
+    },
+    Rejected {
+        session_id: String,
+        initiator: String,
+        target: String,
+        #[serde(with = "time::serde::rfc3339")]
+        time: OffsetDateTime,
+        reason: String,
+    },
+}
+
+impl AuditLog {
+    /// Audit log stored under the `remote-access` directory of `logs.path`
+    pub fn from_tedge_config(config: &TEdgeConfig) -> Self {
+        let dir = config.logs.path.join("remote-access");
+        AuditLog::new(dir.join(AUDIT_FILE_NAME).into_std_path_buf())
+    }
+
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        AuditLog {
+            path: Some(path.into()),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// An audit log that records nothing
+    pub fn disabled() -> Self {
+        AuditLog {
+            path: None,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub async fn record(&self, record: AuditRecord) {
+        let Some(path) = self.path.as_ref() else {
+            return;
+        };
+        let _guard = self.lock.lock().await;
+        if let Err(err) = append(path, &record).await {
+            warn!(
+                "Failed to write remote access audit log {}: {err}",
+                path.display()
+            );
+        }
+    }
+}
+
+async fn append(path: &PathBuf, record: &AuditRecord) -> Result<(), std::io::Error> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(line.as_bytes()).await?;
+    file.flush().await
+}
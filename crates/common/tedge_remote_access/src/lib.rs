@@ -0,0 +1,26 @@
+//! Cloud-agnostic remote access tunnels
+//!
+//! A remote access tunnel forwards a TCP connection to a local target (e.g. an SSH server)
+//! over a stream opened by a cloud-specific adapter (e.g. a Cumulocity websocket).
+//!
+//! This crate provides what is common to all the clouds:
+//! - the [`TunnelPolicy`] restricting the targets and the duration of the sessions,
+//! - the [`TunnelService`] running concurrent sessions along that policy,
+//! - the [`AuditLog`] recording who opened which session, when, and how much data has been exchanged.
+//!
+//! The cloud adapter is only responsible for opening the cloud side of the tunnel.
+mod audit;
+mod policy;
+mod service;
+mod session;
+
+pub use audit::AuditLog;
+pub use audit::AuditRecord;
+pub use policy::InvalidTargetPattern;
+pub use policy::TargetPattern;
+pub use policy::TunnelPolicy;
+pub use service::TunnelError;
+pub use service::TunnelRequest;
+pub use service::TunnelService;
+pub use session::SessionEnd;
+pub use session::SessionStats;
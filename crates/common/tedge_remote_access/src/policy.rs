@@ -0,0 +1,157 @@
+use std::str::FromStr;
+use std::time::Duration;
+use tedge_config::TEdgeConfig;
+
+/// The rules applied to all the remote access sessions
+#[derive(Clone, Debug, Default)]
+pub struct TunnelPolicy {
+    /// The targets that can be reached, none if empty
+    pub allowed_targets: Vec<TargetPattern>,
+
+    /// Close the sessions with no traffic for this duration
+    pub idle_timeout: Option<Duration>,
+
+    /// Close the sessions opened for this duration
+    pub max_duration: Option<Duration>,
+}
+
+impl TunnelPolicy {
+    /// Build the policy defined by the `remote_access` settings
+    ///
+    /// Invalid target patterns are ignored with a warning.
+    pub fn from_tedge_config(config: &TEdgeConfig) -> Self {
+        let allowed_targets = config
+            .remote_access
+            .allowed_targets
+            .0
+            .iter()
+            .filter_map(|pattern| match pattern.parse() {
+                Ok(pattern) => Some(pattern),
+                Err(err) => {
+                    tracing::warn!("Ignoring remote_access.allowed_targets entry: {err}");
+                    None
+                }
+            })
+            .collect();
+        let non_zero = |duration: Duration| (!duration.is_zero()).then_some(duration);
+
+        TunnelPolicy {
+            allowed_targets,
+            idle_timeout: non_zero(config.remote_access.idle_timeout.duration()),
+            max_duration: non_zero(config.remote_access.max_duration.duration()),
+        }
+    }
+
+    /// Check that a target can be reached through a tunnel
+    pub fn allows(&self, host: &str, port: u16) -> bool {
+        self.allowed_targets
+            .iter()
+            .any(|pattern| pattern.matches(host, port))
+    }
+}
+
+/// A `host:port` pattern, where the host and the port can be `*`, and the port a range `min-max`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TargetPattern {
+    host: Option<String>,
+    ports: Option<(u16, u16)>,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Invalid target pattern {pattern:?}: {reason}")]
+pub struct InvalidTargetPattern {
+    pattern: String,
+    reason: &'static str,
+}
+
+impl TargetPattern {
+    pub fn matches(&self, host: &str, port: u16) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let host_matches = self
+            .host
+            .as_ref()
+            .is_none_or(|pattern| pattern.eq_ignore_ascii_case(host));
+        let port_matches = self
+            .ports
+            .is_none_or(|(min, max)| min <= port && port <= max);
+        host_matches && port_matches
+    }
+}
+
+impl FromStr for TargetPattern {
+    type Err = InvalidTargetPattern;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason| InvalidTargetPattern {
+            pattern: pattern.to_string(),
+            reason,
+        };
+        let (host, port) = pattern
+            .rsplit_once(':')
+            .ok_or_else(|| invalid("expected host:port"))?;
+
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let host = match host {
+            "" => return Err(invalid("missing host")),
+            "*" => None,
+            host => Some(host.to_string()),
+        };
+
+        let parse_port = |port: &str| port.parse::<u16>().map_err(|_| invalid("invalid port"));
+        let ports = match port {
+            "*" => None,
+            range => match range.split_once('-') {
+                Some((min, max)) => {
+                    let (min, max) = (parse_port(min)?, parse_port(max)?);
+                    if min > max {
+                        return Err(invalid("empty port range"));
+                    }
+                    Some((min, max))
+                }
+                None => {
+                    let port = parse_port(range)?;
+                    Some((port, port))
+                }
+            },
+        };
+
+        Ok(TargetPattern { host, ports })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_are_checked_against_the_allowlist() {
+        let policy = TunnelPolicy {
+            allowed_targets: ["127.0.0.1:22", "[::1]:*", "*:5900-5910", "Gateway:80"]
+                .iter()
+                .map(|pattern| pattern.parse().unwrap())
+                .collect(),
+            ..Default::default()
+        };
+
+        assert!(policy.allows("127.0.0.1", 22));
+        assert!(!policy.allows("127.0.0.1", 23));
+        assert!(policy.allows("::1", 8080));
+        assert!(policy.allows("[::1]", 8080));
+        assert!(policy.allows("192.168.1.10", 5905));
+        assert!(!policy.allows("192.168.1.10", 5911));
+        assert!(policy.allows("gateway", 80));
+        assert!(!policy.allows("10.0.0.1", 22));
+    }
+
+    #[test]
+    fn nothing_is_allowed_by_an_empty_allowlist() {
+        assert!(!TunnelPolicy::default().allows("127.0.0.1", 22));
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        for pattern in ["localhost", ":22", "host:port", "host:90-80", "host:70000"] {
+            assert!(pattern.parse::<TargetPattern>().is_err(), "{pattern}");
+        }
+    }
+}
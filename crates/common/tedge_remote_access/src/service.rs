@@ -0,0 +1,261 @@
+use crate::session::run_session;
+use crate::AuditLog;
+use crate::AuditRecord;
+use crate::SessionEnd;
+use crate::SessionStats;
+use crate::TunnelPolicy;
+use std::future::Future;
+use std::sync::Arc;
+use time::OffsetDateTime;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tracing::info;
+
+/// Runs concurrent remote access sessions along a [`TunnelPolicy`]
+///
+/// Each session is run in its own task and recorded in the [`AuditLog`].
+#[derive(Clone)]
+pub struct TunnelService {
+    policy: Arc<TunnelPolicy>,
+    audit: Arc<AuditLog>,
+}
+
+/// A request from the cloud to open a tunnel to a local target
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TunnelRequest {
+    /// Cloud-specific identifier of the session
+    pub session_id: String,
+
+    /// Who requested the session, as known by the cloud
+    pub initiator: String,
+
+    pub host: String,
+    pub port: u16,
+}
+
+impl TunnelRequest {
+    fn target(&self) -> String {
+        if self.host.contains(':') && !self.host.starts_with('[') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TunnelError {
+    #[error("Target {0} is not allowed by remote_access.allowed_targets")]
+    TargetNotAllowed(String),
+
+    #[error("Failed to connect to target {target}: {error}")]
+    Target {
+        target: String,
+        error: std::io::Error,
+    },
+
+    #[error("Failed to connect to the cloud: {0}")]
+    Cloud(String),
+}
+
+impl TunnelService {
+    pub fn new(policy: TunnelPolicy, audit: AuditLog) -> Self {
+        TunnelService {
+            policy: Arc::new(policy),
+            audit: Arc::new(audit),
+        }
+    }
+
+    /// Open a tunnel between the requested target and the cloud
+    ///
+    /// The cloud side of the tunnel is only connected if the target is allowed by the policy.
+    /// Once both sides are connected, the session is spawned in the background,
+    /// the returned handle resolving when the session is closed.
+    pub async fn open<C, E>(
+        &self,
+        request: TunnelRequest,
+        connect_cloud: impl Future<Output = Result<C, E>>,
+    ) -> Result<JoinHandle<(SessionEnd, SessionStats)>, TunnelError>
+    where
+        C: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        E: std::fmt::Display,
+    {
+        let target = request.target();
+        if !self.policy.allows(&request.host, request.port) {
+            let error = TunnelError::TargetNotAllowed(target);
+            self.reject(&request, &error).await;
+            return Err(error);
+        }
+
+        let host = request.host.trim_start_matches('[').trim_end_matches(']');
+        let (target_stream, cloud_stream) =
+            tokio::join!(TcpStream::connect((host, request.port)), connect_cloud);
+        let target_stream = match target_stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                let error = TunnelError::Target { target, error };
+                self.reject(&request, &error).await;
+                return Err(error);
+            }
+        };
+        let cloud_stream = match cloud_stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                let error = TunnelError::Cloud(err.to_string());
+                self.reject(&request, &error).await;
+                return Err(error);
+            }
+        };
+
+        let started = OffsetDateTime::now_utc();
+        info!(
+            "Remote access session {} opened by {} to {target}",
+            request.session_id, request.initiator
+        );
+        self.audit
+            .record(AuditRecord::Started {
+                session_id: request.session_id.clone(),
+                initiator: request.initiator.clone(),
+                target: target.clone(),
+                time: started,
+            })
+            .await;
+
+        let policy = self.policy.clone();
+        let audit = self.audit.clone();
+        Ok(tokio::spawn(async move {
+            let (reason, stats) = run_session(target_stream, cloud_stream, &policy).await;
+            info!(
+                "Remote access session {} to {target} closed: {reason:?}",
+                request.session_id
+            );
+            audit
+                .record(AuditRecord::Ended {
+                    session_id: request.session_id,
+                    initiator: request.initiator,
+                    target,
+                    started,
+                    time: OffsetDateTime::now_utc(),
+                    reason: reason.clone(),
+                    stats,
+                })
+                .await;
+            (reason, stats)
+        }))
+    }
+
+    async fn reject(&self, request: &TunnelRequest, error: &TunnelError) {
+        self.audit
+            .record(AuditRecord::Rejected {
+                session_id: request.session_id.clone(),
+                initiator: request.initiator.clone(),
+                target: request.target(),
+                time: OffsetDateTime::now_utc(),
+                reason: error.to_string(),
+            })
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::duplex;
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::io::DuplexStream;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn sessions_to_unlisted_targets_are_rejected_and_audited() {
+        let ttd = tempfile::tempdir().unwrap();
+        let audit_path = ttd.path().join("audit.jsonl");
+        let service = TunnelService::new(policy(&["127.0.0.1:22"]), AuditLog::new(&audit_path));
+
+        let mut cloud_connected = false;
+        let result = service
+            .open(request("s-1", "127.0.0.1", 8080), async {
+                cloud_connected = true;
+                Ok::<DuplexStream, String>(duplex(64).0)
+            })
+            .await;
+
+        assert!(matches!(result, Err(TunnelError::TargetNotAllowed(_))));
+        assert!(!cloud_connected);
+        let audit = std::fs::read_to_string(&audit_path).unwrap();
+        assert!(audit.contains(r#""event":"rejected""#), "{audit}");
+        assert!(audit.contains(r#""target":"127.0.0.1:8080""#), "{audit}");
+    }
+
+    #[tokio::test]
+    async fn concurrent_sessions_are_audited() {
+        let ttd = tempfile::tempdir().unwrap();
+        let audit_path = ttd.path().join("audit.jsonl");
+        let service = TunnelService::new(policy(&["127.0.0.1:*"]), AuditLog::new(&audit_path));
+
+        // An echo server as target
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (mut reader, mut writer) = stream.split();
+                    let _ = tokio::io::copy(&mut reader, &mut writer).await;
+                });
+            }
+        });
+
+        let mut sessions = vec![];
+        let mut clouds = vec![];
+        for id in ["s-1", "s-2"] {
+            let (cloud, cloud_peer) = duplex(1024);
+            let session = service
+                .open(request(id, "127.0.0.1", port), async {
+                    Ok::<_, String>(cloud)
+                })
+                .await
+                .unwrap();
+            sessions.push(session);
+            clouds.push(cloud_peer);
+        }
+
+        for (i, cloud) in clouds.iter_mut().enumerate() {
+            let message = format!("hello {i}");
+            cloud.write_all(message.as_bytes()).await.unwrap();
+            let mut buffer = vec![0; message.len()];
+            cloud.read_exact(&mut buffer).await.unwrap();
+            assert_eq!(buffer, message.as_bytes());
+        }
+        drop(clouds);
+
+        for session in sessions {
+            let (end, stats) = session.await.unwrap();
+            assert_eq!(end, SessionEnd::ClosedByCloud);
+            assert_eq!(stats.bytes_to_target, 7);
+            assert_eq!(stats.bytes_from_target, 7);
+        }
+
+        let audit = std::fs::read_to_string(&audit_path).unwrap();
+        assert_eq!(audit.matches(r#""event":"started""#).count(), 2, "{audit}");
+        assert_eq!(audit.matches(r#""event":"ended""#).count(), 2, "{audit}");
+        assert!(audit.contains(r#""bytes_to_target":7"#), "{audit}");
+    }
+
+    fn policy(targets: &[&str]) -> TunnelPolicy {
+        TunnelPolicy {
+            allowed_targets: targets.iter().map(|t| t.parse().unwrap()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn request(session_id: &str, host: &str, port: u16) -> TunnelRequest {
+        TunnelRequest {
+            session_id: session_id.to_string(),
+            initiator: "test".to_string(),
+            host: host.to_string(),
+            port,
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use crate::TunnelPolicy;
+use serde::Serialize;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio::time::sleep_until;
+use tokio::time::Instant;
+
+const BUFFER_SIZE: usize = 16 * 1024;
+
+/// Why a session has been closed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SessionEnd {
+    /// The connection has been closed by the target
+    ClosedByTarget,
+
+    /// The connection has been closed by the cloud
+    ClosedByCloud,
+
+    /// No data has been exchanged for longer than the idle timeout
+    IdleTimeout,
+
+    /// The session has been opened for longer than the maximum session duration
+    MaxDuration,
+
+    /// The connection has been interrupted by an error
+    Error(String),
+}
+
+/// The amount of data exchanged during a session
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SessionStats {
+    /// Bytes received from the cloud and sent to the target
+    pub bytes_to_target: u64,
+
+    /// Bytes received from the target and sent to the cloud
+    pub bytes_from_target: u64,
+}
+
+/// Forward the data between the target and the cloud, until either side closes the connection
+/// or the session reaches a limit set by the policy
+pub(crate) async fn run_session<T, C>(
+    target: T,
+    cloud: C,
+    policy: &TunnelPolicy,
+) -> (SessionEnd, SessionStats)
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+    C: AsyncRead + AsyncWrite + Unpin,
+{
+    let (mut target_reader, mut target_writer) = tokio::io::split(target);
+    let (mut cloud_reader, mut cloud_writer) = tokio::io::split(cloud);
+    let mut target_buffer = vec![0; BUFFER_SIZE];
+    let mut cloud_buffer = vec![0; BUFFER_SIZE];
+    let mut stats = SessionStats::default();
+
+    let far_future = Instant::now() + std::time::Duration::from_secs(86400 * 365 * 30);
+    let deadline = policy
+        .max_duration
+        .map_or(far_future, |duration| Instant::now() + duration);
+    let idle_deadline = |now: Instant| {
+        policy
+            .idle_timeout
+            .map_or(far_future, |duration| now + duration)
+    };
+    let mut idle = idle_deadline(Instant::now());
+
+    let end = loop {
+        tokio::select! {
+            read = target_reader.read(&mut target_buffer) => match read {
+                Ok(0) => break SessionEnd::ClosedByTarget,
+                Ok(n) => {
+                    if let Err(err) = cloud_writer.write_all(&target_buffer[..n]).await {
+                        break SessionEnd::Error(err.to_string());
+                    }
+                    stats.bytes_from_target += n as u64;
+                }
+                Err(err) => break SessionEnd::Error(err.to_string()),
+            },
+            read = cloud_reader.read(&mut cloud_buffer) => match read {
+                Ok(0) => break SessionEnd::ClosedByCloud,
+                Ok(n) => {
+                    if let Err(err) = target_writer.write_all(&cloud_buffer[..n]).await {
+                        break SessionEnd::Error(err.to_string());
+                    }
+                    stats.bytes_to_target += n as u64;
+                }
+                Err(err) => break SessionEnd::Error(err.to_string()),
+            },
+            _ = sleep_until(idle) => break SessionEnd::IdleTimeout,
+            _ = sleep_until(deadline) => break SessionEnd::MaxDuration,
+        }
+        idle = idle_deadline(Instant::now());
+    };
+
+    let _ = tokio::join!(target_writer.shutdown(), cloud_writer.shutdown());
+    (end, stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::duplex;
+
+    #[tokio::test]
+    async fn data_is_forwarded_both_ways() {
+        let (target, mut target_peer) = duplex(1024);
+        let (cloud, mut cloud_peer) = duplex(1024);
+        let session =
+            tokio::spawn(async move { run_session(target, cloud, &TunnelPolicy::default()).await });
+
+        cloud_peer.write_all(b"ping").await.unwrap();
+        let mut buffer = [0; 4];
+        target_peer.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"ping");
+
+        target_peer.write_all(b"pong!").await.unwrap();
+        let mut buffer = [0; 5];
+        cloud_peer.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"pong!");
+
+        drop(target_peer);
+        let (end, stats) = session.await.unwrap();
+        assert_eq!(end, SessionEnd::ClosedByTarget);
+        assert_eq!(
+            stats,
+            SessionStats {
+                bytes_to_target: 4,
+                bytes_from_target: 5
+            }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_sessions_are_closed() {
+        let (target, _target_peer) = duplex(1024);
+        let (cloud, _cloud_peer) = duplex(1024);
+        let policy = TunnelPolicy {
+            idle_timeout: Some(Duration::from_secs(60)),
+            max_duration: Some(Duration::from_secs(3600)),
+            ..Default::default()
+        };
+
+        let (end, _) = run_session(target, cloud, &policy).await;
+        assert_eq!(end, SessionEnd::IdleTimeout);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sessions_are_closed_after_the_max_duration() {
+        let (target, _target_peer) = duplex(1024);
+        let (cloud, mut cloud_peer) = duplex(1024);
+        let policy = TunnelPolicy {
+            idle_timeout: Some(Duration::from_secs(60)),
+            max_duration: Some(Duration::from_secs(135)),
+            ..Default::default()
+        };
+        let session = tokio::spawn(async move { run_session(target, cloud, &policy).await });
+
+        // Keep the session active
+        for _ in 0..10 {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            if cloud_peer.write_all(b"keep-alive").await.is_err() {
+                break;
+            }
+        }
+
+        let (end, stats) = session.await.unwrap();
+        assert_eq!(end, SessionEnd::MaxDuration);
+        assert_eq!(stats.bytes_to_target, 40);
+    }
+}
@@ -154,6 +154,46 @@ impl C8yEndPoint {
         )
     }
 
+    pub fn proxy_url_for_create_measurements(&self) -> String {
+        Self::url_for_create_measurements(&self.proxy.base_url())
+    }
+
+    pub fn proxy_url_for_smartrest_template(&self, collection: &str) -> String {
+        Self::url_for_smartrest_template(&self.proxy.base_url(), collection)
+    }
+
+    pub fn proxy_url_for_create_managed_object(&self) -> String {
+        Self::url_for_create_managed_object(&self.proxy.base_url())
+    }
+
+    pub fn proxy_url_for_managed_object(&self, internal_id: &str) -> String {
+        Self::url_for_managed_object(&self.proxy.base_url(), internal_id)
+    }
+
+    pub fn proxy_url_for_external_ids(&self, internal_id: &str) -> String {
+        Self::url_for_external_ids(&self.proxy.base_url(), internal_id)
+    }
+
+    fn url_for_create_measurements(host: &str) -> String {
+        format!("{host}/measurement/measurements")
+    }
+
+    fn url_for_smartrest_template(host: &str, collection: &str) -> String {
+        format!("{host}/identity/externalIds/c8y_SmartRest2DeviceIdentifier/{collection}")
+    }
+
+    fn url_for_create_managed_object(host: &str) -> String {
+        format!("{host}/inventory/managedObjects")
+    }
+
+    fn url_for_managed_object(host: &str, internal_id: &str) -> String {
+        format!("{host}/inventory/managedObjects/{internal_id}")
+    }
+
+    fn url_for_external_ids(host: &str, internal_id: &str) -> String {
+        format!("{host}/identity/globalIds/{internal_id}/externalIds")
+    }
+
     fn url_for_sw_list(host: &str, internal_id: &str) -> String {
         format!("{host}/inventory/managedObjects/{internal_id}")
     }
@@ -43,6 +43,15 @@ pub struct C8yEventResponse {
     pub id: String,
 }
 
+/// A collection of measurements, as posted to the Cumulocity measurement API
+///
+/// Each measurement is either related to its source by internal id (`source.id`)
+/// or by external id (`externalSource.externalId`) till the internal id is known.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct C8yMeasurementCollection {
+    pub measurements: Vec<Value>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct C8yManagedObject {
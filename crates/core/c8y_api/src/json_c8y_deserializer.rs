@@ -40,6 +40,7 @@ pub enum C8yDeviceControlOperation {
     DownloadConfigFile(C8yDownloadConfigFile),
     Firmware(C8yFirmware),
     DeviceProfile(C8yDeviceProfile),
+    Command(C8yCommand),
     Custom,
 }
 
@@ -71,6 +72,8 @@ impl C8yDeviceControlOperation {
             C8yDeviceControlOperation::DeviceProfile(C8yDeviceProfile::from_json_value(
                 value.clone(),
             )?)
+        } else if let Some(value) = hashmap.get("c8y_Command") {
+            C8yDeviceControlOperation::Command(C8yCommand::from_json_value(value.clone())?)
         } else {
             C8yDeviceControlOperation::Custom
         };
@@ -164,6 +167,23 @@ impl C8yOperation {
 #[derive(Debug, Deserialize, Eq, PartialEq)]
 pub struct C8yRestart {}
 
+/// Representation of c8y_Command JSON object
+///
+/// ```rust
+/// use c8y_api::json_c8y_deserializer::C8yCommand;
+///
+/// // Example input from c8y
+/// let data = r#"{"text": "uptime"}"#;
+///
+/// // Parse the data
+/// let req: C8yCommand = serde_json::from_str(data).unwrap();
+/// assert_eq!(req.text, "uptime");
+/// ```
+#[derive(Debug, Deserialize, Eq, PartialEq)]
+pub struct C8yCommand {
+    pub text: String,
+}
+
 /// Representation of c8y_SoftwareUpdate JSON object
 ///
 /// ```rust
@@ -574,6 +594,8 @@ impl C8yDeviceControlOperationHelper for C8yFirmware {}
 
 impl C8yDeviceControlOperationHelper for C8yDeviceProfile {}
 
+impl C8yDeviceControlOperationHelper for C8yCommand {}
+
 #[derive(thiserror::Error, Debug)]
 pub enum C8yJsonOverMqttDeserializerError {
     #[error("Parameter {parameter} is not recognized. {hint}")]
@@ -7,4 +7,5 @@ pub mod message_ids;
 pub mod payload;
 pub mod smartrest_deserializer;
 pub mod smartrest_serializer;
+pub mod template_collection;
 pub mod topic;
@@ -189,6 +189,7 @@ pub enum CumulocitySupportedOperations {
     C8yDownloadConfigFile,
     C8yFirmware,
     C8yDeviceProfile,
+    C8yCommand,
     C8yCustom(String),
 }
 
@@ -202,6 +203,7 @@ impl CumulocitySupportedOperations {
             CumulocitySupportedOperations::C8yDownloadConfigFile => "c8y_DownloadConfigFile",
             CumulocitySupportedOperations::C8yFirmware => "c8y_Firmware",
             CumulocitySupportedOperations::C8yDeviceProfile => "c8y_DeviceProfile",
+            CumulocitySupportedOperations::C8yCommand => "c8y_Command",
             CumulocitySupportedOperations::C8yCustom(operation) => operation.as_str(),
         }
     }
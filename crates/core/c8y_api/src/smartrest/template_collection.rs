@@ -0,0 +1,149 @@
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+/// Type of the managed objects used by Cumulocity to store SmartREST 2.0 template collections
+pub const SMARTREST_TEMPLATE_TYPE: &str = "c8y_SmartRest2Template";
+
+/// Type of the external ids used by Cumulocity to name SmartREST 2.0 template collections
+pub const SMARTREST_TEMPLATE_IDENTITY_TYPE: &str = "c8y_SmartRest2DeviceIdentifier";
+
+/// A SmartREST 2.0 template collection
+///
+/// The JSON representation is the one used by Cumulocity when a template collection is exported,
+/// i.e. a managed object with an `__externalId` naming the collection
+/// and a `com_cumulocity_model_smartrest_csv_CsvSmartRestTemplate` fragment listing the templates.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmartRestTemplateCollection {
+    #[serde(rename = "__externalId")]
+    pub name: String,
+
+    #[serde(rename = "com_cumulocity_model_smartrest_csv_CsvSmartRestTemplate")]
+    pub templates: SmartRestTemplates,
+}
+
+/// The request and response templates of a SmartREST 2.0 template collection
+///
+/// The templates are kept as provided, only their message ids being interpreted.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartRestTemplates {
+    #[serde(default)]
+    pub request_templates: Vec<Value>,
+
+    #[serde(default)]
+    pub response_templates: Vec<Value>,
+}
+
+/// A SmartREST 2.0 template collection as returned by Cumulocity
+#[derive(Clone, Debug, Deserialize)]
+pub struct RegisteredSmartRestTemplates {
+    pub id: String,
+
+    #[serde(
+        rename = "com_cumulocity_model_smartrest_csv_CsvSmartRestTemplate",
+        default
+    )]
+    pub templates: SmartRestTemplates,
+}
+
+impl SmartRestTemplateCollection {
+    /// The managed object to be created in Cumulocity for this collection
+    pub fn managed_object(&self) -> Value {
+        json!({
+            "name": self.name,
+            "type": SMARTREST_TEMPLATE_TYPE,
+            "com_cumulocity_model_smartrest_csv_CsvSmartRestTemplate": self.templates,
+        })
+    }
+
+    /// The fragment to be updated in Cumulocity for this collection
+    pub fn managed_object_update(&self) -> Value {
+        json!({
+            "com_cumulocity_model_smartrest_csv_CsvSmartRestTemplate": self.templates,
+        })
+    }
+}
+
+impl SmartRestTemplates {
+    /// The message ids of the request templates
+    pub fn request_ids(&self) -> BTreeSet<&str> {
+        message_ids(&self.request_templates)
+    }
+
+    /// The message ids of the response templates
+    pub fn response_ids(&self) -> BTreeSet<&str> {
+        message_ids(&self.response_templates)
+    }
+
+    /// The message ids of the templates that are not defined by the registered templates
+    pub fn missing_from(&self, registered: &SmartRestTemplates) -> Vec<String> {
+        let registered_requests = registered.request_ids();
+        let registered_responses = registered.response_ids();
+        self.request_ids()
+            .difference(&registered_requests)
+            .chain(self.response_ids().difference(&registered_responses))
+            .map(|id| id.to_string())
+            .collect()
+    }
+}
+
+fn message_ids(templates: &[Value]) -> BTreeSet<&str> {
+    templates
+        .iter()
+        .filter_map(|template| template.get("msgId"))
+        .filter_map(|id| id.as_str())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_exported_template_collection() {
+        let exported = r#"{
+            "name": "tedge-custom",
+            "type": "c8y_SmartRest2Template",
+            "com_cumulocity_model_smartrest_csv_CsvSmartRestTemplate": {
+                "requestTemplates": [
+                    {"msgId": "100", "api": "INVENTORY", "method": "PUT", "name": "setHardware"}
+                ],
+                "responseTemplates": [
+                    {"msgId": "dm101", "condition": "c8y_Command", "pattern": ["c8y_Command.text"]}
+                ]
+            },
+            "__externalId": "tedge-custom"
+        }"#;
+
+        let collection: SmartRestTemplateCollection = serde_json::from_str(exported).unwrap();
+        assert_eq!(collection.name, "tedge-custom");
+        assert_eq!(collection.templates.request_ids(), BTreeSet::from(["100"]));
+        assert_eq!(
+            collection.templates.response_ids(),
+            BTreeSet::from(["dm101"])
+        );
+        assert_eq!(collection.managed_object()["type"], SMARTREST_TEMPLATE_TYPE);
+    }
+
+    #[test]
+    fn missing_templates_are_detected_by_message_id() {
+        let expected: SmartRestTemplates = serde_json::from_value(json!({
+            "requestTemplates": [{"msgId": "100"}, {"msgId": "101"}],
+            "responseTemplates": [{"msgId": "dm101"}]
+        }))
+        .unwrap();
+        let registered: SmartRestTemplates = serde_json::from_value(json!({
+            "requestTemplates": [{"msgId": "100", "name": "enriched by c8y"}]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            expected.missing_from(&registered),
+            vec!["101".to_string(), "dm101".to_string()]
+        );
+        assert!(expected.missing_from(&expected).is_empty());
+    }
+}
@@ -56,6 +56,9 @@ impl From<BridgeConfigAwsParams> for BridgeConfig {
         let shadow_topic =
             format!("shadow/# both 1 {topic_prefix}/ $aws/things/{remote_clientid}/");
 
+        // topic to interact with the jobs of the device
+        let jobs_topic = format!("jobs/# both 1 {topic_prefix}/ $aws/things/{remote_clientid}/");
+
         // echo topic mapping to check the connection
         let connection_check_pub_msg_topic = format!(
             r#""" out 1 {topic_prefix}/test-connection thinedge/devices/{remote_clientid}/test-connection"#
@@ -104,6 +107,7 @@ impl From<BridgeConfigAwsParams> for BridgeConfig {
                 pub_msg_topic,
                 sub_msg_topic,
                 shadow_topic,
+                jobs_topic,
                 connection_check_pub_msg_topic,
                 connection_check_sub_msg_topic,
             ],
@@ -157,6 +161,7 @@ fn test_bridge_config_from_aws_params() -> anyhow::Result<()> {
             "td/# out 1 aws/ thinedge/alpha/".into(),
             "cmd/# in 1 aws/ thinedge/alpha/".into(),
             "shadow/# both 1 aws/ $aws/things/alpha/".into(),
+            "jobs/# both 1 aws/ $aws/things/alpha/".into(),
             r#""" out 1 aws/test-connection thinedge/devices/alpha/test-connection"#.into(),
             r#""" in 1 aws/connection-success thinedge/devices/alpha/test-connection"#.into(),
         ],
@@ -219,6 +224,7 @@ fn test_bridge_config_aws_custom_topic_prefix() -> anyhow::Result<()> {
             "td/# out 1 aws-custom/ thinedge/alpha/".into(),
             "cmd/# in 1 aws-custom/ thinedge/alpha/".into(),
             "shadow/# both 1 aws-custom/ $aws/things/alpha/".into(),
+            "jobs/# both 1 aws-custom/ $aws/things/alpha/".into(),
             r#""" out 1 aws-custom/test-connection thinedge/devices/alpha/test-connection"#.into(),
             r#""" in 1 aws-custom/connection-success thinedge/devices/alpha/test-connection"#
                 .into(),
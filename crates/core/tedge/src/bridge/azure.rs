@@ -104,7 +104,8 @@ impl From<BridgeConfigAzureParams> for BridgeConfig {
                 // Digital twin
                 format!("twin/res/# in 1 {topic_prefix}/ $iothub/"),
                 format!("twin/GET/# out 1 {topic_prefix}/ $iothub/"),
-                format!("twin/PATCH/# out 1 {topic_prefix}/ $iothub/"),
+                format!("twin/PATCH/properties/reported/# out 1 {topic_prefix}/ $iothub/"),
+                format!("twin/PATCH/properties/desired/# in 1 {topic_prefix}/ $iothub/"),
             ],
             bridge_location,
             connection_check_attempts: 5,
@@ -158,7 +159,8 @@ fn test_bridge_config_from_azure_params() -> anyhow::Result<()> {
             "methods/res/# out 1 az/ $iothub/".into(),
             "twin/res/# in 1 az/ $iothub/".into(),
             "twin/GET/# out 1 az/ $iothub/".into(),
-            "twin/PATCH/# out 1 az/ $iothub/".into(),
+            "twin/PATCH/properties/reported/# out 1 az/ $iothub/".into(),
+            "twin/PATCH/properties/desired/# in 1 az/ $iothub/".into(),
         ],
         try_private: false,
         start_type: "automatic".into(),
@@ -224,7 +226,8 @@ fn test_azure_bridge_config_with_custom_prefix() -> anyhow::Result<()> {
             "methods/res/# out 1 az-custom/ $iothub/".into(),
             "twin/res/# in 1 az-custom/ $iothub/".into(),
             "twin/GET/# out 1 az-custom/ $iothub/".into(),
-            "twin/PATCH/# out 1 az-custom/ $iothub/".into(),
+            "twin/PATCH/properties/reported/# out 1 az-custom/ $iothub/".into(),
+            "twin/PATCH/properties/desired/# in 1 az-custom/ $iothub/".into(),
         ],
         try_private: false,
         start_type: "automatic".into(),
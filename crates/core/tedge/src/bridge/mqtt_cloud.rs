@@ -0,0 +1,198 @@
+use super::config::ProxyWrapper;
+use super::BridgeConfig;
+use crate::bridge::config::BridgeLocation;
+use camino::Utf8PathBuf;
+use std::borrow::Cow;
+use std::time::Duration;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_config::models::auth_method::AuthType;
+use tedge_config::models::HostPort;
+use tedge_config::models::TopicPrefix;
+use tedge_config::models::MQTT_TLS_PORT;
+use tedge_config::tedge_toml::ProfileName;
+
+#[derive(Debug)]
+pub struct BridgeConfigMqttCloudParams {
+    pub mqtt_host: HostPort<MQTT_TLS_PORT>,
+    pub config_file: Cow<'static, str>,
+    pub remote_clientid: String,
+    pub remote_username: Option<String>,
+    pub remote_password: Option<String>,
+    pub bridge_root_cert_path: Utf8PathBuf,
+    pub bridge_certfile: Utf8PathBuf,
+    pub bridge_keyfile: Utf8PathBuf,
+    pub bridge_location: BridgeLocation,
+    pub topic_prefix: TopicPrefix,
+    pub profile_name: Option<ProfileName>,
+    pub mqtt_schema: MqttSchema,
+    pub keepalive_interval: Duration,
+    pub proxy: Option<rumqttc::Proxy>,
+    pub auth_type: AuthType,
+    /// Remote topic filters of the messages forwarded to the remote broker
+    pub outgoing_filters: Vec<String>,
+    /// Remote topic filters of the messages received from the remote broker
+    pub incoming_filters: Vec<String>,
+}
+
+impl From<BridgeConfigMqttCloudParams> for BridgeConfig {
+    fn from(params: BridgeConfigMqttCloudParams) -> Self {
+        let BridgeConfigMqttCloudParams {
+            mqtt_host,
+            config_file,
+            remote_clientid,
+            remote_username,
+            remote_password,
+            bridge_root_cert_path,
+            bridge_certfile,
+            bridge_keyfile,
+            bridge_location,
+            topic_prefix,
+            profile_name,
+            mqtt_schema,
+            keepalive_interval,
+            proxy,
+            auth_type,
+            outgoing_filters,
+            incoming_filters,
+        } = params;
+
+        // the remote topics are used as is, locally prefixed by the topic prefix
+        let mut topics: Vec<String> = outgoing_filters
+            .iter()
+            .map(|filter| format!(r#"{filter} out 1 {topic_prefix}/ """#))
+            .chain(
+                incoming_filters
+                    .iter()
+                    .map(|filter| format!(r#"{filter} in 1 {topic_prefix}/ """#)),
+            )
+            .collect();
+
+        // echo topic mapping to check the connection
+        topics.push(format!(
+            r#""" out 1 {topic_prefix}/test-connection tedge/{remote_clientid}/test-connection"#
+        ));
+        topics.push(format!(
+            r#""" in 1 {topic_prefix}/connection-success tedge/{remote_clientid}/test-connection"#
+        ));
+
+        let service_name = format!("mosquitto-{topic_prefix}-bridge");
+        let health = mqtt_schema.topic_for(
+            &EntityTopicId::default_main_service(&service_name).unwrap(),
+            &Channel::Health,
+        );
+        Self {
+            cloud_name: "mqtt_cloud".into(),
+            config_file,
+            connection: if let Some(profile) = &profile_name {
+                format!("edge_to_mqtt_cloud@{profile}")
+            } else {
+                "edge_to_mqtt_cloud".into()
+            },
+            address: mqtt_host,
+            remote_username,
+            remote_password,
+            bridge_root_cert_path,
+            remote_clientid,
+            local_clientid: if let Some(profile) = &profile_name {
+                format!("MqttCloud@{profile}")
+            } else {
+                "MqttCloud".into()
+            },
+            bridge_certfile,
+            bridge_keyfile,
+            use_mapper: true,
+            use_agent: false,
+            try_private: false,
+            start_type: "automatic".into(),
+            clean_session: false,
+            include_local_clean_session: false, // local_clean_session being equal to clean_session, the former is useless and safer to ignore
+            local_clean_session: false,
+            notifications: true,
+            notifications_local_only: true,
+            notification_topic: health.name,
+            bridge_attempt_unsubscribe: false,
+            topics,
+            bridge_location,
+            connection_check_attempts: 5,
+            auth_type,
+            mosquitto_version: None,
+            keepalive_interval,
+            proxy: proxy.map(ProxyWrapper),
+        }
+    }
+}
+
+#[test]
+fn test_bridge_config_from_mqtt_cloud_params() -> anyhow::Result<()> {
+    let mut mqtt_host = HostPort::<MQTT_TLS_PORT>::try_from("broker.example.com")?;
+    mqtt_host.set_port(8884);
+    let params = BridgeConfigMqttCloudParams {
+        mqtt_host: mqtt_host.clone(),
+        config_file: "mqtt_cloud@edge-bridge.conf".into(),
+        remote_clientid: "alpha".into(),
+        remote_username: Some("octocat".into()),
+        remote_password: Some("abcd1234".into()),
+        bridge_root_cert_path: "./test_root.pem".into(),
+        bridge_certfile: "./test-certificate.pem".into(),
+        bridge_keyfile: "./test-private-key.pem".into(),
+        bridge_location: BridgeLocation::Mosquitto,
+        topic_prefix: "mqtt-edge".try_into().unwrap(),
+        profile_name: Some("edge".parse().unwrap()),
+        mqtt_schema: MqttSchema::with_root("te".into()),
+        keepalive_interval: Duration::from_secs(60),
+        proxy: None,
+        auth_type: AuthType::Basic,
+        outgoing_filters: vec![
+            "tedge/alpha/+/m/+".into(),
+            "tedge/alpha/+/cmd/+/+/status".into(),
+        ],
+        incoming_filters: vec!["tedge/alpha/+/cmd/+/+".into()],
+    };
+
+    let bridge = BridgeConfig::from(params);
+
+    let expected = BridgeConfig {
+        cloud_name: "mqtt_cloud".into(),
+        config_file: "mqtt_cloud@edge-bridge.conf".into(),
+        connection: "edge_to_mqtt_cloud@edge".into(),
+        address: mqtt_host,
+        remote_username: Some("octocat".into()),
+        remote_password: Some("abcd1234".into()),
+        bridge_root_cert_path: Utf8PathBuf::from("./test_root.pem"),
+        remote_clientid: "alpha".into(),
+        local_clientid: "MqttCloud@edge".into(),
+        bridge_certfile: "./test-certificate.pem".into(),
+        bridge_keyfile: "./test-private-key.pem".into(),
+        use_mapper: true,
+        use_agent: false,
+        topics: vec![
+            r#"tedge/alpha/+/m/+ out 1 mqtt-edge/ """#.into(),
+            r#"tedge/alpha/+/cmd/+/+/status out 1 mqtt-edge/ """#.into(),
+            r#"tedge/alpha/+/cmd/+/+ in 1 mqtt-edge/ """#.into(),
+            r#""" out 1 mqtt-edge/test-connection tedge/alpha/test-connection"#.into(),
+            r#""" in 1 mqtt-edge/connection-success tedge/alpha/test-connection"#.into(),
+        ],
+        try_private: false,
+        start_type: "automatic".into(),
+        clean_session: false,
+        include_local_clean_session: false,
+        local_clean_session: false,
+        notifications: true,
+        notifications_local_only: true,
+        notification_topic: "te/device/main/service/mosquitto-mqtt-edge-bridge/status/health"
+            .into(),
+        bridge_attempt_unsubscribe: false,
+        bridge_location: BridgeLocation::Mosquitto,
+        connection_check_attempts: 5,
+        auth_type: AuthType::Basic,
+        mosquitto_version: None,
+        keepalive_interval: Duration::from_secs(60),
+        proxy: None,
+    };
+
+    assert_eq!(bridge, expected);
+
+    Ok(())
+}
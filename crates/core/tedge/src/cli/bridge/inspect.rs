@@ -77,6 +77,10 @@ async fn run_inspect(
         CloudArg::Az { .. } => {
             print_non_configurable_or_disabled(w, config, &cloud);
         }
+        #[cfg(feature = "mqtt_cloud")]
+        CloudArg::MqttCloud { .. } => {
+            print_non_configurable_or_disabled(w, config, &cloud);
+        }
     }
 
     Ok(())
@@ -61,6 +61,18 @@ enum CloudTopicArg {
         #[clap(long)]
         profile: Option<ProfileName>,
 
+        /// The MQTT topic to test (local or remote, wildcards are not supported)
+        topic: String,
+    },
+    #[cfg(feature = "mqtt_cloud")]
+    #[clap(name = "mqtt_cloud")]
+    MqttCloud {
+        /// The cloud profile you wish to use
+        ///
+        /// [env: TEDGE_CLOUD_PROFILE]
+        #[clap(long)]
+        profile: Option<ProfileName>,
+
         /// The MQTT topic to test (local or remote, wildcards are not supported)
         topic: String,
     },
@@ -81,6 +93,10 @@ impl CloudTopicArg {
             Self::Az { profile, .. } => CloudArg::Az {
                 profile: profile.clone(),
             },
+            #[cfg(feature = "mqtt_cloud")]
+            Self::MqttCloud { profile, .. } => CloudArg::MqttCloud {
+                profile: profile.clone(),
+            },
         }
     }
 
@@ -92,6 +108,8 @@ impl CloudTopicArg {
             Self::Aws { topic, .. } => topic,
             #[cfg(feature = "azure")]
             Self::Az { topic, .. } => topic,
+            #[cfg(feature = "mqtt_cloud")]
+            Self::MqttCloud { topic, .. } => topic,
         }
     }
 }
@@ -162,6 +180,11 @@ async fn run_test(
             print_non_configurable_or_disabled(w, config, &cloud);
             Ok(Status::NoMatches)
         }
+        #[cfg(feature = "mqtt_cloud")]
+        CloudArg::MqttCloud { .. } => {
+            print_non_configurable_or_disabled(w, config, &cloud);
+            Ok(Status::NoMatches)
+        }
     }
 }
 
@@ -15,7 +15,7 @@ pub use upload::UploadCertCmd;
 /// Create a device private key and CSR
 ///
 /// Return the CSR in the format expected by c8y CA
-async fn create_device_csr(
+pub(super) async fn create_device_csr(
     common_name: String,
     key: super::create_csr::Key,
     current_cert: Option<Utf8PathBuf>,
@@ -30,13 +30,14 @@ async fn create_device_csr(
         user: "tedge".to_string(),
         group: "tedge".to_string(),
         csr_template,
+        new_hsm_key: None,
     };
     create_cmd.create_certificate_signing_request().await?;
     Ok(())
 }
 
 /// Return the CSR in the format expected by c8y CA
-async fn read_csr_from_file(csr_path: &Utf8PathBuf) -> Result<String, CertError> {
+pub(super) async fn read_csr_from_file(csr_path: &Utf8PathBuf) -> Result<String, CertError> {
     let csr = read_cert_to_string(csr_path).await?;
     Ok(parse_csr_contents(csr))
 }
@@ -65,7 +66,10 @@ fn parse_csr_contents(csr: String) -> String {
 ///
 /// The c8y CA being EST compliant, its response is encoded along PKCS#7
 /// and the x509 certificate has to be extracted from this envelop.
-async fn store_device_cert(cert_path: &Utf8PathBuf, pk7_base64: String) -> Result<(), CertError> {
+pub(super) async fn store_device_cert(
+    cert_path: &Utf8PathBuf,
+    pk7_base64: String,
+) -> Result<(), CertError> {
     let x509_pem = pk7_to_x509(pk7_base64)?;
 
     override_public_key(cert_path, x509_pem).await
@@ -78,7 +82,7 @@ async fn store_device_cert(cert_path: &Utf8PathBuf, pk7_base64: String) -> Resul
 /// Meaning the content is a:
 /// - base64-encoded
 /// - BER [SignedData object](https://datatracker.ietf.org/doc/html/rfc2315.html#section-9.1)
-fn pk7_to_x509(pk7_base64: String) -> Result<String, IllFormedPk7Cert> {
+pub(super) fn pk7_to_x509(pk7_base64: String) -> Result<String, IllFormedPk7Cert> {
     use base64::prelude::*;
     use rasn::ber;
     use rasn::der;
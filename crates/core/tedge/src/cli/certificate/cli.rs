@@ -1,7 +1,10 @@
 use super::create::CreateCertCmd;
 use super::create_csr::CreateCsrCmd;
+use super::est::EnrollCertCmd;
+use super::est::EstAuth;
 use super::remove::RemoveCertCmd;
 use super::renew::RenewCertCmd;
+use super::show::CertVerification;
 use super::show::ShowCertCmd;
 use crate::certificate_is_self_signed;
 use crate::cli::certificate::c8y;
@@ -31,6 +34,7 @@ use tedge_config::tedge_toml::CloudConfig;
 use tedge_config::tedge_toml::ProfileName;
 use tedge_config::TEdgeConfig;
 use tracing::debug;
+use url::Url;
 
 #[derive(clap::Subcommand, Debug)]
 pub enum TEdgeCertCli {
@@ -62,6 +66,43 @@ pub enum TEdgeCertCli {
         #[clap(long = "output-path", global = true, value_hint = ValueHint::FilePath)]
         output_path: Option<Utf8PathBuf>,
 
+        /// Generate a new keypair on the PKCS #11 token and use it to sign the CSR
+        ///
+        /// After the key is generated, tedge config is updated to use the new key,
+        /// as done by `tedge cert create-key-hsm`.
+        #[clap(long, global = true)]
+        hsm: bool,
+
+        /// The URI of the token where the keypair should be created.
+        ///
+        /// If this argument is missing, a list of available initialized tokens will be shown.
+        #[clap(long, global = true, requires = "hsm")]
+        token: Option<String>,
+
+        /// Human readable description (CKA_LABEL attribute) for the key.
+        #[clap(long, global = true, default_value = "tedge")]
+        key_label: String,
+
+        /// Key identifier for the keypair (CKA_ID attribute), as a sequence of hex digits.
+        #[clap(long, global = true, requires = "hsm")]
+        key_id: Option<String>,
+
+        /// The type of the key.
+        #[clap(long, global = true, default_value = "ecdsa")]
+        key_type: KeyType,
+
+        /// The size of the RSA keys in bits. Should only be used with --key-type rsa.
+        #[clap(long, global = true, default_value = "2048")]
+        bits: RsaBits,
+
+        /// The curve (size) of the ECDSA key. Should only be used with --key-type ecdsa.
+        #[clap(long, global = true, default_value = "p256")]
+        curve: EcCurve,
+
+        /// User PIN value for logging into the PKCS #11 token.
+        #[clap(long, global = true, requires = "hsm")]
+        pin: Option<String>,
+
         #[clap(subcommand)]
         cloud: Option<CloudArg>,
     },
@@ -194,6 +235,21 @@ pub enum TEdgeCertCli {
         #[clap(long = "new", default_value_t = false, global = true)]
         show_new: bool,
 
+        /// Verify that the certificate can be used to connect the cloud
+        ///
+        /// The certificate chain is built up to the root certificates configured for the cloud
+        /// (`root_cert_path`), and the key usages are checked to allow TLS client authentication.
+        /// Intermediate certificates are expected to be stored in the certificate file,
+        /// after the device certificate.
+        #[clap(long, default_value_t = false, global = true)]
+        verify: bool,
+
+        /// Certificate revocation list (CRL) used to check the certificate is not revoked
+        ///
+        /// Implies `--verify`.
+        #[clap(long = "crl", global = true, value_hint = ValueHint::FilePath)]
+        crl_path: Option<Utf8PathBuf>,
+
         #[clap(subcommand)]
         cloud: Option<CloudArg>,
     },
@@ -214,6 +270,67 @@ pub enum TEdgeCertCli {
     /// Request and download the device certificate
     #[clap(subcommand)]
     Download(DownloadCertCli),
+
+    /// Enroll the device certificate using an EST server (RFC 7030)
+    ///
+    /// A CSR is generated using the device id and private key configured for the given cloud profile,
+    /// the private key being possibly stored in an HSM, and sent to the EST server.
+    /// The device authenticates on the EST server using either bootstrap credentials
+    /// (`--username` and `--password`) or an existing certificate (`--bootstrap-cert` and `--bootstrap-key`).
+    ///
+    /// With `--renew`, the current device certificate is used to authenticate the request
+    /// and the new certificate is stored next to the current one with a `.new` suffix,
+    /// to be promoted by the `tedge connect` command, as done by `tedge cert renew`.
+    Enroll {
+        /// URL of the EST server
+        ///
+        /// The `/.well-known/est` path is added if not already present.
+        /// Example: https://est.example.com/.well-known/est/iot
+        #[clap(long, value_hint = ValueHint::Url)]
+        est: Url,
+
+        /// The device identifier to be used as the common name for the certificate
+        #[clap(long = "device-id", global = true)]
+        id: Option<String>,
+
+        /// Bootstrap username for HTTP basic authentication
+        #[clap(long, global = true)]
+        #[arg(env = "EST_USERNAME", hide_env_values = true)]
+        username: Option<String>,
+
+        /// Bootstrap password for HTTP basic authentication
+        #[clap(long, global = true, allow_hyphen_values = true, requires = "username")]
+        #[arg(env = "EST_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+
+        /// Existing certificate used to authenticate the device, e.g. a manufacturer certificate
+        #[clap(long, global = true, value_hint = ValueHint::FilePath, requires = "bootstrap_key")]
+        bootstrap_cert: Option<Utf8PathBuf>,
+
+        /// Private key of the bootstrap certificate
+        #[clap(long, global = true, value_hint = ValueHint::FilePath, requires = "bootstrap_cert")]
+        bootstrap_key: Option<Utf8PathBuf>,
+
+        /// Renew the current device certificate (`simplereenroll`)
+        #[clap(long, global = true, conflicts_with_all = ["username", "bootstrap_cert"])]
+        renew: bool,
+
+        /// Path where the CA certificates returned by the EST server are stored
+        ///
+        /// If none is provided, the CA certificates are not retrieved.
+        #[clap(long, global = true, value_hint = ValueHint::FilePath)]
+        cacerts_path: Option<Utf8PathBuf>,
+
+        /// Path to a Certificate Signing Request (CSR) ready to be used
+        ///
+        /// If none is provided a CSR is generated using the device id and private key
+        /// configured for the given cloud profile.
+        #[clap(long = "csr-path", global = true, value_hint = ValueHint::FilePath)]
+        csr_path: Option<Utf8PathBuf>,
+
+        #[clap(subcommand)]
+        cloud: Option<CloudArg>,
+    },
 }
 
 #[derive(clap::ValueEnum, Clone, Debug, Eq, PartialEq, strum_macros::Display)]
@@ -225,6 +342,22 @@ pub enum CA {
     C8y,
 }
 
+/// The template of the CSRs created for the device, as configured
+pub fn csr_template(config: &TEdgeConfig) -> CsrTemplate {
+    CsrTemplate {
+        max_cn_size: 64,
+        validity_period_days: config
+            .certificate
+            .validity
+            .requested_duration
+            .duration()
+            .as_secs() as u32
+            / (24 * 3600),
+        organization_name: config.certificate.organization.to_string(),
+        organizational_unit_name: config.certificate.organization_unit.to_string(),
+    }
+}
+
 #[async_trait::async_trait]
 impl BuildCommand for TEdgeCertCli {
     async fn build_command(self, config: &TEdgeConfig) -> Result<Box<dyn Command>, ConfigError> {
@@ -234,18 +367,7 @@ impl BuildCommand for TEdgeCertCli {
             (crate::BROKER_USER, crate::BROKER_USER)
         };
 
-        let csr_template = CsrTemplate {
-            max_cn_size: 64,
-            validity_period_days: config
-                .certificate
-                .validity
-                .requested_duration
-                .duration()
-                .as_secs() as u32
-                / (24 * 3600),
-            organization_name: config.certificate.organization.to_string(),
-            organizational_unit_name: config.certificate.organization_unit.to_string(),
-        };
+        let csr_template = csr_template(config);
 
         let cmd = match self {
             TEdgeCertCli::Create { id, cloud } => {
@@ -265,6 +387,14 @@ impl BuildCommand for TEdgeCertCli {
             TEdgeCertCli::CreateCsr {
                 id,
                 output_path,
+                hsm,
+                token,
+                key_label,
+                key_id,
+                key_type,
+                bits,
+                curve,
+                pin,
                 cloud,
             } => {
                 let cloud: Option<Cloud> = cloud.map(<_>::try_into).transpose()?;
@@ -276,6 +406,24 @@ impl BuildCommand for TEdgeCertCli {
                 let cryptoki = config
                     .device
                     .cryptoki_config(cloud_config.as_ref().map(|c| &**c as &dyn CloudConfig))?;
+                let new_hsm_key = if hsm {
+                    let cryptoki_config =
+                        cryptoki.clone().context("Cryptoki config is not enabled")?;
+                    Some(CreateKeyHsmCmd {
+                        cryptoki_config,
+                        label: key_label,
+                        r#type: key_type,
+                        bits,
+                        curve,
+                        id: key_id,
+                        pin,
+                        outfile_pubkey: None,
+                        cloud: cloud.clone(),
+                        token,
+                    })
+                } else {
+                    None
+                };
                 let key = cryptoki
                     .map(super::create_csr::Key::Cryptoki)
                     .unwrap_or(Key::Local(
@@ -301,6 +449,7 @@ impl BuildCommand for TEdgeCertCli {
                     user: user.to_owned(),
                     group: group.to_owned(),
                     csr_template,
+                    new_hsm_key,
                 };
                 cmd.into_boxed()
             }
@@ -345,10 +494,26 @@ impl BuildCommand for TEdgeCertCli {
                 cloud,
                 cert_path,
                 show_new,
+                verify,
+                crl_path,
             } => {
                 let cloud: Option<Cloud> = cloud.map(<_>::try_into).transpose()?;
                 let device_cert_path = config.device_cert_path(cloud.as_ref())?.into();
                 let cert_path = cert_path.unwrap_or(device_cert_path);
+                let verification = if verify || crl_path.is_some() {
+                    let cloud_config = config.as_cloud_config(
+                        cloud
+                            .as_ref()
+                            .map(|c| c.into())
+                            .unwrap_or(tedge_config::tedge_toml::Cloud::C8y(None)),
+                    )?;
+                    Some(CertVerification {
+                        root_cert_path: cloud_config.root_cert_path().to_owned(),
+                        crl_path,
+                    })
+                } else {
+                    None
+                };
                 let cmd = ShowCertCmd {
                     cert_path: if show_new {
                         CertificateShift::new_certificate_path(&cert_path)
@@ -357,6 +522,7 @@ impl BuildCommand for TEdgeCertCli {
                     },
                     minimum: config.certificate.validity.minimum_duration.duration(),
                     validity_check_only: false,
+                    verification,
                 };
 
                 cmd.into_boxed()
@@ -369,6 +535,7 @@ impl BuildCommand for TEdgeCertCli {
                     cert_path: cert_path.unwrap_or(device_cert_path),
                     minimum: config.certificate.validity.minimum_duration.duration(),
                     validity_check_only: true,
+                    verification: None,
                 };
                 cmd.into_boxed()
             }
@@ -455,6 +622,73 @@ impl BuildCommand for TEdgeCertCli {
                 cmd.into_boxed()
             }
 
+            TEdgeCertCli::Enroll {
+                est,
+                id,
+                username,
+                password,
+                bootstrap_cert,
+                bootstrap_key,
+                renew,
+                cacerts_path,
+                csr_path,
+                cloud,
+            } => {
+                let cloud: Option<Cloud> = cloud.map(<_>::try_into).transpose()?;
+                let cloud_config = config.as_cloud_config(
+                    cloud
+                        .as_ref()
+                        .map(|c| c.into())
+                        .unwrap_or(tedge_config::tedge_toml::Cloud::C8y(None)),
+                )?;
+                let cert_path: Utf8PathBuf = config.device_cert_path(cloud.as_ref())?.into();
+                let cryptoki = config.device.cryptoki_config(Some(&*cloud_config))?;
+                let key = cryptoki
+                    .map(super::create_csr::Key::Cryptoki)
+                    .unwrap_or(Key::Local(config.device_key_path(cloud.as_ref())?.into()));
+                let (csr_path, generate_csr) = match csr_path {
+                    None => (config.device_csr_path(cloud.as_ref())?.into(), true),
+                    Some(csr_path) => (csr_path, false),
+                };
+                // When renewing, the common name is taken from the current certificate
+                let device_id = if renew {
+                    get_device_id(id, config, &cloud).await.unwrap_or_default()
+                } else {
+                    get_device_id(id, config, &cloud).await?
+                };
+                let auth = match (username, bootstrap_cert, bootstrap_key) {
+                    _ if renew => EstAuth::Certificate {
+                        cert_path: cert_path.clone(),
+                        key: key.clone(),
+                    },
+                    (Some(username), _, _) => EstAuth::Basic {
+                        username,
+                        password: password.unwrap_or_default(),
+                    },
+                    (None, Some(cert_path), Some(key_path)) => EstAuth::Certificate {
+                        cert_path,
+                        key: Key::Local(key_path),
+                    },
+                    _ => EstAuth::None,
+                };
+
+                let cmd = EnrollCertCmd {
+                    est_url: est,
+                    device_id,
+                    auth,
+                    http_config: config.cloud_root_certs().await?,
+                    root_cert_path: cloud_config.root_cert_path().to_owned(),
+                    cert_path,
+                    key,
+                    csr_path,
+                    generate_csr,
+                    renew,
+                    cacerts_path,
+                    csr_template,
+                };
+                cmd.into_boxed()
+            }
+
             TEdgeCertCli::Renew {
                 csr_path,
                 cloud,
@@ -508,7 +742,7 @@ impl BuildCommand for TEdgeCertCli {
                             let c8y_config = config.mapper_config(profile)?;
                             C8yEndPoint::local_proxy(&c8y_config)?
                         }
-                        #[cfg(any(feature = "aws", feature = "azure"))]
+                        #[cfg(any(feature = "aws", feature = "azure", feature = "mqtt_cloud"))]
                         Some(cloud) => {
                             return Err(
                                 anyhow!("Certificate renewal is not supported for {cloud}").into()
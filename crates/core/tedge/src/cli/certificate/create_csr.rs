@@ -1,11 +1,15 @@
+use super::create_key::save_key_uri_to_config;
+use super::create_key::CreateKeyHsmCmd;
 use super::error::CertError;
 use crate::command::Command;
 use crate::log::MaybeFancy;
 use crate::override_public_key;
 use crate::persist_new_private_key;
 use crate::reuse_private_key;
+use anyhow::Context;
 use camino::Utf8PathBuf;
 use certificate::parse_root_certificate::CryptokiConfig;
+use certificate::parse_root_certificate::SecretString;
 use certificate::CsrTemplate;
 use certificate::KeyCertPair;
 use certificate::KeyKind;
@@ -33,6 +37,9 @@ pub struct CreateCsrCmd {
 
     /// CSR template
     pub csr_template: CsrTemplate,
+
+    /// Generate a new keypair on a PKCS #11 token, to be used instead of the configured key
+    pub new_hsm_key: Option<CreateKeyHsmCmd>,
 }
 
 #[derive(Debug, Clone)]
@@ -47,8 +54,14 @@ impl Command for CreateCsrCmd {
         "Generate a Certificate Signing Request.".into()
     }
 
-    async fn execute(&self, _: TEdgeConfig) -> Result<(), MaybeFancy<anyhow::Error>> {
-        self.create_certificate_signing_request().await?;
+    async fn execute(&self, config: TEdgeConfig) -> Result<(), MaybeFancy<anyhow::Error>> {
+        match &self.new_hsm_key {
+            None => self.create_certificate_signing_request().await?,
+            Some(create_key) => {
+                let key = self.create_hsm_key(create_key, config).await?;
+                self.create_signing_request_with_key(&key).await?
+            }
+        }
         eprintln!("Certificate Signing Request was successfully created.");
         Ok(())
     }
@@ -57,11 +70,38 @@ impl Command for CreateCsrCmd {
 impl CreateCsrCmd {
     #[instrument(skip_all)]
     pub async fn create_certificate_signing_request(&self) -> Result<(), CertError> {
+        self.create_signing_request_with_key(&self.key).await
+    }
+
+    /// Create a keypair on the PKCS #11 token and select it in tedge config
+    async fn create_hsm_key(
+        &self,
+        create_key: &CreateKeyHsmCmd,
+        config: TEdgeConfig,
+    ) -> Result<Key, anyhow::Error> {
+        let Some(token) = create_key.token.clone() else {
+            create_key.print_available_tokens()?;
+            std::process::exit(1);
+        };
+
+        let new_key = create_key.create_key(token)?;
+        save_key_uri_to_config(config, create_key.cloud.as_ref(), &new_key.uri)
+            .await
+            .context("Failed to save the key URI to tedge-config")?;
+
+        let cryptoki_config = create_key.cryptoki_config.clone().with_key(
+            new_key.uri.into(),
+            create_key.pin.clone().map(SecretString::from),
+        );
+        Ok(Key::Cryptoki(cryptoki_config))
+    }
+
+    async fn create_signing_request_with_key(&self, key: &Key) -> Result<(), CertError> {
         let id = &self.id;
         let csr_path = &self.csr_path;
         debug!(?id, ?csr_path);
 
-        let previous_key = match &self.key {
+        let previous_key = match key {
             Key::Local(key_path) => reuse_private_key(key_path)
                 .await
                 .map_err(|e| CertError::IoError(e).key_context(key_path.clone()))?,
@@ -76,7 +116,7 @@ impl CreateCsrCmd {
         let cert =
             KeyCertPair::new_certificate_sign_request(&self.csr_template, id, &previous_key)?;
 
-        if let Key::Local(key_path) = &self.key {
+        if let Key::Local(key_path) = key {
             if let KeyKind::New = previous_key {
                 persist_new_private_key(
                     key_path,
@@ -118,6 +158,7 @@ mod tests {
             group: "mosquitto".to_string(),
             csr_template: CsrTemplate::default(),
             current_cert: None,
+            new_hsm_key: None,
         };
 
         assert_matches!(cmd.create_certificate_signing_request().await, Ok(()));
@@ -162,6 +203,7 @@ mod tests {
             group: "mosquitto".to_string(),
             csr_template: CsrTemplate::default(),
             current_cert: None,
+            new_hsm_key: None,
         };
 
         // create csr using existing private key and device_id from public cert
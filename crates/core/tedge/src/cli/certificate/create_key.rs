@@ -6,6 +6,7 @@ use tedge_config::TEdgeConfig;
 use tedge_p11_server::pkcs11::CreateKeyParams;
 use tedge_p11_server::pkcs11::KeyTypeParams;
 use tedge_p11_server::service::CreateKeyRequest;
+use tedge_p11_server::service::CreateKeyResponse;
 use tedge_p11_server::CryptokiConfig;
 use tedge_p11_server::SecretString;
 use tracing::warn;
@@ -75,6 +76,43 @@ impl Command for CreateKeyHsmCmd {
     }
 
     async fn execute(&self, config: TEdgeConfig) -> Result<(), MaybeFancy<anyhow::Error>> {
+        let Some(token) = self.token.clone() else {
+            self.print_available_tokens()?;
+            std::process::exit(1);
+        };
+
+        let key = self.create_key(token)?;
+
+        // Operations below may fail for some reason (e.g. no permissions to write to outfile), but
+        // the key was still created, so we still consider the operation succeeded.
+        if let Some(outfile) = &self.outfile_pubkey {
+            let r = std::fs::write(outfile.as_ref(), &key.pem);
+            if let Err(e) = r {
+                warn!(?e, path=%outfile, "Failed to save the public key to file");
+            }
+        }
+
+        if let Err(e) = save_key_uri_to_config(config, self.cloud.as_ref(), &key.uri).await {
+            warn!(?e, "Failed to save public key URI to tedge-config. You may need to enter key URI in tedge-config manually to use the new key.")
+        }
+
+        Ok(())
+    }
+}
+
+impl CreateKeyHsmCmd {
+    pub fn print_available_tokens(&self) -> anyhow::Result<()> {
+        let cryptoki = tedge_p11_server::tedge_p11_service(self.cryptoki_config.clone())?;
+        eprintln!("No token URL was provided for this operation; the available tokens are:");
+        let tokens = cryptoki.get_tokens_uris()?;
+        for token_uri in tokens {
+            eprintln!("{token_uri}");
+        }
+        Ok(())
+    }
+
+    /// Generate a keypair on the given token
+    pub fn create_key(&self, token: String) -> anyhow::Result<CreateKeyResponse> {
         let key = match self.r#type {
             KeyType::Rsa => KeyTypeParams::Rsa {
                 bits: self.bits.into(),
@@ -91,15 +129,6 @@ impl Command for CreateKeyHsmCmd {
             .context("invalid id")?;
 
         let cryptoki = tedge_p11_server::tedge_p11_service(self.cryptoki_config.clone())?;
-        let Some(token) = self.token.clone() else {
-            eprintln!("No token URL was provided for this operation; the available tokens are:");
-            let tokens = cryptoki.get_tokens_uris()?;
-            for token_uri in tokens {
-                eprintln!("{token_uri}");
-            }
-            std::process::exit(1);
-        };
-
         let params = CreateKeyParams {
             key,
             label: self.label.clone(),
@@ -114,30 +143,15 @@ impl Command for CreateKeyHsmCmd {
             pin: self.pin.clone().map(SecretString::from),
         })?;
 
-        let pubkey_pem = key.pem;
-        let uri = key.uri;
         eprintln!("New keypair was successfully created.");
-        eprintln!("Key URI: {uri}");
-        eprintln!("Public key:\n{pubkey_pem}\n");
+        eprintln!("Key URI: {}", key.uri);
+        eprintln!("Public key:\n{}\n", key.pem);
 
-        // Operations below may fail for some reason (e.g. no permissions to write to outfile), but
-        // the key was still created, so we still consider the operation succeeded.
-        if let Some(outfile) = &self.outfile_pubkey {
-            let r = std::fs::write(outfile.as_ref(), pubkey_pem);
-            if let Err(e) = r {
-                warn!(?e, path=%outfile, "Failed to save the public key to file");
-            }
-        }
-
-        if let Err(e) = save_key_uri_to_config(config, self.cloud.as_ref(), &uri).await {
-            warn!(?e, "Failed to save public key URI to tedge-config. You may need to enter key URI in tedge-config manually to use the new key.")
-        }
-
-        Ok(())
+        Ok(key)
     }
 }
 
-async fn save_key_uri_to_config(
+pub async fn save_key_uri_to_config(
     config: TEdgeConfig,
     cloud: Option<&Cloud>,
     uri: &str,
@@ -182,6 +196,8 @@ fn extract_device_id_for_cloud(
         crate::cli::common::MaybeBorrowedCloud::Aws(_) => "aws",
         crate::cli::common::MaybeBorrowedCloud::Azure(_) => "az",
         crate::cli::common::MaybeBorrowedCloud::C8y(_) => "c8y",
+        #[cfg(feature = "mqtt_cloud")]
+        crate::cli::common::MaybeBorrowedCloud::MqttCloud(_) => "mqtt_cloud",
     });
 
     if let Some(cloud) = cloud {
@@ -8,6 +8,7 @@ mod create;
 mod create_csr;
 mod create_key;
 mod error;
+mod est;
 mod remove;
 mod renew;
 mod shift;
@@ -2,6 +2,7 @@ use crate::command::Command;
 use crate::log::MaybeFancy;
 use anyhow::Context;
 use camino::Utf8PathBuf;
+use certificate::verify::verify_client_certificate;
 use certificate::PemCertificate;
 use certificate::ValidityStatus;
 use std::time::Duration;
@@ -28,6 +29,18 @@ pub struct ShowCertCmd {
 
     /// Only check the certificate validity
     pub validity_check_only: bool,
+
+    /// Verify the certificate chain and its usage for TLS client authentication
+    pub verification: Option<CertVerification>,
+}
+
+/// The trust anchors and revocation list used to verify a device certificate
+pub struct CertVerification {
+    /// The root certificates, either a file or a directory
+    pub root_cert_path: Utf8PathBuf,
+
+    /// The certificate revocation list, if any
+    pub crl_path: Option<Utf8PathBuf>,
 }
 
 #[async_trait::async_trait]
@@ -62,12 +75,25 @@ impl Command for ShowCertCmd {
     }
 }
 
+impl CertVerification {
+    fn display_success(&self) -> String {
+        match &self.crl_path {
+            None => format!("VERIFIED (trusted by: {})", self.root_cert_path),
+            Some(crl_path) => format!(
+                "VERIFIED (trusted by: {}, not revoked by: {crl_path})",
+                self.root_cert_path
+            ),
+        }
+    }
+}
+
 impl ShowCertCmd {
     pub async fn show(cert_path: &Utf8PathBuf) -> Result<(), anyhow::Error> {
         let cmd = ShowCertCmd {
             cert_path: cert_path.clone(),
             minimum: humantime::parse_duration("30d")?,
             validity_check_only: false,
+            verification: None,
         };
         cmd.show_certificate().await
     }
@@ -104,6 +130,32 @@ impl ShowCertCmd {
             pem.serial_hex()?
         );
         print_async!(stdout, "Thumbprint:    {}\n", pem.thumbprint()?);
+
+        if let Some(verification) = &self.verification {
+            let status = if pem.issuer()? == pem.subject()? {
+                "SELF-SIGNED (the certificate has to be registered on the cloud)"
+                    .yellow()
+                    .to_string()
+            } else {
+                match verify_client_certificate(
+                    &self.cert_path,
+                    &verification.root_cert_path,
+                    verification
+                        .crl_path
+                        .as_ref()
+                        .map(|path| path.as_std_path()),
+                ) {
+                    Ok(()) => verification.display_success().green().to_string(),
+                    Err(err) => {
+                        print_async!(stdout, "Verification:  {}\n", "FAILED".red());
+                        let _ = stdout.flush().await;
+                        return Err(err)
+                            .with_context(|| format!("verifying certificate {}", self.cert_path));
+                    }
+                }
+            };
+            print_async!(stdout, "Verification:  {}\n", status);
+        }
         let _ = stdout.flush().await;
 
         Ok(())
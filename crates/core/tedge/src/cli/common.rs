@@ -39,6 +39,15 @@ pub enum CloudArg {
         #[arg(add(ArgValueCandidates::new(profile_completions)))]
         profile: Option<ProfileName>,
     },
+    #[cfg(feature = "mqtt_cloud")]
+    MqttCloud {
+        /// The cloud profile you wish to use
+        ///
+        /// [env: TEDGE_CLOUD_PROFILE]
+        #[clap(long)]
+        #[arg(add(ArgValueCandidates::new(profile_completions)))]
+        profile: Option<ProfileName>,
+    },
 }
 
 impl TryFrom<CloudArg> for Cloud {
@@ -77,12 +86,18 @@ impl CloudArg {
             Self::C8y {
                 profile: Some(profile),
             } => Cloud::c8y(Some(profile)),
+            #[cfg(feature = "mqtt_cloud")]
+            Self::MqttCloud {
+                profile: Some(profile),
+            } => Cloud::mqtt_cloud(Some(profile)),
             #[cfg(feature = "aws")]
             Self::Aws { profile: None } => Cloud::aws(read_env()?),
             #[cfg(feature = "azure")]
             Self::Az { profile: None } => Cloud::az(read_env()?),
             #[cfg(feature = "c8y")]
             Self::C8y { profile: None } => Cloud::c8y(read_env()?),
+            #[cfg(feature = "mqtt_cloud")]
+            Self::MqttCloud { profile: None } => Cloud::mqtt_cloud(read_env()?),
         })
     }
 }
@@ -100,6 +115,9 @@ pub enum MaybeBorrowedCloud<'a> {
     Azure(Option<Cow<'a, ProfileName>>),
     #[cfg(feature = "aws")]
     Aws(Option<Cow<'a, ProfileName>>),
+    #[strum(serialize = "MQTT cloud")]
+    #[cfg(feature = "mqtt_cloud")]
+    MqttCloud(Option<Cow<'a, ProfileName>>),
 }
 
 impl fmt::Display for MaybeBorrowedCloud<'_> {
@@ -114,6 +132,8 @@ impl fmt::Display for MaybeBorrowedCloud<'_> {
                 Self::Azure(_) => "Azure",
                 #[cfg(feature = "aws")]
                 Self::Aws(_) => "Aws",
+                #[cfg(feature = "mqtt_cloud")]
+                Self::MqttCloud(_) => "MQTT cloud",
             }
         )
     }
@@ -128,6 +148,10 @@ impl<'a> From<&'a MaybeBorrowedCloud<'a>> for tedge_config::tedge_toml::Cloud<'a
             MaybeBorrowedCloud::Azure(p) => tedge_config::tedge_toml::Cloud::Az(p.as_deref()),
             #[cfg(feature = "aws")]
             MaybeBorrowedCloud::Aws(p) => tedge_config::tedge_toml::Cloud::Aws(p.as_deref()),
+            #[cfg(feature = "mqtt_cloud")]
+            MaybeBorrowedCloud::MqttCloud(p) => {
+                tedge_config::tedge_toml::Cloud::MqttCloud(p.as_deref())
+            }
         }
     }
 }
@@ -147,6 +171,11 @@ impl Cloud {
     pub fn aws(profile: Option<ProfileName>) -> Self {
         Self::Aws(profile.map(Cow::Owned))
     }
+
+    #[cfg(feature = "mqtt_cloud")]
+    pub fn mqtt_cloud(profile: Option<ProfileName>) -> Self {
+        Self::MqttCloud(profile.map(Cow::Owned))
+    }
 }
 
 impl<'a> CloudBorrow<'a> {
@@ -162,6 +191,10 @@ impl<'a> CloudBorrow<'a> {
     pub fn aws_borrowed(profile: Option<&'a ProfileName>) -> Self {
         Self::Aws(profile.map(Cow::Borrowed))
     }
+    #[cfg(feature = "mqtt_cloud")]
+    pub fn mqtt_cloud_borrowed(profile: Option<&'a ProfileName>) -> Self {
+        Self::MqttCloud(profile.map(Cow::Borrowed))
+    }
 }
 
 impl MaybeBorrowedCloud<'_> {
@@ -173,6 +206,8 @@ impl MaybeBorrowedCloud<'_> {
             Self::Azure(profile) => SystemService::TEdgeMapperAz(profile.as_deref()),
             #[cfg(feature = "c8y")]
             Self::C8y(profile) => SystemService::TEdgeMapperC8y(profile.as_deref()),
+            #[cfg(feature = "mqtt_cloud")]
+            Self::MqttCloud(profile) => SystemService::TEdgeMapperMqttCloud(profile.as_deref()),
         }
     }
 
@@ -190,6 +225,10 @@ impl MaybeBorrowedCloud<'_> {
             Self::Azure(None) => "az-bridge.conf".into(),
             #[cfg(feature = "azure")]
             Self::Azure(Some(profile)) => format!("az@{profile}-bridge.conf").into(),
+            #[cfg(feature = "mqtt_cloud")]
+            Self::MqttCloud(None) => "mqtt_cloud-bridge.conf".into(),
+            #[cfg(feature = "mqtt_cloud")]
+            Self::MqttCloud(Some(profile)) => format!("mqtt_cloud@{profile}-bridge.conf").into(),
         }
     }
 
@@ -201,6 +240,8 @@ impl MaybeBorrowedCloud<'_> {
             Self::Aws(profile) => profile.as_deref(),
             #[cfg(feature = "azure")]
             Self::Azure(profile) => profile.as_deref(),
+            #[cfg(feature = "mqtt_cloud")]
+            Self::MqttCloud(profile) => profile.as_deref(),
         }
     }
 }
@@ -230,6 +271,11 @@ async fn profile_completions_for_config_dir(dir: impl AsRef<Path>) -> Vec<Comple
         .map(CompletionCandidate::new)
         .chain(tc.az_keys_str().flatten().map(CompletionCandidate::new))
         .chain(tc.aws_keys_str().flatten().map(CompletionCandidate::new))
+        .chain(
+            tc.mqtt_cloud_keys_str()
+                .flatten()
+                .map(CompletionCandidate::new),
+        )
         .collect()
 }
 
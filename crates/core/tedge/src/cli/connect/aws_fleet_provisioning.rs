@@ -0,0 +1,445 @@
+//! Device provisioning by claim with AWS IoT fleet provisioning
+//!
+//! The device connects AWS IoT with a claim certificate shared by a fleet of devices,
+//! requests a certificate for its own CSR and registers the thing using a provisioning template.
+//! The issued certificate is then stored as the device certificate, to be used by the bridge.
+//!
+//! The device private key never leaves the device: either a file or a key stored on an HSM.
+//! This is why `CreateKeysAndCertificate` is not used, but `CreateCertificateFromCsr`.
+//!
+//! See <https://docs.aws.amazon.com/iot/latest/developerguide/fleet-provision-api.html>
+use crate::cli::certificate::csr_template;
+use crate::override_public_key;
+use crate::persist_new_private_key;
+use crate::reuse_private_key;
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use certificate::KeyCertPair;
+use certificate::KeyKind;
+use rumqttc::AsyncClient;
+use rumqttc::Event;
+use rumqttc::Incoming;
+use rumqttc::MqttOptions;
+use rumqttc::Packet;
+use rumqttc::QoS::AtLeastOnce;
+use rumqttc::Transport;
+use serde::Deserialize;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+use std::time::Duration;
+use tedge_config::tedge_toml::mapper_config::AwsMapperSpecificConfig;
+use tedge_config::tedge_toml::MqttAuthClientConfigCloudBroker;
+use tedge_config::tedge_toml::MqttAuthConfigCloudBroker;
+use tedge_config::tedge_toml::PrivateKeyType;
+use tedge_config::tedge_toml::ProfileName;
+use tedge_config::TEdgeConfig;
+use tracing::debug;
+
+/// Root of the AWS IoT reserved topics
+///
+/// Only a test stand-in for AWS IoT is expected to use another root,
+/// as brokers commonly forbid clients to subscribe to `$` topics.
+const AWS_TOPIC_ROOT: &str = "$aws";
+
+/// How long to wait for the thing to be registered
+pub(crate) const FLEET_PROVISIONING_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The thing registered by AWS IoT and the certificate issued for the device
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct ProvisionedThing {
+    pub thing_name: String,
+    pub certificate_pem: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateCertificateResponse {
+    certificate_pem: String,
+    certificate_ownership_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RegisterThingResponse {
+    thing_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ErrorResponse {
+    status_code: Option<u16>,
+    error_code: Option<String>,
+    error_message: Option<String>,
+}
+
+/// Check if the device has to be provisioned on connect
+///
+/// This is the case when a provisioning template is configured and the device has no certificate yet.
+pub(crate) fn is_configured(
+    tedge_config: &TEdgeConfig,
+    profile: Option<&ProfileName>,
+) -> anyhow::Result<bool> {
+    let aws_config = tedge_config.mapper_config::<AwsMapperSpecificConfig>(&profile)?;
+    let template_name = &aws_config.cloud_specific.provisioning.template_name;
+    Ok(template_name.or_none().is_some() && !aws_config.device.cert_path.exists())
+}
+
+/// Provision the device using the claim certificate and store the certificate issued for the device
+pub(crate) async fn provision_device(
+    tedge_config: &TEdgeConfig,
+    profile: Option<&ProfileName>,
+) -> anyhow::Result<ProvisionedThing> {
+    let aws_config = tedge_config.mapper_config::<AwsMapperSpecificConfig>(&profile)?;
+    let provisioning = &aws_config.cloud_specific.provisioning;
+    let template_name = provisioning.template_name.or_config_not_set()?;
+    let parameters = template_parameters(
+        provisioning
+            .template_parameters
+            .or_none()
+            .map(|parameters| parameters.0.as_slice())
+            .unwrap_or_default(),
+    )?;
+    let device_id = aws_config.device.id()?;
+    let cert_path = aws_config.device.cert_path.to_path_buf();
+    let key_path = aws_config.device.key_path.to_path_buf();
+
+    // Create a CSR, reusing the device private key if any
+    let key = match tedge_config.device.cryptoki_config(Some(&aws_config))? {
+        Some(cryptoki_config) => KeyKind::from_cryptoki(cryptoki_config, None)?,
+        None => reuse_private_key(&key_path)
+            .await
+            .with_context(|| format!("Failed to read the device private key {key_path}"))?,
+    };
+    let csr =
+        KeyCertPair::new_certificate_sign_request(&csr_template(tedge_config), &device_id, &key)?;
+    if let KeyKind::New = key {
+        let (user, group) = if tedge_config.mqtt.bridge.built_in {
+            ("tedge", "tedge")
+        } else {
+            (crate::BROKER_USER, crate::BROKER_USER)
+        };
+        persist_new_private_key(&key_path, csr.private_key_pem_string()?, user, group)
+            .await
+            .with_context(|| format!("Failed to store the device private key {key_path}"))?;
+    }
+
+    // Connect AWS IoT with the claim certificate
+    let claim_auth = MqttAuthConfigCloudBroker {
+        ca_path: aws_config.root_cert_path.to_path_buf(),
+        client: Some(MqttAuthClientConfigCloudBroker {
+            cert_file: provisioning
+                .claim_cert_path
+                .or_config_not_set()?
+                .to_path_buf(),
+            private_key: PrivateKeyType::File(
+                provisioning
+                    .claim_key_path
+                    .or_config_not_set()?
+                    .to_path_buf(),
+            ),
+        }),
+    };
+    let tls_config = claim_auth
+        .to_rustls_client_config()
+        .context("Failed to create the TLS config with the claim certificate")?;
+    let mut mqtt_options = MqttOptions::new(
+        &device_id,
+        aws_config.url().or_config_not_set()?.to_string(),
+        8883,
+    );
+    mqtt_options.set_keep_alive(Duration::from_secs(30));
+    mqtt_options.set_transport(Transport::tls_with_config(tls_config.into()));
+
+    let thing = register_thing(
+        mqtt_options,
+        AWS_TOPIC_ROOT,
+        template_name,
+        &csr.certificate_signing_request_string()?,
+        parameters,
+        FLEET_PROVISIONING_TIMEOUT,
+    )
+    .await?;
+
+    override_public_key(&cert_path, thing.certificate_pem.clone())
+        .await
+        .with_context(|| format!("Failed to store the device certificate {cert_path}"))?;
+
+    Ok(thing)
+}
+
+/// Parse the `name=value` template parameters
+fn template_parameters(parameters: &[String]) -> anyhow::Result<Map<String, Value>> {
+    parameters
+        .iter()
+        .map(|parameter| match parameter.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => Ok((
+                name.trim().to_string(),
+                Value::String(value.trim().to_string()),
+            )),
+            _ => bail!(
+                "Invalid provisioning template parameter {parameter:?}: expected `name=value`"
+            ),
+        })
+        .collect()
+}
+
+/// Request a certificate for the device CSR and register the thing with the given template
+pub(crate) async fn register_thing(
+    mqtt_options: MqttOptions,
+    topic_root: &str,
+    template_name: &str,
+    csr: &str,
+    parameters: Map<String, Value>,
+    timeout: Duration,
+) -> anyhow::Result<ProvisionedThing> {
+    let create_certificate_topic = format!("{topic_root}/certificates/create-from-csr/json");
+    let register_thing_topic =
+        format!("{topic_root}/provisioning-templates/{template_name}/provision/json");
+
+    let (client, mut event_loop) = AsyncClient::new(mqtt_options, 10);
+    let registration = tokio::time::timeout(timeout, async {
+        let mut pending_subscriptions = 2;
+        let mut certificate_pem = None;
+        loop {
+            match event_loop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    client.subscribe(format!("{create_certificate_topic}/+"), AtLeastOnce).await?;
+                    client.subscribe(format!("{register_thing_topic}/+"), AtLeastOnce).await?;
+                }
+                Ok(Event::Incoming(Packet::SubAck(_))) => {
+                    pending_subscriptions -= 1;
+                    if pending_subscriptions == 0 {
+                        debug!("Requesting a certificate for the device CSR");
+                        client
+                            .publish(
+                                &create_certificate_topic,
+                                AtLeastOnce,
+                                false,
+                                json!({ "certificateSigningRequest": csr }).to_string(),
+                            )
+                            .await?;
+                    }
+                }
+                Ok(Event::Incoming(Packet::Publish(response))) => {
+                    let payload = std::str::from_utf8(&response.payload).unwrap_or_default();
+                    let Some((request, outcome)) = response.topic.rsplit_once('/') else {
+                        continue;
+                    };
+                    debug!("Received AWS IoT response on {}: {payload}", response.topic);
+                    match (request, outcome) {
+                        (request, "accepted") if request == create_certificate_topic => {
+                            let certificate: CreateCertificateResponse = serde_json::from_str(payload)
+                                .context("Invalid certificate received from AWS IoT")?;
+                            certificate_pem = Some(certificate.certificate_pem);
+                            client
+                                .publish(
+                                    &register_thing_topic,
+                                    AtLeastOnce,
+                                    false,
+                                    json!({
+                                        "certificateOwnershipToken": certificate.certificate_ownership_token,
+                                        "parameters": parameters,
+                                    })
+                                    .to_string(),
+                                )
+                                .await?;
+                        }
+                        (request, "accepted") if request == register_thing_topic => {
+                            let thing: RegisterThingResponse = serde_json::from_str(payload)
+                                .context("Invalid thing registration received from AWS IoT")?;
+                            let Some(certificate_pem) = certificate_pem.take() else {
+                                bail!("AWS IoT registered the thing before issuing a certificate");
+                            };
+                            return Ok(ProvisionedThing {
+                                thing_name: thing.thing_name,
+                                certificate_pem,
+                            });
+                        }
+                        (request, "rejected") if request == create_certificate_topic => {
+                            bail!("AWS IoT rejected the device CSR: {}", error_message(payload))
+                        }
+                        (request, "rejected") if request == register_thing_topic => {
+                            bail!("AWS IoT rejected the thing registration: {}", error_message(payload))
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(Event::Incoming(Incoming::Disconnect)) => {
+                    bail!("Unexpectedly disconnected from AWS IoT")
+                }
+                Err(err) => {
+                    return Err(err).context("Failed to connect to AWS IoT with the claim certificate")
+                }
+                _ => {}
+            }
+        }
+    })
+    .await
+    .unwrap_or_else(|_| {
+        Err(anyhow!(
+            "The thing has not been registered by AWS IoT after {}",
+            humantime::format_duration(timeout)
+        ))
+    });
+
+    let _ = client.disconnect().await;
+    registration
+}
+
+fn error_message(payload: &str) -> String {
+    match serde_json::from_str::<ErrorResponse>(payload) {
+        Ok(ErrorResponse {
+            status_code,
+            error_code,
+            error_message,
+        }) => format!(
+            "{} {}: {}",
+            status_code.map(|code| code.to_string()).unwrap_or_default(),
+            error_code.unwrap_or_default(),
+            error_message.unwrap_or_default()
+        ),
+        Err(_) => payload.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mqtt_tests::test_mqtt_broker;
+    use mqtt_tests::test_mqtt_server::MqttProcessHandler;
+
+    #[test]
+    fn parse_template_parameters() {
+        let parameters = template_parameters(&[
+            "SerialNumber=0042".to_string(),
+            " Model = rpi4 ".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(
+            Value::Object(parameters),
+            json!({"SerialNumber": "0042", "Model": "rpi4"})
+        );
+
+        let err = template_parameters(&["SerialNumber".to_string()]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"Invalid provisioning template parameter "SerialNumber": expected `name=value`"#
+        );
+    }
+
+    #[tokio::test]
+    async fn thing_is_registered_with_the_device_csr() {
+        let broker = test_mqtt_broker();
+        broker.map_messages_background(|(topic, payload)| {
+            if topic == "aws/ping" {
+                vec![("aws/pong".to_string(), "".to_string())]
+            } else if topic == "aws/certificates/create-from-csr/json"
+                && payload.contains("BEGIN CERTIFICATE REQUEST")
+            {
+                vec![(
+                    "aws/certificates/create-from-csr/json/accepted".to_string(),
+                    r#"{
+                        "certificateId":"c3f1",
+                        "certificatePem":"-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n",
+                        "certificateOwnershipToken":"token-1"
+                    }"#
+                    .to_string(),
+                )]
+            } else if topic == "aws/provisioning-templates/fleet/provision/json"
+                && payload.contains(r#""certificateOwnershipToken":"token-1""#)
+                && payload.contains(r#""SerialNumber":"0042""#)
+            {
+                vec![(
+                    "aws/provisioning-templates/fleet/provision/json/accepted".to_string(),
+                    r#"{"deviceConfiguration":{},"thingName":"my-device"}"#.to_string(),
+                )]
+            } else {
+                vec![]
+            }
+        });
+        wait_for_stand_in(broker).await;
+
+        let thing = register_thing(
+            MqttOptions::new("my-device", "127.0.0.1", broker.port),
+            "aws",
+            "fleet",
+            &test_csr(),
+            template_parameters(&["SerialNumber=0042".to_string()]).unwrap(),
+            Duration::from_secs(10),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            thing,
+            ProvisionedThing {
+                thing_name: "my-device".to_string(),
+                certificate_pem: "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n"
+                    .to_string(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_registrations_are_reported() {
+        let broker = test_mqtt_broker();
+        broker.map_messages_background(|(topic, _)| {
+            if topic == "aws/ping" {
+                vec![("aws/pong".to_string(), "".to_string())]
+            } else if topic == "aws/certificates/create-from-csr/json" {
+                vec![(
+                    "aws/certificates/create-from-csr/json/accepted".to_string(),
+                    r#"{"certificatePem":"pem","certificateOwnershipToken":"token-2"}"#
+                        .to_string(),
+                )]
+            } else if topic == "aws/provisioning-templates/fleet/provision/json" {
+                vec![(
+                    "aws/provisioning-templates/fleet/provision/json/rejected".to_string(),
+                    r#"{"statusCode":400,"errorCode":"InvalidParameters","errorMessage":"Missing SerialNumber"}"#
+                        .to_string(),
+                )]
+            } else {
+                vec![]
+            }
+        });
+        wait_for_stand_in(broker).await;
+
+        let err = register_thing(
+            MqttOptions::new("my-device", "127.0.0.1", broker.port),
+            "aws",
+            "fleet",
+            &test_csr(),
+            Map::new(),
+            Duration::from_secs(10),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "AWS IoT rejected the thing registration: 400 InvalidParameters: Missing SerialNumber"
+        );
+    }
+
+    /// Wait for the AWS IoT stand-in to be listening
+    async fn wait_for_stand_in(broker: &MqttProcessHandler) {
+        while broker
+            .wait_for_response_on_publish("aws/ping", "", "aws/pong", Duration::from_millis(100))
+            .await
+            .is_none()
+        {}
+    }
+
+    fn test_csr() -> String {
+        KeyCertPair::new_certificate_sign_request(
+            &certificate::CsrTemplate::default(),
+            "my-device",
+            &KeyKind::New,
+        )
+        .unwrap()
+        .certificate_signing_request_string()
+        .unwrap()
+    }
+}
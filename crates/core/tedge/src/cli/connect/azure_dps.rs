@@ -0,0 +1,368 @@
+//! Device registration with the Azure IoT Hub Device Provisioning Service (DPS)
+//!
+//! The device connects the DPS endpoint over MQTT, authenticated with its certificate,
+//! and requests its registration. DPS then asynchronously assigns the device to an IoT Hub,
+//! the device polling the status of the registration till completed.
+//!
+//! See <https://learn.microsoft.com/en-us/azure/iot-dps/iot-dps-mqtt-support>
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use rumqttc::AsyncClient;
+use rumqttc::Event;
+use rumqttc::Incoming;
+use rumqttc::MqttOptions;
+use rumqttc::Packet;
+use rumqttc::QoS::AtLeastOnce;
+use rumqttc::Transport;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use tedge_config::tedge_toml::mapper_config::AzMapperSpecificConfig;
+use tedge_config::tedge_toml::ProfileName;
+use tedge_config::tedge_toml::WritableKey;
+use tedge_config::TEdgeConfig;
+use tracing::debug;
+
+const DPS_API_VERSION: &str = "2019-03-31";
+
+/// Root of the DPS topics
+///
+/// Only a test stand-in for DPS is expected to use another root,
+/// as brokers commonly forbid clients to subscribe to `$` topics.
+const DPS_TOPIC_ROOT: &str = "$dps";
+
+/// How long to wait between two polls of the registration status, if not told by DPS
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(3);
+
+/// How long to wait for the device to be assigned to an IoT Hub
+pub(crate) const DPS_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The IoT Hub and device id assigned to the device by DPS
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct DpsAssignment {
+    pub assigned_hub: String,
+    pub device_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RegistrationOperationStatus {
+    operation_id: String,
+    status: String,
+    registration_state: Option<RegistrationState>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RegistrationState {
+    assigned_hub: Option<String>,
+    device_id: Option<String>,
+    error_message: Option<String>,
+}
+
+/// Check if the device has to be registered to DPS on connect
+pub(crate) fn is_configured(
+    tedge_config: &TEdgeConfig,
+    profile: Option<&ProfileName>,
+) -> anyhow::Result<bool> {
+    let az_config = tedge_config.mapper_config::<AzMapperSpecificConfig>(&profile)?;
+    Ok(az_config.cloud_specific.dps.id_scope.or_none().is_some())
+}
+
+/// Register the device to DPS and persist the assigned IoT Hub and device id
+///
+/// The configuration has then to be reloaded.
+pub(crate) async fn provision_device(
+    tedge_config: &TEdgeConfig,
+    profile: Option<&ProfileName>,
+) -> anyhow::Result<DpsAssignment> {
+    let az_config = tedge_config.mapper_config::<AzMapperSpecificConfig>(&profile)?;
+    let dps_config = &az_config.cloud_specific.dps;
+    let id_scope = dps_config.id_scope.or_config_not_set()?;
+    let registration_id = az_config.device.id()?;
+
+    let mut mqtt_options = dps_mqtt_options(
+        &dps_config.url.host().to_string(),
+        dps_config.url.port().into(),
+        id_scope,
+        &registration_id,
+    );
+    let tls_config = tedge_config
+        .mqtt_client_config_rustls(&az_config)
+        .context("Failed to create the TLS config to connect DPS")?;
+    mqtt_options.set_transport(Transport::tls_with_config(tls_config.into()));
+    let assignment =
+        register_device(mqtt_options, DPS_TOPIC_ROOT, &registration_id, DPS_TIMEOUT).await?;
+
+    let key_prefix = match profile {
+        None => "az".to_string(),
+        Some(profile) => format!("az.profiles.{profile}"),
+    };
+    let url_key: WritableKey = format!("{key_prefix}.url").parse()?;
+    let device_id_key: WritableKey = format!("{key_prefix}.device.id").parse()?;
+    TEdgeConfig::load(tedge_config.root_dir())
+        .await?
+        .update_toml(&|dto, _reader| {
+            dto.try_update_str(&url_key, &assignment.assigned_hub)?;
+            dto.try_update_str(&device_id_key, &assignment.device_id)?;
+            Ok(())
+        })
+        .await?;
+
+    Ok(assignment)
+}
+
+/// MQTT connection options to register a device on a DPS instance
+///
+/// The transport, i.e. the TLS configuration with the device certificate, has to be set by the caller.
+pub(crate) fn dps_mqtt_options(
+    host: &str,
+    port: u16,
+    id_scope: &str,
+    registration_id: &str,
+) -> MqttOptions {
+    let mut mqtt_options = MqttOptions::new(registration_id, host, port);
+    mqtt_options.set_credentials(
+        format!("{id_scope}/registrations/{registration_id}/api-version={DPS_API_VERSION}"),
+        "",
+    );
+    mqtt_options.set_keep_alive(Duration::from_secs(30));
+    mqtt_options.set_clean_session(true);
+    mqtt_options
+}
+
+/// Register the device on DPS, returning the IoT Hub and device id assigned to the device
+pub(crate) async fn register_device(
+    mqtt_options: MqttOptions,
+    topic_root: &str,
+    registration_id: &str,
+    timeout: Duration,
+) -> anyhow::Result<DpsAssignment> {
+    let (client, mut event_loop) = AsyncClient::new(mqtt_options, 10);
+    let registration = tokio::time::timeout(timeout, async {
+        let mut rid = 0u32;
+        loop {
+            match event_loop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    client.subscribe(format!("{topic_root}/registrations/res/#"), AtLeastOnce).await?;
+                }
+                Ok(Event::Incoming(Packet::SubAck(_))) => {
+                    rid += 1;
+                    debug!("Requesting the device registration to DPS");
+                    request_registration(&client, topic_root, rid, registration_id).await?;
+                }
+                Ok(Event::Incoming(Packet::Publish(response))) => {
+                    let Some((status, retry_after)) = parse_response_topic(topic_root, &response.topic) else {
+                        continue;
+                    };
+                    let payload = std::str::from_utf8(&response.payload).unwrap_or_default();
+                    debug!("Received DPS response {status}: {payload}");
+                    let operation_id = match status {
+                        200..=299 => {
+                            let operation: RegistrationOperationStatus =
+                                serde_json::from_str(payload)
+                                    .context("Invalid registration status received from DPS")?;
+                            if let Some(assignment) = assignment_of(&operation)? {
+                                return Ok(assignment);
+                            }
+                            operation.operation_id
+                        }
+                        429 => {
+                            // Throttled: retry the registration request
+                            tokio::time::sleep(retry_after.unwrap_or(DEFAULT_RETRY_AFTER)).await;
+                            rid += 1;
+                            request_registration(&client, topic_root, rid, registration_id).await?;
+                            continue;
+                        }
+                        _ => bail!("DPS rejected the registration with status {status}: {payload}"),
+                    };
+
+                    tokio::time::sleep(retry_after.unwrap_or(DEFAULT_RETRY_AFTER)).await;
+                    rid += 1;
+                    client
+                        .publish(
+                            format!("{topic_root}/registrations/GET/iotdps-get-operationstatus/?$rid={rid}&operationId={operation_id}"),
+                            AtLeastOnce,
+                            false,
+                            "",
+                        )
+                        .await?;
+                }
+                Ok(Event::Incoming(Incoming::Disconnect)) => {
+                    bail!("Unexpectedly disconnected from DPS")
+                }
+                Err(err) => {
+                    return Err(err).context("Failed to connect to the Azure Device Provisioning Service")
+                }
+                _ => {}
+            }
+        }
+    })
+    .await
+    .unwrap_or_else(|_| {
+        Err(anyhow!(
+            "The device has not been assigned to an IoT Hub by DPS after {}",
+            humantime::format_duration(timeout)
+        ))
+    });
+
+    let _ = client.disconnect().await;
+    registration
+}
+
+async fn request_registration(
+    client: &AsyncClient,
+    topic_root: &str,
+    rid: u32,
+    registration_id: &str,
+) -> Result<(), rumqttc::ClientError> {
+    client
+        .publish(
+            format!("{topic_root}/registrations/PUT/iotdps-register/?$rid={rid}"),
+            AtLeastOnce,
+            false,
+            json!({ "registrationId": registration_id }).to_string(),
+        )
+        .await
+}
+
+/// Return the assignment of the device, if the registration is completed
+fn assignment_of(operation: &RegistrationOperationStatus) -> anyhow::Result<Option<DpsAssignment>> {
+    let state = operation.registration_state.as_ref();
+    match operation.status.as_str() {
+        "assigned" => {
+            let assigned_hub = state.and_then(|state| state.assigned_hub.clone());
+            let device_id = state.and_then(|state| state.device_id.clone());
+            match (assigned_hub, device_id) {
+                (Some(assigned_hub), Some(device_id)) => Ok(Some(DpsAssignment {
+                    assigned_hub,
+                    device_id,
+                })),
+                _ => bail!("DPS assigned the device without providing the IoT Hub and device id"),
+            }
+        }
+        "unassigned" | "assigning" => Ok(None),
+        status => {
+            let reason = state
+                .and_then(|state| state.error_message.clone())
+                .unwrap_or_default();
+            bail!("The device registration to DPS failed with status {status}: {reason}")
+        }
+    }
+}
+
+/// Extract the status and retry-after delay of a DPS response topic
+///
+/// e.g. `$dps/registrations/res/202/?$rid=1&retry-after=3`
+fn parse_response_topic(topic_root: &str, topic: &str) -> Option<(u16, Option<Duration>)> {
+    let response = topic
+        .strip_prefix(topic_root)?
+        .strip_prefix("/registrations/res/")?;
+    let (status, properties) = response.split_once('/')?;
+    let status = status.parse().ok()?;
+    let retry_after = properties
+        .trim_start_matches('?')
+        .split('&')
+        .find_map(|property| property.strip_prefix("retry-after="))
+        .and_then(|secs| secs.parse().ok())
+        .map(Duration::from_secs);
+    Some((status, retry_after))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mqtt_tests::test_mqtt_broker;
+
+    #[test]
+    fn parse_dps_response_topics() {
+        assert_eq!(
+            parse_response_topic(
+                DPS_TOPIC_ROOT,
+                "$dps/registrations/res/202/?$rid=1&retry-after=5"
+            ),
+            Some((202, Some(Duration::from_secs(5))))
+        );
+        assert_eq!(
+            parse_response_topic(DPS_TOPIC_ROOT, "$dps/registrations/res/200/?$rid=2"),
+            Some((200, None))
+        );
+        assert_eq!(
+            parse_response_topic(DPS_TOPIC_ROOT, "az/twin/res/200/?$rid=2"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn device_is_registered_with_dps() {
+        let broker = test_mqtt_broker();
+        broker.map_messages_background(|(topic, _)| {
+            if topic == "dps/ping" {
+                vec![("dps/pong".to_string(), "".to_string())]
+            } else if topic.starts_with("dps/registrations/PUT/iotdps-register/") {
+                vec![(
+                    "dps/registrations/res/202/?$rid=1&retry-after=1".to_string(),
+                    r#"{"operationId":"4.a1b2","status":"assigning"}"#.to_string(),
+                )]
+            } else if topic.starts_with("dps/registrations/GET/iotdps-get-operationstatus/")
+                && topic.ends_with("operationId=4.a1b2")
+            {
+                vec![(
+                    "dps/registrations/res/200/?$rid=2".to_string(),
+                    r#"{
+                        "operationId":"4.a1b2",
+                        "status":"assigned",
+                        "registrationState":{
+                            "registrationId":"my-device",
+                            "assignedHub":"my-hub.azure-devices.net",
+                            "deviceId":"my-device",
+                            "status":"assigned"
+                        }
+                    }"#
+                    .to_string(),
+                )]
+            } else {
+                vec![]
+            }
+        });
+
+        // Wait for the DPS stand-in to be listening
+        while broker
+            .wait_for_response_on_publish("dps/ping", "", "dps/pong", Duration::from_millis(100))
+            .await
+            .is_none()
+        {}
+
+        let mqtt_options = dps_mqtt_options("127.0.0.1", broker.port, "0ne00AB1234", "my-device");
+        let assignment = register_device(mqtt_options, "dps", "my-device", Duration::from_secs(10))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            assignment,
+            DpsAssignment {
+                assigned_hub: "my-hub.azure-devices.net".to_string(),
+                device_id: "my-device".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn failed_registrations_are_reported() {
+        let operation: RegistrationOperationStatus = serde_json::from_str(
+            r#"{
+                "operationId":"4.a1b2",
+                "status":"failed",
+                "registrationState":{"status":"failed","errorMessage":"Custom allocation failed"}
+            }"#,
+        )
+        .unwrap();
+
+        let err = assignment_of(&operation).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The device registration to DPS failed with status failed: Custom allocation failed"
+        );
+    }
+}
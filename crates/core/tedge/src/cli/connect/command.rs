@@ -4,6 +4,8 @@ use crate::bridge::aws::BridgeConfigAwsParams;
 use crate::bridge::azure::BridgeConfigAzureParams;
 #[cfg(feature = "c8y")]
 use crate::bridge::c8y::BridgeConfigC8yParams;
+#[cfg(feature = "mqtt_cloud")]
+use crate::bridge::mqtt_cloud::BridgeConfigMqttCloudParams;
 use crate::bridge::BridgeConfig;
 use crate::bridge::BridgeLocation;
 use crate::bridge::CommonMosquittoConfig;
@@ -29,6 +31,10 @@ use c8y_api::http_proxy::read_c8y_credentials;
 use camino::Utf8PathBuf;
 use certificate::parse_root_certificate::CryptokiConfig;
 use mqtt_channel::Topic;
+#[cfg(feature = "mqtt_cloud")]
+use mqtt_cloud_mapper_ext::read_mqtt_cloud_credentials;
+#[cfg(feature = "mqtt_cloud")]
+use mqtt_cloud_mapper_ext::MqttCloudTopics;
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::hash::Hash;
@@ -42,7 +48,7 @@ use tedge_config::all_or_nothing;
 use tedge_config::models::auth_method::AuthType;
 use tedge_config::models::proxy_scheme::ProxyScheme;
 use tedge_config::models::AbsolutePath;
-#[cfg(any(feature = "aws", feature = "azure"))]
+#[cfg(any(feature = "aws", feature = "azure", feature = "mqtt_cloud"))]
 use tedge_config::models::HostPort;
 use tedge_config::models::TopicPrefix;
 use tedge_config::models::MQTT_SERVICE_TLS_PORT;
@@ -54,12 +60,14 @@ use tedge_config::tedge_toml::mapper_config::AzMapperSpecificConfig;
 use tedge_config::tedge_toml::mapper_config::C8yMapperSpecificConfig;
 use tedge_config::tedge_toml::mapper_config::HasUrl;
 use tedge_config::tedge_toml::mapper_config::MapperConfig;
+#[cfg(feature = "mqtt_cloud")]
+use tedge_config::tedge_toml::mapper_config::MqttCloudMapperSpecificConfig;
 use tedge_config::tedge_toml::mapper_config::SpecialisedCloudConfig;
 #[cfg(feature = "c8y")]
 use tedge_config::tedge_toml::ProfileName;
 use tedge_config::tedge_toml::TEdgeConfigReaderMqtt;
 use tedge_config::TEdgeConfig;
-#[cfg(any(feature = "aws", feature = "azure"))]
+#[cfg(any(feature = "aws", feature = "azure", feature = "mqtt_cloud"))]
 use tedge_config::TEdgeConfigError;
 use tedge_utils::file::path_exists;
 use tedge_utils::paths::create_directories;
@@ -72,7 +80,7 @@ pub(crate) const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
 #[cfg(feature = "c8y")]
 pub(crate) const CONNECTION_TIMEOUT: Duration = Duration::from_secs(60);
 const MOSQUITTO_RESTART_TIMEOUT_SECONDS: u64 = 20;
-#[cfg(any(feature = "aws", feature = "azure"))]
+#[cfg(any(feature = "aws", feature = "azure", feature = "mqtt_cloud"))]
 const MQTT_TLS_PORT: u16 = 8883;
 
 pub struct ConnectCommand {
@@ -101,6 +109,7 @@ impl Command for ConnectCommand {
     }
 
     async fn execute(&self, tedge_config: TEdgeConfig) -> Result<(), MaybeFancy<anyhow::Error>> {
+        let tedge_config = self.provision_device(tedge_config).await?;
         let bridge_config = bridge_config(&tedge_config, &self.cloud)
             .await
             .map_err(anyhow::Error::new)?;
@@ -176,6 +185,58 @@ impl Command for ConnectCommand {
 }
 
 impl ConnectCommand {
+    /// Register the device to the cloud provisioning service, if configured
+    ///
+    /// Return the configuration updated with the endpoint, device id or certificate assigned to the device.
+    async fn provision_device(
+        &self,
+        tedge_config: TEdgeConfig,
+    ) -> Result<TEdgeConfig, MaybeFancy<anyhow::Error>> {
+        if self.is_test_connection || self.offline_mode {
+            return Ok(tedge_config);
+        }
+        match &self.cloud {
+            #[cfg(feature = "azure")]
+            Cloud::Azure(profile) => {
+                let profile = profile.as_deref();
+                if !azure_dps::is_configured(&tedge_config, profile)? {
+                    return Ok(tedge_config);
+                }
+                let spinner = Spinner::start(
+                    "Registering the device with the Azure Device Provisioning Service",
+                );
+                let res = azure_dps::provision_device(&tedge_config, profile).await;
+                let assignment = spinner.finish(res)?;
+                eprintln!(
+                    "Device {} assigned to the IoT Hub {}",
+                    assignment.device_id, assignment.assigned_hub
+                );
+                Ok(TEdgeConfig::load(tedge_config.root_dir())
+                    .await
+                    .map_err(anyhow::Error::new)?)
+            }
+            #[cfg(feature = "aws")]
+            Cloud::Aws(profile) => {
+                let profile = profile.as_deref();
+                if !aws_fleet_provisioning::is_configured(&tedge_config, profile)? {
+                    return Ok(tedge_config);
+                }
+                let spinner =
+                    Spinner::start("Provisioning the device with AWS IoT fleet provisioning");
+                let res = aws_fleet_provisioning::provision_device(&tedge_config, profile).await;
+                let thing = spinner.finish(res)?;
+                eprintln!("Thing {} registered with AWS IoT", thing.thing_name);
+                Ok(TEdgeConfig::load(tedge_config.root_dir())
+                    .await
+                    .map_err(anyhow::Error::new)?)
+            }
+            #[cfg(feature = "c8y")]
+            Cloud::C8y(_) => Ok(tedge_config),
+            #[cfg(feature = "mqtt_cloud")]
+            Cloud::MqttCloud(_) => Ok(tedge_config),
+        }
+    }
+
     async fn check_bridge(
         &self,
         tedge_config: &TEdgeConfig,
@@ -286,6 +347,8 @@ impl ConnectCommand {
             Cloud::Aws(_) => (),
             #[cfg(feature = "azure")]
             Cloud::Azure(_) => (),
+            #[cfg(feature = "mqtt_cloud")]
+            Cloud::MqttCloud(_) => (),
         }
 
         if connection_check_success {
@@ -396,6 +459,8 @@ impl ConnectCommand {
             Cloud::Aws(_) => Ok(()),
             #[cfg(feature = "azure")]
             Cloud::Azure(_) => Ok(()),
+            #[cfg(feature = "mqtt_cloud")]
+            Cloud::MqttCloud(_) => Ok(()),
         }
     }
 }
@@ -414,6 +479,14 @@ async fn credentials_path_for(
         Cloud::Aws(_) => Ok(None),
         #[cfg(feature = "azure")]
         Cloud::Azure(_) => Ok(None),
+        #[cfg(feature = "mqtt_cloud")]
+        Cloud::MqttCloud(profile) => {
+            let mqtt_cloud_config =
+                _config.mapper_config::<MqttCloudMapperSpecificConfig>(profile)?;
+            Ok(Some(
+                mqtt_cloud_config.cloud_specific.credentials_path.clone(),
+            ))
+        }
     }
 }
 
@@ -455,6 +528,8 @@ impl ConnectCommand {
             Cloud::Aws(_) => Ok(None),
             #[cfg(feature = "azure")]
             Cloud::Azure(_) => Ok(None),
+            #[cfg(feature = "mqtt_cloud")]
+            Cloud::MqttCloud(_) => Ok(None),
         }
     }
 
@@ -493,6 +568,10 @@ impl ConnectCommand {
             }
             #[cfg(feature = "c8y")]
             Cloud::C8y(profile) => check_device_status_c8y(tedge_config, profile.as_deref()).await,
+            #[cfg(feature = "mqtt_cloud")]
+            Cloud::MqttCloud(profile) => {
+                mqtt_cloud::check_device_status_mqtt_cloud(tedge_config, profile.as_deref()).await
+            }
         };
         spinner.finish(res)
     }
@@ -546,6 +625,12 @@ fn validate_config(config: &TEdgeConfig, cloud: &MaybeBorrowedCloud<'_>) -> anyh
             disallow_matching_bridge_topic_prefix(&configs)?;
             disallow_matching_proxy_bind_port(&configs)?;
         }
+        #[cfg(feature = "mqtt_cloud")]
+        MaybeBorrowedCloud::MqttCloud(_) => {
+            let configs = config.all_mapper_configs::<MqttCloudMapperSpecificConfig>();
+            disallow_matching_url_device_id(&configs)?;
+            disallow_matching_bridge_topic_prefix(&configs)?;
+        }
     }
     Ok(())
 }
@@ -795,6 +880,58 @@ pub async fn bridge_config(
                 custom_topics: c8y_config.cloud_specific.mqtt_service.topics.clone(),
             };
 
+            Ok(BridgeConfig::from(params))
+        }
+        #[cfg(feature = "mqtt_cloud")]
+        MaybeBorrowedCloud::MqttCloud(profile) => {
+            let mqtt_cloud_config =
+                config.mapper_config::<MqttCloudMapperSpecificConfig>(profile)?;
+            let cloud_specific = &mqtt_cloud_config.cloud_specific;
+
+            let auth_type = cloud_specific
+                .auth_method
+                .to_type(&cloud_specific.credentials_path);
+            let (remote_username, remote_password) = match auth_type {
+                AuthType::Certificate => (None, None),
+                AuthType::Basic => {
+                    let (username, password) =
+                        read_mqtt_cloud_credentials(&cloud_specific.credentials_path)
+                            .map_err(anyhow::Error::from)?;
+                    (Some(username), Some(password))
+                }
+            };
+
+            let remote_clientid = mqtt_cloud_config.device.id()?.clone();
+            let topics =
+                MqttCloudTopics::try_new(&cloud_specific.topic_templates, &remote_clientid)
+                    .map_err(anyhow::Error::from)?;
+
+            let mut mqtt_host = HostPort::<MQTT_TLS_PORT>::try_from(
+                mqtt_cloud_config.url().or_config_not_set()?.as_str(),
+            )
+            .map_err(TEdgeConfigError::from)?;
+            mqtt_host.set_port(cloud_specific.port);
+
+            let params = BridgeConfigMqttCloudParams {
+                mqtt_host,
+                config_file: cloud.mosquitto_config_filename(),
+                remote_clientid,
+                remote_username,
+                remote_password,
+                bridge_root_cert_path: mqtt_cloud_config.root_cert_path.clone().into(),
+                bridge_certfile: mqtt_cloud_config.device.cert_path.clone().into(),
+                bridge_keyfile: mqtt_cloud_config.device.key_path.clone().into(),
+                bridge_location,
+                topic_prefix: mqtt_cloud_config.bridge.topic_prefix.clone(),
+                profile_name: profile.clone().map(Cow::into_owned),
+                mqtt_schema,
+                keepalive_interval: mqtt_cloud_config.bridge.keepalive_interval.duration(),
+                proxy,
+                auth_type,
+                outgoing_filters: topics.outgoing_filters(),
+                incoming_filters: topics.incoming_filters(),
+            };
+
             Ok(BridgeConfig::from(params))
         }
     }
@@ -878,6 +1015,8 @@ impl ConnectCommand {
             Cloud::Aws(_) => (),
             #[cfg(feature = "azure")]
             Cloud::Azure(_) => (),
+            #[cfg(feature = "mqtt_cloud")]
+            Cloud::MqttCloud(_) => (),
         }
 
         if let Err(err) =
@@ -4,10 +4,16 @@ pub use self::error::*;
 
 #[cfg(feature = "aws")]
 mod aws;
+#[cfg(feature = "aws")]
+mod aws_fleet_provisioning;
 #[cfg(feature = "azure")]
 mod azure;
+#[cfg(feature = "azure")]
+mod azure_dps;
 #[cfg(feature = "c8y")]
 mod c8y;
 mod cli;
 mod command;
 mod error;
+#[cfg(feature = "mqtt_cloud")]
+mod mqtt_cloud;
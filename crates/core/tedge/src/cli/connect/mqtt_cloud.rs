@@ -0,0 +1,130 @@
+use super::command::bridge_health_topic;
+use super::command::is_bridge_health_up_message;
+use crate::cli::RESPONSE_TIMEOUT;
+use crate::ConnectError;
+use crate::DeviceStatus;
+use anyhow::anyhow;
+use rumqttc::Event;
+use rumqttc::Incoming;
+use rumqttc::Outgoing;
+use rumqttc::Packet;
+use rumqttc::QoS::AtLeastOnce;
+use tedge_config::tedge_toml::mapper_config::MqttCloudMapperSpecificConfig;
+use tedge_config::tedge_toml::ProfileName;
+use tedge_config::TEdgeConfig;
+
+pub async fn check_device_status_mqtt_cloud(
+    tedge_config: &TEdgeConfig,
+    profile: Option<&ProfileName>,
+) -> Result<DeviceStatus, ConnectError> {
+    let mqtt_cloud_config =
+        tedge_config.mapper_config::<MqttCloudMapperSpecificConfig>(&profile)?;
+    let topic_prefix = &mqtt_cloud_config.bridge.topic_prefix;
+    let topic_pub_check_connection = format!("{topic_prefix}/test-connection");
+    let topic_sub_check_connection = format!("{topic_prefix}/connection-success");
+    let built_in_bridge_health = bridge_health_topic(topic_prefix, tedge_config).name;
+    const CLIENT_ID: &str = "check_connection_mqtt_cloud";
+    const REGISTRATION_PAYLOAD: &[u8] = b"";
+
+    let mut mqtt_options = tedge_config
+        .mqtt_config()?
+        .with_session_prefix(CLIENT_ID)
+        .rumqttc_options()?;
+    mqtt_options.set_keep_alive(RESPONSE_TIMEOUT);
+
+    let (client, mut event_loop) = rumqttc::AsyncClient::new(mqtt_options, 10);
+    let mut acknowledged = false;
+
+    if tedge_config.mqtt.bridge.built_in {
+        client
+            .subscribe(&built_in_bridge_health, AtLeastOnce)
+            .await?;
+    }
+    client
+        .subscribe(&topic_sub_check_connection, AtLeastOnce)
+        .await?;
+
+    let mut err = None;
+    loop {
+        match event_loop.poll().await {
+            Ok(Event::Incoming(Packet::SubAck(_))) => {
+                // We are ready to get the response, hence send the request
+                client
+                    .publish(
+                        &topic_pub_check_connection,
+                        AtLeastOnce,
+                        false,
+                        REGISTRATION_PAYLOAD,
+                    )
+                    .await?;
+            }
+            Ok(Event::Incoming(Packet::PubAck(_))) => {
+                // The request has been sent
+                acknowledged = true;
+            }
+            Ok(Event::Incoming(Packet::Publish(response))) => {
+                if response.topic == topic_sub_check_connection {
+                    // We got a response
+                    break;
+                } else if is_bridge_health_up_message(
+                    &response,
+                    &built_in_bridge_health,
+                    tedge_config.mqtt.bridge.built_in,
+                ) {
+                    // Built in bridge is now up, republish the message in case it was never received by the bridge
+                    client
+                        .publish(
+                            &topic_pub_check_connection,
+                            AtLeastOnce,
+                            false,
+                            REGISTRATION_PAYLOAD,
+                        )
+                        .await?;
+                }
+            }
+            Ok(Event::Outgoing(Outgoing::PingReq)) => {
+                // No messages have been received for a while
+                err = Some(if acknowledged {
+                    anyhow!("Didn't receive a response from the MQTT broker")
+                } else {
+                    anyhow!("Local MQTT publish has timed out")
+                });
+                break;
+            }
+            Ok(Event::Incoming(Incoming::Disconnect)) => {
+                err = Some(anyhow!(
+                    "Client was disconnected from mosquitto during connection check"
+                ));
+                break;
+            }
+            Err(e) => {
+                err = Some(
+                    anyhow::Error::from(e)
+                        .context("Failed to connect to mosquitto for connection check"),
+                );
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    // Cleanly disconnect client
+    client.disconnect().await?;
+    loop {
+        match event_loop.poll().await {
+            Ok(Event::Outgoing(Outgoing::Disconnect)) | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    match err {
+        None => Ok(DeviceStatus::AlreadyExists),
+        // In Cumulocity we connect directly first to create a device so we know we can connect so
+        // we return `DeviceStatus::Unknown` when we can't check its status, but here we can fail to
+        // even connect because we're connecting through the bridge and haven't connected directly
+        // prior
+        Some(err) => Err(err
+            .context("Failed to verify device is connected to the MQTT broker")
+            .into()),
+    }
+}
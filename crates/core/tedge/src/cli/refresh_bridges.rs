@@ -99,6 +99,12 @@ fn possible_clouds(config: &TEdgeConfig) -> impl Iterator<Item = CloudBorrow<'_>
     let iter = iter.chain(config.az_keys().map(CloudBorrow::az_borrowed));
     #[cfg(feature = "aws")]
     let iter = iter.chain(config.aws_keys().map(CloudBorrow::aws_borrowed));
+    #[cfg(feature = "mqtt_cloud")]
+    let iter = iter.chain(
+        config
+            .mqtt_cloud_keys()
+            .map(CloudBorrow::mqtt_cloud_borrowed),
+    );
 
     iter
 }
@@ -0,0 +1,143 @@
+use crate::command::Command;
+use crate::log::MaybeFancy;
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use mqtt_channel::PubChannel;
+use mqtt_channel::StreamExt;
+use reqwest::Client;
+use std::path::PathBuf;
+use std::time::Duration;
+use tedge_api::commands::CommandStatus;
+use tedge_api::commands::FileUploadCmd;
+use tedge_api::commands::FileUploadCmdPayload;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_config::TEdgeConfig;
+use tedge_utils::file::path_exists;
+use tokio::fs::File;
+
+/// Upload a file to the cloud the device is connected to
+///
+/// The file is staged on the file transfer service,
+/// then a `file_upload` command is published for the mappers to push the file to their cloud.
+pub struct FileUpload {
+    /// Client to the file transfer service
+    pub http: Client,
+
+    /// Where to stage the file on the file transfer service
+    pub tedge_url: String,
+
+    /// MQTT connection settings
+    pub mqtt_config: mqtt_channel::Config,
+    pub mqtt_schema: MqttSchema,
+
+    /// The device or service which the file is related to
+    pub topic_id: EntityTopicId,
+    pub cmd_id: String,
+
+    /// Path to the uploaded file
+    pub file: PathBuf,
+
+    /// The file upload request
+    pub request: FileUploadCmdPayload,
+
+    /// How long to wait for the file to be uploaded
+    pub timeout: Duration,
+}
+
+#[async_trait::async_trait]
+impl Command for FileUpload {
+    fn description(&self) -> String {
+        format!("upload the file {:?} to the cloud", self.file)
+    }
+
+    async fn execute(&self, _: TEdgeConfig) -> Result<(), MaybeFancy<Error>> {
+        if !path_exists(&self.file).await {
+            return Err(anyhow!("Failed to open file: {:?}", self.file))?;
+        }
+        self.stage_file().await?;
+        let result = self.request_upload().await;
+        if let Err(err) = self.unstage_file().await {
+            eprintln!("Failed to remove the file from the file transfer service: {err:#}");
+        }
+        let remote_url = result?;
+        println!("{remote_url}");
+        Ok(())
+    }
+}
+
+impl FileUpload {
+    async fn stage_file(&self) -> Result<(), Error> {
+        let file = File::open(&self.file)
+            .await
+            .with_context(|| format!("Failed to open file: {:?}", self.file))?;
+        self.http
+            .put(&self.tedge_url)
+            .body(file)
+            .send()
+            .await?
+            .error_for_status()
+            .context("Failed to stage the file on the file transfer service")?;
+        Ok(())
+    }
+
+    async fn unstage_file(&self) -> Result<(), Error> {
+        self.http
+            .delete(&self.tedge_url)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Publish the upload command and wait for a mapper to complete it
+    async fn request_upload(&self) -> Result<String, Error> {
+        let command = FileUploadCmd {
+            target: self.topic_id.clone(),
+            cmd_id: self.cmd_id.clone(),
+            payload: self.request.clone(),
+        };
+
+        let mut mqtt = mqtt_channel::Connection::new(&self.mqtt_config).await?;
+        mqtt.published
+            .publish(command.command_message(&self.mqtt_schema))
+            .await?;
+
+        let outcome = tokio::time::timeout(self.timeout, async {
+            while let Some(message) = mqtt.received.next().await {
+                let Some(command) = FileUploadCmd::try_from_bytes(
+                    self.topic_id.clone(),
+                    self.cmd_id.clone(),
+                    message.payload_bytes(),
+                )?
+                else {
+                    continue;
+                };
+                match command.status() {
+                    CommandStatus::Successful => {
+                        return Ok(command.payload.remote_url.unwrap_or_default())
+                    }
+                    CommandStatus::Failed { reason } => {
+                        return Err(anyhow!("Failed to upload the file: {reason}"))
+                    }
+                    _ => continue,
+                }
+            }
+            Err(anyhow!("Connection to the MQTT broker closed unexpectedly"))
+        })
+        .await
+        .unwrap_or_else(|_| {
+            Err(anyhow!(
+                "No cloud mapper uploaded the file after {}",
+                humantime::format_duration(self.timeout)
+            ))
+        });
+
+        mqtt.published
+            .publish(command.clearing_message(&self.mqtt_schema))
+            .await?;
+        mqtt.close().await;
+        outcome
+    }
+}
@@ -1,13 +1,25 @@
 use crate::command::BuildCommand;
 use crate::command::Command;
 use crate::ConfigError;
+use anyhow::anyhow;
+use anyhow::Context;
 use c8y_api::http_proxy::C8yEndPoint;
+use certificate::CloudHttpConfig;
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
+use tedge_api::commands::CommandStatus;
+use tedge_api::commands::FileUploadCmdPayload;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_config::models::TopicPrefix;
 use tedge_config::tedge_toml::ProfileName;
 use tedge_config::TEdgeConfig;
 
 mod c8y;
+mod file;
 
 #[derive(clap::Subcommand, Debug)]
 pub enum UploadCmd {
@@ -52,6 +64,52 @@ pub enum UploadCmd {
         #[clap(long)]
         device_id: Option<String>,
     },
+
+    /// Upload a file to the cloud the device is connected to
+    ///
+    /// The file is staged on the file transfer service,
+    /// and pushed to the cloud by the mapper handling the resulting `file_upload` command:
+    /// attached to an event on Cumulocity, stored as a blob on Azure, or as an S3 object on AWS.
+    /// On success, the URL of the uploaded file is printed.
+    #[clap(verbatim_doc_comment)]
+    File {
+        /// Path to the uploaded file
+        #[clap(long)]
+        file: PathBuf,
+
+        /// Name of the file in the cloud. Defaults to the name of the uploaded file
+        #[clap(long)]
+        name: Option<String>,
+
+        /// MIME type of the file content
+        ///
+        /// If not provided, the mime type is determined from the file extension
+        /// If no rules apply, application/octet-stream is taken as a default
+        #[clap(long, verbatim_doc_comment)]
+        #[arg(value_parser = parse_mime_type)]
+        mime_type: Option<String>,
+
+        /// Cloud-specific category of the file, as the Cumulocity event type
+        #[clap(long = "type")]
+        file_type: Option<String>,
+
+        /// Topic prefix of the mapper expected to upload the file, as `c8y`, `az` or `aws`
+        ///
+        /// If not provided, the file is uploaded by all the running mappers.
+        #[clap(long)]
+        cloud: Option<TopicPrefix>,
+
+        /// Topic identifier of the device or service which the file is related to
+        ///
+        /// If not given, the file is related to the main device.
+        #[clap(long)]
+        topic_id: Option<EntityTopicId>,
+
+        /// How long to wait for the file to be uploaded
+        #[clap(long, default_value = "5m")]
+        #[arg(value_parser = humantime::parse_duration)]
+        timeout: Duration,
+    },
 }
 
 fn parse_json(input: &str) -> Result<HashMap<String, serde_json::Value>, anyhow::Error> {
@@ -100,12 +158,94 @@ impl BuildCommand for UploadCmd {
                     file,
                     mime_type,
                 }
+                .into_boxed()
+            }
+            UploadCmd::File {
+                file,
+                name,
+                mime_type,
+                file_type,
+                cloud,
+                topic_id,
+                timeout,
+            } => {
+                let name = match name {
+                    Some(name) => name,
+                    None => file
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .map(|name| name.to_string())
+                        .ok_or_else(|| anyhow!("Invalid file name: {file:?}"))?,
+                };
+                let mime_type = mime_type.unwrap_or_else(|| {
+                    mime_guess::from_path(&file)
+                        .first_or_octet_stream()
+                        .to_string()
+                });
+                let topic_id = topic_id.unwrap_or_else(|| config.mqtt.device_topic_id.clone());
+                let cmd_id = format!("tedge-upload-{}", nanoid::nanoid!());
+
+                let identity = config.http.client.auth.identity()?;
+                let http = file_transfer_client(config.cloud_root_certs().await?, identity)?;
+                let protocol = config.http.cert_path.or_none().map_or("http", |_| "https");
+                let tedge_url = format!(
+                    "{protocol}://{}:{}/te/v1/files/file_upload/{cmd_id}/{name}",
+                    config.http.client.host, config.http.client.port
+                );
+
+                let mqtt_schema = MqttSchema::with_root(config.mqtt.topic_root.clone());
+                let command_topic = mqtt_schema.topic_for(
+                    &topic_id,
+                    &Channel::Command {
+                        operation: OperationType::FileUpload,
+                        cmd_id: cmd_id.clone(),
+                    },
+                );
+                let mqtt_config = config
+                    .mqtt_config()
+                    .context("Failed to configure the MQTT connection")?
+                    .with_session_name(cmd_id.clone())
+                    .with_clean_session(true)
+                    .with_subscriptions(command_topic.into());
+
+                file::FileUpload {
+                    http,
+                    request: FileUploadCmdPayload {
+                        status: CommandStatus::Init,
+                        tedge_url: tedge_url.clone(),
+                        name,
+                        file_type,
+                        mime_type: Some(mime_type),
+                        cloud: cloud.map(|cloud| cloud.to_string()),
+                        remote_url: None,
+                    },
+                    tedge_url,
+                    mqtt_config,
+                    mqtt_schema,
+                    topic_id,
+                    cmd_id,
+                    file,
+                    timeout,
+                }
+                .into_boxed()
             }
         };
-        Ok(cmd.into_boxed())
+        Ok(cmd)
     }
 }
 
+fn file_transfer_client(
+    http_config: CloudHttpConfig,
+    identity: Option<reqwest::Identity>,
+) -> Result<reqwest::Client, anyhow::Error> {
+    let builder = http_config.client_builder();
+    let builder = match identity {
+        Some(identity) => builder.identity(identity),
+        None => builder,
+    };
+    Ok(builder.build()?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -9,5 +9,10 @@ pub type ConfigError = crate::error::TEdgeError;
 const BROKER_USER: &str = "mosquitto";
 const BROKER_GROUP: &str = "mosquitto";
 
-#[cfg(not(any(feature = "aws", feature = "azure", feature = "c8y")))]
-compile_error!("Either feature \"aws\", \"azure\", or \"c8y\" must be enabled.");
+#[cfg(not(any(
+    feature = "aws",
+    feature = "azure",
+    feature = "c8y",
+    feature = "mqtt_cloud"
+)))]
+compile_error!("Either feature \"aws\", \"azure\", \"c8y\", or \"mqtt_cloud\" must be enabled.");
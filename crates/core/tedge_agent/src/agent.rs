@@ -1,3 +1,10 @@
+use crate::certificate_renewal;
+use crate::certificate_renewal::builder::CertificateRenewalBuilder;
+use crate::certificate_renewal::builder::CertificateRenewalConfig;
+use crate::command_manager::builder::CommandManagerBuilder;
+use crate::command_manager::config::CommandManagerConfig;
+use crate::device_inventory::builder::DeviceInventoryBuilder;
+use crate::device_inventory::builder::DeviceInventoryConfig;
 use crate::device_profile_manager::DeviceProfileManagerBuilder;
 use crate::entity_manager;
 use crate::entity_manager::server::EntityStoreRequest;
@@ -68,6 +75,7 @@ use tedge_mqtt_ext::MqttConfig;
 use tedge_mqtt_ext::TopicFilter;
 use tedge_script_ext::ScriptActor;
 use tedge_signal_ext::SignalActor;
+use tedge_timer_ext::TimerActor;
 use tedge_uploader_ext::UploaderActor;
 use tedge_utils::file::create_directory_with_defaults;
 use tracing::info;
@@ -104,6 +112,10 @@ pub(crate) struct AgentConfig {
     pub capabilities: Capabilities,
     pub log_plugin_dirs: Vec<Utf8PathBuf>,
     pub config_plugin_dirs: Vec<Utf8PathBuf>,
+    pub file_cache_max_size: u64,
+    pub cert_renewal_config: Option<CertificateRenewalConfig>,
+    pub inventory_config: Option<DeviceInventoryConfig>,
+    pub command_config: Option<CommandManagerConfig>,
     entity_auto_register: bool,
     entity_store_clean_start: bool,
 }
@@ -195,6 +207,31 @@ impl AgentConfig {
         )
         .into();
 
+        let file_cache_max_size = tedge_config.agent.file_cache.max_size;
+        let cert_renewal_config = if tedge_config.certificate.renewal.enable {
+            Some(CertificateRenewalConfig::from_tedge_config(
+                MqttSchema::with_root(mqtt_topic_root.to_string()),
+                mqtt_device_topic_id.clone(),
+                &tedge_config,
+            )?)
+        } else {
+            None
+        };
+        let inventory_config = if tedge_config.agent.inventory.enable {
+            Some(DeviceInventoryConfig::new(
+                &config_dir,
+                MqttSchema::with_root(mqtt_topic_root.to_string()),
+                mqtt_device_topic_id.clone(),
+                tedge_config.agent.inventory.interval.duration(),
+            ))
+        } else {
+            None
+        };
+        let command_config = if tedge_config.agent.enable.command {
+            Some(CommandManagerConfig::from_tedge_config(&tedge_config)?)
+        } else {
+            None
+        };
         let entity_auto_register = tedge_config.agent.entity_store.auto_register;
         let entity_store_clean_start = tedge_config.agent.entity_store.clean_start;
         let log_plugin_dirs = tedge_config
@@ -239,6 +276,10 @@ impl AgentConfig {
             capabilities,
             log_plugin_dirs,
             config_plugin_dirs,
+            file_cache_max_size,
+            cert_renewal_config,
+            inventory_config,
+            command_config,
             entity_auto_register,
             entity_store_clean_start,
         })
@@ -293,6 +334,9 @@ impl Agent {
         // Load device profile manager before the workflow actor
         // as it will create the device_profile workflow if it does not already exist
         DeviceProfileManagerBuilder::try_new(&self.config.operations_dir).await?;
+        if self.config.cert_renewal_config.is_some() {
+            certificate_renewal::create_workflow_definition(&self.config.operations_dir).await?;
+        }
 
         // Inotify actor
         let mut fs_watch_actor_builder = FsWatchActorBuilder::new();
@@ -328,6 +372,36 @@ impl Agent {
         workflow_actor_builder.register_builtin_operation(&mut restart_actor_builder);
         workflow_actor_builder.register_builtin_operation(&mut software_update_builder);
 
+        // Certificate renewal actor
+        let cert_renewal_builders = self.config.cert_renewal_config.map(|config| {
+            let mut timer_actor_builder = TimerActor::builder();
+            let cert_renewal_builder = CertificateRenewalBuilder::new(
+                config,
+                &mut mqtt_actor_builder,
+                &mut timer_actor_builder,
+            );
+            (cert_renewal_builder, timer_actor_builder)
+        });
+
+        // Device inventory actor
+        let inventory_builders = self.config.inventory_config.map(|config| {
+            let mut timer_actor_builder = TimerActor::builder();
+            let inventory_builder = DeviceInventoryBuilder::new(
+                config,
+                &mut mqtt_actor_builder,
+                &mut timer_actor_builder,
+            );
+            (inventory_builder, timer_actor_builder)
+        });
+
+        // Command actor, if the operation is enabled
+        let command_actor_builder = self.config.command_config.map(|config| {
+            let mut command_actor_builder =
+                CommandManagerBuilder::new(config, &mut uploader_actor_builder);
+            workflow_actor_builder.register_builtin_operation(&mut command_actor_builder);
+            command_actor_builder
+        });
+
         // Shutdown on SIGINT
         let signal_actor_builder = SignalActor::builder(&runtime.get_handle());
 
@@ -472,6 +546,7 @@ impl Agent {
                 mqtt_schema,
                 self.config.fts_url.clone(),
                 self.config.data_dir,
+                self.config.file_cache_max_size,
                 &mut downloader_actor_builder,
                 &mut mqtt_actor_builder,
             );
@@ -496,11 +571,22 @@ impl Agent {
         if let Some(log_actor_builder) = log_actor_builder {
             runtime.spawn(log_actor_builder).await?;
         }
+        if let Some(command_actor_builder) = command_actor_builder {
+            runtime.spawn(command_actor_builder).await?;
+        }
         runtime.spawn(restart_actor_builder).await?;
         runtime.spawn(software_update_builder).await?;
         runtime.spawn(script_runner).await?;
         runtime.spawn(workflow_actor_builder).await?;
         runtime.spawn(health_actor).await?;
+        if let Some((cert_renewal_builder, timer_actor_builder)) = cert_renewal_builders {
+            runtime.spawn(cert_renewal_builder).await?;
+            runtime.spawn(timer_actor_builder).await?;
+        }
+        if let Some((inventory_builder, timer_actor_builder)) = inventory_builders {
+            runtime.spawn(inventory_builder).await?;
+            runtime.spawn(timer_actor_builder).await?;
+        }
 
         runtime.run_to_completion().await?;
 
@@ -0,0 +1,218 @@
+use crate::certificate_renewal::builder::CertificateRenewalConfig;
+use crate::certificate_renewal::builder::CertificateRenewalInput;
+use crate::certificate_renewal::builder::RenewableCertificate;
+use crate::certificate_renewal::builder::RenewalTimerStart;
+use async_trait::async_trait;
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use certificate::CertificateError;
+use certificate::PemCertificate;
+use certificate::ValidityStatus;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tedge_actors::Actor;
+use tedge_actors::LoggingReceiver;
+use tedge_actors::LoggingSender;
+use tedge_actors::MessageReceiver;
+use tedge_actors::RuntimeError;
+use tedge_actors::Sender;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_api::CommandStatus;
+use tedge_config::models::CloudType;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_timer_ext::SetTimeout;
+use time::OffsetDateTime;
+use tracing::info;
+use tracing::warn;
+
+/// Timer event triggering a check of the device certificates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenewalCheck;
+
+/// Payload of the `cert_renew` commands
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CertRenewCmdPayload {
+    #[serde(flatten)]
+    pub status: CommandStatus,
+    pub cloud: CloudType,
+    /// The cloud profile, empty for the default profile
+    #[serde(default)]
+    pub profile: String,
+    pub ca: String,
+    pub certificate: Utf8PathBuf,
+}
+
+pub struct CertificateRenewalActor {
+    config: CertificateRenewalConfig,
+    input_receiver: LoggingReceiver<CertificateRenewalInput>,
+    mqtt_publisher: LoggingSender<MqttMessage>,
+    timer_sender: LoggingSender<RenewalTimerStart>,
+    /// The clouds for which a certificate renewal is in progress, indexed by command topic
+    renewals_in_progress: HashMap<String, String>,
+}
+
+#[async_trait]
+impl Actor for CertificateRenewalActor {
+    fn name(&self) -> &str {
+        "CertificateRenewal"
+    }
+
+    async fn run(mut self) -> Result<(), RuntimeError> {
+        // Give some time to receive the retained renewal commands that are still in progress
+        self.timer_sender
+            .send(SetTimeout::new(Duration::from_secs(1), RenewalCheck))
+            .await?;
+
+        while let Some(input) = self.input_receiver.recv().await {
+            match input {
+                CertificateRenewalInput::MqttMessage(message) => {
+                    self.process_command_update(message)
+                }
+                CertificateRenewalInput::RenewalTimerComplete(_) => {
+                    self.check_certificates().await?;
+                    self.timer_sender
+                        .send(SetTimeout::new(self.config.interval, RenewalCheck))
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl CertificateRenewalActor {
+    pub fn new(
+        config: CertificateRenewalConfig,
+        input_receiver: LoggingReceiver<CertificateRenewalInput>,
+        mqtt_publisher: LoggingSender<MqttMessage>,
+        timer_sender: LoggingSender<RenewalTimerStart>,
+    ) -> Self {
+        Self {
+            config,
+            input_receiver,
+            mqtt_publisher,
+            timer_sender,
+            renewals_in_progress: HashMap::new(),
+        }
+    }
+
+    /// Track the renewals in progress, so a renewal is not triggered twice for the same cloud
+    fn process_command_update(&mut self, message: MqttMessage) {
+        let topic = message.topic.name;
+        if message.payload.as_bytes().is_empty() {
+            self.renewals_in_progress.remove(&topic);
+            return;
+        }
+
+        match serde_json::from_slice::<CertRenewCmdPayload>(message.payload.as_bytes()) {
+            Ok(CertRenewCmdPayload {
+                status: CommandStatus::Successful | CommandStatus::Failed { .. },
+                ..
+            }) => {
+                self.renewals_in_progress.remove(&topic);
+            }
+            Ok(command) => {
+                let cloud = cloud_name(command.cloud, &command.profile);
+                self.renewals_in_progress.insert(topic, cloud);
+            }
+            Err(err) => warn!("Ignoring malformed cert_renew command on {topic}: {err}"),
+        }
+    }
+
+    async fn check_certificates(&mut self) -> Result<(), RuntimeError> {
+        for certificate in self.config.certificates.clone() {
+            let cloud = certificate.cloud_name();
+            if self.renewals_in_progress.values().any(|c| c == &cloud) {
+                continue;
+            }
+
+            match needs_renewal(&certificate.cert_path, self.config.minimum_validity) {
+                Ok(true) => self.trigger_renewal(&certificate).await?,
+                Ok(false) => {}
+                Err(err) => warn!(
+                    "Cannot check the validity of the {cloud} certificate {}: {err}",
+                    certificate.cert_path
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn trigger_renewal(
+        &mut self,
+        certificate: &RenewableCertificate,
+    ) -> Result<(), RuntimeError> {
+        let cloud = certificate.cloud_name();
+        let cmd_id = format!(
+            "tedge-agent-{}",
+            OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000
+        );
+        let channel = Channel::Command {
+            operation: OperationType::Custom("cert_renew".to_string()),
+            cmd_id,
+        };
+        let topic = self
+            .config
+            .mqtt_schema
+            .topic_for(&self.config.device_topic_id, &channel);
+
+        let command = CertRenewCmdPayload {
+            status: CommandStatus::Init,
+            cloud: certificate.cloud,
+            profile: certificate
+                .profile
+                .as_ref()
+                .map(|profile| profile.to_string())
+                .unwrap_or_default(),
+            ca: self.config.ca.clone(),
+            certificate: certificate.cert_path.clone(),
+        };
+
+        info!(
+            "The {cloud} certificate {} is about to expire: triggering its renewal",
+            certificate.cert_path
+        );
+        let payload = serde_json::to_string(&command).unwrap();
+        self.renewals_in_progress.insert(topic.name.clone(), cloud);
+        self.mqtt_publisher
+            .send(MqttMessage::new(&topic, payload).with_retain())
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Check if a certificate expires within the given duration
+pub fn needs_renewal(
+    cert_path: &Utf8Path,
+    minimum_validity: Duration,
+) -> Result<bool, CertificateError> {
+    let certificate = PemCertificate::from_pem_file(cert_path)?;
+    Ok(match certificate.still_valid()? {
+        ValidityStatus::Valid { expired_in } => expired_in <= minimum_validity,
+        ValidityStatus::Expired { .. } => true,
+        ValidityStatus::NotValidYet { .. } => false,
+    })
+}
+
+fn cloud_name(cloud: CloudType, profile: &str) -> String {
+    if profile.is_empty() {
+        cloud.to_string()
+    } else {
+        format!("{cloud}@{profile}")
+    }
+}
+
+impl RenewableCertificate {
+    fn cloud_name(&self) -> String {
+        match &self.profile {
+            None => self.cloud.to_string(),
+            Some(profile) => cloud_name(self.cloud, profile.as_ref()),
+        }
+    }
+}
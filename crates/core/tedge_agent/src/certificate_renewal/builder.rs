@@ -0,0 +1,186 @@
+use crate::certificate_renewal::actor::CertificateRenewalActor;
+use crate::certificate_renewal::actor::RenewalCheck;
+use anyhow::Context;
+use camino::Utf8PathBuf;
+use std::convert::Infallible;
+use std::time::Duration;
+use tedge_actors::fan_in_message_type;
+use tedge_actors::Builder;
+use tedge_actors::CloneSender;
+use tedge_actors::DynSender;
+use tedge_actors::LoggingSender;
+use tedge_actors::MessageSink;
+use tedge_actors::MessageSource;
+use tedge_actors::RuntimeRequest;
+use tedge_actors::RuntimeRequestSink;
+use tedge_actors::Service;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::mqtt_topics::ChannelFilter;
+use tedge_api::mqtt_topics::EntityFilter;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_config::models::CloudType;
+use tedge_config::tedge_toml::Cloud;
+use tedge_config::tedge_toml::ProfileName;
+use tedge_config::TEdgeConfig;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::TopicFilter;
+use tedge_timer_ext::SetTimeout;
+use tedge_timer_ext::Timeout;
+
+pub type RenewalTimerStart = SetTimeout<RenewalCheck>;
+pub type RenewalTimerComplete = Timeout<RenewalCheck>;
+
+fan_in_message_type!(CertificateRenewalInput[MqttMessage, RenewalTimerComplete] : Debug);
+
+#[derive(Debug, Clone)]
+pub struct CertificateRenewalConfig {
+    pub mqtt_schema: MqttSchema,
+    pub device_topic_id: EntityTopicId,
+    /// Interval between two checks of the certificates
+    pub interval: Duration,
+    /// A certificate is renewed when expiring within this duration
+    pub minimum_validity: Duration,
+    /// The CA used to renew the certificates
+    pub ca: String,
+    pub certificates: Vec<RenewableCertificate>,
+}
+
+/// A device certificate used to connect a cloud
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenewableCertificate {
+    pub cloud: CloudType,
+    pub profile: Option<ProfileName>,
+    pub cert_path: Utf8PathBuf,
+}
+
+impl CertificateRenewalConfig {
+    pub fn from_tedge_config(
+        mqtt_schema: MqttSchema,
+        device_topic_id: EntityTopicId,
+        tedge_config: &TEdgeConfig,
+    ) -> Result<Self, anyhow::Error> {
+        let renewal = &tedge_config.certificate.renewal;
+        let mut certificates = Vec::new();
+        for cloud in renewal.clouds.0.iter() {
+            let (cloud, profile) = parse_cloud_profile(cloud)?;
+            let cert_path = tedge_config
+                .device_cert_path(Some(match cloud {
+                    CloudType::C8y => Cloud::C8y(profile.as_ref()),
+                    CloudType::Az => Cloud::Az(profile.as_ref()),
+                    CloudType::Aws => Cloud::Aws(profile.as_ref()),
+                    CloudType::MqttCloud => Cloud::MqttCloud(profile.as_ref()),
+                }))?
+                .into();
+            certificates.push(RenewableCertificate {
+                cloud,
+                profile,
+                cert_path,
+            });
+        }
+
+        Ok(CertificateRenewalConfig {
+            mqtt_schema,
+            device_topic_id,
+            interval: renewal.interval.duration(),
+            minimum_validity: tedge_config
+                .certificate
+                .validity
+                .minimum_duration
+                .duration(),
+            ca: renewal.ca.clone(),
+            certificates,
+        })
+    }
+
+    pub fn subscriptions(&self) -> TopicFilter {
+        self.mqtt_schema.topics(
+            EntityFilter::Entity(&self.device_topic_id),
+            ChannelFilter::Command(OperationType::Custom("cert_renew".to_string())),
+        )
+    }
+}
+
+/// Parse a cloud name optionally followed by a profile name, as in `c8y` or `c8y@eu`
+fn parse_cloud_profile(value: &str) -> Result<(CloudType, Option<ProfileName>), anyhow::Error> {
+    let (cloud, profile) = match value.split_once('@') {
+        None => (value, None),
+        Some((cloud, profile)) => (cloud, Some(profile)),
+    };
+    let cloud = cloud
+        .parse()
+        .with_context(|| format!("Invalid cloud in certificate.renewal.clouds: {value}"))?;
+    let profile = profile
+        .map(str::parse)
+        .transpose()
+        .with_context(|| format!("Invalid profile in certificate.renewal.clouds: {value}"))?;
+    Ok((cloud, profile))
+}
+
+pub struct CertificateRenewalBuilder {
+    config: CertificateRenewalConfig,
+    box_builder: SimpleMessageBoxBuilder<CertificateRenewalInput, MqttMessage>,
+    mqtt_publisher: DynSender<MqttMessage>,
+    timer_sender: DynSender<RenewalTimerStart>,
+}
+
+impl CertificateRenewalBuilder {
+    pub fn new(
+        config: CertificateRenewalConfig,
+        mqtt_actor: &mut (impl MessageSource<MqttMessage, TopicFilter> + MessageSink<MqttMessage>),
+        timer: &mut impl Service<RenewalTimerStart, RenewalTimerComplete>,
+    ) -> Self {
+        let box_builder = SimpleMessageBoxBuilder::new("CertificateRenewal", 16);
+        mqtt_actor.connect_sink(config.subscriptions(), &box_builder.get_sender());
+        let mqtt_publisher = mqtt_actor.get_sender();
+        let timer_sender = timer.connect_client(box_builder.get_sender().sender_clone());
+
+        Self {
+            config,
+            box_builder,
+            mqtt_publisher,
+            timer_sender,
+        }
+    }
+}
+
+impl RuntimeRequestSink for CertificateRenewalBuilder {
+    fn get_signal_sender(&self) -> DynSender<RuntimeRequest> {
+        self.box_builder.get_signal_sender()
+    }
+}
+
+impl Builder<CertificateRenewalActor> for CertificateRenewalBuilder {
+    type Error = Infallible;
+
+    fn try_build(self) -> Result<CertificateRenewalActor, Self::Error> {
+        Ok(self.build())
+    }
+
+    fn build(self) -> CertificateRenewalActor {
+        let mqtt_publisher =
+            LoggingSender::new("CertificateRenewal => Mqtt".into(), self.mqtt_publisher);
+        let timer_sender =
+            LoggingSender::new("CertificateRenewal => Timer".into(), self.timer_sender);
+        let (_, input_receiver) = self.box_builder.build().into_split();
+
+        CertificateRenewalActor::new(self.config, input_receiver, mqtt_publisher, timer_sender)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cloud_with_and_without_profile() {
+        assert_eq!(parse_cloud_profile("c8y").unwrap(), (CloudType::C8y, None));
+        assert_eq!(
+            parse_cloud_profile("az@eu").unwrap(),
+            (CloudType::Az, Some("eu".parse().unwrap()))
+        );
+        assert!(parse_cloud_profile("foo").is_err());
+        assert!(parse_cloud_profile("aws@").is_err());
+    }
+}
@@ -0,0 +1,31 @@
+//! Renews the device certificates before they expire.
+//!
+//! The certificates used to connect the clouds listed in `certificate.renewal.clouds`
+//! are periodically checked, and when one expires within `certificate.validity.minimum_duration`
+//! a `cert_renew` command is triggered for that cloud.
+//!
+//! The renewal itself is done by the `cert_renew` workflow,
+//! so the progress can be observed as for any other operation and the steps can be customized.
+//! The default workflow gets a new certificate from the configured CA with `tedge cert renew`,
+//! then promotes this new certificate and restarts the bridge with `tedge reconnect`.
+
+use camino::Utf8PathBuf;
+use tedge_utils::file::create_file_with_defaults;
+use tedge_utils::file::FileError;
+
+pub(crate) mod actor;
+pub(crate) mod builder;
+
+#[cfg(test)]
+mod tests;
+
+/// Create the `cert_renew` workflow, if not already defined by the user
+pub async fn create_workflow_definition(ops_dir: &Utf8PathBuf) -> Result<(), FileError> {
+    let workflow_file = ops_dir.join("cert_renew.toml");
+    if !workflow_file.exists() {
+        let workflow_definition = include_str!("../resources/cert_renew.toml");
+
+        create_file_with_defaults(workflow_file, Some(workflow_definition)).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use crate::certificate_renewal::actor::RenewalCheck;
+use crate::certificate_renewal::builder::CertificateRenewalBuilder;
+use crate::certificate_renewal::builder::CertificateRenewalConfig;
+use crate::certificate_renewal::builder::RenewableCertificate;
+use crate::certificate_renewal::builder::RenewalTimerComplete;
+use crate::certificate_renewal::builder::RenewalTimerStart;
+use certificate::CsrTemplate;
+use certificate::KeyCertPair;
+use certificate::KeyKind;
+use serde_json::json;
+use std::time::Duration;
+use tedge_actors::test_helpers::FakeServerBox;
+use tedge_actors::test_helpers::FakeServerBoxBuilder;
+use tedge_actors::test_helpers::WithTimeout;
+use tedge_actors::Actor;
+use tedge_actors::Builder;
+use tedge_actors::MessageReceiver;
+use tedge_actors::Sender;
+use tedge_actors::SimpleMessageBox;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_config::models::CloudType;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::Topic;
+use tedge_test_utils::fs::TempTedgeDir;
+use tedge_timer_ext::Timeout;
+
+const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+const DAY: Duration = Duration::from_secs(24 * 3600);
+
+#[tokio::test]
+async fn renewal_is_triggered_for_certificate_about_to_expire() {
+    let ttd = TempTedgeDir::new();
+    let cert_path = create_certificate(&ttd, "c8y-cert.pem", 10);
+    let mut test = spawn_actor(vec![certificate(CloudType::C8y, None, &cert_path)]);
+
+    test.fire_timer().await;
+
+    let message = test.mqtt.recv().with_timeout(TEST_TIMEOUT).await.unwrap();
+    let message = message.unwrap();
+    assert!(message.retain);
+    assert!(message
+        .topic
+        .name
+        .starts_with("te/device/main///cmd/cert_renew/tedge-agent-"));
+    let payload: serde_json::Value = serde_json::from_slice(message.payload_bytes()).unwrap();
+    assert_eq!(
+        payload,
+        json!({
+            "status": "init",
+            "cloud": "c8y",
+            "profile": "",
+            "ca": "c8y",
+            "certificate": cert_path,
+        })
+    );
+
+    // The next check is scheduled
+    assert_eq!(test.next_timer().await.duration, Duration::from_secs(3600));
+}
+
+#[tokio::test]
+async fn no_renewal_for_certificate_still_valid() {
+    let ttd = TempTedgeDir::new();
+    let cert_path = create_certificate(&ttd, "c8y-cert.pem", 365);
+    let mut test = spawn_actor(vec![certificate(CloudType::C8y, None, &cert_path)]);
+
+    test.fire_timer().await;
+
+    assert_eq!(test.next_timer().await.duration, Duration::from_secs(3600));
+    assert!(test.mqtt.recv().with_timeout(TEST_TIMEOUT).await.is_err());
+}
+
+#[tokio::test]
+async fn renewal_is_triggered_for_each_cloud_profile() {
+    let ttd = TempTedgeDir::new();
+    let valid_cert = create_certificate(&ttd, "c8y-cert.pem", 365);
+    let expiring_cert = create_certificate(&ttd, "c8y-eu-cert.pem", 1);
+    let mut test = spawn_actor(vec![
+        certificate(CloudType::C8y, None, &valid_cert),
+        certificate(CloudType::C8y, Some("eu"), &expiring_cert),
+    ]);
+
+    test.fire_timer().await;
+
+    let message = test
+        .mqtt
+        .recv()
+        .with_timeout(TEST_TIMEOUT)
+        .await
+        .unwrap()
+        .unwrap();
+    let payload: serde_json::Value = serde_json::from_slice(message.payload_bytes()).unwrap();
+    assert_eq!(payload["profile"], "eu");
+    assert_eq!(payload["certificate"], json!(expiring_cert));
+
+    test.next_timer().await;
+    assert!(test.mqtt.recv().with_timeout(TEST_TIMEOUT).await.is_err());
+}
+
+#[tokio::test]
+async fn no_renewal_triggered_while_one_is_in_progress() {
+    let ttd = TempTedgeDir::new();
+    let cert_path = create_certificate(&ttd, "c8y-cert.pem", 10);
+    let mut test = spawn_actor(vec![certificate(CloudType::C8y, None, &cert_path)]);
+
+    // A renewal triggered before a restart is still in progress
+    let in_progress = MqttMessage::new(
+        &Topic::new_unchecked("te/device/main///cmd/cert_renew/tedge-agent-123"),
+        json!({
+            "status": "reconnect",
+            "cloud": "c8y",
+            "ca": "c8y",
+            "certificate": cert_path,
+        })
+        .to_string(),
+    )
+    .with_retain();
+    test.mqtt.send(in_progress).await.unwrap();
+    test.fire_timer().await;
+
+    test.next_timer().await;
+    assert!(test.mqtt.recv().with_timeout(TEST_TIMEOUT).await.is_err());
+
+    // Once this renewal completes, a new renewal can be triggered
+    let cleared = MqttMessage::new(
+        &Topic::new_unchecked("te/device/main///cmd/cert_renew/tedge-agent-123"),
+        "",
+    )
+    .with_retain();
+    test.mqtt.send(cleared).await.unwrap();
+    test.timer.send(Timeout::new(RenewalCheck)).await.unwrap();
+
+    let message = test
+        .mqtt
+        .recv()
+        .with_timeout(TEST_TIMEOUT)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_ne!(
+        message.topic.name,
+        "te/device/main///cmd/cert_renew/tedge-agent-123"
+    );
+}
+
+struct TestHandle {
+    mqtt: SimpleMessageBox<MqttMessage, MqttMessage>,
+    timer: FakeServerBox<RenewalTimerStart, RenewalTimerComplete>,
+}
+
+impl TestHandle {
+    async fn next_timer(&mut self) -> RenewalTimerStart {
+        self.timer
+            .recv()
+            .with_timeout(TEST_TIMEOUT)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    async fn fire_timer(&mut self) {
+        self.next_timer().await;
+        self.timer.send(Timeout::new(RenewalCheck)).await.unwrap();
+    }
+}
+
+fn spawn_actor(certificates: Vec<RenewableCertificate>) -> TestHandle {
+    let config = CertificateRenewalConfig {
+        mqtt_schema: MqttSchema::default(),
+        device_topic_id: EntityTopicId::default_main_device(),
+        interval: Duration::from_secs(3600),
+        minimum_validity: 30 * DAY,
+        ca: "c8y".to_string(),
+        certificates,
+    };
+
+    let mut mqtt_builder: SimpleMessageBoxBuilder<MqttMessage, MqttMessage> =
+        SimpleMessageBoxBuilder::new("MQTT", 10);
+    let mut timer_builder: FakeServerBoxBuilder<RenewalTimerStart, RenewalTimerComplete> =
+        FakeServerBoxBuilder::default();
+
+    let actor =
+        CertificateRenewalBuilder::new(config, &mut mqtt_builder, &mut timer_builder).build();
+    tokio::spawn(async move { actor.run().await });
+
+    TestHandle {
+        mqtt: mqtt_builder.build(),
+        timer: timer_builder.build(),
+    }
+}
+
+fn certificate(
+    cloud: CloudType,
+    profile: Option<&str>,
+    cert_path: &camino::Utf8Path,
+) -> RenewableCertificate {
+    RenewableCertificate {
+        cloud,
+        profile: profile.map(|p| p.parse().unwrap()),
+        cert_path: cert_path.to_owned(),
+    }
+}
+
+fn create_certificate(ttd: &TempTedgeDir, name: &str, validity_days: u32) -> camino::Utf8PathBuf {
+    let template = CsrTemplate {
+        validity_period_days: validity_days,
+        ..CsrTemplate::default()
+    };
+    let cert =
+        KeyCertPair::new_selfsigned_certificate(&template, "test-device", &KeyKind::New).unwrap();
+    let cert_path = ttd.utf8_path().join(name);
+    std::fs::write(&cert_path, cert.certificate_pem_string().unwrap()).unwrap();
+    cert_path
+}
@@ -0,0 +1,138 @@
+use crate::command_manager::config::CommandManagerConfig;
+use crate::command_manager::error::CommandManagerError;
+use crate::command_manager::execute::execute;
+use crate::command_manager::execute::CommandOutput;
+use async_trait::async_trait;
+use tedge_actors::Actor;
+use tedge_actors::ClientMessageBox;
+use tedge_actors::MessageReceiver;
+use tedge_actors::RuntimeError;
+use tedge_actors::Sender;
+use tedge_actors::SimpleMessageBox;
+use tedge_api::commands::CommandStatus;
+use tedge_api::commands::ShellCommandCmd;
+use tedge_uploader_ext::UploadRequest;
+use tedge_uploader_ext::UploadResult;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+pub type CommandOutputUploadRequest = (String, UploadRequest);
+pub type CommandOutputUploadResult = (String, UploadResult);
+
+/// Executes the shell commands allowed by the configuration,
+/// uploading their output to the file transfer service.
+///
+/// The commands are executed one at a time.
+pub struct CommandManagerActor {
+    config: CommandManagerConfig,
+    message_box: SimpleMessageBox<ShellCommandCmd, ShellCommandCmd>,
+    uploader: ClientMessageBox<CommandOutputUploadRequest, CommandOutputUploadResult>,
+}
+
+#[async_trait]
+impl Actor for CommandManagerActor {
+    fn name(&self) -> &str {
+        "CommandManagerActor"
+    }
+
+    async fn run(mut self) -> Result<(), RuntimeError> {
+        while let Some(mut request) = self.message_box.recv().await {
+            match request.status() {
+                CommandStatus::Init | CommandStatus::Scheduled => {
+                    request.executing();
+                    self.message_box.send(request).await?;
+                }
+                CommandStatus::Executing => {
+                    let response = self.handle_command(request).await;
+                    self.message_box.send(response).await?;
+                }
+                CommandStatus::Unknown
+                | CommandStatus::Successful
+                | CommandStatus::Failed { .. } => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl CommandManagerActor {
+    pub fn new(
+        config: CommandManagerConfig,
+        message_box: SimpleMessageBox<ShellCommandCmd, ShellCommandCmd>,
+        uploader: ClientMessageBox<CommandOutputUploadRequest, CommandOutputUploadResult>,
+    ) -> Self {
+        Self {
+            config,
+            message_box,
+            uploader,
+        }
+    }
+
+    async fn handle_command(&mut self, mut request: ShellCommandCmd) -> ShellCommandCmd {
+        let command = request.payload.command.clone();
+        match self.execute_and_upload(&request).await {
+            Ok(output) => {
+                request.payload.exit_code = output.exit_code;
+                request.payload.output_truncated = output.truncated;
+                match output.exit_code {
+                    Some(0) => {
+                        info!("Command {command:?} executed successfully");
+                        request.successful()
+                    }
+                    Some(code) => request.failed(format!("Command exited with code {code}")),
+                    None => request.failed("Command terminated by a signal"),
+                }
+            }
+            Err(err) => {
+                error!("Failed to execute command {command:?}: {err}");
+                request.failed(err.to_string())
+            }
+        }
+        request
+    }
+
+    async fn execute_and_upload(
+        &mut self,
+        request: &ShellCommandCmd,
+    ) -> Result<CommandOutput, CommandManagerError> {
+        let command = &request.payload.command;
+        if !self.config.is_allowed(command) {
+            return Err(CommandManagerError::NotAllowed(command.clone()));
+        }
+
+        info!("Executing command {command:?}");
+        let output = execute(&self.config, command).await?;
+        if output.truncated {
+            warn!(
+                "The output of {command:?} exceeds {} bytes and has been truncated",
+                self.config.max_output_size
+            );
+        }
+
+        if let Some(tedge_url) = &request.payload.tedge_url {
+            let output_path = self
+                .config
+                .tmp_dir
+                .join(format!("command-{}.log", request.cmd_id));
+            tokio::fs::write(&output_path, &output.output).await?;
+
+            let upload_request = UploadRequest::new(tedge_url, &output_path);
+            let upload = self
+                .uploader
+                .await_response((request.cmd_id.clone(), upload_request))
+                .await;
+            let _ = tokio::fs::remove_file(&output_path).await;
+            match upload {
+                Ok((_, Ok(_))) => {}
+                Ok((_, Err(err))) => {
+                    return Err(CommandManagerError::UploadFailed(err.to_string()))
+                }
+                Err(err) => return Err(CommandManagerError::UploadFailed(err.to_string())),
+            }
+        }
+
+        Ok(output)
+    }
+}
@@ -0,0 +1,94 @@
+use crate::command_manager::actor::CommandManagerActor;
+use crate::command_manager::actor::CommandOutputUploadRequest;
+use crate::command_manager::actor::CommandOutputUploadResult;
+use crate::command_manager::config::CommandManagerConfig;
+use tedge_actors::Builder;
+use tedge_actors::ClientMessageBox;
+use tedge_actors::DynSender;
+use tedge_actors::LinkError;
+use tedge_actors::MappingSender;
+use tedge_actors::MessageSink;
+use tedge_actors::MessageSource;
+use tedge_actors::NoConfig;
+use tedge_actors::RequestEnvelope;
+use tedge_actors::RuntimeRequest;
+use tedge_actors::RuntimeRequestSink;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::commands::ShellCommandCmd;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_api::workflow::GenericCommandData;
+use tedge_api::workflow::GenericCommandState;
+use tedge_api::workflow::OperationName;
+
+pub struct CommandManagerBuilder {
+    config: CommandManagerConfig,
+    message_box: SimpleMessageBoxBuilder<ShellCommandCmd, ShellCommandCmd>,
+    uploader: ClientMessageBox<CommandOutputUploadRequest, CommandOutputUploadResult>,
+}
+
+impl CommandManagerBuilder {
+    pub fn new(
+        config: CommandManagerConfig,
+        uploader: &mut impl MessageSink<
+            RequestEnvelope<CommandOutputUploadRequest, CommandOutputUploadResult>,
+        >,
+    ) -> Self {
+        let message_box = SimpleMessageBoxBuilder::new("CommandManager", 10);
+        let uploader = ClientMessageBox::new(uploader);
+
+        Self {
+            config,
+            message_box,
+            uploader,
+        }
+    }
+}
+
+impl MessageSink<ShellCommandCmd> for CommandManagerBuilder {
+    fn get_sender(&self) -> DynSender<ShellCommandCmd> {
+        self.message_box.get_sender()
+    }
+}
+
+impl MessageSource<ShellCommandCmd, NoConfig> for CommandManagerBuilder {
+    fn connect_sink(&mut self, config: NoConfig, peer: &impl MessageSink<ShellCommandCmd>) {
+        self.message_box.connect_sink(config, peer)
+    }
+}
+
+impl MessageSource<GenericCommandData, NoConfig> for CommandManagerBuilder {
+    fn connect_sink(&mut self, config: NoConfig, peer: &impl MessageSink<GenericCommandData>) {
+        self.message_box.connect_sink(config, &peer.get_sender())
+    }
+}
+
+impl IntoIterator for &CommandManagerBuilder {
+    type Item = (OperationName, DynSender<GenericCommandState>);
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let sender =
+            MappingSender::new(self.message_box.get_sender(), |msg: GenericCommandState| {
+                msg.try_into().ok()
+            });
+        vec![(OperationType::Command.to_string(), sender.into())].into_iter()
+    }
+}
+
+impl RuntimeRequestSink for CommandManagerBuilder {
+    fn get_signal_sender(&self) -> DynSender<RuntimeRequest> {
+        self.message_box.get_signal_sender()
+    }
+}
+
+impl Builder<CommandManagerActor> for CommandManagerBuilder {
+    type Error = LinkError;
+
+    fn try_build(self) -> Result<CommandManagerActor, Self::Error> {
+        Ok(self.build())
+    }
+
+    fn build(self) -> CommandManagerActor {
+        CommandManagerActor::new(self.config, self.message_box.build(), self.uploader)
+    }
+}
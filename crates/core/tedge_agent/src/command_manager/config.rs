@@ -0,0 +1,53 @@
+use crate::command_manager::error::CommandManagerError;
+use camino::Utf8PathBuf;
+use regex::Regex;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct CommandManagerConfig {
+    /// The patterns a command line must fully match to be executed
+    pub allowed: Vec<Regex>,
+
+    /// The user running the commands, if not the agent user
+    pub user: Option<String>,
+
+    pub working_dir: Utf8PathBuf,
+    pub timeout: Duration,
+    pub max_output_size: usize,
+    pub tmp_dir: Utf8PathBuf,
+}
+
+impl CommandManagerConfig {
+    pub fn from_tedge_config(
+        tedge_config: &tedge_config::TEdgeConfig,
+    ) -> Result<CommandManagerConfig, CommandManagerError> {
+        let command = &tedge_config.agent.command;
+        let allowed = command
+            .allowed
+            .0
+            .iter()
+            .map(|pattern| allowed_pattern(pattern))
+            .collect::<Result<_, _>>()?;
+
+        Ok(CommandManagerConfig {
+            allowed,
+            user: command.user.or_none().cloned(),
+            working_dir: command.working_dir.clone().into(),
+            timeout: command.timeout.duration(),
+            max_output_size: command.max_output_size as usize,
+            tmp_dir: tedge_config.tmp.path.clone().into(),
+        })
+    }
+
+    /// Check that a command line is allowed, i.e. fully matches one of the allowed patterns
+    pub fn is_allowed(&self, command: &str) -> bool {
+        self.allowed.iter().any(|pattern| pattern.is_match(command))
+    }
+}
+
+pub fn allowed_pattern(pattern: &str) -> Result<Regex, CommandManagerError> {
+    Regex::new(&format!("^(?:{pattern})$")).map_err(|error| CommandManagerError::InvalidPattern {
+        pattern: pattern.to_string(),
+        error,
+    })
+}
@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CommandManagerError {
+    #[error("Invalid agent.command.allowed pattern {pattern:?}: {error}")]
+    InvalidPattern {
+        pattern: String,
+        error: regex::Error,
+    },
+
+    #[error("Command not allowed by agent.command.allowed: {0}")]
+    NotAllowed(String),
+
+    #[error("Invalid command line {command:?}: {reason}")]
+    InvalidCommandLine { command: String, reason: String },
+
+    #[error("Failed to execute {command:?}: {error}")]
+    ExecutionFailed {
+        command: String,
+        error: std::io::Error,
+    },
+
+    #[error("Command still running after {} seconds", .0.as_secs())]
+    Timeout(Duration),
+
+    #[error("Failed to upload the command output: {0}")]
+    UploadFailed(String),
+
+    #[error(transparent)]
+    FromIo(#[from] std::io::Error),
+}
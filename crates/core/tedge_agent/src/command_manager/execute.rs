@@ -0,0 +1,105 @@
+use crate::command_manager::config::CommandManagerConfig;
+use crate::command_manager::error::CommandManagerError;
+use std::process::Stdio;
+use std::sync::Mutex;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+
+/// The outcome of a command
+#[derive(Debug)]
+pub struct CommandOutput {
+    /// None if the command has been terminated by a signal
+    pub exit_code: Option<i32>,
+
+    /// The interleaved stdout and stderr, up to the maximum output size
+    pub output: Vec<u8>,
+
+    /// True if the output exceeded the maximum output size
+    pub truncated: bool,
+}
+
+/// Execute a command line, without a shell, along the configured constraints
+pub async fn execute(
+    config: &CommandManagerConfig,
+    command_line: &str,
+) -> Result<CommandOutput, CommandManagerError> {
+    let invalid = |reason: &str| CommandManagerError::InvalidCommandLine {
+        command: command_line.to_string(),
+        reason: reason.to_string(),
+    };
+    let words = shell_words::split(command_line).map_err(|err| invalid(&err.to_string()))?;
+    let Some((program, args)) = words.split_first() else {
+        return Err(invalid("empty command"));
+    };
+
+    let mut command = match &config.user {
+        Some(user) => {
+            let mut command = Command::new("sudo");
+            command.args(["-n", "-u", user, "--", program]);
+            command
+        }
+        None => Command::new(program),
+    };
+    let mut child = command
+        .args(args)
+        .current_dir(&config.working_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|error| CommandManagerError::ExecutionFailed {
+            command: command_line.to_string(),
+            error,
+        })?;
+
+    let output = Mutex::new(Vec::new());
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let run = async {
+        let (stdout, stderr) = tokio::join!(
+            capture(stdout, &output, config.max_output_size),
+            capture(stderr, &output, config.max_output_size)
+        );
+        let status = child.wait().await?;
+        Ok::<_, std::io::Error>((status, stdout? || stderr?))
+    };
+
+    match tokio::time::timeout(config.timeout, run).await {
+        Ok(result) => {
+            let (status, truncated) = result?;
+            Ok(CommandOutput {
+                exit_code: status.code(),
+                output: output.into_inner().unwrap_or_default(),
+                truncated,
+            })
+        }
+        Err(_) => {
+            let _ = child.kill().await;
+            Err(CommandManagerError::Timeout(config.timeout))
+        }
+    }
+}
+
+/// Append the content of a pipe to the output, discarding what exceeds the maximum size
+///
+/// Return true if some content has been discarded
+async fn capture(
+    mut pipe: impl AsyncRead + Unpin,
+    output: &Mutex<Vec<u8>>,
+    max_size: usize,
+) -> Result<bool, std::io::Error> {
+    let mut truncated = false;
+    let mut buffer = [0; 4096];
+    loop {
+        let n = pipe.read(&mut buffer).await?;
+        if n == 0 {
+            return Ok(truncated);
+        }
+        let mut output = output.lock().unwrap();
+        let room = max_size.saturating_sub(output.len());
+        truncated |= n > room;
+        output.extend_from_slice(&buffer[..n.min(room)]);
+    }
+}
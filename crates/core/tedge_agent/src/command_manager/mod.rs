@@ -0,0 +1,8 @@
+pub mod actor;
+pub mod builder;
+pub mod config;
+pub mod error;
+mod execute;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,224 @@
+use crate::command_manager::actor::CommandOutputUploadRequest;
+use crate::command_manager::actor::CommandOutputUploadResult;
+use crate::command_manager::builder::CommandManagerBuilder;
+use crate::command_manager::config::allowed_pattern;
+use crate::command_manager::config::CommandManagerConfig;
+use std::time::Duration;
+use tedge_actors::test_helpers::FakeServerBox;
+use tedge_actors::test_helpers::FakeServerBoxBuilder;
+use tedge_actors::test_helpers::MessageReceiverExt;
+use tedge_actors::test_helpers::TimedMessageBox;
+use tedge_actors::Actor;
+use tedge_actors::Builder;
+use tedge_actors::MessageReceiver;
+use tedge_actors::MessageSource;
+use tedge_actors::NoConfig;
+use tedge_actors::Sender;
+use tedge_actors::SimpleMessageBox;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::commands::CommandStatus;
+use tedge_api::commands::ShellCommandCmd;
+use tedge_api::commands::ShellCommandCmdPayload;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_test_utils::fs::TempTedgeDir;
+use tedge_uploader_ext::UploadResponse;
+
+const TEST_TIMEOUT_MS: Duration = Duration::from_millis(3000);
+
+type ConverterBox = TimedMessageBox<SimpleMessageBox<ShellCommandCmd, ShellCommandCmd>>;
+type UploaderBox =
+    TimedMessageBox<FakeServerBox<CommandOutputUploadRequest, CommandOutputUploadResult>>;
+
+#[tokio::test]
+async fn allowed_commands_are_executed_and_their_output_uploaded() {
+    let temp_dir = TempTedgeDir::new();
+    let (mut converter, mut uploader) = spawn_command_manager(config(&temp_dir, &["echo .*"]));
+
+    converter
+        .send(command("1234", "echo 'hello world'", CommandStatus::Init))
+        .await
+        .unwrap();
+    assert_eq!(
+        converter.recv().await.unwrap().status(),
+        CommandStatus::Executing
+    );
+
+    converter
+        .send(command(
+            "1234",
+            "echo 'hello world'",
+            CommandStatus::Executing,
+        ))
+        .await
+        .unwrap();
+    let (cmd_id, upload_request) = uploader.recv().await.unwrap();
+    assert_eq!(cmd_id, "1234");
+    assert_eq!(
+        upload_request.url,
+        "http://127.0.0.1:8000/te/v1/files/main/command/1234"
+    );
+    let output = std::fs::read_to_string(&upload_request.file_path).unwrap();
+    assert_eq!(output, "hello world\n");
+    let upload_response = UploadResponse::new(&upload_request.url, upload_request.file_path);
+    uploader.send((cmd_id, Ok(upload_response))).await.unwrap();
+
+    let response = converter.recv().await.unwrap();
+    assert_eq!(response.status(), CommandStatus::Successful);
+    assert_eq!(response.payload.exit_code, Some(0));
+    assert!(!response.payload.output_truncated);
+}
+
+#[tokio::test]
+async fn commands_not_matching_the_allowlist_are_rejected() {
+    let temp_dir = TempTedgeDir::new();
+    let (mut converter, _uploader) = spawn_command_manager(config(&temp_dir, &["echo .*"]));
+
+    // The patterns must match the whole command line
+    for command_line in ["rm -rf /", "cat /etc/shadow; echo ok", "ls; echo ok"] {
+        converter
+            .send(command("1234", command_line, CommandStatus::Executing))
+            .await
+            .unwrap();
+        let response = converter.recv().await.unwrap();
+        assert_eq!(
+            response.status(),
+            CommandStatus::Failed {
+                reason: format!("Command not allowed by agent.command.allowed: {command_line}")
+            }
+        );
+        assert_eq!(response.payload.exit_code, None);
+    }
+}
+
+#[tokio::test]
+async fn commands_are_not_interpreted_by_a_shell() {
+    let temp_dir = TempTedgeDir::new();
+    let (mut converter, _uploader) = spawn_command_manager(config(&temp_dir, &["echo .*"]));
+
+    let mut request = command("1234", "echo a && touch pwned", CommandStatus::Executing);
+    request.payload.tedge_url = None;
+    converter.send(request).await.unwrap();
+
+    let response = converter.recv().await.unwrap();
+    assert_eq!(response.status(), CommandStatus::Successful);
+    assert!(!temp_dir.path().join("pwned").exists());
+}
+
+#[tokio::test]
+async fn non_zero_exit_codes_are_reported_as_failures() {
+    let temp_dir = TempTedgeDir::new();
+    let (mut converter, _uploader) = spawn_command_manager(config(&temp_dir, &["false"]));
+
+    let mut request = command("1234", "false", CommandStatus::Executing);
+    request.payload.tedge_url = None;
+    converter.send(request).await.unwrap();
+
+    let response = converter.recv().await.unwrap();
+    assert_eq!(
+        response.status(),
+        CommandStatus::Failed {
+            reason: "Command exited with code 1".to_string()
+        }
+    );
+    assert_eq!(response.payload.exit_code, Some(1));
+}
+
+#[tokio::test]
+async fn large_outputs_are_truncated() {
+    let temp_dir = TempTedgeDir::new();
+    let mut config = config(&temp_dir, &["seq .*"]);
+    config.max_output_size = 10;
+    let (mut converter, mut uploader) = spawn_command_manager(config);
+
+    converter
+        .send(command("1234", "seq 1 100", CommandStatus::Executing))
+        .await
+        .unwrap();
+    let (cmd_id, upload_request) = uploader.recv().await.unwrap();
+    let output = std::fs::read_to_string(&upload_request.file_path).unwrap();
+    assert_eq!(output, "1\n2\n3\n4\n5\n");
+    let upload_response = UploadResponse::new(&upload_request.url, upload_request.file_path);
+    uploader.send((cmd_id, Ok(upload_response))).await.unwrap();
+
+    let response = converter.recv().await.unwrap();
+    assert_eq!(response.status(), CommandStatus::Successful);
+    assert!(response.payload.output_truncated);
+}
+
+#[tokio::test]
+async fn commands_running_too_long_are_killed() {
+    let temp_dir = TempTedgeDir::new();
+    let mut config = config(&temp_dir, &["sleep [0-9]+"]);
+    config.timeout = Duration::from_secs(1);
+    let (mut converter, _uploader) = spawn_command_manager(config);
+
+    let mut request = command("1234", "sleep 60", CommandStatus::Executing);
+    request.payload.tedge_url = None;
+    converter.send(request).await.unwrap();
+
+    let response = converter.recv().await.unwrap();
+    assert_eq!(
+        response.status(),
+        CommandStatus::Failed {
+            reason: "Command still running after 1 seconds".to_string()
+        }
+    );
+}
+
+#[test]
+fn invalid_patterns_are_reported() {
+    assert!(allowed_pattern("uptime").is_ok());
+    assert!(allowed_pattern("cat (").is_err());
+}
+
+fn config(temp_dir: &TempTedgeDir, allowed: &[&str]) -> CommandManagerConfig {
+    CommandManagerConfig {
+        allowed: allowed
+            .iter()
+            .map(|pattern| allowed_pattern(pattern).unwrap())
+            .collect(),
+        user: None,
+        working_dir: temp_dir.utf8_path_buf(),
+        timeout: Duration::from_secs(10),
+        max_output_size: 65536,
+        tmp_dir: temp_dir.utf8_path_buf(),
+    }
+}
+
+fn command(cmd_id: &str, command_line: &str, status: CommandStatus) -> ShellCommandCmd {
+    ShellCommandCmd {
+        target: EntityTopicId::default_main_device(),
+        cmd_id: cmd_id.to_string(),
+        payload: ShellCommandCmdPayload {
+            status,
+            command: command_line.to_string(),
+            tedge_url: Some(format!(
+                "http://127.0.0.1:8000/te/v1/files/main/command/{cmd_id}"
+            )),
+            exit_code: None,
+            output_truncated: false,
+            log_path: None,
+        },
+    }
+}
+
+fn spawn_command_manager(config: CommandManagerConfig) -> (ConverterBox, UploaderBox) {
+    let mut converter_builder: SimpleMessageBoxBuilder<ShellCommandCmd, ShellCommandCmd> =
+        SimpleMessageBoxBuilder::new("Converter", 5);
+    let mut uploader_builder: FakeServerBoxBuilder<
+        CommandOutputUploadRequest,
+        CommandOutputUploadResult,
+    > = FakeServerBoxBuilder::default();
+
+    let mut command_builder = CommandManagerBuilder::new(config, &mut uploader_builder);
+    converter_builder.connect_sink(NoConfig, &command_builder);
+    command_builder.connect_sink(NoConfig, &converter_builder);
+
+    let actor = command_builder.build();
+    tokio::spawn(async move { actor.run().await });
+
+    (
+        converter_builder.build().with_timeout(TEST_TIMEOUT_MS),
+        uploader_builder.build().with_timeout(TEST_TIMEOUT_MS),
+    )
+}
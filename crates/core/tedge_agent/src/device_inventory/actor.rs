@@ -0,0 +1,115 @@
+use crate::device_inventory::builder::DeviceInventoryConfig;
+use crate::device_inventory::builder::InventoryTimerComplete;
+use crate::device_inventory::builder::InventoryTimerStart;
+use crate::device_inventory::collector::builtin_facts;
+use crate::device_inventory::collector::collector_facts;
+use async_trait::async_trait;
+use serde_json::Map;
+use serde_json::Value;
+use std::time::Duration;
+use tedge_actors::Actor;
+use tedge_actors::LoggingReceiver;
+use tedge_actors::LoggingSender;
+use tedge_actors::MessageReceiver;
+use tedge_actors::RuntimeError;
+use tedge_actors::Sender;
+use tedge_api::mqtt_topics::Channel;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_timer_ext::SetTimeout;
+
+/// Timer event triggering a collection of the device facts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InventoryRefresh;
+
+pub struct DeviceInventoryActor {
+    config: DeviceInventoryConfig,
+    input_receiver: LoggingReceiver<InventoryTimerComplete>,
+    mqtt_publisher: LoggingSender<MqttMessage>,
+    timer_sender: LoggingSender<InventoryTimerStart>,
+    /// The fragments as last published
+    published: Map<String, Value>,
+}
+
+#[async_trait]
+impl Actor for DeviceInventoryActor {
+    fn name(&self) -> &str {
+        "DeviceInventory"
+    }
+
+    async fn run(mut self) -> Result<(), RuntimeError> {
+        // Let the agent start before running the collector scripts
+        self.timer_sender
+            .send(SetTimeout::new(Duration::from_secs(1), InventoryRefresh))
+            .await?;
+
+        while self.input_receiver.recv().await.is_some() {
+            self.refresh_inventory().await?;
+            self.timer_sender
+                .send(SetTimeout::new(self.config.interval, InventoryRefresh))
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl DeviceInventoryActor {
+    pub fn new(
+        config: DeviceInventoryConfig,
+        input_receiver: LoggingReceiver<InventoryTimerComplete>,
+        mqtt_publisher: LoggingSender<MqttMessage>,
+        timer_sender: LoggingSender<InventoryTimerStart>,
+    ) -> Self {
+        Self {
+            config,
+            input_receiver,
+            mqtt_publisher,
+            timer_sender,
+            published: Map::new(),
+        }
+    }
+
+    /// Collect the device facts and publish the fragments that changed since the last collection
+    async fn refresh_inventory(&mut self) -> Result<(), RuntimeError> {
+        let mut facts = builtin_facts(&self.config.system_root);
+        facts.extend(collector_facts(&self.config.collectors_dir).await);
+
+        let removed: Vec<String> = self
+            .published
+            .keys()
+            .filter(|key| !facts.contains_key(*key))
+            .cloned()
+            .collect();
+        for fragment_key in removed {
+            self.published.remove(&fragment_key);
+            self.publish_fragment(fragment_key, "".to_string()).await?;
+        }
+
+        for (fragment_key, value) in facts {
+            if self.published.get(&fragment_key) == Some(&value) {
+                continue;
+            }
+            self.publish_fragment(fragment_key.clone(), value.to_string())
+                .await?;
+            self.published.insert(fragment_key, value);
+        }
+
+        Ok(())
+    }
+
+    async fn publish_fragment(
+        &mut self,
+        fragment_key: String,
+        payload: String,
+    ) -> Result<(), RuntimeError> {
+        let channel = Channel::EntityTwinData { fragment_key };
+        let topic = self
+            .config
+            .mqtt_schema
+            .topic_for(&self.config.device_topic_id, &channel);
+        self.mqtt_publisher
+            .send(MqttMessage::new(&topic, payload).with_retain())
+            .await?;
+        Ok(())
+    }
+}
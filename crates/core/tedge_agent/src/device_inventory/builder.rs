@@ -0,0 +1,103 @@
+use crate::device_inventory::actor::DeviceInventoryActor;
+use crate::device_inventory::actor::InventoryRefresh;
+use camino::Utf8PathBuf;
+use std::convert::Infallible;
+use std::time::Duration;
+use tedge_actors::Builder;
+use tedge_actors::CloneSender;
+use tedge_actors::DynSender;
+use tedge_actors::LoggingSender;
+use tedge_actors::MessageSink;
+use tedge_actors::NoMessage;
+use tedge_actors::RuntimeRequest;
+use tedge_actors::RuntimeRequestSink;
+use tedge_actors::Service;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_timer_ext::SetTimeout;
+use tedge_timer_ext::Timeout;
+
+pub type InventoryTimerStart = SetTimeout<InventoryRefresh>;
+pub type InventoryTimerComplete = Timeout<InventoryRefresh>;
+
+const INVENTORY_COLLECTORS_DIR: &str = "device/inventory.d";
+
+#[derive(Debug, Clone)]
+pub struct DeviceInventoryConfig {
+    pub mqtt_schema: MqttSchema,
+    pub device_topic_id: EntityTopicId,
+    /// Interval between two collections of the device facts
+    pub interval: Duration,
+    /// Directory of the scripts collecting extra facts
+    pub collectors_dir: Utf8PathBuf,
+    /// Root of the `/proc`, `/sys` and `/etc` directories the builtin facts are read from
+    pub system_root: Utf8PathBuf,
+}
+
+impl DeviceInventoryConfig {
+    pub fn new(
+        config_dir: &Utf8PathBuf,
+        mqtt_schema: MqttSchema,
+        device_topic_id: EntityTopicId,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            mqtt_schema,
+            device_topic_id,
+            interval,
+            collectors_dir: config_dir.join(INVENTORY_COLLECTORS_DIR),
+            system_root: "/".into(),
+        }
+    }
+}
+
+pub struct DeviceInventoryBuilder {
+    config: DeviceInventoryConfig,
+    box_builder: SimpleMessageBoxBuilder<InventoryTimerComplete, NoMessage>,
+    mqtt_publisher: DynSender<MqttMessage>,
+    timer_sender: DynSender<InventoryTimerStart>,
+}
+
+impl DeviceInventoryBuilder {
+    pub fn new(
+        config: DeviceInventoryConfig,
+        mqtt_actor: &mut impl MessageSink<MqttMessage>,
+        timer: &mut impl Service<InventoryTimerStart, InventoryTimerComplete>,
+    ) -> Self {
+        let box_builder = SimpleMessageBoxBuilder::new("DeviceInventory", 4);
+        let mqtt_publisher = mqtt_actor.get_sender();
+        let timer_sender = timer.connect_client(box_builder.get_sender().sender_clone());
+
+        Self {
+            config,
+            box_builder,
+            mqtt_publisher,
+            timer_sender,
+        }
+    }
+}
+
+impl RuntimeRequestSink for DeviceInventoryBuilder {
+    fn get_signal_sender(&self) -> DynSender<RuntimeRequest> {
+        self.box_builder.get_signal_sender()
+    }
+}
+
+impl Builder<DeviceInventoryActor> for DeviceInventoryBuilder {
+    type Error = Infallible;
+
+    fn try_build(self) -> Result<DeviceInventoryActor, Self::Error> {
+        Ok(self.build())
+    }
+
+    fn build(self) -> DeviceInventoryActor {
+        let mqtt_publisher =
+            LoggingSender::new("DeviceInventory => Mqtt".into(), self.mqtt_publisher);
+        let timer_sender = LoggingSender::new("DeviceInventory => Timer".into(), self.timer_sender);
+        let (_, input_receiver) = self.box_builder.build().into_split();
+
+        DeviceInventoryActor::new(self.config, input_receiver, mqtt_publisher, timer_sender)
+    }
+}
@@ -0,0 +1,329 @@
+//! Collect the device facts published as twin fragments.
+//!
+//! The builtin facts are read from `/proc`, `/sys` and `/etc/os-release`,
+//! the root of these paths being configurable for tests.
+use camino::Utf8Path;
+use nix::ifaddrs::getifaddrs;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::os::unix::fs::PermissionsExt;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::time::timeout;
+use tracing::warn;
+
+/// The time given to a collector script to output its facts
+const COLLECTOR_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Collect the builtin facts: `os`, `hardware`, `storage` and `network`
+pub fn builtin_facts(root: &Utf8Path) -> Map<String, Value> {
+    let mut facts = Map::new();
+    if let Some(os) = os_facts(root) {
+        facts.insert("os".to_string(), os);
+    }
+    facts.insert("hardware".to_string(), hardware_facts(root));
+    if let Some(storage) = storage_facts(root) {
+        facts.insert("storage".to_string(), storage);
+    }
+    let interfaces = network_interfaces(root);
+    if !interfaces.is_empty() {
+        facts.insert("network".to_string(), json!({ "interfaces": interfaces }));
+    }
+    facts
+}
+
+fn os_facts(root: &Utf8Path) -> Option<Value> {
+    let os_release = std::fs::read_to_string(root.join("etc/os-release")).ok()?;
+    let os_release = parse_os_release(&os_release);
+    let mut os = Map::new();
+    for (key, property) in [
+        ("ID", "id"),
+        ("NAME", "name"),
+        ("VERSION_ID", "version"),
+        ("PRETTY_NAME", "prettyName"),
+    ] {
+        if let Some(value) = os_release.get(key) {
+            os.insert(property.to_string(), value.clone().into());
+        }
+    }
+    if let Some(kernel) = read_trimmed(root, "proc/sys/kernel/osrelease") {
+        os.insert("kernel".to_string(), kernel.into());
+    }
+    if let Some(hostname) = read_trimmed(root, "proc/sys/kernel/hostname") {
+        os.insert("hostname".to_string(), hostname.into());
+    }
+    Some(Value::Object(os))
+}
+
+fn parse_os_release(content: &str) -> BTreeMap<&str, String> {
+    content
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim().trim_matches('"').trim_matches('\'');
+            (key.trim(), value.to_string())
+        })
+        .collect()
+}
+
+fn hardware_facts(root: &Utf8Path) -> Value {
+    let cpuinfo = std::fs::read_to_string(root.join("proc/cpuinfo")).unwrap_or_default();
+    let cpuinfo: Vec<(&str, &str)> = cpuinfo
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect();
+    let cpu_property = |name: &str| {
+        cpuinfo
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.to_string())
+    };
+
+    let mut hardware = Map::new();
+    let model = read_trimmed(root, "sys/firmware/devicetree/base/model")
+        .or_else(|| read_trimmed(root, "sys/class/dmi/id/product_name"))
+        .or_else(|| cpu_property("Model"));
+    if let Some(model) = model {
+        hardware.insert("model".to_string(), model.into());
+    }
+    let serial = read_trimmed(root, "sys/firmware/devicetree/base/serial-number")
+        .or_else(|| read_trimmed(root, "sys/class/dmi/id/product_serial"))
+        .or_else(|| cpu_property("Serial"));
+    if let Some(serial) = serial {
+        hardware.insert("serialNumber".to_string(), serial.into());
+    }
+    let revision =
+        read_trimmed(root, "sys/class/dmi/id/product_version").or_else(|| cpu_property("Revision"));
+    if let Some(revision) = revision {
+        hardware.insert("revision".to_string(), revision.into());
+    }
+
+    let mut cpu = Map::new();
+    if let Some(model) = cpu_property("model name").or_else(|| cpu_property("Hardware")) {
+        cpu.insert("model".to_string(), model.into());
+    }
+    let cores = cpuinfo
+        .iter()
+        .filter(|(key, _)| *key == "processor")
+        .count();
+    if cores > 0 {
+        cpu.insert("cores".to_string(), cores.into());
+    }
+    cpu.insert(
+        "architecture".to_string(),
+        std::env::consts::ARCH.to_string().into(),
+    );
+    hardware.insert("cpu".to_string(), Value::Object(cpu));
+
+    if let Some(total) = memory_total(root) {
+        hardware.insert("memory".to_string(), json!({ "total": total }));
+    }
+
+    Value::Object(hardware)
+}
+
+/// The total memory in bytes, as given by `/proc/meminfo`
+fn memory_total(root: &Utf8Path) -> Option<u64> {
+    let meminfo = std::fs::read_to_string(root.join("proc/meminfo")).ok()?;
+    let line = meminfo.lines().find(|line| line.starts_with("MemTotal:"))?;
+    let kb = line
+        .trim_start_matches("MemTotal:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+/// The total and available space in bytes of the root file system
+fn storage_facts(root: &Utf8Path) -> Option<Value> {
+    let stats = nix::sys::statvfs::statvfs(root.as_std_path()).ok()?;
+    let block_size = stats.fragment_size() as u64;
+    Some(json!({
+        "total": stats.blocks() as u64 * block_size,
+        "available": stats.blocks_available() as u64 * block_size,
+    }))
+}
+
+/// The network interfaces listed by `/sys/class/net`, the loopback being excluded
+fn network_interfaces(root: &Utf8Path) -> Vec<Value> {
+    let Ok(entries) = root.join("sys/class/net").read_dir_utf8() else {
+        return vec![];
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string())
+        .filter(|name| name != "lo")
+        .collect();
+    names.sort();
+
+    let mut addresses: BTreeMap<String, (Vec<String>, Vec<String>)> = BTreeMap::new();
+    if let Ok(ifaddrs) = getifaddrs() {
+        for ifaddr in ifaddrs {
+            let (Some(address), Some(netmask)) = (ifaddr.address, ifaddr.netmask) else {
+                continue;
+            };
+            let entry = addresses.entry(ifaddr.interface_name).or_default();
+            if let (Some(ip), Some(mask)) = (address.as_sockaddr_in(), netmask.as_sockaddr_in()) {
+                let prefix = u32::from(mask.ip()).count_ones();
+                entry.0.push(format!("{}/{prefix}", ip.ip()));
+            } else if let (Some(ip), Some(mask)) =
+                (address.as_sockaddr_in6(), netmask.as_sockaddr_in6())
+            {
+                let prefix = u128::from(mask.ip()).count_ones();
+                entry.1.push(format!("{}/{prefix}", ip.ip()));
+            }
+        }
+    }
+
+    names
+        .into_iter()
+        .map(|name| {
+            let dir = format!("sys/class/net/{name}");
+            let mut interface = Map::new();
+            interface.insert("name".to_string(), name.clone().into());
+            if let Some(mac) = read_trimmed(root, &format!("{dir}/address")) {
+                interface.insert("mac".to_string(), mac.into());
+            }
+            if let Some(state) = read_trimmed(root, &format!("{dir}/operstate")) {
+                interface.insert("state".to_string(), state.into());
+            }
+            let (ipv4, ipv6) = addresses.remove(&name).unwrap_or_default();
+            interface.insert("ipv4".to_string(), ipv4.into());
+            interface.insert("ipv6".to_string(), ipv6.into());
+            Value::Object(interface)
+        })
+        .collect()
+}
+
+fn read_trimmed(root: &Utf8Path, path: &str) -> Option<String> {
+    let content = std::fs::read_to_string(root.join(path)).ok()?;
+    // Device tree strings are null terminated
+    let content = content.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    (!content.is_empty()).then(|| content.to_string())
+}
+
+/// Run the executable scripts of the collector directory, merging the JSON objects they output
+///
+/// The scripts are run in alphabetical order, so a script can override the facts of a previous one,
+/// including the builtin facts.
+pub async fn collector_facts(collectors_dir: &Utf8Path) -> Map<String, Value> {
+    let mut facts = Map::new();
+    let Ok(entries) = collectors_dir.read_dir_utf8() else {
+        return facts;
+    };
+    let mut scripts: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            path.metadata().is_ok_and(|metadata| {
+                metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+            })
+        })
+        .collect();
+    scripts.sort();
+
+    for script in scripts {
+        match run_collector(&script).await {
+            Ok(script_facts) => facts.extend(script_facts),
+            Err(err) => warn!("Ignoring the inventory collector {script}: {err}"),
+        }
+    }
+    facts
+}
+
+async fn run_collector(script: &Utf8Path) -> Result<Map<String, Value>, String> {
+    let output = Command::new(script)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = timeout(COLLECTOR_TIMEOUT, output)
+        .await
+        .map_err(|_| format!("no output after {}s", COLLECTOR_TIMEOUT.as_secs()))?
+        .map_err(|err| err.to_string())?;
+    if !output.status.success() {
+        return Err(format!("exited with {}", output.status));
+    }
+    match serde_json::from_slice(&output.stdout) {
+        Ok(Value::Object(facts)) => Ok(facts),
+        Ok(_) => Err("the output is not a JSON object".to_string()),
+        Err(err) => Err(format!("the output is not a JSON object: {err}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tedge_test_utils::fs::TempTedgeDir;
+
+    #[test]
+    fn collect_facts_from_proc_and_sys() {
+        let ttd = TempTedgeDir::new();
+        ttd.dir("etc").file("os-release").with_raw_content(
+            "NAME=\"Debian GNU/Linux\"\nVERSION_ID=\"12\"\nID=debian\nPRETTY_NAME=\"Debian GNU/Linux 12 (bookworm)\"\n",
+        );
+        let proc = ttd.dir("proc");
+        proc.file("meminfo")
+            .with_raw_content("MemTotal:        3884332 kB\nMemFree:          248364 kB\n");
+        proc.file("cpuinfo").with_raw_content(
+            "processor\t: 0\nmodel name\t: ARMv7 Processor rev 3 (v7l)\n\nprocessor\t: 1\nmodel name\t: ARMv7 Processor rev 3 (v7l)\n\nRevision\t: c03111\nSerial\t\t: 10000000d8e3f1d2\nModel\t\t: Raspberry Pi 4 Model B Rev 1.1\n",
+        );
+        let kernel = proc.dir("sys").dir("kernel");
+        kernel
+            .file("osrelease")
+            .with_raw_content("6.1.0-rpi7-rpi-v8\n");
+        kernel.file("hostname").with_raw_content("raspberrypi\n");
+        let interface = ttd.dir("sys").dir("class").dir("net").dir("enx00test");
+        interface
+            .file("address")
+            .with_raw_content("dc:a6:32:01:02:03\n");
+        interface.file("operstate").with_raw_content("up\n");
+        ttd.dir("sys").dir("class").dir("net").dir("lo");
+
+        let facts = builtin_facts(ttd.utf8_path());
+
+        assert_eq!(
+            facts["os"],
+            json!({
+                "id": "debian",
+                "name": "Debian GNU/Linux",
+                "version": "12",
+                "prettyName": "Debian GNU/Linux 12 (bookworm)",
+                "kernel": "6.1.0-rpi7-rpi-v8",
+                "hostname": "raspberrypi",
+            })
+        );
+        assert_eq!(
+            facts["hardware"],
+            json!({
+                "model": "Raspberry Pi 4 Model B Rev 1.1",
+                "serialNumber": "10000000d8e3f1d2",
+                "revision": "c03111",
+                "cpu": {
+                    "model": "ARMv7 Processor rev 3 (v7l)",
+                    "cores": 2,
+                    "architecture": std::env::consts::ARCH,
+                },
+                "memory": { "total": 3977555968u64 },
+            })
+        );
+        assert_eq!(
+            facts["network"],
+            json!({
+                "interfaces": [{
+                    "name": "enx00test",
+                    "mac": "dc:a6:32:01:02:03",
+                    "state": "up",
+                    "ipv4": [],
+                    "ipv6": [],
+                }]
+            })
+        );
+        assert!(facts["storage"]["total"].as_u64().unwrap() > 0);
+    }
+}
@@ -0,0 +1,15 @@
+//! Publishes the device hardware, operating system and network facts as twin fragments.
+//!
+//! When `agent.inventory.enable` is set, the device facts are collected every `agent.inventory.interval`
+//! and published on the twin topics of the main device, only the fragments that changed being republished.
+//!
+//! The builtin facts (`os`, `hardware`, `storage` and `network`) can be completed or overridden
+//! by the executable scripts of the `device/inventory.d` directory of the config directory,
+//! each printing on its standard output a JSON object of twin fragments (e.g. `{ "mobile": { "imei": "..." } }`).
+
+pub(crate) mod actor;
+pub(crate) mod builder;
+pub(crate) mod collector;
+
+#[cfg(test)]
+mod tests;
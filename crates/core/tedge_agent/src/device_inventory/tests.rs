@@ -0,0 +1,204 @@
+use crate::device_inventory::actor::InventoryRefresh;
+use crate::device_inventory::builder::DeviceInventoryBuilder;
+use crate::device_inventory::builder::DeviceInventoryConfig;
+use crate::device_inventory::builder::InventoryTimerComplete;
+use crate::device_inventory::builder::InventoryTimerStart;
+use std::os::unix::fs::PermissionsExt;
+use std::time::Duration;
+use tedge_actors::test_helpers::FakeServerBox;
+use tedge_actors::test_helpers::FakeServerBoxBuilder;
+use tedge_actors::test_helpers::WithTimeout;
+use tedge_actors::Actor;
+use tedge_actors::Builder;
+use tedge_actors::MessageReceiver;
+use tedge_actors::Sender;
+use tedge_actors::SimpleMessageBox;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_test_utils::fs::TempTedgeDir;
+use tedge_timer_ext::Timeout;
+
+const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn device_facts_are_published_as_twin_fragments() {
+    let ttd = TempTedgeDir::new();
+    ttd.dir("etc")
+        .file("os-release")
+        .with_raw_content("NAME=\"Debian GNU/Linux\"\nVERSION_ID=\"12\"\n");
+    create_collector(&ttd, "mobile", r#"{"mobile":{"imei":"356938035643809"}}"#);
+    let mut test = spawn_actor(&ttd);
+
+    test.fire_timer().await;
+
+    let messages = test.twin_updates(3).await;
+    assert_eq!(
+        messages,
+        vec![
+            (
+                "te/device/main///twin/hardware".to_string(),
+                format!(
+                    r#"{{"cpu":{{"architecture":"{}"}}}}"#,
+                    std::env::consts::ARCH
+                )
+            ),
+            (
+                "te/device/main///twin/mobile".to_string(),
+                r#"{"imei":"356938035643809"}"#.to_string()
+            ),
+            (
+                "te/device/main///twin/os".to_string(),
+                r#"{"name":"Debian GNU/Linux","version":"12"}"#.to_string()
+            ),
+        ]
+    );
+
+    // The next collection is scheduled
+    assert_eq!(test.next_timer().await.duration, Duration::from_secs(3600));
+}
+
+#[tokio::test]
+async fn only_updated_fragments_are_republished() {
+    let ttd = TempTedgeDir::new();
+    create_collector(&ttd, "mobile", r#"{"mobile":{"operator":"ACME"}}"#);
+    create_collector(&ttd, "sensors", r#"{"sensors":{"count":2}}"#);
+    let mut test = spawn_actor(&ttd);
+
+    test.fire_timer().await;
+    assert_eq!(test.twin_updates(3).await.len(), 3);
+
+    // A collector script overrides the facts of the previous ones
+    create_collector(&ttd, "mobile", "{}");
+    create_collector(&ttd, "sensors", r#"{"sensors":{"count":3}}"#);
+    create_collector(&ttd, "z-override", r#"{"hardware":{"model":"Gateway X"}}"#);
+    test.fire_timer().await;
+
+    assert_eq!(
+        test.twin_updates(3).await,
+        vec![
+            ("te/device/main///twin/mobile".to_string(), "".to_string()),
+            (
+                "te/device/main///twin/hardware".to_string(),
+                r#"{"model":"Gateway X"}"#.to_string()
+            ),
+            (
+                "te/device/main///twin/sensors".to_string(),
+                r#"{"count":3}"#.to_string()
+            ),
+        ]
+    );
+    test.assert_no_more_updates().await;
+}
+
+#[tokio::test]
+async fn failing_collectors_are_ignored() {
+    let ttd = TempTedgeDir::new();
+    create_collector(&ttd, "broken", "not json");
+    create_collector(&ttd, "mobile", r#"{"mobile":{"operator":"ACME"}}"#);
+    let mut test = spawn_actor(&ttd);
+
+    test.fire_timer().await;
+
+    let topics: Vec<String> = test
+        .twin_updates(2)
+        .await
+        .into_iter()
+        .map(|(topic, _)| topic)
+        .collect();
+    assert_eq!(
+        topics,
+        vec![
+            "te/device/main///twin/hardware",
+            "te/device/main///twin/mobile"
+        ]
+    );
+}
+
+struct TestHandle {
+    mqtt: SimpleMessageBox<MqttMessage, MqttMessage>,
+    timer: FakeServerBox<InventoryTimerStart, InventoryTimerComplete>,
+}
+
+impl TestHandle {
+    async fn next_timer(&mut self) -> InventoryTimerStart {
+        self.timer
+            .recv()
+            .with_timeout(TEST_TIMEOUT)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    async fn fire_timer(&mut self) {
+        self.next_timer().await;
+        self.timer
+            .send(Timeout::new(InventoryRefresh))
+            .await
+            .unwrap();
+    }
+
+    async fn assert_no_more_updates(&mut self) {
+        while let Ok(Some(message)) = self
+            .mqtt
+            .recv()
+            .with_timeout(Duration::from_millis(200))
+            .await
+        {
+            assert!(message.topic.name.ends_with("/twin/storage"), "{message:?}");
+        }
+    }
+
+    /// Receive twin updates, ignoring the storage fragment which depends on the test host
+    async fn twin_updates(&mut self, count: usize) -> Vec<(String, String)> {
+        let mut updates = vec![];
+        while updates.len() < count {
+            let message = self
+                .mqtt
+                .recv()
+                .with_timeout(TEST_TIMEOUT)
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(message.retain);
+            if message.topic.name.ends_with("/twin/storage") {
+                continue;
+            }
+            updates.push((
+                message.topic.name.clone(),
+                message.payload_str().unwrap().to_string(),
+            ));
+        }
+        updates
+    }
+}
+
+fn spawn_actor(ttd: &TempTedgeDir) -> TestHandle {
+    let mut config = DeviceInventoryConfig::new(
+        &ttd.utf8_path_buf(),
+        MqttSchema::default(),
+        EntityTopicId::default_main_device(),
+        Duration::from_secs(3600),
+    );
+    config.system_root = ttd.utf8_path_buf();
+
+    let mut mqtt_builder: SimpleMessageBoxBuilder<MqttMessage, MqttMessage> =
+        SimpleMessageBoxBuilder::new("MQTT", 10);
+    let mut timer_builder: FakeServerBoxBuilder<InventoryTimerStart, InventoryTimerComplete> =
+        FakeServerBoxBuilder::default();
+
+    let actor = DeviceInventoryBuilder::new(config, &mut mqtt_builder, &mut timer_builder).build();
+    tokio::spawn(async move { actor.run().await });
+
+    TestHandle {
+        mqtt: mqtt_builder.build(),
+        timer: timer_builder.build(),
+    }
+}
+
+fn create_collector(ttd: &TempTedgeDir, name: &str, output: &str) {
+    let script = ttd.dir("device").dir("inventory.d").utf8_path().join(name);
+    std::fs::write(&script, format!("#!/bin/sh\ncat <<'EOF'\n{output}\nEOF\n")).unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
@@ -0,0 +1,250 @@
+//! History of the health status transitions of the services
+//!
+//! Only the transitions are recorded, i.e. a status is ignored when the same as the previous one,
+//! and only the latest [HEALTH_HISTORY_SIZE] transitions of a service are kept.
+use serde::Serialize;
+use serde_json::json;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::store::RingBuffer;
+use tedge_api::Status;
+use time::OffsetDateTime;
+
+/// How many transitions are kept per service
+pub const HEALTH_HISTORY_SIZE: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HealthTransition {
+    pub status: Status,
+    #[serde(with = "time::serde::rfc3339")]
+    pub time: OffsetDateTime,
+}
+
+#[derive(Debug)]
+struct ServiceHealth {
+    parent: Option<EntityTopicId>,
+    transitions: RingBuffer<HealthTransition>,
+}
+
+/// The health of a service, as returned by `GET /v1/health`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ServiceHealthReport {
+    #[serde(rename = "@topic-id")]
+    pub topic_id: EntityTopicId,
+    #[serde(rename = "@parent", skip_serializing_if = "Option::is_none")]
+    pub parent: Option<EntityTopicId>,
+    pub status: Status,
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_transition: OffsetDateTime,
+    /// The ratio of time the service has been `up` since the oldest transition kept
+    pub uptime_ratio: f64,
+    pub history: Vec<HealthTransition>,
+}
+
+/// The health of all the services, as returned by `GET /v1/health`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HealthReport {
+    /// `down` if any service is down, `up` otherwise
+    pub status: Status,
+    pub services: Vec<ServiceHealthReport>,
+}
+
+#[derive(Debug, Default)]
+pub struct HealthHistory {
+    services: BTreeMap<EntityTopicId, ServiceHealth>,
+}
+
+impl HealthHistory {
+    /// Record the health status of a service
+    ///
+    /// Returns `true` if this is a transition, i.e. a status different from the previous one.
+    pub fn update(
+        &mut self,
+        service: &EntityTopicId,
+        parent: Option<EntityTopicId>,
+        status: Status,
+        now: OffsetDateTime,
+    ) -> bool {
+        let health = self
+            .services
+            .entry(service.clone())
+            .or_insert_with(|| ServiceHealth {
+                parent: None,
+                transitions: RingBuffer::new(HEALTH_HISTORY_SIZE),
+            });
+        health.parent = parent;
+        if health
+            .transitions
+            .last()
+            .is_some_and(|last| last.status == status)
+        {
+            return false;
+        }
+        health
+            .transitions
+            .push(HealthTransition { status, time: now });
+        true
+    }
+
+    /// Forget the health of a service, returning its parent if the service was known
+    pub fn remove(&mut self, service: &EntityTopicId) -> Option<Option<EntityTopicId>> {
+        self.services.remove(service).map(|health| health.parent)
+    }
+
+    pub fn report(&self, now: OffsetDateTime) -> HealthReport {
+        let services: Vec<_> = self
+            .services
+            .iter()
+            .filter_map(|(topic_id, health)| {
+                let last = health.transitions.last()?;
+                Some(ServiceHealthReport {
+                    topic_id: topic_id.clone(),
+                    parent: health.parent.clone(),
+                    status: last.status.clone(),
+                    last_transition: last.time,
+                    uptime_ratio: uptime_ratio(&health.transitions, now),
+                    history: health.transitions.iter().cloned().collect(),
+                })
+            })
+            .collect();
+        HealthReport {
+            status: rolled_up_status(services.iter().map(|service| &service.status)),
+            services,
+        }
+    }
+
+    /// The rolled-up health of the services of a device, `Value::Null` if the device has no services
+    pub fn device_health(&self, device: &EntityTopicId) -> Value {
+        let statuses: BTreeMap<&EntityTopicId, &Status> = self
+            .services
+            .iter()
+            .filter(|(_, health)| health.parent.as_ref() == Some(device))
+            .filter_map(|(topic_id, health)| Some((topic_id, &health.transitions.last()?.status)))
+            .collect();
+        if statuses.is_empty() {
+            return Value::Null;
+        }
+
+        let down: Vec<_> = statuses
+            .iter()
+            .filter(|(_, status)| **status == &Status::Down)
+            .map(|(topic_id, _)| topic_id.as_str())
+            .collect();
+        json!({
+            "status": rolled_up_status(statuses.values().copied()),
+            "services": statuses.len(),
+            "down": down,
+        })
+    }
+}
+
+fn rolled_up_status<'a>(mut statuses: impl Iterator<Item = &'a Status>) -> Status {
+    if statuses.any(|status| status == &Status::Down) {
+        Status::Down
+    } else {
+        Status::Up
+    }
+}
+
+fn uptime_ratio(transitions: &RingBuffer<HealthTransition>, now: OffsetDateTime) -> f64 {
+    let mut up = Duration::ZERO;
+    let mut total = Duration::ZERO;
+    let mut iter = transitions.iter().peekable();
+    while let Some(transition) = iter.next() {
+        let end = iter.peek().map_or(now, |next| next.time);
+        let elapsed = Duration::try_from(end - transition.time).unwrap_or_default();
+        if transition.status == Status::Up {
+            up += elapsed;
+        }
+        total += elapsed;
+    }
+
+    if total.is_zero() {
+        // No time elapsed since the first transition: only the current status is relevant
+        let is_up = transitions
+            .last()
+            .is_some_and(|last| last.status == Status::Up);
+        return if is_up { 1.0 } else { 0.0 };
+    }
+    up.as_secs_f64() / total.as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::ext::NumericalDuration;
+
+    #[test]
+    fn only_transitions_are_recorded() {
+        let mut history = HealthHistory::default();
+        let service = EntityTopicId::default_main_service("my-app").unwrap();
+        let main = Some(EntityTopicId::default_main_device());
+        let start = OffsetDateTime::from_unix_timestamp(1_792_400_000).unwrap();
+
+        assert!(history.update(&service, main.clone(), Status::Up, start));
+        assert!(!history.update(&service, main.clone(), Status::Up, start + 10.seconds()));
+        assert!(history.update(&service, main.clone(), Status::Down, start + 60.seconds()));
+        assert!(history.update(&service, main.clone(), Status::Up, start + 90.seconds()));
+
+        let report = history.report(start + 100.seconds());
+        assert_eq!(report.status, Status::Up);
+        let [service_report] = &report.services[..] else {
+            panic!("expected a single service, got {report:?}");
+        };
+        assert_eq!(service_report.status, Status::Up);
+        assert_eq!(service_report.last_transition, start + 90.seconds());
+        assert_eq!(service_report.history.len(), 3);
+        assert_eq!(service_report.uptime_ratio, 0.7);
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut history = HealthHistory::default();
+        let service = EntityTopicId::default_main_service("my-app").unwrap();
+        let start = OffsetDateTime::from_unix_timestamp(1_792_400_000).unwrap();
+
+        for i in 0..(HEALTH_HISTORY_SIZE as i64 + 10) {
+            let status = if i % 2 == 0 { Status::Up } else { Status::Down };
+            history.update(&service, None, status, start + i.seconds());
+        }
+
+        let report = history.report(start + 1.hours());
+        assert_eq!(report.services[0].history.len(), HEALTH_HISTORY_SIZE);
+        assert_eq!(report.services[0].history[0].time, start + 10.seconds());
+    }
+
+    #[test]
+    fn device_health_is_rolled_up_from_its_services() {
+        let mut history = HealthHistory::default();
+        let main = EntityTopicId::default_main_device();
+        let child = EntityTopicId::default_child_device("child1").unwrap();
+        let now = OffsetDateTime::from_unix_timestamp(1_792_400_000).unwrap();
+
+        assert_eq!(history.device_health(&main), Value::Null);
+
+        let agent = EntityTopicId::default_main_service("tedge-agent").unwrap();
+        let app = EntityTopicId::default_main_service("my-app").unwrap();
+        let child_app = EntityTopicId::default_child_service("child1", "my-app").unwrap();
+        history.update(&agent, Some(main.clone()), Status::Up, now);
+        history.update(&app, Some(main.clone()), Status::Down, now);
+        history.update(&child_app, Some(child.clone()), Status::Up, now);
+
+        assert_eq!(
+            history.device_health(&main),
+            json!({"status": "down", "services": 2, "down": ["device/main/service/my-app"]})
+        );
+        assert_eq!(
+            history.device_health(&child),
+            json!({"status": "up", "services": 1, "down": []})
+        );
+        assert_eq!(history.report(now).status, Status::Down);
+
+        assert_eq!(history.remove(&app), Some(Some(main.clone())));
+        assert_eq!(
+            history.device_health(&main),
+            json!({"status": "up", "services": 1, "down": []})
+        );
+    }
+}
@@ -1,3 +1,4 @@
+pub(crate) mod health_history;
 pub(crate) mod server;
 
 #[cfg(test)]
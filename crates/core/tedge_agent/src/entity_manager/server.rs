@@ -1,14 +1,18 @@
+use crate::entity_manager::health_history::HealthHistory;
+use crate::entity_manager::health_history::HealthReport;
 use async_trait::async_trait;
 use futures::channel::mpsc;
 use futures::StreamExt as _;
 use serde_json::Map;
 use serde_json::Value;
+use std::collections::BTreeSet;
 use tedge_actors::LoggingSender;
 use tedge_actors::MappingSender;
 use tedge_actors::MessageSink;
 use tedge_actors::Sender;
 use tedge_actors::Server;
 use tedge_api::entity::EntityMetadata;
+use tedge_api::entity::EntityType;
 use tedge_api::entity_store;
 use tedge_api::entity_store::EntityRegistrationMessage;
 use tedge_api::entity_store::EntityTwinMessage;
@@ -21,11 +25,16 @@ use tedge_api::mqtt_topics::EntityTopicId;
 use tedge_api::mqtt_topics::MqttSchema;
 use tedge_api::pending_entity_store::RegisteredEntityData;
 use tedge_api::EntityStore;
+use tedge_api::HealthStatus;
 use tedge_mqtt_ext::MqttMessage;
 use tedge_mqtt_ext::MqttRequest;
 use tedge_mqtt_ext::TopicFilter;
+use time::OffsetDateTime;
 use tracing::error;
 
+/// The twin fragment where the rolled-up health of the services of a device is published
+const DEVICE_HEALTH_FRAGMENT: &str = "health";
+
 #[derive(Debug)]
 pub enum EntityStoreRequest {
     Get(EntityTopicId),
@@ -38,6 +47,7 @@ pub enum EntityStoreRequest {
     SetTwinFragment(EntityTwinMessage),
     GetTwinFragments(EntityTopicId),
     SetTwinFragments(EntityTopicId, Map<String, Value>),
+    GetHealth,
 }
 
 #[derive(Debug)]
@@ -52,11 +62,13 @@ pub enum EntityStoreResponse {
     SetTwinFragment(Result<bool, entity_store::Error>),
     GetTwinFragments(Result<Map<String, Value>, entity_store::Error>),
     SetTwinFragments(Result<(), entity_store::Error>),
+    GetHealth(HealthReport),
 }
 
 pub struct EntityStoreServer {
     config: EntityStoreServerConfig,
     entity_store: EntityStore,
+    health_history: HealthHistory,
     mqtt_publisher: LoggingSender<MqttMessage>,
     retain_requests: LoggingSender<(mpsc::UnboundedSender<MqttMessage>, TopicFilter)>,
 }
@@ -97,6 +109,7 @@ impl EntityStoreServer {
         Self {
             config,
             entity_store,
+            health_history: HealthHistory::default(),
             mqtt_publisher,
             retain_requests,
         }
@@ -162,6 +175,10 @@ impl Server for EntityStoreServer {
                 let res = self.set_entity_twin_fragments(&topic_id, fragments).await;
                 EntityStoreResponse::SetTwinFragments(res)
             }
+            EntityStoreRequest::GetHealth => {
+                let report = self.health_history.report(OffsetDateTime::now_utc());
+                EntityStoreResponse::GetHealth(report)
+            }
             EntityStoreRequest::MqttMessage(mqtt_message) => {
                 self.process_mqtt_message(mqtt_message).await;
                 EntityStoreResponse::Ok
@@ -242,19 +259,64 @@ impl EntityStoreServer {
             }
         }
 
-        if let Channel::EntityTwinData { fragment_key } = channel {
-            let fragment_value = if message.payload().is_empty() {
-                Value::Null
-            } else {
-                serde_json::from_slice(message.payload_bytes())?
-            };
-            let twin_message = EntityTwinMessage::new(topic_id, fragment_key, fragment_value);
-            self.entity_store.update_twin_fragment(twin_message)?;
+        match channel {
+            Channel::EntityTwinData { fragment_key } => {
+                let fragment_value = if message.payload().is_empty() {
+                    Value::Null
+                } else {
+                    serde_json::from_slice(message.payload_bytes())?
+                };
+                let twin_message = EntityTwinMessage::new(topic_id, fragment_key, fragment_value);
+                self.entity_store.update_twin_fragment(twin_message)?;
+            }
+            Channel::Health => self.process_health_status(&topic_id, &message).await,
+            _ => {}
         }
 
         Ok(())
     }
 
+    /// Record the health status transitions of the services,
+    /// updating the rolled-up health of their device
+    async fn process_health_status(&mut self, topic_id: &EntityTopicId, message: &MqttMessage) {
+        let device = if message.payload().is_empty() {
+            self.health_history.remove(topic_id).flatten()
+        } else {
+            let Some(entity) = self.entity_store.get(topic_id) else {
+                return;
+            };
+            if entity.r#type != EntityType::Service {
+                return;
+            }
+            let parent = entity.parent.clone();
+            let Ok(health) =
+                HealthStatus::try_from_health_status_message(message, &self.config.mqtt_schema)
+            else {
+                return;
+            };
+            let updated = self.health_history.update(
+                topic_id,
+                parent.clone(),
+                health.status,
+                OffsetDateTime::now_utc(),
+            );
+            parent.filter(|_| updated)
+        };
+
+        if let Some(device) = device {
+            self.publish_device_health(device).await;
+        }
+    }
+
+    async fn publish_device_health(&mut self, device: EntityTopicId) {
+        let health = self.health_history.device_health(&device);
+        let twin_message =
+            EntityTwinMessage::new(device, DEVICE_HEALTH_FRAGMENT.to_string(), health);
+        if let Err(err) = self.set_twin_fragment(twin_message).await {
+            error!("Failed to update the device health: {err}");
+        }
+    }
+
     async fn set_twin_fragment(
         &mut self,
         twin_message: EntityTwinMessage,
@@ -344,6 +406,11 @@ impl EntityStoreServer {
         if deleted.is_empty() {
             return deleted;
         }
+        let devices_with_removed_services: BTreeSet<_> = deleted
+            .iter()
+            .filter_map(|entity| self.health_history.remove(&entity.topic_id).flatten())
+            .filter(|device| self.entity_store.get(device).is_some())
+            .collect();
 
         let mut topics = TopicFilter::empty();
         for entity in deleted.iter() {
@@ -386,6 +453,10 @@ impl EntityStoreServer {
             self.publish_message(clear_entity_msg).await;
         }
 
+        for device in devices_with_removed_services {
+            self.publish_device_health(device).await;
+        }
+
         deleted
     }
 
@@ -1,3 +1,4 @@
+use crate::entity_manager::server::EntityStoreRequest;
 use crate::entity_manager::server::EntityStoreResponse;
 use crate::entity_manager::tests::model::Action;
 use crate::entity_manager::tests::model::Action::AddDevice;
@@ -15,6 +16,7 @@ use tedge_actors::Server;
 use tedge_api::entity::EntityMetadata;
 use tedge_api::entity::EntityType;
 use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::Status;
 use tedge_mqtt_ext::test_helpers::assert_received_contains_str;
 use tedge_mqtt_ext::MqttMessage;
 
@@ -222,6 +224,65 @@ async fn clear_entity_twin_data() {
     assert_eq!(entity.twin_data.get("x"), None);
 }
 
+#[tokio::test]
+async fn service_health_is_rolled_up_on_the_device_twin() {
+    let handle = entity::server("device-under-test");
+    let (mut entity_store, mut mqtt_output) = (handle.entity_store, handle.mqtt_output);
+
+    entity_store
+        .process_mqtt_message(
+            MqttMessage::from((
+                "te/device/main/service/my-app/status/health",
+                r#"{"status":"up"}"#,
+            ))
+            .with_retain(),
+        )
+        .await;
+    mqtt_output.skip(1).await; // Skip the auto-registration message
+    mqtt_output
+        .assert_received([MqttMessage::from((
+            "te/device/main///twin/health",
+            r#"{"down":[],"services":1,"status":"up"}"#,
+        ))
+        .with_retain()])
+        .await;
+
+    // The same status is not a transition
+    entity_store
+        .process_mqtt_message(
+            MqttMessage::from((
+                "te/device/main/service/my-app/status/health",
+                r#"{"status":"up","pid":1234}"#,
+            ))
+            .with_retain(),
+        )
+        .await;
+    entity_store
+        .process_mqtt_message(
+            MqttMessage::from((
+                "te/device/main/service/my-app/status/health",
+                r#"{"status":"down"}"#,
+            ))
+            .with_retain(),
+        )
+        .await;
+    mqtt_output
+        .assert_received([MqttMessage::from((
+            "te/device/main///twin/health",
+            r#"{"down":["device/main/service/my-app"],"services":1,"status":"down"}"#,
+        ))
+        .with_retain()])
+        .await;
+
+    let EntityStoreResponse::GetHealth(report) =
+        entity_store.handle(EntityStoreRequest::GetHealth).await
+    else {
+        panic!("Unexpected response");
+    };
+    assert_eq!(report.status, Status::Down);
+    assert_eq!(report.services[0].history.len(), 2);
+}
+
 proptest! {
     //#![proptest_config(proptest::prelude::ProptestConfig::with_cases(1000))]
     #[test]
@@ -4,6 +4,7 @@
 //! - `POST /v1/entities`: Registers a new entity.
 //! - `GET /v1/entities/*path`: Retrieves an existing entity.
 //! - `DELETE /v1/entities/*path`: Deregisters an existing entity.
+//! - `GET /v1/health`: Retrieves the health status history of all the services.
 //!
 //! References:
 //!
@@ -172,6 +173,7 @@ pub(crate) fn entity_store_router(state: AgentState) -> Router {
                 .patch(patch_resource)
                 .delete(delete_resource),
         )
+        .route("/v1/health", get(get_health))
         .layer(DefaultBodyLimit::max(HTTP_MAX_PAYLOAD_SIZE))
         .with_state(state)
 }
@@ -383,6 +385,20 @@ async fn get_entity(
     }
 }
 
+async fn get_health(State(state): State<AgentState>) -> Result<impl IntoResponse, Error> {
+    let response = state
+        .entity_store_handle
+        .clone()
+        .await_response(EntityStoreRequest::GetHealth)
+        .await?;
+
+    let EntityStoreResponse::GetHealth(report) = response else {
+        return Err(Error::InvalidEntityStoreResponse);
+    };
+
+    Ok(Json(report))
+}
+
 async fn update_entity(
     state: AgentState,
     topic_id: EntityTopicId,
@@ -559,6 +575,7 @@ async fn delete_entity_twin_fragments(
 #[cfg(test)]
 mod tests {
     use super::AgentState;
+    use crate::entity_manager::health_history::HealthHistory;
     use crate::entity_manager::server::EntityStoreRequest;
     use crate::entity_manager::server::EntityStoreResponse;
     use crate::http_server::entity_store::entity_store_router;
@@ -582,8 +599,10 @@ mod tests {
     use tedge_api::entity::EntityType;
     use tedge_api::entity_store;
     use tedge_api::mqtt_topics::EntityTopicId;
+    use tedge_api::Status;
     use tedge_test_utils::fs::TempTedgeDir;
     use test_case::test_case;
+    use time::OffsetDateTime;
     use tower::Service;
 
     #[tokio::test]
@@ -1681,6 +1700,60 @@ mod tests {
         assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
     }
 
+    #[tokio::test]
+    async fn get_health() {
+        let TestHandle {
+            mut app,
+            mut entity_store_box,
+        } = setup();
+
+        // Mock entity store actor response
+        tokio::spawn(async move {
+            if let Some(mut req) = entity_store_box.recv().await {
+                if let EntityStoreRequest::GetHealth = req.request {
+                    let mut history = HealthHistory::default();
+                    let now = OffsetDateTime::from_unix_timestamp(1_792_400_000).unwrap();
+                    history.update(
+                        &EntityTopicId::default_main_service("my-app").unwrap(),
+                        Some(EntityTopicId::default_main_device()),
+                        Status::Down,
+                        now,
+                    );
+                    req.reply_to
+                        .send(EntityStoreResponse::GetHealth(history.report(now)))
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/v1/health")
+            .body(Body::empty())
+            .expect("request builder");
+
+        let response = app.call(req).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let health: Value = serde_json::from_slice(&body).unwrap();
+        assert_json_eq!(
+            health,
+            json!({
+                "status": "down",
+                "services": [{
+                    "@topic-id": "device/main/service/my-app",
+                    "@parent": "device/main//",
+                    "status": "down",
+                    "last_transition": "2026-10-19T08:53:20Z",
+                    "uptime_ratio": 0.0,
+                    "history": [{"status": "down", "time": "2026-10-19T08:53:20Z"}]
+                }]
+            })
+        );
+    }
+
     async fn assert_non_existent_entity_response(response: Response<Body>) {
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
 
@@ -10,6 +10,7 @@
 //! - File transfer HTTP server
 //! - Restart management
 //! - Software management
+//! - Device certificate renewal
 
 use std::sync::Arc;
 
@@ -19,6 +20,9 @@ use tedge_config::log_init;
 use tracing::log::warn;
 
 mod agent;
+mod certificate_renewal;
+mod command_manager;
+mod device_inventory;
 mod device_profile_manager;
 mod entity_manager;
 mod http_server;
@@ -0,0 +1,302 @@
+//! Content-addressed index of the files downloaded into the agent cache directory.
+//!
+//! Artifacts are keyed by the SHA-256 digest of their remote URL, so that the same file requested
+//! by several operations (e.g. the same configuration file pushed to 50 child devices) is only
+//! downloaded once. An artifact is *in use* as long as an operation that references it is still
+//! in progress; only unused artifacts are evicted, least recently used first, when the total size
+//! of the cache exceeds the configured limit.
+
+use camino::Utf8PathBuf;
+use std::collections::HashMap;
+use tracing::info;
+use tracing::warn;
+
+pub struct ArtifactCache {
+    dir: Utf8PathBuf,
+    /// Maximum total size of the cached artifacts in bytes, `0` meaning no limit
+    max_size: u64,
+    entries: HashMap<String, CacheEntry>,
+    total_size: u64,
+    clock: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CacheEntry {
+    size: u64,
+    last_used: u64,
+    users: usize,
+}
+
+impl ArtifactCache {
+    pub fn new(dir: impl Into<Utf8PathBuf>, max_size: u64) -> Self {
+        ArtifactCache {
+            dir: dir.into(),
+            max_size,
+            entries: HashMap::new(),
+            total_size: 0,
+            clock: 0,
+        }
+    }
+
+    /// Build the cache index from the artifacts already present in the cache directory
+    ///
+    /// The files are ordered by modification time, so the least recently downloaded artifacts
+    /// are the first to be evicted.
+    pub fn load(dir: impl Into<Utf8PathBuf>, max_size: u64) -> Self {
+        let mut cache = ArtifactCache::new(dir, max_size);
+
+        let mut artifacts = Vec::new();
+        match cache.dir.read_dir_utf8() {
+            Ok(entries) => {
+                for entry in entries.flatten() {
+                    let Ok(metadata) = entry.metadata() else {
+                        continue;
+                    };
+                    if !metadata.is_file() || !is_artifact_key(entry.file_name()) {
+                        continue;
+                    }
+                    let modified = metadata.modified().ok();
+                    artifacts.push((modified, entry.file_name().to_string(), metadata.len()));
+                }
+            }
+            Err(err) => warn!(
+                "Failed to read the file cache directory {}: {err}",
+                cache.dir
+            ),
+        }
+
+        artifacts.sort();
+        for (_, key, size) in artifacts {
+            cache.insert(&key, size);
+        }
+
+        cache
+    }
+
+    /// The cache key of the artifact downloaded from the given URL
+    pub fn key(remote_url: &str) -> String {
+        sha256::digest(remote_url)
+    }
+
+    /// The path where the artifact with the given key is stored
+    pub fn path(&self, key: &str) -> Utf8PathBuf {
+        self.dir.join(key)
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Mark a cached artifact as in use by an operation, returning its path
+    ///
+    /// Returns `None` if the artifact is not cached, in which case it has to be downloaded.
+    pub fn acquire(&mut self, key: &str) -> Option<Utf8PathBuf> {
+        if !self.entries.contains_key(key) {
+            return None;
+        }
+
+        let path = self.path(key);
+        if !path.is_file() {
+            // The file has been removed behind our back
+            self.remove_entry(key);
+            return None;
+        }
+
+        let now = self.tick();
+        let entry = self.entries.get_mut(key)?;
+        entry.users += 1;
+        entry.last_used = now;
+        Some(path)
+    }
+
+    /// Release an artifact previously acquired by an operation
+    pub fn release(&mut self, key: &str) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.users = entry.users.saturating_sub(1);
+        }
+    }
+
+    /// Add a freshly downloaded artifact to the index
+    pub fn insert(&mut self, key: &str, size: u64) {
+        let now = self.tick();
+        let users = self.remove_entry(key).map(|e| e.users).unwrap_or_default();
+        self.total_size += size;
+        self.entries.insert(
+            key.to_string(),
+            CacheEntry {
+                size,
+                last_used: now,
+                users,
+            },
+        );
+    }
+
+    /// Remove the least recently used artifacts until the cache fits in its size limit
+    ///
+    /// Artifacts still in use by an operation are never evicted.
+    /// Returns the keys of the evicted artifacts.
+    pub fn evict(&mut self) -> Vec<String> {
+        let mut evicted = Vec::new();
+        if self.max_size == 0 || self.total_size <= self.max_size {
+            return evicted;
+        }
+
+        let mut candidates: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.users == 0)
+            .map(|(key, entry)| (entry.last_used, key.clone()))
+            .collect();
+        candidates.sort();
+
+        for (_, key) in candidates {
+            if self.total_size <= self.max_size {
+                break;
+            }
+            let path = self.path(&key);
+            if let Err(err) = std::fs::remove_file(&path) {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to evict {path} from the file cache: {err}");
+                    continue;
+                }
+            }
+            info!("Evicted {path} from the file cache");
+            self.remove_entry(&key);
+            evicted.push(key);
+        }
+
+        evicted
+    }
+
+    fn remove_entry(&mut self, key: &str) -> Option<CacheEntry> {
+        let entry = self.entries.remove(key)?;
+        self.total_size -= entry.size;
+        Some(entry)
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+/// Only the files named after a SHA-256 digest are managed by the cache
+fn is_artifact_key(file_name: &str) -> bool {
+    file_name.len() == 64 && file_name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+impl std::fmt::Debug for ArtifactCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArtifactCache")
+            .field("dir", &self.dir)
+            .field("max_size", &self.max_size)
+            .field("entries", &self.entries.len())
+            .field("total_size", &self.total_size)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tedge_test_utils::fs::TempTedgeDir;
+
+    fn cache_with_files(dir: &TempTedgeDir, max_size: u64, files: &[(&str, u64)]) -> ArtifactCache {
+        let mut cache = ArtifactCache::new(dir.utf8_path(), max_size);
+        for (url, size) in files {
+            let key = ArtifactCache::key(url);
+            std::fs::write(cache.path(&key), vec![0u8; *size as usize]).unwrap();
+            cache.insert(&key, *size);
+        }
+        cache
+    }
+
+    #[test]
+    fn same_url_maps_to_same_artifact() {
+        let ttd = TempTedgeDir::new();
+        let mut cache = cache_with_files(&ttd, 0, &[("http://cloud/firmware.bin", 10)]);
+
+        let key = ArtifactCache::key("http://cloud/firmware.bin");
+        assert_eq!(cache.acquire(&key), Some(cache.path(&key)));
+        assert_eq!(cache.acquire(&key), Some(cache.path(&key)));
+        assert_eq!(
+            cache.acquire(&ArtifactCache::key("http://cloud/other.bin")),
+            None
+        );
+    }
+
+    #[test]
+    fn least_recently_used_artifacts_are_evicted_first() {
+        let ttd = TempTedgeDir::new();
+        let mut cache = cache_with_files(&ttd, 25, &[("a", 10), ("b", 10)]);
+
+        // "a" becomes the most recently used
+        let a = ArtifactCache::key("a");
+        cache.acquire(&a);
+        cache.release(&a);
+
+        let c = ArtifactCache::key("c");
+        std::fs::write(cache.path(&c), [0u8; 10]).unwrap();
+        cache.insert(&c, 10);
+
+        assert_eq!(cache.evict(), vec![ArtifactCache::key("b")]);
+        assert_eq!(cache.total_size(), 20);
+        assert!(!cache.path(&ArtifactCache::key("b")).exists());
+        assert!(cache.path(&a).exists());
+        assert!(cache.path(&c).exists());
+    }
+
+    #[test]
+    fn artifacts_in_use_are_not_evicted() {
+        let ttd = TempTedgeDir::new();
+        let mut cache = cache_with_files(&ttd, 5, &[("a", 10), ("b", 10)]);
+
+        let a = ArtifactCache::key("a");
+        cache.acquire(&a);
+
+        assert_eq!(cache.evict(), vec![ArtifactCache::key("b")]);
+        assert_eq!(cache.total_size(), 10);
+        assert!(cache.contains(&a));
+
+        cache.release(&a);
+        assert_eq!(cache.evict(), vec![a]);
+        assert_eq!(cache.total_size(), 0);
+    }
+
+    #[test]
+    fn no_eviction_without_size_limit() {
+        let ttd = TempTedgeDir::new();
+        let mut cache = cache_with_files(&ttd, 0, &[("a", 10), ("b", 10)]);
+
+        assert!(cache.evict().is_empty());
+        assert_eq!(cache.total_size(), 20);
+    }
+
+    #[test]
+    fn index_is_rebuilt_from_cache_directory() {
+        let ttd = TempTedgeDir::new();
+        cache_with_files(&ttd, 0, &[("a", 10), ("b", 5)]);
+        ttd.file("not-an-artifact");
+
+        let mut cache = ArtifactCache::load(ttd.utf8_path(), 0);
+        assert_eq!(cache.total_size(), 15);
+        assert!(cache.acquire(&ArtifactCache::key("a")).is_some());
+        assert!(!cache.contains("not-an-artifact"));
+    }
+
+    #[test]
+    fn artifacts_removed_from_disk_are_forgotten() {
+        let ttd = TempTedgeDir::new();
+        let mut cache = cache_with_files(&ttd, 0, &[("a", 10)]);
+
+        let a = ArtifactCache::key("a");
+        std::fs::remove_file(cache.path(&a)).unwrap();
+
+        assert_eq!(cache.acquire(&a), None);
+        assert_eq!(cache.total_size(), 0);
+    }
+}
@@ -10,7 +10,14 @@
 //! This actor, for all child devices, for operations that have `remoteUrl` property, tries to
 //! download the file from this URL, places it in the File Transfer Service, and inserts the URL to
 //! download the file from the FTS in the `tedgeUrl` property.
+//!
+//! Downloaded files are kept in an [ArtifactCache] shared by all the operations: a file requested
+//! by several operations is downloaded only once, even when the requests are concurrent, and the
+//! least recently used files are evicted when the cache grows over `agent.file_cache.max_size`.
+
+mod artifact_cache;
 
+use artifact_cache::ArtifactCache;
 use async_trait::async_trait;
 use camino::Utf8PathBuf;
 use std::collections::HashMap;
@@ -65,9 +72,15 @@ pub struct FileCacheActor {
     mqtt_schema: MqttSchema,
     data_dir: DataDir,
 
-    pending_operations: HashMap<String, ConfigUpdateCmdPayload>,
+    artifact_cache: ArtifactCache,
+    /// The operations waiting for an artifact to be downloaded, indexed by artifact key
+    pending_downloads: HashMap<String, Vec<PendingOperation>>,
+    /// The artifact used by each in-progress operation, indexed by command topic
+    operation_artifacts: HashMap<String, String>,
 }
 
+type PendingOperation = (Topic, ConfigUpdateCmdPayload);
+
 #[async_trait]
 impl Actor for FileCacheActor {
     fn name(&self) -> &str {
@@ -113,25 +126,23 @@ impl FileCacheActor {
                 }
             };
 
-        if update_payload.remote_url.is_empty() || update_payload.tedge_url.is_some() {
+        if update_payload.remote_url.is_empty() {
             return Ok(());
         }
 
         match &update_payload.status {
-            CommandStatus::Executing => {
-                self.download_config_file_to_cache(&mqtt_message.topic, &update_payload)
+            CommandStatus::Executing if update_payload.tedge_url.is_none() => {
+                self.download_config_file_to_cache(&mqtt_message.topic, update_payload)
                     .await?;
             }
-            CommandStatus::Successful => self.delete_symlink_for_config_update(
-                &entity,
-                &update_payload.config_type,
-                &cmd_id,
-            )?,
-            CommandStatus::Failed { .. } => self.delete_symlink_for_config_update(
-                &entity,
-                &update_payload.config_type,
-                &cmd_id,
-            )?,
+            CommandStatus::Successful | CommandStatus::Failed { .. } => {
+                self.delete_symlink_for_config_update(
+                    &entity,
+                    &update_payload.config_type,
+                    &cmd_id,
+                )?;
+                self.release_artifact(&mqtt_message.topic);
+            }
             _ => {}
         }
 
@@ -139,41 +150,112 @@ impl FileCacheActor {
     }
 
     async fn process_download(&mut self, download: IdDownloadResult) -> Result<(), RuntimeError> {
-        let (topic, result) = download;
-
-        let Some(mut operation) = self.pending_operations.remove(&topic) else {
-            return Ok(());
-        };
+        let (key, result) = download;
 
-        let (entity, Channel::Command { cmd_id, .. }) = self
-            .mqtt_schema
-            .entity_channel_of(&topic)
-            .expect("only topics targeting config update command should be inserted")
-        else {
+        let Some(operations) = self.pending_downloads.remove(&key) else {
             return Ok(());
         };
 
-        let download = match result {
-            // if cant download file, operation failed
+        match result {
+            // if cant download file, all the operations waiting for it failed
             Err(err) => {
                 let error_message = format!("tedge-agent failed downloading a file: {err}");
-                operation.failed(&error_message);
                 error!("{}", error_message);
-                let message = MqttMessage::new(
-                    &Topic::new_unchecked(&topic),
-                    serde_json::to_string(&operation).unwrap(),
+                for (topic, mut operation) in operations {
+                    operation.failed(&error_message);
+                    let message =
+                        MqttMessage::new(&topic, serde_json::to_string(&operation).unwrap());
+                    self.mqtt_sender.send(message).await?;
+                }
+            }
+            Ok(download) => {
+                let size = std::fs::metadata(&download.file_path)
+                    .map(|metadata| metadata.len())
+                    .unwrap_or_default();
+                self.artifact_cache.insert(&key, size);
+                info!(
+                    "Downloaded {} to cache, cache size is now {} bytes",
+                    download.file_path.display(),
+                    self.artifact_cache.total_size()
                 );
-                self.mqtt_sender.send(message).await?;
+                for (topic, operation) in operations {
+                    self.serve_cached_artifact(&key, &topic, operation).await?;
+                }
+                self.artifact_cache.evict();
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn download_config_file_to_cache(
+        &mut self,
+        config_update_topic: &Topic,
+        config_update_payload: ConfigUpdateCmdPayload,
+    ) -> Result<(), RuntimeError> {
+        let remote_url = config_update_payload.remote_url.clone();
+        let key = ArtifactCache::key(&remote_url);
+        let operation = (config_update_topic.clone(), config_update_payload);
+
+        // The same file is already being downloaded for another operation
+        if let Some(operations) = self.pending_downloads.get_mut(&key) {
+            info!("Waiting for config file from `{remote_url}` to be downloaded to cache");
+            operations.push(operation);
+            return Ok(());
+        }
+
+        if self.artifact_cache.contains(&key) {
+            let (topic, payload) = &operation;
+            if self
+                .serve_cached_artifact(&key, topic, payload.clone())
+                .await?
+            {
+                info!("Using cached config file from `{remote_url}`");
                 return Ok(());
             }
-            Ok(download) => download,
+        }
+
+        info!("Downloading config file from `{remote_url}` to cache");
+
+        let dest_path = self.artifact_cache.path(&key);
+        let download_request = DownloadRequest::new(&remote_url, dest_path.as_std_path());
+
+        self.pending_downloads.insert(key.clone(), vec![operation]);
+        self.downloader_sender.send((key, download_request)).await?;
+
+        Ok(())
+    }
+
+    /// Make a cached artifact available to an operation through the File Transfer Service
+    ///
+    /// Returns `false` if the artifact is no longer in the cache.
+    async fn serve_cached_artifact(
+        &mut self,
+        key: &str,
+        topic: &Topic,
+        mut operation: ConfigUpdateCmdPayload,
+    ) -> Result<bool, RuntimeError> {
+        let Ok((entity, Channel::Command { cmd_id, .. })) =
+            self.mqtt_schema.entity_channel_of(topic)
+        else {
+            return Ok(true);
+        };
+
+        let Some(artifact_path) = self.artifact_cache.acquire(key) else {
+            return Ok(false);
         };
+        if let Some(previous) = self
+            .operation_artifacts
+            .insert(topic.name.clone(), key.to_string())
+        {
+            self.artifact_cache.release(&previous);
+        }
 
         self.create_symlink_for_config_update(
             &entity,
             &operation.config_type,
             &cmd_id,
-            download.file_path,
+            artifact_path,
         )?;
 
         let url_symlink_path = symlink_path(&entity, &operation.config_type, &cmd_id);
@@ -185,40 +267,17 @@ impl FileCacheActor {
 
         operation.tedge_url = Some(tedge_url);
 
-        let mqtt_message = MqttMessage::new(
-            &Topic::new(&topic).unwrap(),
-            serde_json::to_string(&operation).unwrap(),
-        );
-        self.mqtt_sender.send(mqtt_message).await.unwrap();
+        let mqtt_message = MqttMessage::new(topic, serde_json::to_string(&operation).unwrap());
+        self.mqtt_sender.send(mqtt_message).await?;
 
-        Ok(())
+        Ok(true)
     }
 
-    async fn download_config_file_to_cache(
-        &mut self,
-        config_update_topic: &Topic,
-        config_update_payload: &ConfigUpdateCmdPayload,
-    ) -> Result<(), RuntimeError> {
-        let remote_url = &config_update_payload.remote_url;
-
-        let file_cache_key = sha256::digest(remote_url);
-        let dest_path = self.data_dir.cache_dir().join(file_cache_key);
-        let topic = config_update_topic.name.clone();
-
-        info!("Downloading config file from `{remote_url}` to cache");
-
-        let download_request = DownloadRequest::new(remote_url, dest_path.as_std_path());
-
-        self.pending_operations.insert(
-            config_update_topic.name.clone(),
-            config_update_payload.clone(),
-        );
-
-        self.downloader_sender
-            .send((topic, download_request))
-            .await?;
-
-        Ok(())
+    fn release_artifact(&mut self, topic: &Topic) {
+        if let Some(key) = self.operation_artifacts.remove(&topic.name) {
+            self.artifact_cache.release(&key);
+            self.artifact_cache.evict();
+        }
     }
 
     fn create_symlink_for_config_update(
@@ -283,6 +342,7 @@ pub struct FileCacheActorBuilder {
     mqtt_schema: MqttSchema,
     tedge_http_host: Arc<str>,
     data_dir: DataDir,
+    file_cache_max_size: u64,
 }
 
 impl FileCacheActorBuilder {
@@ -290,6 +350,7 @@ impl FileCacheActorBuilder {
         mqtt_schema: MqttSchema,
         tedge_http_host: Arc<str>,
         data_dir: DataDir,
+        file_cache_max_size: u64,
         downloader_actor: &mut impl Service<IdDownloadRequest, IdDownloadResult>,
         mqtt_actor: &mut (impl MessageSource<MqttMessage, TopicFilter> + MessageSink<MqttMessage>),
     ) -> Self {
@@ -308,6 +369,7 @@ impl FileCacheActorBuilder {
             mqtt_schema,
             tedge_http_host,
             data_dir,
+            file_cache_max_size,
         }
     }
 
@@ -340,6 +402,8 @@ impl Builder<FileCacheActor> for FileCacheActorBuilder {
 
     fn build(self) -> FileCacheActor {
         let (_, rx) = self.message_box.build().into_split();
+        let artifact_cache =
+            ArtifactCache::load(self.data_dir.cache_dir(), self.file_cache_max_size);
         FileCacheActor {
             mqtt_sender: self.mqtt_sender,
             downloader_sender: self.download_sender,
@@ -349,7 +413,9 @@ impl Builder<FileCacheActor> for FileCacheActorBuilder {
             mqtt_schema: self.mqtt_schema,
             data_dir: self.data_dir,
 
-            pending_operations: HashMap::new(),
+            artifact_cache,
+            pending_downloads: HashMap::new(),
+            operation_artifacts: HashMap::new(),
         }
     }
 }
@@ -954,6 +954,103 @@ impl CommandPayload for FirmwareUpdateCmdPayload {
     }
 }
 
+/// Command to execute a shell command on a device
+pub type ShellCommandCmd = Command<ShellCommandCmdPayload>;
+
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ShellCommandCmdPayload {
+    #[serde(flatten)]
+    pub status: CommandStatus,
+    /// The command line to execute
+    pub command: String,
+    /// Where to upload the command output (stdout and stderr), if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tedge_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// Set when the output has been truncated to the maximum output size
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub output_truncated: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_path: Option<Utf8PathBuf>,
+}
+
+impl Jsonify for ShellCommandCmdPayload {}
+
+impl CommandPayload for ShellCommandCmdPayload {
+    fn operation_type() -> OperationType {
+        OperationType::Command
+    }
+
+    fn status(&self) -> CommandStatus {
+        self.status.clone()
+    }
+
+    fn set_status(&mut self, status: CommandStatus) {
+        self.status = status
+    }
+}
+
+/// Command to push a file from the device to the cloud
+pub type FileUploadCmd = Command<FileUploadCmdPayload>;
+
+#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FileUploadCmdPayload {
+    #[serde(flatten)]
+    pub status: CommandStatus,
+    /// Where the file to upload is made available on the file transfer service
+    pub tedge_url: String,
+    /// The name of the file in the cloud
+    pub name: String,
+    /// A cloud-specific category for the file (e.g. the Cumulocity event type)
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub file_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// The topic prefix of the mapper expected to upload the file (e.g. `c8y`, `az` or `aws`)
+    ///
+    /// If not set, the file is uploaded by any mapper.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cloud: Option<String>,
+    /// Where the file has been uploaded, set by the mapper on success
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_url: Option<String>,
+}
+
+impl Jsonify for FileUploadCmdPayload {}
+
+impl CommandPayload for FileUploadCmdPayload {
+    fn operation_type() -> OperationType {
+        OperationType::FileUpload
+    }
+
+    fn status(&self) -> CommandStatus {
+        self.status.clone()
+    }
+
+    fn set_status(&mut self, status: CommandStatus) {
+        self.status = status
+    }
+}
+
+impl FileUploadCmdPayload {
+    /// Check if the file is to be uploaded by the mapper with the given topic prefix
+    pub fn is_for_cloud(&self, topic_prefix: &str) -> bool {
+        self.cloud
+            .as_deref()
+            .is_none_or(|cloud| cloud == topic_prefix)
+    }
+
+    /// The MIME type of the file, `application/octet-stream` if not provided
+    pub fn mime_type_or_default(&self) -> String {
+        self.mime_type
+            .clone()
+            .unwrap_or_else(|| "application/octet-stream".to_string())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1049,4 +1146,49 @@ mod tests {
         // However, if serialized again the custom status is lost
         assert_eq!(request.to_json(), r#"{"status":"unknown"}"#);
     }
+
+    #[test]
+    fn serde_shell_command() {
+        let request = ShellCommandCmdPayload::from_json(
+            r#"{"status":"init","command":"uptime","tedgeUrl":"http://127.0.0.1:8000/te/v1/files/main/command/123"}"#,
+        )
+        .unwrap();
+        assert_eq!(request.command, "uptime");
+        assert_eq!(request.exit_code, None);
+        assert!(!request.output_truncated);
+
+        let response = ShellCommandCmdPayload {
+            status: CommandStatus::Successful,
+            exit_code: Some(0),
+            output_truncated: true,
+            ..request
+        };
+        assert_eq!(
+            response.to_json(),
+            r#"{"status":"successful","command":"uptime","tedgeUrl":"http://127.0.0.1:8000/te/v1/files/main/command/123","exitCode":0,"outputTruncated":true}"#
+        );
+    }
+
+    #[test]
+    fn serde_file_upload_command() {
+        let request = FileUploadCmdPayload::from_json(
+            r#"{"status":"init","tedgeUrl":"http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234","name":"report.pdf","cloud":"az"}"#,
+        )
+        .unwrap();
+        assert_eq!(request.name, "report.pdf");
+        assert!(request.is_for_cloud("az"));
+        assert!(!request.is_for_cloud("c8y"));
+
+        let response = FileUploadCmdPayload {
+            status: CommandStatus::Successful,
+            remote_url: Some("https://example.blob.core.windows.net/uploads/report.pdf".into()),
+            cloud: None,
+            ..request
+        };
+        assert!(response.is_for_cloud("c8y"));
+        assert_eq!(
+            response.to_json(),
+            r#"{"status":"successful","tedgeUrl":"http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234","name":"report.pdf","remoteUrl":"https://example.blob.core.windows.net/uploads/report.pdf"}"#
+        );
+    }
 }
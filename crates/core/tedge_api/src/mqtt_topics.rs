@@ -733,6 +733,8 @@ pub enum OperationType {
     FirmwareUpdate,
     Health,
     DeviceProfile,
+    Command,
+    FileUpload,
     Custom(String),
 }
 
@@ -775,6 +777,8 @@ impl<'a> From<&'a str> for OperationType {
             "config_update" => OperationType::ConfigUpdate,
             "firmware_update" => OperationType::FirmwareUpdate,
             "device_profile" => OperationType::DeviceProfile,
+            "command" => OperationType::Command,
+            "file_upload" => OperationType::FileUpload,
             operation => OperationType::Custom(operation.to_string()),
         }
     }
@@ -792,6 +796,8 @@ impl Display for OperationType {
             OperationType::FirmwareUpdate => write!(f, "firmware_update"),
             OperationType::Health => write!(f, "health"),
             OperationType::DeviceProfile => write!(f, "device_profile"),
+            OperationType::Command => write!(f, "command"),
+            OperationType::FileUpload => write!(f, "file_upload"),
             OperationType::Custom(operation) => write!(f, "{operation}"),
         }
     }
@@ -30,6 +30,15 @@ impl<T> RingBuffer<T> {
         self.buffer.push_back(item);
     }
 
+    pub fn iter(&self) -> vec_deque::Iter<'_, T> {
+        self.buffer.iter()
+    }
+
+    /// The most recent item, if any
+    pub fn last(&self) -> Option<&T> {
+        self.buffer.back()
+    }
+
     pub fn take(&mut self) -> Self {
         let capacity = self.buffer.capacity();
         std::mem::replace(self, RingBuffer::new(capacity))
@@ -358,7 +358,8 @@ impl OperationWorkflow {
             OperationType::Custom(_)
             | OperationType::Restart
             | OperationType::DeviceProfile
-            | OperationType::FirmwareUpdate => {
+            | OperationType::FirmwareUpdate
+            | OperationType::Command => {
                 let meta_topic = schema.capability_topic_for(target, self.operation.clone());
                 let payload = "{}".to_string();
                 Some(
@@ -374,6 +375,7 @@ impl OperationWorkflow {
             | OperationType::LogUpload
             | OperationType::ConfigSnapshot
             | OperationType::ConfigUpdate
+            | OperationType::FileUpload
             | OperationType::Health => None,
         }
     }
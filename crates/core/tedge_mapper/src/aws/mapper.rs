@@ -3,7 +3,14 @@ use crate::core::mapper::start_basic_actors;
 use crate::core::mqtt::configure_proxy;
 use anyhow::Context;
 use async_trait::async_trait;
+use aws_mapper_ext::file_upload::AwsFileUploadActorBuilder;
+use aws_mapper_ext::file_upload::AwsFileUploadConfig;
+use aws_mapper_ext::jobs::AwsJobsActorBuilder;
+use aws_mapper_ext::jobs::AwsJobsConfig;
+use aws_mapper_ext::shadow::AwsShadowActorBuilder;
+use aws_mapper_ext::shadow::AwsShadowConfig;
 use aws_mapper_ext::AwsConverter;
+use std::time::Duration;
 use tedge_api::mqtt_topics::EntityTopicId;
 use tedge_api::mqtt_topics::MqttSchema;
 use tedge_api::service_health_topic;
@@ -14,6 +21,7 @@ use tedge_config::TEdgeConfig;
 use tedge_file_system_ext::FsWatchActorBuilder;
 use tedge_flows::FlowsMapperBuilder;
 use tedge_flows::FlowsMapperConfig;
+use tedge_http_ext::HttpActor;
 use tedge_mqtt_bridge::rumqttc::Transport;
 use tedge_mqtt_bridge::BridgeConfig;
 use tedge_mqtt_bridge::MqttBridgeActorBuilder;
@@ -21,6 +29,9 @@ use tedge_watch_ext::WatchActorBuilder;
 use tracing::warn;
 use yansi::Paint;
 
+/// How long to wait for a presigned URL when uploading a file to S3
+const FILE_UPLOAD_RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
 pub struct AwsMapper {
     pub profile: Option<ProfileName>,
 }
@@ -105,7 +116,47 @@ impl TEdgeComponent for AwsMapper {
         flows_mapper.connect_fs(&mut fs_actor);
         flows_mapper.connect_cmd(&mut cmd_watcher_actor);
 
+        let mut http_actor = HttpActor::new(tedge_config.http.client_tls_config()?).builder();
+        let file_upload_config = AwsFileUploadConfig {
+            mqtt_schema: mqtt_schema.clone(),
+            topic_prefix: prefix.value().clone(),
+            response_timeout: FILE_UPLOAD_RESPONSE_TIMEOUT,
+        };
+        let file_upload_actor =
+            AwsFileUploadActorBuilder::new(file_upload_config, &mut mqtt_actor, &mut http_actor);
+
+        let bridge_service_name = if tedge_config.mqtt.bridge.built_in {
+            format!("tedge-mapper-bridge-{prefix}")
+        } else {
+            format!("mosquitto-{prefix}-bridge")
+        };
+        let bridge_health_topic = service_health_topic(
+            &mqtt_schema,
+            &tedge_config.mqtt.device_topic_id,
+            &bridge_service_name,
+        );
+        let shadow_config = AwsShadowConfig {
+            mqtt_schema: mqtt_schema.clone(),
+            topic_prefix: prefix.value().clone(),
+            device_topic_id: tedge_config.mqtt.device_topic_id.clone(),
+            bridge_health_topic: bridge_health_topic.clone(),
+            state_dir: config_dir.join(format!(".tedge-mapper-{prefix}")),
+        };
+        let shadow_actor = AwsShadowActorBuilder::new(shadow_config, &mut mqtt_actor);
+
+        let jobs_config = AwsJobsConfig {
+            mqtt_schema: mqtt_schema.clone(),
+            topic_prefix: prefix.value().clone(),
+            device_topic_id: tedge_config.mqtt.device_topic_id.clone(),
+            bridge_health_topic,
+        };
+        let jobs_actor = AwsJobsActorBuilder::new(jobs_config, &mut mqtt_actor);
+
         runtime.spawn(flows_mapper).await?;
+        runtime.spawn(file_upload_actor).await?;
+        runtime.spawn(shadow_actor).await?;
+        runtime.spawn(jobs_actor).await?;
+        runtime.spawn(http_actor).await?;
         runtime.spawn(fs_actor).await?;
         runtime.spawn(cmd_watcher_actor).await?;
         runtime.spawn(mqtt_actor).await?;
@@ -131,6 +182,9 @@ fn built_in_bridge_rules(
     // topic to interact with the shadow of the device
     bridge.forward_bidirectionally("shadow/#", local_prefix.clone(), things_prefix.clone())?;
 
+    // topic to interact with the jobs of the device
+    bridge.forward_bidirectionally("jobs/#", local_prefix.clone(), things_prefix.clone())?;
+
     // echo topic mapping to check the connection
     bridge.forward_from_local(
         "",
@@ -3,6 +3,12 @@ use crate::core::mapper::start_basic_actors;
 use crate::core::mqtt::configure_proxy;
 use anyhow::Context;
 use async_trait::async_trait;
+use az_mapper_ext::file_upload::AzFileUploadActorBuilder;
+use az_mapper_ext::file_upload::AzFileUploadConfig;
+use az_mapper_ext::methods::AzMethodsActorBuilder;
+use az_mapper_ext::methods::AzMethodsConfig;
+use az_mapper_ext::twin::AzTwinActorBuilder;
+use az_mapper_ext::twin::AzTwinConfig;
 use az_mapper_ext::AzureConverter;
 use std::borrow::Cow;
 use tedge_api::mqtt_topics::EntityTopicId;
@@ -17,6 +23,7 @@ use tedge_flows::ConnectedFlowRegistry;
 use tedge_flows::FlowRegistryExt;
 use tedge_flows::FlowsMapperBuilder;
 use tedge_flows::FlowsMapperConfig;
+use tedge_http_ext::HttpActor;
 use tedge_mqtt_bridge::rumqttc::Transport;
 use tedge_mqtt_bridge::BridgeConfig;
 use tedge_mqtt_bridge::MqttBridgeActorBuilder;
@@ -118,7 +125,57 @@ impl TEdgeComponent for AzureMapper {
         flows_mapper.connect_fs(&mut fs_actor);
         flows_mapper.connect_cmd(&mut cmd_watcher_actor);
 
+        let mut local_http_actor = HttpActor::new(tedge_config.http.client_tls_config()?).builder();
+        let mut cloud_http_actor = HttpActor::new(
+            tedge_config
+                .mqtt_client_config_rustls(&az_config)
+                .context("Failed to create HTTP TLS config")?,
+        )
+        .builder();
+        let file_upload_config = AzFileUploadConfig {
+            mqtt_schema: mqtt_schema.clone(),
+            topic_prefix: prefix.value().clone(),
+            iot_hub_host: az_config.url().or_config_not_set()?.to_string(),
+            device_id: az_config.device.id()?.to_string(),
+        };
+        let file_upload_actor = AzFileUploadActorBuilder::new(
+            file_upload_config,
+            &mut mqtt_actor,
+            &mut local_http_actor,
+            &mut cloud_http_actor,
+        );
+
+        let bridge_service_name = if tedge_config.mqtt.bridge.built_in {
+            format!("tedge-mapper-bridge-{prefix}")
+        } else {
+            format!("mosquitto-{prefix}-bridge")
+        };
+        let twin_config = AzTwinConfig {
+            mqtt_schema: mqtt_schema.clone(),
+            topic_prefix: prefix.value().clone(),
+            device_topic_id: tedge_config.mqtt.device_topic_id.clone(),
+            bridge_health_topic: service_health_topic(
+                &mqtt_schema,
+                &tedge_config.mqtt.device_topic_id,
+                &bridge_service_name,
+            ),
+            state_dir: config_dir.join(format!(".tedge-mapper-{prefix}")),
+        };
+        let twin_actor = AzTwinActorBuilder::new(twin_config, &mut mqtt_actor);
+
+        let methods_config = AzMethodsConfig {
+            mqtt_schema: mqtt_schema.clone(),
+            topic_prefix: prefix.value().clone(),
+            device_topic_id: tedge_config.mqtt.device_topic_id.clone(),
+        };
+        let methods_actor = AzMethodsActorBuilder::new(methods_config, &mut mqtt_actor);
+
         runtime.spawn(flows_mapper).await?;
+        runtime.spawn(file_upload_actor).await?;
+        runtime.spawn(twin_actor).await?;
+        runtime.spawn(methods_actor).await?;
+        runtime.spawn(local_http_actor).await?;
+        runtime.spawn(cloud_http_actor).await?;
         runtime.spawn(fs_actor).await?;
         runtime.spawn(cmd_watcher_actor).await?;
         runtime.spawn(mqtt_actor).await?;
@@ -151,7 +208,16 @@ fn built_in_bridge_rules(
 
     // Digital twin
     bridge.forward_from_local("twin/GET/#", local_prefix.clone(), iothub_prefix)?;
-    bridge.forward_from_local("twin/PATCH/#", local_prefix.clone(), iothub_prefix)?;
+    bridge.forward_from_local(
+        "twin/PATCH/properties/reported/#",
+        local_prefix.clone(),
+        iothub_prefix,
+    )?;
+    bridge.forward_from_remote(
+        "twin/PATCH/properties/desired/#",
+        local_prefix.clone(),
+        iothub_prefix,
+    )?;
     bridge.forward_from_remote("twin/res/#", local_prefix.clone(), iothub_prefix)?;
 
     Ok(bridge)
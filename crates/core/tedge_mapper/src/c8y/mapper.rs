@@ -11,6 +11,7 @@ use c8y_mapper_ext::availability::AvailabilityConfig;
 use c8y_mapper_ext::compatibility_adapter::OldAgentAdapter;
 use c8y_mapper_ext::config::C8yMapperConfig;
 use c8y_mapper_ext::converter::CumulocityConverter;
+use c8y_mapper_ext::smartrest_templates::SmartRestTemplates;
 use mqtt_channel::Config;
 use tedge_api::entity::EntityExternalId;
 use tedge_api::mqtt_topics::EntityTopicId;
@@ -165,6 +166,10 @@ impl TEdgeComponent for CumulocityMapper {
 
         let flows_dir =
             tedge_flows::flows_dir(cfg_dir, "c8y", self.profile.as_ref().map(|p| p.as_ref()));
+        c8y_mapper_actor.set_smartrest_templates(SmartRestTemplates {
+            templates_dir: flows_dir.with_file_name("smartrest"),
+            subscribed: c8y_config.cloud_specific.smartrest.templates.clone(),
+        });
         let flows = c8y_mapper_actor.flow_registry(flows_dir).await?;
         let stats_config = &tedge_config.flows.stats;
         let service_config = FlowsMapperConfig::new(
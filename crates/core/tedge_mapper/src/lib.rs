@@ -9,6 +9,10 @@ use crate::c8y::mapper::CumulocityMapper;
 use crate::collectd::mapper::CollectdMapper;
 use crate::core::component::TEdgeComponent;
 use crate::flows::GenMapper;
+#[cfg(feature = "mqtt_cloud")]
+use crate::mqtt_cloud::mapper::MqttCloudMapper;
+#[cfg(feature = "sparkplug")]
+use crate::sparkplug::mapper::SparkplugMapper;
 use anyhow::Context;
 use clap::Parser;
 use flockfile::check_another_instance_is_not_running;
@@ -27,6 +31,10 @@ pub mod c8y;
 mod collectd;
 mod core;
 mod flows;
+#[cfg(feature = "mqtt_cloud")]
+pub mod mqtt_cloud;
+#[cfg(feature = "sparkplug")]
+mod sparkplug;
 
 /// Set the cloud profile either from the CLI argument or env variable,
 /// then set the environment variable so child processes automatically
@@ -62,6 +70,12 @@ fn lookup_component(component_name: MapperName) -> Box<dyn TEdgeComponent> {
         MapperName::C8y { profile } => Box::new(CumulocityMapper {
             profile: read_and_set_var!(profile, "TEDGE_CLOUD_PROFILE"),
         }),
+        #[cfg(feature = "mqtt_cloud")]
+        MapperName::MqttCloud { profile } => Box::new(MqttCloudMapper {
+            profile: read_and_set_var!(profile, "TEDGE_CLOUD_PROFILE"),
+        }),
+        #[cfg(feature = "sparkplug")]
+        MapperName::Sparkplug => Box::new(SparkplugMapper),
         MapperName::Local => Box::new(GenMapper),
     }
 }
@@ -111,7 +125,15 @@ pub enum MapperName {
         #[clap(long)]
         profile: Option<ProfileName>,
     },
+    #[cfg(feature = "mqtt_cloud")]
+    MqttCloud {
+        /// The cloud profile to use
+        #[clap(long)]
+        profile: Option<ProfileName>,
+    },
     Collectd,
+    #[cfg(feature = "sparkplug")]
+    Sparkplug,
     Local,
 }
 
@@ -136,7 +158,15 @@ impl fmt::Display for MapperName {
             MapperName::C8y {
                 profile: Some(profile),
             } => write!(f, "tedge-mapper-c8y@{profile}"),
+            #[cfg(feature = "mqtt_cloud")]
+            MapperName::MqttCloud { profile: None } => write!(f, "tedge-mapper-mqtt-cloud"),
+            #[cfg(feature = "mqtt_cloud")]
+            MapperName::MqttCloud {
+                profile: Some(profile),
+            } => write!(f, "tedge-mapper-mqtt-cloud@{profile}"),
             MapperName::Collectd => write!(f, "tedge-mapper-collectd"),
+            #[cfg(feature = "sparkplug")]
+            MapperName::Sparkplug => write!(f, "tedge-mapper-sparkplug"),
             MapperName::Local => write!(f, "tedge-mapper-local"),
         }
     }
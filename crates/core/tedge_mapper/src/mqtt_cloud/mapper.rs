@@ -0,0 +1,197 @@
+use crate::core::component::TEdgeComponent;
+use crate::core::mapper::start_basic_actors;
+use crate::core::mqtt::configure_proxy;
+use anyhow::Context;
+use async_trait::async_trait;
+use mqtt_cloud_mapper_ext::read_mqtt_cloud_credentials;
+use mqtt_cloud_mapper_ext::MqttCloudConverter;
+use mqtt_cloud_mapper_ext::MqttCloudTopics;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_api::service_health_topic;
+use tedge_config::models::auth_method::AuthType;
+use tedge_config::models::TopicPrefix;
+use tedge_config::tedge_toml::mapper_config::MqttCloudMapperSpecificConfig;
+use tedge_config::tedge_toml::ProfileName;
+use tedge_config::TEdgeConfig;
+use tedge_file_system_ext::FsWatchActorBuilder;
+use tedge_flows::FlowsMapperBuilder;
+use tedge_flows::FlowsMapperConfig;
+use tedge_mqtt_bridge::rumqttc::Transport;
+use tedge_mqtt_bridge::use_credentials;
+use tedge_mqtt_bridge::BridgeConfig;
+use tedge_mqtt_bridge::MqttBridgeActorBuilder;
+use tedge_watch_ext::WatchActorBuilder;
+use tracing::warn;
+use yansi::Paint;
+
+pub struct MqttCloudMapper {
+    pub profile: Option<ProfileName>,
+}
+
+#[async_trait]
+impl TEdgeComponent for MqttCloudMapper {
+    async fn start(
+        &self,
+        tedge_config: TEdgeConfig,
+        config_dir: &tedge_config::Path,
+    ) -> Result<(), anyhow::Error> {
+        let mqtt_cloud_config =
+            tedge_config.mapper_config::<MqttCloudMapperSpecificConfig>(&self.profile)?;
+        let prefix = &mqtt_cloud_config.bridge.topic_prefix;
+        let mapper_name = format!("tedge-mapper-{prefix}");
+        let (mut runtime, mut mqtt_actor) = start_basic_actors(&mapper_name, &tedge_config).await?;
+        let mqtt_schema = MqttSchema::with_root(tedge_config.mqtt.topic_root.clone());
+        let service_topic_id = EntityTopicId::default_main_service(&mapper_name)?;
+
+        let device_id = mqtt_cloud_config.device.id()?;
+        let topics = MqttCloudTopics::try_new(
+            &mqtt_cloud_config.cloud_specific.topic_templates,
+            &device_id,
+        )?;
+
+        if tedge_config.mqtt.bridge.built_in {
+            let device_topic_id = tedge_config.mqtt.device_topic_id.clone();
+
+            let rules = built_in_bridge_rules(&device_id, prefix, &topics)?;
+
+            let mut cloud_config = tedge_mqtt_bridge::MqttOptions::new(
+                device_id,
+                mqtt_cloud_config.url().or_config_not_set()?.to_string(),
+                mqtt_cloud_config.cloud_specific.port,
+            );
+            cloud_config.set_clean_session(false);
+            cloud_config.set_keep_alive(mqtt_cloud_config.bridge.keepalive_interval.duration());
+
+            let credentials_path = &mqtt_cloud_config.cloud_specific.credentials_path;
+            match mqtt_cloud_config
+                .cloud_specific
+                .auth_method
+                .to_type(credentials_path)
+            {
+                AuthType::Certificate => {
+                    let tls_config = tedge_config
+                        .mqtt_client_config_rustls(&mqtt_cloud_config)
+                        .context("Failed to create MQTT TLS config")?;
+                    cloud_config.set_transport(Transport::tls_with_config(tls_config.into()));
+                }
+                AuthType::Basic => {
+                    let (username, password) = read_mqtt_cloud_credentials(credentials_path)?;
+                    use_credentials(
+                        &mut cloud_config,
+                        &*mqtt_cloud_config.root_cert_path,
+                        username,
+                        password,
+                    )?;
+                }
+            }
+
+            configure_proxy(&tedge_config, &mut cloud_config)?;
+
+            let bridge_name = format!("tedge-mapper-bridge-{prefix}");
+            let health_topic = service_health_topic(&mqtt_schema, &device_topic_id, &bridge_name);
+
+            let bridge_actor = MqttBridgeActorBuilder::new(
+                &tedge_config,
+                &bridge_name,
+                &health_topic,
+                rules,
+                cloud_config,
+                None,
+            )
+            .await;
+            runtime.spawn(bridge_actor).await?;
+        } else if tedge_config.proxy.address.or_none().is_some() {
+            warn!("`proxy.address` is configured without the built-in bridge enabled. The bridge MQTT connection to the cloud will {} communicate via the configured proxy.", "not".bold())
+        }
+
+        let converter = MqttCloudConverter::new(
+            mqtt_cloud_config.cloud_specific.mapper.timestamp,
+            &mqtt_schema,
+            mqtt_cloud_config.cloud_specific.mapper.timestamp_format,
+            prefix.value().clone(),
+            mqtt_cloud_config.mapper.mqtt.max_payload_size.0,
+            mqtt_cloud_config.topics.0.clone(),
+            topics,
+        );
+        let flows_dir = tedge_flows::flows_dir(
+            config_dir,
+            "mqtt_cloud",
+            self.profile.as_ref().map(|p| p.as_ref()),
+        );
+        let flows = converter.flow_registry(flows_dir).await?;
+        let te = &tedge_config.mqtt.topic_root;
+        let stats_config = &tedge_config.flows.stats;
+        let service_config = FlowsMapperConfig::new(
+            &format!("{te}/{service_topic_id}"),
+            stats_config.interval.duration(),
+            stats_config.on_message,
+            stats_config.on_interval,
+        );
+
+        let mut fs_actor = FsWatchActorBuilder::new();
+        let mut cmd_watcher_actor = WatchActorBuilder::new();
+
+        let mut flows_mapper = FlowsMapperBuilder::try_new(flows, service_config).await?;
+        flows_mapper.connect(&mut mqtt_actor);
+        flows_mapper.connect_fs(&mut fs_actor);
+        flows_mapper.connect_cmd(&mut cmd_watcher_actor);
+
+        runtime.spawn(flows_mapper).await?;
+        runtime.spawn(fs_actor).await?;
+        runtime.spawn(cmd_watcher_actor).await?;
+        runtime.spawn(mqtt_actor).await?;
+        runtime.run_to_completion().await?;
+        Ok(())
+    }
+}
+
+/// The rules forwarding messages between the local broker and the remote MQTT broker
+///
+/// The remote topics are those given by the topic templates, published locally under `<topic_prefix>/`.
+pub fn built_in_bridge_rules(
+    remote_client_id: &str,
+    topic_prefix: &TopicPrefix,
+    topics: &MqttCloudTopics,
+) -> Result<BridgeConfig, anyhow::Error> {
+    let local_prefix = format!("{topic_prefix}/");
+    let conn_check = format!("tedge/{remote_client_id}/test-connection");
+    let mut bridge = BridgeConfig::new();
+
+    for filter in topics.outgoing_filters() {
+        bridge.forward_from_local(filter, local_prefix.clone(), "")?;
+    }
+    for filter in topics.incoming_filters() {
+        bridge.forward_from_remote(filter, local_prefix.clone(), "")?;
+    }
+
+    // echo topic mapping to check the connection
+    bridge.forward_from_local(
+        "",
+        format!("{local_prefix}test-connection"),
+        conn_check.clone(),
+    )?;
+    bridge.forward_from_remote("", format!("{local_prefix}connection-success"), conn_check)?;
+
+    Ok(bridge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tedge_config::tedge_toml::mapper_config::MqttCloudTopicTemplates;
+
+    #[test]
+    fn bridge_rules_are_valid() {
+        let templates = MqttCloudTopicTemplates {
+            measurements: "tedge/{device_id}/{entity}/m/{type}".to_string(),
+            events: "tedge/{device_id}/{entity}/e/{type}".to_string(),
+            alarms: "tedge/{device_id}/{entity}/a/{type}".to_string(),
+            twin: "tedge/{device_id}/{entity}/twin/{type}".to_string(),
+            commands: "tedge/{device_id}/{entity}/cmd/{operation}/{cmd_id}".to_string(),
+        };
+        let topics = MqttCloudTopics::try_new(&templates, "test-device-id").unwrap();
+
+        built_in_bridge_rules("test-device-id", &"mqtt".try_into().unwrap(), &topics).unwrap();
+    }
+}
@@ -0,0 +1,62 @@
+use crate::core::component::TEdgeComponent;
+use crate::core::mapper::start_basic_actors;
+use async_trait::async_trait;
+use sparkplug_mapper_ext::next_bd_seq;
+use sparkplug_mapper_ext::SparkplugActorBuilder;
+use sparkplug_mapper_ext::SparkplugConfig;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_config::tedge_toml::Cloud;
+use tedge_config::TEdgeConfig;
+use tedge_mqtt_ext::MqttActorBuilder;
+
+const SPARKPLUG_MAPPER_NAME: &str = "tedge-mapper-sparkplug";
+
+pub struct SparkplugMapper;
+
+#[async_trait]
+impl TEdgeComponent for SparkplugMapper {
+    async fn start(
+        &self,
+        tedge_config: TEdgeConfig,
+        config_dir: &tedge_config::Path,
+    ) -> Result<(), anyhow::Error> {
+        let (mut runtime, mut mqtt_actor) =
+            start_basic_actors(SPARKPLUG_MAPPER_NAME, &tedge_config).await?;
+
+        let edge_node_id = match tedge_config.sparkplug.edge_node_id.or_none() {
+            Some(edge_node_id) => edge_node_id.clone(),
+            None => tedge_config.device_id(None::<Cloud>)?,
+        };
+        let state_dir = config_dir.join(format!(".{SPARKPLUG_MAPPER_NAME}"));
+        let config = SparkplugConfig {
+            mqtt_schema: MqttSchema::with_root(tedge_config.mqtt.topic_root.clone()),
+            device_topic_id: tedge_config.mqtt.device_topic_id.clone(),
+            group_id: tedge_config.sparkplug.group_id.clone(),
+            edge_node_id,
+            bd_seq: next_bd_seq(&state_dir),
+        };
+
+        // The NDEATH is registered as the last will of the Sparkplug session,
+        // and births are requested again whenever this session is re-established.
+        let session_name = format!("{SPARKPLUG_MAPPER_NAME}-{}", config.edge_node_id);
+        let death_certificate = config.death_certificate();
+        let rebirth_request = config.rebirth_request();
+        let mut sparkplug_mqtt = MqttActorBuilder::new(
+            tedge_config
+                .sparkplug_mqtt_config()?
+                .with_session_name(session_name)
+                .with_clean_session(true)
+                .with_last_will_message(death_certificate)
+                .with_initial_message(move || rebirth_request.clone()),
+        );
+
+        let sparkplug_actor =
+            SparkplugActorBuilder::new(config, &mut mqtt_actor, &mut sparkplug_mqtt);
+
+        runtime.spawn(sparkplug_actor).await?;
+        runtime.spawn(sparkplug_mqtt).await?;
+        runtime.spawn(mqtt_actor).await?;
+        runtime.run_to_completion().await?;
+        Ok(())
+    }
+}
@@ -1,4 +1,7 @@
-pub async fn start_watchdog(_: tedge_config::TEdgeConfig) -> Result<(), anyhow::Error> {
+pub async fn start_watchdog(
+    _: tedge_config::TEdgeConfig,
+    _: &tedge_config::Path,
+) -> Result<(), anyhow::Error> {
     Err(anyhow::Error::from(WatchdogError::WatchdogNotAvailable))
 }
 
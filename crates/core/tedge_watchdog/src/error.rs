@@ -33,6 +33,9 @@ pub enum WatchdogError {
     #[error("Did not find the WatchdogSec in {file}")]
     NoWatchdogSec { file: String },
 
+    #[error("Invalid recovery policies in {path}: {reason}")]
+    InvalidRecoveryPolicies { path: String, reason: String },
+
     #[error(transparent)]
     FromCertificateError(#[from] CertificateError),
 
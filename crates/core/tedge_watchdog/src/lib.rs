@@ -8,6 +8,8 @@ mod systemd_watchdog;
 use systemd_watchdog as watchdog;
 #[cfg(target_os = "linux")]
 mod error;
+#[cfg(target_os = "linux")]
+mod recovery;
 
 // on non-linux, we do nothing for now
 #[cfg(not(target_os = "linux"))]
@@ -34,5 +36,5 @@ pub async fn run(watchdog_opt: WatchdogOpt) -> Result<(), anyhow::Error> {
     )?;
 
     let tedge_config = tedge_config::TEdgeConfig::load(&watchdog_opt.common.config_dir).await?;
-    watchdog::start_watchdog(tedge_config).await
+    watchdog::start_watchdog(tedge_config, &watchdog_opt.common.config_dir).await
 }
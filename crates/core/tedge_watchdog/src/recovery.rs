@@ -0,0 +1,688 @@
+//! Recovery of the services reported `down` on their health endpoint
+//!
+//! The recovery policies are declared in `/etc/tedge/watchdog/recovery.toml`:
+//!
+//! ```toml
+//! # Policy applied to any service of the device without a specific policy
+//! [default]
+//! max_retries = 3
+//! retry_interval = "30s"
+//!
+//! [services.my-app]
+//! restart_command = ["/usr/bin/docker", "restart", "my-app"]
+//! max_retries = 5
+//! reboot = true
+//! ```
+//!
+//! A service reported `down` is given `retry_interval` to be back `up` on its own,
+//! before being restarted with its `restart_command` (by default the `init.restart` command of `system.toml`).
+//! Once `max_retries` restarts failed to bring the service `up`, a critical alarm is raised on the service
+//! and, if `reboot` is set, the device is restarted using the `restart` operation.
+use crate::error::WatchdogError;
+use camino::Utf8Path;
+use futures::SinkExt;
+use futures::StreamExt;
+use mqtt_channel::MqttMessage;
+use mqtt_channel::QoS;
+use mqtt_channel::Topic;
+use mqtt_channel::TopicFilter;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::Instant;
+use tedge_api::entity::EntityType;
+use tedge_api::entity_is_mosquitto_bridge_service;
+use tedge_api::entity_store::EntityRegistrationMessage;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::ChannelFilter;
+use tedge_api::mqtt_topics::EntityFilter;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_api::workflow::GenericCommandState;
+use tedge_api::HealthStatus;
+use tedge_api::Status;
+use tedge_config::models::seconds::SecondsOrHumanTime;
+use tedge_config::SystemConfig;
+use tedge_config::TEdgeConfig;
+use time::OffsetDateTime;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+/// The file declaring the recovery policies, relative to the config directory
+const RECOVERY_POLICIES_FILE: &str = "watchdog/recovery.toml";
+
+/// The type of the alarm raised when a service cannot be recovered
+const RECOVERY_ALARM_TYPE: &str = "service_recovery_failed";
+
+/// The prefix of the ids of the restart commands triggered by the watchdog
+const COMMAND_ID_PREFIX: &str = "tedge-watchdog-";
+
+/// How often the recovery deadlines are checked
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+const WATCHDOG_SERVICE: &str = "tedge-watchdog";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecoveryPolicies {
+    /// The policy of the services without a specific policy
+    default: Option<RecoveryPolicy>,
+
+    /// The policies of specific services, by service name
+    #[serde(default)]
+    services: HashMap<String, RecoveryPolicy>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecoveryPolicy {
+    /// The command restarting the service, `{}` being replaced by the service name
+    restart_command: Option<Vec<String>>,
+
+    /// How many times the service is restarted before escalating
+    #[serde(default = "RecoveryPolicy::default_max_retries")]
+    max_retries: u32,
+
+    /// How long a service is given to be `up` again, before each restart attempt
+    #[serde(default = "RecoveryPolicy::default_retry_interval")]
+    retry_interval: SecondsOrHumanTime,
+
+    /// Restart the device when the service cannot be recovered
+    #[serde(default)]
+    reboot: bool,
+}
+
+impl RecoveryPolicy {
+    fn default_max_retries() -> u32 {
+        3
+    }
+
+    fn default_retry_interval() -> SecondsOrHumanTime {
+        "30s".parse().unwrap()
+    }
+}
+
+impl RecoveryPolicies {
+    /// Load the recovery policies, returning `None` if no policies are declared
+    pub fn load(config_dir: &Utf8Path) -> Result<Option<Self>, WatchdogError> {
+        let path = config_dir.join(RECOVERY_POLICIES_FILE);
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+        let policies =
+            toml::from_str(&content).map_err(|err| WatchdogError::InvalidRecoveryPolicies {
+                path: path.to_string(),
+                reason: err.to_string(),
+            })?;
+        Ok(Some(policies))
+    }
+
+    /// The policy applied to a service
+    ///
+    /// The default policy doesn't apply to the mosquitto bridges, which are not services on their own.
+    fn policy_for(&self, service: &str, is_bridge: bool) -> Option<&RecoveryPolicy> {
+        self.services
+            .get(service)
+            .or_else(|| self.default.as_ref().filter(|_| !is_bridge))
+    }
+}
+
+/// An action to be performed to recover a service
+#[derive(Debug, PartialEq, Eq)]
+pub enum RecoveryAction {
+    Restart {
+        service: String,
+        command: Vec<String>,
+    },
+    Publish(MqttMessage),
+}
+
+#[derive(Debug)]
+enum RecoveryState {
+    /// The service is down, and will be restarted at the deadline unless up again
+    Recovering { attempts: u32, deadline: Instant },
+
+    /// All the restart attempts failed
+    Failed,
+}
+
+#[derive(Debug)]
+struct SupervisedService {
+    name: String,
+    parent: EntityTopicId,
+    policy: RecoveryPolicy,
+    state: RecoveryState,
+}
+
+/// The name and parent of a registered service
+#[derive(Debug)]
+struct ServiceEntity {
+    name: Option<String>,
+    parent: Option<EntityTopicId>,
+}
+
+/// Tracks the health of the services of the device, deciding when to restart them
+pub struct RecoverySupervisor {
+    mqtt_schema: MqttSchema,
+    device_topic_id: EntityTopicId,
+    policies: RecoveryPolicies,
+    default_restart_command: Vec<String>,
+    registered: HashMap<EntityTopicId, ServiceEntity>,
+    down: HashMap<EntityTopicId, SupervisedService>,
+}
+
+impl RecoverySupervisor {
+    pub fn new(
+        mqtt_schema: MqttSchema,
+        device_topic_id: EntityTopicId,
+        policies: RecoveryPolicies,
+        default_restart_command: Vec<String>,
+    ) -> Self {
+        RecoverySupervisor {
+            mqtt_schema,
+            device_topic_id,
+            policies,
+            default_restart_command,
+            registered: HashMap::new(),
+            down: HashMap::new(),
+        }
+    }
+
+    pub fn subscriptions(&self) -> TopicFilter {
+        let mut topics = TopicFilter::empty();
+        for channel in [
+            ChannelFilter::EntityMetadata,
+            ChannelFilter::Health,
+            ChannelFilter::Command(OperationType::Restart),
+        ] {
+            topics.add_all(self.mqtt_schema.topics(EntityFilter::AnyEntity, channel));
+        }
+        topics
+    }
+
+    pub fn process(&mut self, message: &MqttMessage, now: Instant) -> Vec<RecoveryAction> {
+        let Ok((entity, channel)) = self.mqtt_schema.entity_channel_of(&message.topic) else {
+            return vec![];
+        };
+        match channel {
+            Channel::EntityMetadata => {
+                self.process_registration(entity, message);
+                vec![]
+            }
+            Channel::Health => self.process_health_status(entity, message, now),
+            Channel::Command {
+                operation: OperationType::Restart,
+                cmd_id,
+            } if cmd_id.starts_with(COMMAND_ID_PREFIX) => self.process_restart_command(message),
+            _ => vec![],
+        }
+    }
+
+    /// Restart the services still down at their deadline, escalating once all attempts failed
+    pub fn tick(&mut self, now: Instant) -> Vec<RecoveryAction> {
+        let mut actions = vec![];
+        for (entity, service) in self.down.iter_mut() {
+            let mqtt_schema = &self.mqtt_schema;
+            let RecoveryState::Recovering { attempts, deadline } = &mut service.state else {
+                continue;
+            };
+            if now < *deadline {
+                continue;
+            }
+            if *attempts < service.policy.max_retries {
+                *attempts += 1;
+                *deadline = now + service.policy.retry_interval.duration();
+                info!(
+                    "Restarting {} (attempt {attempts}/{})",
+                    service.name, service.policy.max_retries
+                );
+                actions.push(RecoveryAction::Restart {
+                    service: service.name.clone(),
+                    command: service
+                        .policy
+                        .restart_command
+                        .as_ref()
+                        .unwrap_or(&self.default_restart_command)
+                        .iter()
+                        .map(|arg| arg.replace("{}", &service.name))
+                        .collect(),
+                });
+                continue;
+            }
+
+            error!(
+                "{} is still down after {} restart attempts",
+                service.name, service.policy.max_retries
+            );
+            let attempts = *attempts;
+            service.state = RecoveryState::Failed;
+            actions.push(RecoveryAction::Publish(alarm_message(
+                mqtt_schema,
+                entity,
+                &service.name,
+                attempts,
+            )));
+            if service.policy.reboot {
+                warn!("Restarting {} to recover {}", service.parent, service.name);
+                actions.push(RecoveryAction::Publish(restart_command(
+                    mqtt_schema,
+                    &service.parent,
+                )));
+            }
+        }
+        actions
+    }
+
+    fn process_registration(&mut self, entity: EntityTopicId, message: &MqttMessage) {
+        if message.payload_bytes().is_empty() {
+            self.registered.remove(&entity);
+            self.down.remove(&entity);
+            return;
+        }
+        let Ok(registration) = EntityRegistrationMessage::try_from(entity, message.payload_bytes())
+        else {
+            return;
+        };
+        if registration.r#type == EntityType::Service {
+            let name = registration
+                .twin_data
+                .get("name")
+                .and_then(|name| name.as_str())
+                .map(|name| name.to_string());
+            self.registered.insert(
+                registration.topic_id,
+                ServiceEntity {
+                    name,
+                    parent: registration.parent,
+                },
+            );
+        }
+    }
+
+    fn process_health_status(
+        &mut self,
+        entity: EntityTopicId,
+        message: &MqttMessage,
+        now: Instant,
+    ) -> Vec<RecoveryAction> {
+        if message.payload_bytes().is_empty() {
+            self.down.remove(&entity);
+            return vec![];
+        }
+        let Ok(health) = HealthStatus::try_from_health_status_message(message, &self.mqtt_schema)
+        else {
+            return vec![];
+        };
+        match health.status {
+            Status::Up => match self.down.remove(&entity) {
+                Some(service) if matches!(service.state, RecoveryState::Failed) => {
+                    info!("{} is up again", service.name);
+                    vec![RecoveryAction::Publish(clear_alarm_message(
+                        &self.mqtt_schema,
+                        &entity,
+                    ))]
+                }
+                Some(service) => {
+                    info!("{} is up again", service.name);
+                    vec![]
+                }
+                None => vec![],
+            },
+            Status::Down if !self.down.contains_key(&entity) => {
+                if let Some(service) = self.supervised_service(&entity, now) {
+                    warn!(
+                        "{} is down, restarting it in {}",
+                        service.name, service.policy.retry_interval
+                    );
+                    self.down.insert(entity, service);
+                }
+                vec![]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Clear the restart commands triggered by the watchdog once finished
+    fn process_restart_command(&self, message: &MqttMessage) -> Vec<RecoveryAction> {
+        match GenericCommandState::from_command_message(message) {
+            Ok(command) if command.is_finished() => {
+                vec![RecoveryAction::Publish(command.clear().into_message())]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// The service to be supervised, if hosted by this device and with a recovery policy
+    fn supervised_service(
+        &self,
+        entity: &EntityTopicId,
+        now: Instant,
+    ) -> Option<SupervisedService> {
+        let registered = self.registered.get(entity);
+        let name = registered
+            .and_then(|service| service.name.clone())
+            .or_else(|| entity.default_service_name().map(|name| name.to_string()))?;
+        let parent = registered
+            .and_then(|service| service.parent.clone())
+            .or_else(|| entity.default_service_parent_identifier())?;
+        if parent != self.device_topic_id || name == WATCHDOG_SERVICE {
+            return None;
+        }
+
+        let is_bridge = entity_is_mosquitto_bridge_service(entity);
+        let policy = self.policies.policy_for(&name, is_bridge)?.clone();
+        Some(SupervisedService {
+            name,
+            parent,
+            state: RecoveryState::Recovering {
+                attempts: 0,
+                deadline: now + policy.retry_interval.duration(),
+            },
+            policy,
+        })
+    }
+}
+
+fn alarm_topic(mqtt_schema: &MqttSchema, entity: &EntityTopicId) -> Topic {
+    mqtt_schema.topic_for(
+        entity,
+        &Channel::Alarm {
+            alarm_type: RECOVERY_ALARM_TYPE.to_string(),
+        },
+    )
+}
+
+fn alarm_message(
+    mqtt_schema: &MqttSchema,
+    entity: &EntityTopicId,
+    service: &str,
+    attempts: u32,
+) -> MqttMessage {
+    let payload = json!({
+        "text": format!("{service} is still down after {attempts} restart attempts"),
+        "severity": "critical",
+    });
+    MqttMessage::new(&alarm_topic(mqtt_schema, entity), payload.to_string())
+        .with_retain()
+        .with_qos(QoS::AtLeastOnce)
+}
+
+fn clear_alarm_message(mqtt_schema: &MqttSchema, entity: &EntityTopicId) -> MqttMessage {
+    MqttMessage::new(&alarm_topic(mqtt_schema, entity), "")
+        .with_retain()
+        .with_qos(QoS::AtLeastOnce)
+}
+
+fn restart_command(mqtt_schema: &MqttSchema, device: &EntityTopicId) -> MqttMessage {
+    let cmd_id = format!(
+        "{COMMAND_ID_PREFIX}{}",
+        OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000
+    );
+    let topic = mqtt_schema.topic_for(
+        device,
+        &Channel::Command {
+            operation: OperationType::Restart,
+            cmd_id,
+        },
+    );
+    GenericCommandState::new(topic, "init".to_string(), json!({})).into_message()
+}
+
+/// Restart the services of the device according to the recovery policies
+pub async fn start_recovery_supervisor(
+    tedge_config: &TEdgeConfig,
+    config_dir: &Utf8Path,
+    policies: RecoveryPolicies,
+) -> Result<(), WatchdogError> {
+    let default_restart_command = SystemConfig::try_new(config_dir)
+        .map_err(|err| WatchdogError::CustomError(err.into()))?
+        .init
+        .restart;
+    let mut supervisor = RecoverySupervisor::new(
+        MqttSchema::with_root(tedge_config.mqtt.topic_root.clone()),
+        tedge_config.mqtt.device_topic_id.clone(),
+        policies,
+        default_restart_command,
+    );
+
+    let mqtt_config = tedge_config
+        .mqtt_config()?
+        .with_session_name(format!("{WATCHDOG_SERVICE}#recovery"))
+        .with_subscriptions(supervisor.subscriptions());
+    let mut client = mqtt_channel::Connection::new(&mqtt_config).await?;
+    info!("Starting the recovery of the services reported down");
+
+    let mut ticks = tokio::time::interval(TICK_INTERVAL);
+    loop {
+        let actions = tokio::select! {
+            message = client.received.next() => match message {
+                Some(message) => supervisor.process(&message, Instant::now()),
+                None => return Err(WatchdogError::ChannelClosed),
+            },
+            _ = ticks.tick() => supervisor.tick(Instant::now()),
+        };
+
+        for action in actions {
+            match action {
+                RecoveryAction::Restart { service, command } => {
+                    restart_service(&service, &command).await
+                }
+                RecoveryAction::Publish(message) => client
+                    .published
+                    .send(message)
+                    .await
+                    .map_err(|_| WatchdogError::ChannelClosed)?,
+            }
+        }
+    }
+}
+
+async fn restart_service(service: &str, command: &[String]) {
+    let Some((program, args)) = command.split_first() else {
+        error!("No restart command configured for {service}");
+        return;
+    };
+    match tokio::process::Command::new(program)
+        .args(args)
+        .status()
+        .await
+    {
+        Ok(status) if status.success() => {}
+        Ok(status) => error!("Failed to restart {service}: `{program}` exited with {status}"),
+        Err(err) => error!("Failed to restart {service}: cannot run `{program}`: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_is_restarted_when_not_up_again_in_time() {
+        let mut supervisor = supervisor(
+            r#"
+            [default]
+            max_retries = 2
+            retry_interval = "10s"
+            "#,
+        );
+        let start = Instant::now();
+
+        assert_eq!(supervisor.process(&health("my-app", "down"), start), vec![]);
+        assert_eq!(supervisor.tick(start + secs(9)), vec![]);
+        assert_eq!(
+            supervisor.tick(start + secs(10)),
+            vec![restart("my-app", &["/bin/systemctl", "restart", "my-app"])]
+        );
+        assert_eq!(supervisor.tick(start + secs(15)), vec![]);
+        assert_eq!(
+            supervisor.tick(start + secs(20)),
+            vec![restart("my-app", &["/bin/systemctl", "restart", "my-app"])]
+        );
+
+        assert_eq!(
+            supervisor.process(&health("my-app", "up"), start + secs(25)),
+            vec![]
+        );
+        assert_eq!(supervisor.tick(start + secs(60)), vec![]);
+    }
+
+    #[test]
+    fn alarm_is_raised_and_device_rebooted_once_all_restarts_failed() {
+        let mut supervisor = supervisor(
+            r#"
+            [services.my-app]
+            restart_command = ["/usr/bin/docker", "restart", "my-app"]
+            max_retries = 1
+            retry_interval = 5
+            reboot = true
+            "#,
+        );
+        let start = Instant::now();
+
+        supervisor.process(&health("my-app", "down"), start);
+        assert_eq!(
+            supervisor.tick(start + secs(5)),
+            vec![restart("my-app", &["/usr/bin/docker", "restart", "my-app"])]
+        );
+
+        let actions = supervisor.tick(start + secs(10));
+        let [RecoveryAction::Publish(alarm), RecoveryAction::Publish(reboot)] = &actions[..] else {
+            panic!("expected an alarm and a restart command, got {actions:?}");
+        };
+        assert_eq!(
+            alarm.topic.name,
+            "te/device/main/service/my-app/a/service_recovery_failed"
+        );
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(alarm.payload_bytes()).unwrap(),
+            json!({"text": "my-app is still down after 1 restart attempts", "severity": "critical"})
+        );
+        assert!(reboot
+            .topic
+            .name
+            .starts_with("te/device/main///cmd/restart/tedge-watchdog-"));
+        assert_eq!(reboot.payload_str().unwrap(), r#"{"status":"init"}"#);
+
+        // No further attempts until the service is up again
+        assert_eq!(supervisor.tick(start + secs(60)), vec![]);
+        assert_eq!(
+            supervisor.process(&health("my-app", "up"), start + secs(61)),
+            vec![RecoveryAction::Publish(
+                MqttMessage::new(
+                    &Topic::new_unchecked(
+                        "te/device/main/service/my-app/a/service_recovery_failed"
+                    ),
+                    ""
+                )
+                .with_retain()
+                .with_qos(QoS::AtLeastOnce)
+            )]
+        );
+
+        // The restart command is cleared once finished
+        let finished = MqttMessage::new(&reboot.topic, r#"{"status":"successful"}"#).with_retain();
+        assert_eq!(
+            supervisor.process(&finished, start + secs(120)),
+            vec![RecoveryAction::Publish(
+                MqttMessage::new(&reboot.topic, "")
+                    .with_retain()
+                    .with_qos(QoS::AtLeastOnce)
+            )]
+        );
+    }
+
+    #[test]
+    fn services_without_policy_are_not_supervised() {
+        let mut supervisor = supervisor(
+            r#"
+            [services.my-app]
+            retry_interval = 5
+            "#,
+        );
+        let start = Instant::now();
+
+        supervisor.process(&health("other-app", "down"), start);
+        supervisor.process(&health("mosquitto-c8y-bridge", "down"), start);
+        let child_service = MqttMessage::new(
+            &Topic::new_unchecked("te/device/child1/service/my-app/status/health"),
+            r#"{"status":"down"}"#,
+        );
+        supervisor.process(&child_service, start);
+
+        assert_eq!(supervisor.tick(start + secs(60)), vec![]);
+    }
+
+    #[test]
+    fn registered_services_are_supervised_by_name() {
+        let mut supervisor = supervisor(
+            r#"
+            [services.custom-app]
+            retry_interval = 5
+            "#,
+        );
+        let start = Instant::now();
+
+        let registration = MqttMessage::new(
+            &Topic::new_unchecked("te/factory/apps/custom/1"),
+            r#"{"@type":"service","@parent":"device/main//","name":"custom-app"}"#,
+        )
+        .with_retain();
+        supervisor.process(&registration, start);
+        let health = MqttMessage::new(
+            &Topic::new_unchecked("te/factory/apps/custom/1/status/health"),
+            r#"{"status":"down"}"#,
+        );
+        supervisor.process(&health, start);
+
+        assert_eq!(
+            supervisor.tick(start + secs(5)),
+            vec![restart(
+                "custom-app",
+                &["/bin/systemctl", "restart", "custom-app"]
+            )]
+        );
+    }
+
+    #[test]
+    fn invalid_policies_are_rejected() {
+        let policies = toml::from_str::<RecoveryPolicies>(
+            r#"
+            [default]
+            max_retry = 3
+            "#,
+        );
+        assert!(policies.is_err());
+    }
+
+    fn supervisor(policies: &str) -> RecoverySupervisor {
+        RecoverySupervisor::new(
+            MqttSchema::default(),
+            EntityTopicId::default_main_device(),
+            toml::from_str(policies).unwrap(),
+            SystemConfig::default().init.restart,
+        )
+    }
+
+    fn health(service: &str, status: &str) -> MqttMessage {
+        MqttMessage::new(
+            &Topic::new_unchecked(&format!("te/device/main/service/{service}/status/health")),
+            format!(r#"{{"status":"{status}"}}"#),
+        )
+        .with_retain()
+    }
+
+    fn restart(service: &str, command: &[&str]) -> RecoveryAction {
+        RecoveryAction::Restart {
+            service: service.to_string(),
+            command: command.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+}
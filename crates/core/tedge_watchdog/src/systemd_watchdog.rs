@@ -1,4 +1,5 @@
 use anyhow::Context;
+use camino::Utf8Path;
 use freedesktop_entry_parser::parse_entry;
 use futures::channel::mpsc;
 use futures::stream::FuturesUnordered;
@@ -31,6 +32,8 @@ use tracing::info;
 use tracing::warn;
 
 use crate::error::WatchdogError;
+use crate::recovery::start_recovery_supervisor;
+use crate::recovery::RecoveryPolicies;
 
 const SERVICE_NAME: &str = "tedge-watchdog";
 
@@ -51,15 +54,31 @@ struct HealthStatusExt {
     pub time: Option<JsonValue>,
 }
 
-pub async fn start_watchdog(tedge_config: TEdgeConfig) -> Result<(), anyhow::Error> {
+pub async fn start_watchdog(
+    tedge_config: TEdgeConfig,
+    config_dir: &Utf8Path,
+) -> Result<(), anyhow::Error> {
+    let recovery_policies = RecoveryPolicies::load(config_dir)?;
+
     // Send ready notification to systemd.
     notify_systemd(process::id(), "--ready")?;
 
     // Send heart beat notifications to systemd, to notify about its own health status
     start_watchdog_for_self().await?;
 
-    // Monitor health of tedge services
-    start_watchdog_for_tedge_services(tedge_config).await;
+    let tedge_config = Arc::new(tedge_config);
+    match recovery_policies {
+        // Monitor health of tedge services, while recovering the services reported down
+        Some(policies) => {
+            let recovery = start_recovery_supervisor(&tedge_config, config_dir, policies);
+            let monitoring = start_watchdog_for_tedge_services(tedge_config.clone());
+            let (recovery, ()) = futures::future::join(recovery, monitoring).await;
+            recovery?;
+        }
+
+        // Monitor health of tedge services
+        None => start_watchdog_for_tedge_services(tedge_config).await,
+    }
     Ok(())
 }
 
@@ -91,7 +110,7 @@ async fn start_watchdog_for_self() -> Result<(), WatchdogError> {
     }
 }
 
-async fn start_watchdog_for_tedge_services(tedge_config: TEdgeConfig) {
+async fn start_watchdog_for_tedge_services(tedge_config: Arc<TEdgeConfig>) {
     let mqtt_topic_root = tedge_config.mqtt.topic_root.clone();
     let mqtt_schema = MqttSchema::with_root(mqtt_topic_root);
 
@@ -103,7 +122,9 @@ async fn start_watchdog_for_tedge_services(tedge_config: TEdgeConfig) {
         "tedge-mapper-c8y",
         "tedge-mapper-az",
         "tedge-mapper-aws",
+        "tedge-mapper-mqtt-cloud",
         "tedge-mapper-collectd",
+        "tedge-mapper-sparkplug",
         "tedge-agent",
         "c8y-firmware-plugin",
     ]
@@ -116,7 +137,6 @@ async fn start_watchdog_for_tedge_services(tedge_config: TEdgeConfig) {
     .collect::<Vec<_>>();
 
     let watchdog_tasks = FuturesUnordered::new();
-    let tedge_config = Arc::new(tedge_config);
 
     for service in tedge_services {
         let service_name = service.default_service_name().unwrap();
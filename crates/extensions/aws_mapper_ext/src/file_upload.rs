@@ -0,0 +1,511 @@
+//! Upload files staged on the file transfer service to Amazon S3
+//!
+//! AWS IoT Core provides no file upload API for devices.
+//! Hence, a presigned S3 URL is requested over MQTT to a cloud-side component
+//! (typically an AWS IoT rule triggering a Lambda function):
+//! - the request is published on `<prefix>/td/file-upload`, i.e. `thinedge/<device-id>/td/file-upload` on AWS,
+//! - the response is expected on `<prefix>/cmd/file-upload/<request-id>`,
+//!   i.e. `thinedge/<device-id>/cmd/file-upload/<request-id>` on AWS,
+//! - the file is then streamed from the file transfer service to the presigned URL.
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::time::Duration;
+use tedge_actors::Actor;
+use tedge_actors::Builder;
+use tedge_actors::ChannelError;
+use tedge_actors::ClientMessageBox;
+use tedge_actors::DynSender;
+use tedge_actors::MessageReceiver;
+use tedge_actors::MessageSink;
+use tedge_actors::MessageSource;
+use tedge_actors::NoConfig;
+use tedge_actors::RuntimeError;
+use tedge_actors::RuntimeRequest;
+use tedge_actors::RuntimeRequestSink;
+use tedge_actors::Sender;
+use tedge_actors::Service;
+use tedge_actors::SimpleMessageBox;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::commands::CommandStatus;
+use tedge_api::commands::FileUploadCmd;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::ChannelFilter;
+use tedge_api::mqtt_topics::EntityFilter;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_config::models::TopicPrefix;
+use tedge_http_ext::HttpError;
+use tedge_http_ext::HttpRequest;
+use tedge_http_ext::HttpRequestBuilder;
+use tedge_http_ext::HttpResponseExt;
+use tedge_http_ext::HttpResult;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::QoS;
+use tedge_mqtt_ext::Topic;
+use tedge_mqtt_ext::TopicFilter;
+use tokio::time::Instant;
+use tracing::error;
+use tracing::info;
+
+/// Configuration of the AWS file upload actor
+#[derive(Clone, Debug)]
+pub struct AwsFileUploadConfig {
+    pub mqtt_schema: MqttSchema,
+    pub topic_prefix: TopicPrefix,
+    /// How long to wait for a presigned URL
+    pub response_timeout: Duration,
+}
+
+impl AwsFileUploadConfig {
+    fn request_topic(&self) -> Topic {
+        Topic::new_unchecked(&format!("{}/td/file-upload", self.topic_prefix))
+    }
+
+    fn response_topic(&self, request_id: &str) -> String {
+        format!("{}/cmd/file-upload/{request_id}", self.topic_prefix)
+    }
+
+    fn response_topics(&self) -> TopicFilter {
+        TopicFilter::new_unchecked(&self.response_topic("+"))
+    }
+}
+
+/// Request for a presigned URL, published to AWS
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PresignedUrlRequest<'a> {
+    request_id: &'a str,
+    name: &'a str,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    file_type: Option<&'a str>,
+    mime_type: String,
+}
+
+/// Response to a presigned URL request
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PresignedUrlResponse {
+    url: Option<String>,
+    /// Where the file is stored, e.g. `s3://bucket/key`.
+    ///
+    /// If not provided, the presigned URL stripped of its query is used.
+    remote_url: Option<String>,
+    error: Option<String>,
+}
+
+pub struct AwsFileUploadActor {
+    config: AwsFileUploadConfig,
+    messages: SimpleMessageBox<MqttMessage, MqttMessage>,
+    /// Messages received while waiting for a presigned URL
+    pending_messages: VecDeque<MqttMessage>,
+    http: ClientMessageBox<HttpRequest, HttpResult>,
+}
+
+#[async_trait]
+impl Actor for AwsFileUploadActor {
+    fn name(&self) -> &str {
+        "AwsFileUploadActor"
+    }
+
+    async fn run(mut self) -> Result<(), RuntimeError> {
+        while let Some(message) = self.next_message().await {
+            let Some(command) = self.parse_init_command(&message) else {
+                continue;
+            };
+
+            let command = command.with_status(CommandStatus::Executing);
+            self.messages
+                .send(command.command_message(&self.config.mqtt_schema))
+                .await?;
+
+            let command = match self.upload(&command).await {
+                Ok(remote_url) => {
+                    info!("File {} uploaded to {remote_url}", command.payload.name);
+                    let mut command = command.with_status(CommandStatus::Successful);
+                    command.payload.remote_url = Some(remote_url);
+                    command
+                }
+                Err(FileUploadError::Channel(err)) => return Err(err.into()),
+                Err(err) => {
+                    error!("Failed to upload {}: {err}", command.payload.name);
+                    command.with_error(err.to_string())
+                }
+            };
+
+            // The command is cleared by the requester
+            self.messages
+                .send(command.command_message(&self.config.mqtt_schema))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl AwsFileUploadActor {
+    async fn next_message(&mut self) -> Option<MqttMessage> {
+        match self.pending_messages.pop_front() {
+            Some(message) => Some(message),
+            None => self.messages.recv().await,
+        }
+    }
+
+    /// Return the file upload command carried by this message, if to be processed by this mapper
+    fn parse_init_command(&self, message: &MqttMessage) -> Option<FileUploadCmd> {
+        let Ok((
+            target,
+            Channel::Command {
+                operation: OperationType::FileUpload,
+                cmd_id,
+            },
+        )) = self.config.mqtt_schema.entity_channel_of(&message.topic)
+        else {
+            return None;
+        };
+
+        match FileUploadCmd::try_from_bytes(target, cmd_id, message.payload_bytes()) {
+            Ok(Some(command))
+                if command.status() == CommandStatus::Init
+                    && command
+                        .payload
+                        .is_for_cloud(self.config.topic_prefix.as_ref()) =>
+            {
+                Some(command)
+            }
+            Ok(_) => None,
+            Err(err) => {
+                error!("Invalid file upload command: {err}");
+                None
+            }
+        }
+    }
+
+    async fn upload(&mut self, command: &FileUploadCmd) -> Result<String, FileUploadError> {
+        let (url, remote_url) = self.request_presigned_url(command).await?;
+
+        let request = HttpRequestBuilder::get(&command.payload.tedge_url).build()?;
+        let file = self
+            .http
+            .await_response(request)
+            .await?
+            .error_for_status()
+            .map_err(FileUploadError::FromFileTransferService)?;
+
+        let mut request = HttpRequestBuilder::put(&url)
+            .header("content-type", command.payload.mime_type_or_default());
+        if let Some(length) = file.response.headers().get("content-length") {
+            request = request.header("content-length", length.clone());
+        }
+        let request = request.body(file.response.into_body()).build()?;
+
+        self.http
+            .await_response(request)
+            .await?
+            .error_for_status()
+            .map_err(FileUploadError::ToS3)?;
+
+        Ok(remote_url.unwrap_or_else(|| strip_query(&url).to_string()))
+    }
+
+    async fn request_presigned_url(
+        &mut self,
+        command: &FileUploadCmd,
+    ) -> Result<(String, Option<String>), FileUploadError> {
+        let request_id = command.cmd_id.as_str();
+        let request = PresignedUrlRequest {
+            request_id,
+            name: &command.payload.name,
+            file_type: command.payload.file_type.as_deref(),
+            mime_type: command.payload.mime_type_or_default(),
+        };
+        let payload = serde_json::to_string(&request).expect("infallible serialization");
+        self.messages
+            .send(
+                MqttMessage::new(&self.config.request_topic(), payload).with_qos(QoS::AtLeastOnce),
+            )
+            .await?;
+
+        let response_topic = self.config.response_topic(request_id);
+        let deadline = Instant::now() + self.config.response_timeout;
+        loop {
+            let message = match tokio::time::timeout_at(deadline, self.messages.recv()).await {
+                Ok(Some(message)) => message,
+                Ok(None) => return Err(ChannelError::ReceiveError().into()),
+                Err(_) => return Err(FileUploadError::Timeout(self.config.response_timeout)),
+            };
+            if message.topic.name != response_topic {
+                // Stale responses are ignored, while commands are processed later
+                if !self.config.response_topics().accept(&message) {
+                    self.pending_messages.push_back(message);
+                }
+                continue;
+            }
+
+            let response: PresignedUrlResponse = serde_json::from_slice(message.payload_bytes())
+                .map_err(FileUploadError::InvalidResponse)?;
+            return match (response.url, response.error) {
+                (_, Some(error)) => Err(FileUploadError::Rejected(error)),
+                (Some(url), None) => Ok((url, response.remote_url)),
+                (None, None) => Err(FileUploadError::Rejected(
+                    "no presigned URL provided".to_string(),
+                )),
+            };
+        }
+    }
+}
+
+fn strip_query(url: &str) -> &str {
+    url.split_once('?').map_or(url, |(url, _)| url)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum FileUploadError {
+    #[error(transparent)]
+    Http(#[from] HttpError),
+
+    #[error(transparent)]
+    Channel(#[from] ChannelError),
+
+    #[error("No presigned URL received after {0:?}")]
+    Timeout(Duration),
+
+    #[error("Invalid presigned URL response: {0}")]
+    InvalidResponse(serde_json::Error),
+
+    #[error("Presigned URL request rejected: {0}")]
+    Rejected(String),
+
+    #[error("Failed to get the file from the file transfer service: {0}")]
+    FromFileTransferService(HttpError),
+
+    #[error("Failed to upload the file to S3: {0}")]
+    ToS3(HttpError),
+}
+
+pub struct AwsFileUploadActorBuilder {
+    config: AwsFileUploadConfig,
+    box_builder: SimpleMessageBoxBuilder<MqttMessage, MqttMessage>,
+    http: ClientMessageBox<HttpRequest, HttpResult>,
+}
+
+impl AwsFileUploadActorBuilder {
+    /// Create an actor uploading files to S3
+    ///
+    /// `http` is used to get the files from the file transfer service and to push them to S3.
+    pub fn new(
+        config: AwsFileUploadConfig,
+        mqtt: &mut (impl MessageSource<MqttMessage, TopicFilter> + MessageSink<MqttMessage>),
+        http: &mut impl Service<HttpRequest, HttpResult>,
+    ) -> Self {
+        let mut box_builder = SimpleMessageBoxBuilder::new("AwsFileUpload", 16);
+        let mut topics = config.mqtt_schema.topics(
+            EntityFilter::AnyEntity,
+            ChannelFilter::Command(OperationType::FileUpload),
+        );
+        topics.add_all(config.response_topics());
+        box_builder.connect_source(topics, mqtt);
+        box_builder.connect_sink(NoConfig, mqtt);
+        AwsFileUploadActorBuilder {
+            config,
+            box_builder,
+            http: ClientMessageBox::new(http),
+        }
+    }
+}
+
+impl RuntimeRequestSink for AwsFileUploadActorBuilder {
+    fn get_signal_sender(&self) -> DynSender<RuntimeRequest> {
+        self.box_builder.get_signal_sender()
+    }
+}
+
+impl Builder<AwsFileUploadActor> for AwsFileUploadActorBuilder {
+    type Error = Infallible;
+
+    fn try_build(self) -> Result<AwsFileUploadActor, Self::Error> {
+        Ok(self.build())
+    }
+
+    fn build(self) -> AwsFileUploadActor {
+        AwsFileUploadActor {
+            config: self.config,
+            messages: self.box_builder.build(),
+            pending_messages: VecDeque::new(),
+            http: self.http,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tedge_actors::test_helpers::FakeServerBox;
+    use tedge_actors::test_helpers::FakeServerBoxBuilder;
+    use tedge_actors::test_helpers::MessageReceiverExt;
+    use tedge_actors::test_helpers::TimedMessageBox;
+    use tedge_http_ext::test_helpers::HttpResponseBuilder;
+
+    const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+    type MqttBox = TimedMessageBox<SimpleMessageBox<MqttMessage, MqttMessage>>;
+    type HttpBox = TimedMessageBox<FakeServerBox<HttpRequest, HttpResult>>;
+
+    #[tokio::test]
+    async fn file_is_uploaded_to_a_presigned_url() {
+        let (mut mqtt, mut http) = spawn_file_upload_actor(TEST_TIMEOUT);
+
+        mqtt.send(file_upload_request(json!({
+            "status": "init",
+            "tedgeUrl": "http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234",
+            "name": "report.pdf",
+            "type": "report",
+        })))
+        .await
+        .unwrap();
+        assert_command_eq(
+            mqtt.recv().await,
+            json!({
+                "status": "executing",
+                "tedgeUrl": "http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234",
+                "name": "report.pdf",
+                "type": "report",
+            }),
+        );
+
+        let request = mqtt.recv().await.unwrap();
+        assert_eq!(request.topic.name, "aws/td/file-upload");
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(request.payload_bytes()).unwrap(),
+            json!({
+                "requestId": "1234",
+                "name": "report.pdf",
+                "type": "report",
+                "mimeType": "application/octet-stream",
+            })
+        );
+        mqtt.send(MqttMessage::new(
+            &Topic::new_unchecked("aws/cmd/file-upload/1234"),
+            json!({
+                "url": "https://my-bucket.s3.amazonaws.com/my-device/report.pdf?X-Amz-Signature=secret"
+            })
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+
+        let request = http.recv().await.unwrap();
+        assert_eq!(
+            request.uri(),
+            "http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234"
+        );
+        http.send(HttpResponseBuilder::new().json("some content").build())
+            .await
+            .unwrap();
+
+        let request = http.recv().await.unwrap();
+        assert_eq!(request.method(), "PUT");
+        assert_eq!(
+            request.uri(),
+            "https://my-bucket.s3.amazonaws.com/my-device/report.pdf?X-Amz-Signature=secret"
+        );
+        http.send(HttpResponseBuilder::new().build()).await.unwrap();
+
+        assert_command_eq(
+            mqtt.recv().await,
+            json!({
+                "status": "successful",
+                "tedgeUrl": "http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234",
+                "name": "report.pdf",
+                "type": "report",
+                "remoteUrl": "https://my-bucket.s3.amazonaws.com/my-device/report.pdf"
+            }),
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_presigned_url_request_fails_the_command() {
+        let (mut mqtt, _http) = spawn_file_upload_actor(TEST_TIMEOUT);
+
+        mqtt.send(file_upload_request(json!({
+            "status": "init",
+            "tedgeUrl": "http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234",
+            "name": "report.pdf",
+        })))
+        .await
+        .unwrap();
+        mqtt.skip(2).await;
+
+        mqtt.send(MqttMessage::new(
+            &Topic::new_unchecked("aws/cmd/file-upload/1234"),
+            json!({"error": "Access denied"}).to_string(),
+        ))
+        .await
+        .unwrap();
+
+        assert_command_eq(
+            mqtt.recv().await,
+            json!({
+                "status": "failed",
+                "reason": "Presigned URL request rejected: Access denied",
+                "tedgeUrl": "http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234",
+                "name": "report.pdf",
+            }),
+        );
+    }
+
+    #[tokio::test]
+    async fn file_upload_fails_when_no_presigned_url_is_received() {
+        let (mut mqtt, _http) = spawn_file_upload_actor(Duration::from_millis(100));
+
+        mqtt.send(file_upload_request(json!({
+            "status": "init",
+            "tedgeUrl": "http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234",
+            "name": "report.pdf",
+        })))
+        .await
+        .unwrap();
+        mqtt.skip(2).await;
+
+        assert_command_eq(
+            mqtt.recv().await,
+            json!({
+                "status": "failed",
+                "reason": "No presigned URL received after 100ms",
+                "tedgeUrl": "http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234",
+                "name": "report.pdf",
+            }),
+        );
+    }
+
+    fn file_upload_request(payload: serde_json::Value) -> MqttMessage {
+        let topic = Topic::new_unchecked("te/device/main///cmd/file_upload/1234");
+        MqttMessage::new(&topic, payload.to_string()).with_retain()
+    }
+
+    fn assert_command_eq(message: Option<MqttMessage>, expected: serde_json::Value) {
+        let message = message.expect("a command state update");
+        assert_eq!(message.topic.name, "te/device/main///cmd/file_upload/1234");
+        let payload: serde_json::Value = serde_json::from_slice(message.payload_bytes()).unwrap();
+        assert_eq!(payload, expected);
+    }
+
+    fn spawn_file_upload_actor(response_timeout: Duration) -> (MqttBox, HttpBox) {
+        let config = AwsFileUploadConfig {
+            mqtt_schema: MqttSchema::default(),
+            topic_prefix: "aws".try_into().unwrap(),
+            response_timeout,
+        };
+        let mut mqtt = SimpleMessageBoxBuilder::new("MQTT", 16);
+        let mut http = FakeServerBoxBuilder::default();
+        let actor = AwsFileUploadActorBuilder::new(config, &mut mqtt, &mut http).build();
+        tokio::spawn(actor.run());
+
+        (
+            mqtt.build().with_timeout(TEST_TIMEOUT),
+            http.build().with_timeout(TEST_TIMEOUT),
+        )
+    }
+}
@@ -0,0 +1,604 @@
+//! Execution of AWS IoT Jobs as thin-edge commands
+//!
+//! - The next pending job execution of the thing, notified by AWS on `<prefix>/jobs/notify-next`,
+//!   is translated into a thin-edge command, the job document providing the operation,
+//!   the target entity and the command parameters.
+//! - The state transitions of the command, as driven by the operation workflow,
+//!   are reported to AWS as `IN_PROGRESS`, and its final state as `SUCCEEDED` or `FAILED`.
+//! - On each (re)connection of the bridge, the next pending job is requested,
+//!   so the jobs queued while the device was disconnected are not missed.
+//!
+//! The job id is kept in the id of the command (`<prefix>-job-<job-id>`),
+//! so the job execution can be updated even if the mapper is restarted while the command is executed.
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use tedge_actors::Actor;
+use tedge_actors::Builder;
+use tedge_actors::DynSender;
+use tedge_actors::MessageReceiver;
+use tedge_actors::MessageSink;
+use tedge_actors::MessageSource;
+use tedge_actors::NoConfig;
+use tedge_actors::RuntimeError;
+use tedge_actors::RuntimeRequest;
+use tedge_actors::RuntimeRequestSink;
+use tedge_actors::Sender;
+use tedge_actors::SimpleMessageBox;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::health::HealthStatus;
+use tedge_api::health::Status;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::ChannelFilter;
+use tedge_api::mqtt_topics::EntityFilter;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_api::workflow::GenericCommandState;
+use tedge_config::models::TopicPrefix;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::QoS;
+use tedge_mqtt_ext::Topic;
+use tedge_mqtt_ext::TopicFilter;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+/// The job document property giving the operation to execute
+const OPERATION_PROPERTY: &str = "operation";
+
+/// The job document property giving the entity on which the operation is executed
+const TARGET_PROPERTY: &str = "target";
+
+/// Configuration of the AWS jobs actor
+#[derive(Clone, Debug)]
+pub struct AwsJobsConfig {
+    pub mqtt_schema: MqttSchema,
+    pub topic_prefix: TopicPrefix,
+    /// The main device, i.e. the thing connected to AWS
+    pub device_topic_id: EntityTopicId,
+    /// The health topic of the bridge, used to detect reconnects
+    pub bridge_health_topic: Topic,
+}
+
+impl AwsJobsConfig {
+    fn next_job_topics(&self) -> TopicFilter {
+        let prefix = &self.topic_prefix;
+        let mut topics = TopicFilter::new_unchecked(&format!("{prefix}/jobs/notify-next"));
+        topics.add_unchecked(&format!("{prefix}/jobs/$next/get/accepted"));
+        topics
+    }
+
+    fn rejected_update_topics(&self) -> TopicFilter {
+        TopicFilter::new_unchecked(&format!("{}/jobs/+/update/rejected", self.topic_prefix))
+    }
+
+    fn get_next_job_topic(&self) -> Topic {
+        Topic::new_unchecked(&format!("{}/jobs/$next/get", self.topic_prefix))
+    }
+
+    fn update_job_topic(&self, job_id: &str) -> Topic {
+        Topic::new_unchecked(&format!("{}/jobs/{job_id}/update", self.topic_prefix))
+    }
+
+    fn local_command_topics(&self) -> TopicFilter {
+        self.mqtt_schema
+            .topics(EntityFilter::AnyEntity, ChannelFilter::AnyCommand)
+    }
+
+    fn command_id_prefix(&self) -> String {
+        format!("{}-job-", self.topic_prefix)
+    }
+}
+
+/// The next pending job execution, as notified by AWS
+#[derive(Debug, Deserialize)]
+struct NextJobExecution {
+    execution: Option<JobExecution>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JobExecution {
+    job_id: String,
+    status: String,
+    #[serde(default)]
+    job_document: Value,
+}
+
+pub struct AwsJobsActor {
+    config: AwsJobsConfig,
+    messages: SimpleMessageBox<MqttMessage, MqttMessage>,
+    /// The last command status reported for each job being executed
+    reported: HashMap<String, String>,
+    bridge_up: bool,
+}
+
+#[async_trait]
+impl Actor for AwsJobsActor {
+    fn name(&self) -> &str {
+        "AwsJobsActor"
+    }
+
+    async fn run(mut self) -> Result<(), RuntimeError> {
+        while let Some(message) = self.messages.recv().await {
+            for output in self.process(&message) {
+                self.messages.send(output).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AwsJobsActor {
+    fn process(&mut self, message: &MqttMessage) -> Vec<MqttMessage> {
+        if message.topic == self.config.bridge_health_topic {
+            return self.process_bridge_health(message);
+        }
+        if self.config.next_job_topics().accept(message) {
+            return self.process_next_job(message);
+        }
+        if self.config.rejected_update_topics().accept(message) {
+            error!(
+                "AWS rejected a job execution update on {}: {}",
+                message.topic.name,
+                message.payload_str().unwrap_or_default()
+            );
+            return vec![];
+        }
+        if self.config.local_command_topics().accept(message) {
+            return self.process_command_update(message);
+        }
+        vec![]
+    }
+
+    /// On each bridge reconnection, request the next pending job
+    fn process_bridge_health(&mut self, message: &MqttMessage) -> Vec<MqttMessage> {
+        let Ok(health) =
+            HealthStatus::try_from_health_status_message(message, &self.config.mqtt_schema)
+        else {
+            return vec![];
+        };
+        let was_up = self.bridge_up;
+        self.bridge_up = health.status == Status::Up;
+        if was_up || !self.bridge_up {
+            return vec![];
+        }
+        vec![MqttMessage::new(&self.config.get_next_job_topic(), "{}").with_qos(QoS::AtLeastOnce)]
+    }
+
+    /// Translate a queued job execution into a thin-edge command
+    ///
+    /// e.g. the job `job-42` with the document `{"operation":"restart"}`
+    /// triggers `te/device/main///cmd/restart/aws-job-job-42`
+    fn process_next_job(&mut self, message: &MqttMessage) -> Vec<MqttMessage> {
+        let next: NextJobExecution = match serde_json::from_slice(message.payload_bytes()) {
+            Ok(next) => next,
+            Err(err) => {
+                error!("Invalid job execution received from AWS: {err}");
+                return vec![];
+            }
+        };
+        let Some(execution) = next.execution else {
+            return vec![];
+        };
+        if !is_valid_id(&execution.job_id) {
+            warn!("Ignoring job with an invalid id: {}", execution.job_id);
+            return vec![];
+        }
+        // A job already in progress has been started by a previous run of the mapper,
+        // and is tracked from the retained state of the corresponding command
+        if execution.status != "QUEUED" || self.reported.contains_key(&execution.job_id) {
+            return vec![];
+        }
+
+        let job_id = execution.job_id;
+        match self.job_command(&job_id, execution.job_document) {
+            Ok(command) => {
+                info!("Executing AWS job {job_id} as {}", command.command_topic());
+                vec![command.into_message()]
+            }
+            Err(reason) => {
+                warn!("Rejecting AWS job {job_id}: {reason}");
+                vec![self.job_update(
+                    &job_id,
+                    "FAILED",
+                    json!({"state": "failed", "reason": reason}),
+                )]
+            }
+        }
+    }
+
+    /// Build the init state of the command requested by a job document
+    ///
+    /// The job document must provide the `operation` and, optionally, the `target` entity;
+    /// the remaining properties being the command parameters.
+    fn job_command(&self, job_id: &str, document: Value) -> Result<GenericCommandState, String> {
+        let Value::Object(mut parameters) = document else {
+            return Err("The job document must be a JSON object".to_string());
+        };
+        let operation = match parameters.remove(OPERATION_PROPERTY) {
+            Some(Value::String(operation)) if is_valid_id(&operation) => operation,
+            _ => {
+                return Err(format!(
+                    "Missing or invalid `{OPERATION_PROPERTY}` property"
+                ))
+            }
+        };
+        let target = match parameters.remove(TARGET_PROPERTY) {
+            None => self.config.device_topic_id.clone(),
+            Some(Value::String(target)) => target
+                .parse()
+                .map_err(|_| format!("Invalid `{TARGET_PROPERTY}` entity: {target}"))?,
+            Some(target) => return Err(format!("Invalid `{TARGET_PROPERTY}` entity: {target}")),
+        };
+
+        let topic = self.config.mqtt_schema.topic_for(
+            &target,
+            &Channel::Command {
+                operation: OperationType::from(operation.as_str()),
+                cmd_id: format!("{}{job_id}", self.config.command_id_prefix()),
+            },
+        );
+        Ok(GenericCommandState::new(
+            topic,
+            "init".to_string(),
+            Value::Object(parameters),
+        ))
+    }
+
+    /// Report the state transitions of a command triggered by a job as job execution updates
+    fn process_command_update(&mut self, message: &MqttMessage) -> Vec<MqttMessage> {
+        let Ok((_, Channel::Command { operation, cmd_id })) =
+            self.config.mqtt_schema.entity_channel_of(&message.topic)
+        else {
+            return vec![];
+        };
+        let Some(job_id) = cmd_id.strip_prefix(&self.config.command_id_prefix()) else {
+            return vec![];
+        };
+        if message.payload_bytes().is_empty() {
+            return vec![];
+        }
+        let command = match GenericCommandState::from_command_message(message) {
+            Ok(command) => command,
+            Err(err) => {
+                warn!(
+                    "Ignoring invalid command state on {}: {err}",
+                    message.topic.name
+                );
+                return vec![];
+            }
+        };
+
+        let mut details = Map::new();
+        details.insert(OPERATION_PROPERTY.to_string(), operation.to_string().into());
+        details.insert("state".to_string(), command.status.clone().into());
+
+        if command.is_successful() || command.is_failed() {
+            self.reported.remove(job_id);
+            let status = if command.is_successful() {
+                "SUCCEEDED"
+            } else {
+                let reason = command.failure_reason().unwrap_or("Unknown reason");
+                details.insert("reason".to_string(), reason.into());
+                "FAILED"
+            };
+            let update = self.job_update(job_id, status, Value::Object(details));
+            return vec![update, command.clear().into_message()];
+        }
+
+        if self.reported.get(job_id) == Some(&command.status) {
+            return vec![];
+        }
+        self.reported
+            .insert(job_id.to_string(), command.status.clone());
+        vec![self.job_update(job_id, "IN_PROGRESS", Value::Object(details))]
+    }
+
+    fn job_update(&self, job_id: &str, status: &str, details: Value) -> MqttMessage {
+        MqttMessage::new(
+            &self.config.update_job_topic(job_id),
+            json!({"status": status, "statusDetails": details}).to_string(),
+        )
+        .with_qos(QoS::AtLeastOnce)
+    }
+}
+
+/// Check that a job id or an operation name can be used in a topic name
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+pub struct AwsJobsActorBuilder {
+    config: AwsJobsConfig,
+    box_builder: SimpleMessageBoxBuilder<MqttMessage, MqttMessage>,
+}
+
+impl AwsJobsActorBuilder {
+    pub fn new(
+        config: AwsJobsConfig,
+        mqtt: &mut (impl MessageSource<MqttMessage, TopicFilter> + MessageSink<MqttMessage>),
+    ) -> Self {
+        let mut box_builder = SimpleMessageBoxBuilder::new("AwsJobs", 16);
+        let mut topics = config.next_job_topics();
+        topics.add_all(config.rejected_update_topics());
+        topics.add_all(config.local_command_topics());
+        topics.add_all(config.bridge_health_topic.clone().into());
+        box_builder.connect_source(topics, mqtt);
+        box_builder.connect_sink(NoConfig, mqtt);
+        AwsJobsActorBuilder {
+            config,
+            box_builder,
+        }
+    }
+}
+
+impl RuntimeRequestSink for AwsJobsActorBuilder {
+    fn get_signal_sender(&self) -> DynSender<RuntimeRequest> {
+        self.box_builder.get_signal_sender()
+    }
+}
+
+impl Builder<AwsJobsActor> for AwsJobsActorBuilder {
+    type Error = Infallible;
+
+    fn try_build(self) -> Result<AwsJobsActor, Self::Error> {
+        Ok(self.build())
+    }
+
+    fn build(self) -> AwsJobsActor {
+        AwsJobsActor {
+            config: self.config,
+            messages: self.box_builder.build(),
+            reported: HashMap::new(),
+            bridge_up: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tedge_actors::test_helpers::MessageReceiverExt;
+    use tedge_actors::test_helpers::TimedMessageBox;
+
+    const TEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+    type MqttBox = TimedMessageBox<SimpleMessageBox<MqttMessage, MqttMessage>>;
+
+    #[tokio::test]
+    async fn queued_jobs_are_translated_into_commands() {
+        let mut mqtt = spawn_jobs_actor();
+
+        mqtt.send(next_job(
+            "job-1",
+            "QUEUED",
+            json!({"operation": "software_update", "updateList": []}),
+        ))
+        .await
+        .unwrap();
+        let command = mqtt.recv().await.expect("a command");
+        assert_eq!(
+            command.topic.name,
+            "te/device/main///cmd/software_update/aws-job-job-1"
+        );
+        assert!(command.retain);
+        assert_payload(&command, json!({"status": "init", "updateList": []}));
+
+        mqtt.send(next_job(
+            "job-2",
+            "QUEUED",
+            json!({"operation": "restart", "target": "device/child1//"}),
+        ))
+        .await
+        .unwrap();
+        let command = mqtt.recv().await.expect("a command");
+        assert_eq!(
+            command.topic.name,
+            "te/device/child1///cmd/restart/aws-job-job-2"
+        );
+        assert_payload(&command, json!({"status": "init"}));
+
+        // Jobs already in progress are not started twice
+        mqtt.send(next_job(
+            "job-3",
+            "IN_PROGRESS",
+            json!({"operation": "restart"}),
+        ))
+        .await
+        .unwrap();
+        assert!(mqtt.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn command_transitions_are_reported_as_job_execution_updates() {
+        let mut mqtt = spawn_jobs_actor();
+
+        mqtt.send(command_message(
+            "te/device/main///cmd/firmware_update/aws-job-fw-7",
+            json!({"status": "init", "name": "core", "version": "2.0"}),
+        ))
+        .await
+        .unwrap();
+        let update = mqtt.recv().await.expect("a job update");
+        assert_eq!(update.topic.name, "aws/jobs/fw-7/update");
+        assert_payload(
+            &update,
+            json!({"status": "IN_PROGRESS", "statusDetails": {"operation": "firmware_update", "state": "init"}}),
+        );
+
+        // The same state is not reported twice
+        mqtt.send(command_message(
+            "te/device/main///cmd/firmware_update/aws-job-fw-7",
+            json!({"status": "init", "name": "core", "version": "2.0"}),
+        ))
+        .await
+        .unwrap();
+        mqtt.send(command_message(
+            "te/device/main///cmd/firmware_update/aws-job-fw-7",
+            json!({"status": "downloading", "name": "core", "version": "2.0"}),
+        ))
+        .await
+        .unwrap();
+        let update = mqtt.recv().await.expect("a job update");
+        assert_payload(
+            &update,
+            json!({"status": "IN_PROGRESS", "statusDetails": {"operation": "firmware_update", "state": "downloading"}}),
+        );
+
+        // Commands not triggered by a job are ignored
+        mqtt.send(command_message(
+            "te/device/main///cmd/firmware_update/c8y-mapper-7",
+            json!({"status": "successful"}),
+        ))
+        .await
+        .unwrap();
+
+        mqtt.send(command_message(
+            "te/device/main///cmd/firmware_update/aws-job-fw-7",
+            json!({"status": "failed", "reason": "Checksum mismatch"}),
+        ))
+        .await
+        .unwrap();
+        let update = mqtt.recv().await.expect("a job update");
+        assert_eq!(update.topic.name, "aws/jobs/fw-7/update");
+        assert_payload(
+            &update,
+            json!({"status": "FAILED", "statusDetails": {
+                "operation": "firmware_update",
+                "state": "failed",
+                "reason": "Checksum mismatch"
+            }}),
+        );
+        let clear = mqtt.recv().await.expect("a clear message");
+        assert_eq!(
+            clear.topic.name,
+            "te/device/main///cmd/firmware_update/aws-job-fw-7"
+        );
+        assert!(clear.retain);
+        assert!(clear.payload_bytes().is_empty());
+
+        mqtt.send(command_message(
+            "te/device/child1///cmd/restart/aws-job-8",
+            json!({"status": "successful"}),
+        ))
+        .await
+        .unwrap();
+        let update = mqtt.recv().await.expect("a job update");
+        assert_eq!(update.topic.name, "aws/jobs/8/update");
+        assert_payload(
+            &update,
+            json!({"status": "SUCCEEDED", "statusDetails": {"operation": "restart", "state": "successful"}}),
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_job_documents_are_rejected() {
+        let mut mqtt = spawn_jobs_actor();
+
+        mqtt.send(next_job("job-4", "QUEUED", json!({"updateList": []})))
+            .await
+            .unwrap();
+        let update = mqtt.recv().await.expect("a job update");
+        assert_eq!(update.topic.name, "aws/jobs/job-4/update");
+        assert_payload(
+            &update,
+            json!({"status": "FAILED", "statusDetails": {
+                "state": "failed",
+                "reason": "Missing or invalid `operation` property"
+            }}),
+        );
+    }
+
+    #[tokio::test]
+    async fn next_job_is_requested_on_bridge_reconnect() {
+        let mut mqtt = spawn_jobs_actor();
+
+        mqtt.send(bridge_health("up")).await.unwrap();
+        let request = mqtt.recv().await.expect("a request");
+        assert_eq!(request.topic.name, "aws/jobs/$next/get");
+
+        mqtt.send(bridge_health("up")).await.unwrap();
+        assert!(mqtt.recv().await.is_none());
+
+        mqtt.send(bridge_health("down")).await.unwrap();
+        mqtt.send(bridge_health("up")).await.unwrap();
+        let request = mqtt.recv().await.expect("a request");
+        assert_eq!(request.topic.name, "aws/jobs/$next/get");
+
+        mqtt.send(MqttMessage::new(
+            &Topic::new_unchecked("aws/jobs/$next/get/accepted"),
+            json!({"execution": {
+                "jobId": "job-5",
+                "status": "QUEUED",
+                "jobDocument": {"operation": "restart"}
+            }})
+            .to_string(),
+        ))
+        .await
+        .unwrap();
+        let command = mqtt.recv().await.expect("a command");
+        assert_eq!(
+            command.topic.name,
+            "te/device/main///cmd/restart/aws-job-job-5"
+        );
+    }
+
+    fn next_job(job_id: &str, status: &str, document: Value) -> MqttMessage {
+        MqttMessage::new(
+            &Topic::new_unchecked("aws/jobs/notify-next"),
+            json!({
+                "timestamp": 1700000000,
+                "execution": {
+                    "jobId": job_id,
+                    "status": status,
+                    "queuedAt": 1700000000,
+                    "versionNumber": 1,
+                    "executionNumber": 1,
+                    "jobDocument": document
+                }
+            })
+            .to_string(),
+        )
+    }
+
+    fn command_message(topic: &str, payload: Value) -> MqttMessage {
+        MqttMessage::new(&Topic::new_unchecked(topic), payload.to_string()).with_retain()
+    }
+
+    fn bridge_health(status: &str) -> MqttMessage {
+        MqttMessage::new(
+            &Topic::new_unchecked("te/device/main/service/tedge-mapper-bridge-aws/status/health"),
+            json!({"status": status}).to_string(),
+        )
+    }
+
+    fn assert_payload(message: &MqttMessage, expected: Value) {
+        let payload: Value = serde_json::from_slice(message.payload_bytes()).unwrap();
+        assert_eq!(payload, expected);
+    }
+
+    fn spawn_jobs_actor() -> MqttBox {
+        let config = AwsJobsConfig {
+            mqtt_schema: MqttSchema::default(),
+            topic_prefix: "aws".try_into().unwrap(),
+            device_topic_id: EntityTopicId::default_main_device(),
+            bridge_health_topic: Topic::new_unchecked(
+                "te/device/main/service/tedge-mapper-bridge-aws/status/health",
+            ),
+        };
+        let mut mqtt = SimpleMessageBoxBuilder::new("MQTT", 16);
+        let actor = AwsJobsActorBuilder::new(config, &mut mqtt).build();
+        tokio::spawn(actor.run());
+        mqtt.build().with_timeout(TEST_TIMEOUT)
+    }
+}
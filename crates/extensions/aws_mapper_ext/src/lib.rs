@@ -1,3 +1,7 @@
+pub mod file_upload;
+pub mod jobs;
+pub mod shadow;
+
 use camino::Utf8Path;
 use std::time::SystemTime;
 use tedge_api::mqtt_topics::MqttSchema;
@@ -0,0 +1,737 @@
+//! Synchronization of the thin-edge twin data with AWS IoT device shadows
+//!
+//! - The twin fragments of the main device are reported on the classic shadow of the thing,
+//!   and the twin fragments of the child devices and services on named shadows,
+//!   the name of a shadow being the entity topic id with `:` as separator (e.g. `device:child1`).
+//! - The delta updates are published on the local twin topics,
+//!   except the `configUpdate` property which triggers a `config_update` command.
+//! - The version of the last delta applied on each shadow is persisted,
+//!   so outdated or duplicated deltas are ignored, even after a reconnect or a restart.
+//! - On each (re)connection of the bridge, the full reported state is sent to AWS
+//!   and the shadows are fetched to catch up with the changes made while disconnected.
+use async_trait::async_trait;
+use camino::Utf8PathBuf;
+use serde::Deserialize;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use tedge_actors::Actor;
+use tedge_actors::Builder;
+use tedge_actors::DynSender;
+use tedge_actors::MessageReceiver;
+use tedge_actors::MessageSink;
+use tedge_actors::MessageSource;
+use tedge_actors::NoConfig;
+use tedge_actors::RuntimeError;
+use tedge_actors::RuntimeRequest;
+use tedge_actors::RuntimeRequestSink;
+use tedge_actors::Sender;
+use tedge_actors::SimpleMessageBox;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::commands::CommandStatus;
+use tedge_api::commands::ConfigUpdateCmd;
+use tedge_api::commands::ConfigUpdateCmdPayload;
+use tedge_api::health::HealthStatus;
+use tedge_api::health::Status;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::ChannelFilter;
+use tedge_api::mqtt_topics::EntityFilter;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_config::models::TopicPrefix;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::QoS;
+use tedge_mqtt_ext::Topic;
+use tedge_mqtt_ext::TopicFilter;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+/// The desired property used to trigger a configuration update
+const CONFIG_UPDATE_PROPERTY: &str = "configUpdate";
+
+/// The file where the versions of the last applied deltas are persisted
+const DELTA_VERSIONS_FILE: &str = "shadow-delta-versions.json";
+
+/// Configuration of the AWS shadow actor
+#[derive(Clone, Debug)]
+pub struct AwsShadowConfig {
+    pub mqtt_schema: MqttSchema,
+    pub topic_prefix: TopicPrefix,
+    /// The main device, i.e. the thing connected to AWS
+    pub device_topic_id: EntityTopicId,
+    /// The health topic of the bridge, used to detect reconnects
+    pub bridge_health_topic: Topic,
+    /// Where the versions of the last applied deltas are persisted
+    pub state_dir: Utf8PathBuf,
+}
+
+/// A device shadow: either the classic shadow of the thing or a named shadow
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Shadow {
+    Classic,
+    Named(String),
+}
+
+impl Shadow {
+    /// The key used to persist the version of the shadow
+    fn key(&self) -> &str {
+        match self {
+            Shadow::Classic => "",
+            Shadow::Named(name) => name,
+        }
+    }
+}
+
+impl AwsShadowConfig {
+    fn shadow_topic(&self, shadow: &Shadow, action: &str) -> Topic {
+        let topic = match shadow {
+            Shadow::Classic => format!("{}/shadow/{action}", self.topic_prefix),
+            Shadow::Named(name) => format!("{}/shadow/name/{name}/{action}", self.topic_prefix),
+        };
+        Topic::new_unchecked(&topic)
+    }
+
+    fn shadow_response_topics(&self) -> TopicFilter {
+        let prefix = &self.topic_prefix;
+        let mut topics = TopicFilter::empty();
+        for action in [
+            "update/delta",
+            "update/rejected",
+            "get/accepted",
+            "get/rejected",
+        ] {
+            topics.add_unchecked(&format!("{prefix}/shadow/{action}"));
+            topics.add_unchecked(&format!("{prefix}/shadow/name/+/{action}"));
+        }
+        topics
+    }
+
+    fn local_twin_topics(&self) -> TopicFilter {
+        self.mqtt_schema
+            .topics(EntityFilter::AnyEntity, ChannelFilter::EntityTwinData)
+    }
+
+    /// The shadow where the twin data of an entity are reported
+    fn shadow_of(&self, entity: &EntityTopicId) -> Option<Shadow> {
+        if entity == &self.device_topic_id {
+            return Some(Shadow::Classic);
+        }
+        let name = entity.as_str().trim_end_matches('/').replace('/', ":");
+        let is_valid = !name.is_empty()
+            && name.len() <= 64
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == ':' || c == '_' || c == '-');
+        is_valid.then_some(Shadow::Named(name))
+    }
+
+    /// The entity which twin data are reported on a shadow
+    fn entity_of(&self, shadow: &Shadow) -> Option<EntityTopicId> {
+        match shadow {
+            Shadow::Classic => Some(self.device_topic_id.clone()),
+            Shadow::Named(name) => {
+                let mut segments: Vec<&str> = name.split(':').collect();
+                if segments.len() > 4 {
+                    return None;
+                }
+                segments.resize(4, "");
+                segments.join("/").parse().ok()
+            }
+        }
+    }
+
+    /// Extract the shadow and the action of a shadow response topic
+    ///
+    /// e.g. `aws/shadow/name/device:child1/update/delta` is a delta for the `device:child1` shadow
+    fn parse_shadow_topic<'a>(&self, topic: &'a Topic) -> Option<(Shadow, &'a str)> {
+        let response = topic
+            .name
+            .strip_prefix(self.topic_prefix.as_str())?
+            .strip_prefix("/shadow/")?;
+        match response.strip_prefix("name/") {
+            None => Some((Shadow::Classic, response)),
+            Some(named) => {
+                let (name, action) = named.split_once('/')?;
+                Some((Shadow::Named(name.to_string()), action))
+            }
+        }
+    }
+}
+
+/// A delta update or a shadow document
+#[derive(Debug, Deserialize)]
+struct ShadowMessage {
+    version: Option<i64>,
+    #[serde(default)]
+    state: Value,
+}
+
+pub struct AwsShadowActor {
+    config: AwsShadowConfig,
+    messages: SimpleMessageBox<MqttMessage, MqttMessage>,
+    /// The fragments reported to AWS, per shadow
+    reported: BTreeMap<Shadow, Map<String, Value>>,
+    /// The version of the last delta applied on each shadow
+    delta_versions: BTreeMap<String, i64>,
+    bridge_up: bool,
+}
+
+#[async_trait]
+impl Actor for AwsShadowActor {
+    fn name(&self) -> &str {
+        "AwsShadowActor"
+    }
+
+    async fn run(mut self) -> Result<(), RuntimeError> {
+        while let Some(message) = self.messages.recv().await {
+            for output in self.process(&message) {
+                self.messages.send(output).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AwsShadowActor {
+    fn process(&mut self, message: &MqttMessage) -> Vec<MqttMessage> {
+        if message.topic == self.config.bridge_health_topic {
+            return self.process_bridge_health(message);
+        }
+        if self.config.local_twin_topics().accept(message) {
+            return self.process_local_twin_fragment(message);
+        }
+        if self.config.shadow_response_topics().accept(message) {
+            return self.process_shadow_response(message);
+        }
+        vec![]
+    }
+
+    /// On each bridge reconnection, send the full reported state and fetch the shadows
+    fn process_bridge_health(&mut self, message: &MqttMessage) -> Vec<MqttMessage> {
+        let Ok(health) =
+            HealthStatus::try_from_health_status_message(message, &self.config.mqtt_schema)
+        else {
+            return vec![];
+        };
+        let was_up = self.bridge_up;
+        self.bridge_up = health.status == Status::Up;
+        if was_up || !self.bridge_up {
+            return vec![];
+        }
+
+        info!("Synchronizing the device shadows with AWS");
+        let mut shadows: Vec<Shadow> = self.reported.keys().cloned().collect();
+        if !shadows.contains(&Shadow::Classic) {
+            shadows.insert(0, Shadow::Classic);
+        }
+        let mut messages = vec![];
+        for shadow in shadows {
+            if let Some(reported) = self.reported.get(&shadow) {
+                messages.push(self.reported_state_message(&shadow, reported.clone()));
+            }
+            messages.push(
+                MqttMessage::new(&self.config.shadow_topic(&shadow, "get"), "")
+                    .with_qos(QoS::AtLeastOnce),
+            );
+        }
+        messages
+    }
+
+    fn process_local_twin_fragment(&mut self, message: &MqttMessage) -> Vec<MqttMessage> {
+        let Ok((entity, Channel::EntityTwinData { fragment_key })) =
+            self.config.mqtt_schema.entity_channel_of(&message.topic)
+        else {
+            return vec![];
+        };
+        let Some(shadow) = self.config.shadow_of(&entity) else {
+            warn!("Ignoring twin data of {entity}: no AWS shadow can be named after this entity");
+            return vec![];
+        };
+
+        let value = if message.payload_bytes().is_empty() {
+            // AWS removes a reported property when set to null
+            Value::Null
+        } else {
+            match serde_json::from_slice(message.payload_bytes()) {
+                Ok(value) => value,
+                Err(err) => {
+                    warn!(
+                        "Ignoring invalid twin data on {}: {err}",
+                        message.topic.name
+                    );
+                    return vec![];
+                }
+            }
+        };
+
+        let reported = self.reported.entry(shadow.clone()).or_default();
+        if value.is_null() {
+            if reported.remove(&fragment_key).is_none() {
+                return vec![];
+            }
+        } else if reported.get(&fragment_key) == Some(&value) {
+            return vec![];
+        } else {
+            reported.insert(fragment_key.clone(), value.clone());
+        }
+
+        let mut update = Map::new();
+        update.insert(fragment_key, value);
+        vec![self.reported_state_message(&shadow, update)]
+    }
+
+    fn process_shadow_response(&mut self, message: &MqttMessage) -> Vec<MqttMessage> {
+        let Some((shadow, action)) = self.config.parse_shadow_topic(&message.topic) else {
+            return vec![];
+        };
+        if action.ends_with("rejected") {
+            error!(
+                "AWS rejected a shadow request on {}: {}",
+                message.topic.name,
+                message.payload_str().unwrap_or_default()
+            );
+            return vec![];
+        }
+
+        let shadow_message: ShadowMessage = match serde_json::from_slice(message.payload_bytes()) {
+            Ok(shadow_message) => shadow_message,
+            Err(err) => {
+                error!("Invalid shadow document received from AWS: {err}");
+                return vec![];
+            }
+        };
+        let delta = match action {
+            // A delta message carries the delta as state
+            "update/delta" => shadow_message.state,
+            // A shadow document carries the delta, if any, along the desired and reported states
+            "get/accepted" => shadow_message
+                .state
+                .get("delta")
+                .cloned()
+                .unwrap_or(Value::Null),
+            _ => return vec![],
+        };
+        let Value::Object(delta) = delta else {
+            return vec![];
+        };
+        self.apply_delta(shadow, delta, shadow_message.version)
+    }
+
+    /// Apply the delta of a shadow if more recent than the last applied version
+    fn apply_delta(
+        &mut self,
+        shadow: Shadow,
+        delta: Map<String, Value>,
+        version: Option<i64>,
+    ) -> Vec<MqttMessage> {
+        if let (Some(version), Some(applied)) = (version, self.delta_versions.get(shadow.key())) {
+            if version <= *applied {
+                return vec![];
+            }
+        }
+        let Some(entity) = self.config.entity_of(&shadow) else {
+            warn!("Ignoring delta of the {shadow:?} shadow: not related to any entity");
+            return vec![];
+        };
+
+        let mut messages = vec![];
+        let mut acknowledged = Map::new();
+        for (key, value) in delta {
+            if key == CONFIG_UPDATE_PROPERTY {
+                match self.config_update_command(&entity, &shadow, &value, version) {
+                    Some(command) => {
+                        messages.push(command);
+                        // Report the request as acknowledged, so the delta is not applied twice
+                        acknowledged.insert(key, value);
+                    }
+                    None => {
+                        warn!("Ignoring invalid {CONFIG_UPDATE_PROPERTY} desired property: {value}")
+                    }
+                }
+                continue;
+            }
+            let topic = self
+                .config
+                .mqtt_schema
+                .topic_for(&entity, &Channel::EntityTwinData { fragment_key: key });
+            let payload = if value.is_null() {
+                String::new()
+            } else {
+                value.to_string()
+            };
+            messages.push(
+                MqttMessage::new(&topic, payload)
+                    .with_retain()
+                    .with_qos(QoS::AtLeastOnce),
+            );
+        }
+        if !acknowledged.is_empty() {
+            messages.push(self.reported_state_message(&shadow, acknowledged));
+        }
+
+        if let Some(version) = version {
+            self.delta_versions
+                .insert(shadow.key().to_string(), version);
+            persist_delta_versions(&self.config.state_dir, &self.delta_versions);
+        }
+        messages
+    }
+
+    fn config_update_command(
+        &self,
+        entity: &EntityTopicId,
+        shadow: &Shadow,
+        value: &Value,
+        version: Option<i64>,
+    ) -> Option<MqttMessage> {
+        let config_type = value.get("type")?.as_str()?;
+        let url = value.get("url")?.as_str()?;
+        let shadow_id = match shadow {
+            Shadow::Classic => "classic".to_string(),
+            Shadow::Named(name) => name.replace(':', "_"),
+        };
+        let command = ConfigUpdateCmd {
+            target: entity.clone(),
+            cmd_id: format!(
+                "{}-shadow-{shadow_id}-{}",
+                self.config.topic_prefix,
+                version.unwrap_or_default()
+            ),
+            payload: ConfigUpdateCmdPayload {
+                status: CommandStatus::Init,
+                tedge_url: None,
+                remote_url: url.to_string(),
+                server_url: url.to_string(),
+                config_type: config_type.to_string(),
+                path: None,
+                log_path: None,
+            },
+        };
+        Some(command.command_message(&self.config.mqtt_schema))
+    }
+
+    fn reported_state_message(&self, shadow: &Shadow, reported: Map<String, Value>) -> MqttMessage {
+        MqttMessage::new(
+            &self.config.shadow_topic(shadow, "update"),
+            json!({"state": {"reported": reported}}).to_string(),
+        )
+        .with_qos(QoS::AtLeastOnce)
+    }
+}
+
+fn load_delta_versions(state_dir: &Utf8PathBuf) -> BTreeMap<String, i64> {
+    let path = state_dir.join(DELTA_VERSIONS_FILE);
+    std::fs::read(path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+fn persist_delta_versions(state_dir: &Utf8PathBuf, versions: &BTreeMap<String, i64>) {
+    let path = state_dir.join(DELTA_VERSIONS_FILE);
+    let persisted = std::fs::create_dir_all(state_dir)
+        .and_then(|()| std::fs::write(&path, json!(versions).to_string()));
+    if let Err(err) = persisted {
+        error!("Failed to persist the device shadow versions into {path}: {err}");
+    }
+}
+
+pub struct AwsShadowActorBuilder {
+    config: AwsShadowConfig,
+    box_builder: SimpleMessageBoxBuilder<MqttMessage, MqttMessage>,
+}
+
+impl AwsShadowActorBuilder {
+    pub fn new(
+        config: AwsShadowConfig,
+        mqtt: &mut (impl MessageSource<MqttMessage, TopicFilter> + MessageSink<MqttMessage>),
+    ) -> Self {
+        let mut box_builder = SimpleMessageBoxBuilder::new("AwsShadow", 16);
+        let mut topics = config.local_twin_topics();
+        topics.add_all(config.shadow_response_topics());
+        topics.add_all(config.bridge_health_topic.clone().into());
+        box_builder.connect_source(topics, mqtt);
+        box_builder.connect_sink(NoConfig, mqtt);
+        AwsShadowActorBuilder {
+            config,
+            box_builder,
+        }
+    }
+}
+
+impl RuntimeRequestSink for AwsShadowActorBuilder {
+    fn get_signal_sender(&self) -> DynSender<RuntimeRequest> {
+        self.box_builder.get_signal_sender()
+    }
+}
+
+impl Builder<AwsShadowActor> for AwsShadowActorBuilder {
+    type Error = Infallible;
+
+    fn try_build(self) -> Result<AwsShadowActor, Self::Error> {
+        Ok(self.build())
+    }
+
+    fn build(self) -> AwsShadowActor {
+        let delta_versions = load_delta_versions(&self.config.state_dir);
+        AwsShadowActor {
+            config: self.config,
+            messages: self.box_builder.build(),
+            reported: BTreeMap::new(),
+            delta_versions,
+            bridge_up: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tedge_actors::test_helpers::MessageReceiverExt;
+    use tedge_actors::test_helpers::TimedMessageBox;
+    use tempfile::TempDir;
+
+    const TEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+    type MqttBox = TimedMessageBox<SimpleMessageBox<MqttMessage, MqttMessage>>;
+
+    #[tokio::test]
+    async fn twin_fragments_are_reported_on_classic_and_named_shadows() {
+        let state_dir = TempDir::new().unwrap();
+        let mut mqtt = spawn_shadow_actor(&state_dir);
+
+        mqtt.send(mqtt_message(
+            "te/device/main///twin/os",
+            r#"{"family":"Debian"}"#,
+        ))
+        .await
+        .unwrap();
+        assert_message(
+            mqtt.recv().await,
+            "aws/shadow/update",
+            json!({"state": {"reported": {"os": {"family": "Debian"}}}}),
+        );
+
+        mqtt.send(mqtt_message("te/device/child1///twin/firmware", r#""1.2""#))
+            .await
+            .unwrap();
+        assert_message(
+            mqtt.recv().await,
+            "aws/shadow/name/device:child1/update",
+            json!({"state": {"reported": {"firmware": "1.2"}}}),
+        );
+
+        mqtt.send(mqtt_message(
+            "te/device/main/service/collector/twin/interval",
+            "10",
+        ))
+        .await
+        .unwrap();
+        assert_message(
+            mqtt.recv().await,
+            "aws/shadow/name/device:main:service:collector/update",
+            json!({"state": {"reported": {"interval": 10}}}),
+        );
+
+        // Unchanged fragments are not sent again, while removed ones are reported as null
+        mqtt.send(mqtt_message("te/device/child1///twin/firmware", r#""1.2""#))
+            .await
+            .unwrap();
+        mqtt.send(mqtt_message("te/device/child1///twin/firmware", ""))
+            .await
+            .unwrap();
+        assert_message(
+            mqtt.recv().await,
+            "aws/shadow/name/device:child1/update",
+            json!({"state": {"reported": {"firmware": null}}}),
+        );
+    }
+
+    #[tokio::test]
+    async fn deltas_are_published_on_local_twin_topics() {
+        let state_dir = TempDir::new().unwrap();
+        let mut mqtt = spawn_shadow_actor(&state_dir);
+
+        mqtt.send(mqtt_message(
+            "aws/shadow/name/device:child1/update/delta",
+            r#"{"version":5,"timestamp":1700000000,"state":{"interval":30}}"#,
+        ))
+        .await
+        .unwrap();
+        let message = mqtt.recv().await.unwrap();
+        assert_eq!(message.topic.name, "te/device/child1///twin/interval");
+        assert_eq!(message.payload_str().unwrap(), "30");
+        assert!(message.retain);
+
+        // Outdated deltas are ignored
+        mqtt.send(mqtt_message(
+            "aws/shadow/name/device:child1/update/delta",
+            r#"{"version":4,"timestamp":1700000000,"state":{"interval":10}}"#,
+        ))
+        .await
+        .unwrap();
+        assert!(mqtt.recv().await.is_none());
+
+        // Versions are tracked per shadow
+        mqtt.send(mqtt_message(
+            "aws/shadow/update/delta",
+            r#"{"version":2,"timestamp":1700000000,"state":{"interval":10}}"#,
+        ))
+        .await
+        .unwrap();
+        let message = mqtt.recv().await.unwrap();
+        assert_eq!(message.topic.name, "te/device/main///twin/interval");
+
+        let versions: Value = serde_json::from_slice(
+            &std::fs::read(state_dir.path().join(DELTA_VERSIONS_FILE)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(versions, json!({"": 2, "device:child1": 5}));
+    }
+
+    #[tokio::test]
+    async fn shadows_are_synchronized_on_bridge_reconnect() {
+        let state_dir = TempDir::new().unwrap();
+        std::fs::write(
+            state_dir.path().join(DELTA_VERSIONS_FILE),
+            r#"{"device:child1": 7}"#,
+        )
+        .unwrap();
+        let mut mqtt = spawn_shadow_actor(&state_dir);
+
+        mqtt.send(mqtt_message("te/device/child1///twin/firmware", r#""1.2""#))
+            .await
+            .unwrap();
+        mqtt.skip(1).await;
+
+        mqtt.send(bridge_health("up")).await.unwrap();
+        let request = mqtt.recv().await.unwrap();
+        assert_eq!(request.topic.name, "aws/shadow/get");
+        assert_message(
+            mqtt.recv().await,
+            "aws/shadow/name/device:child1/update",
+            json!({"state": {"reported": {"firmware": "1.2"}}}),
+        );
+        let request = mqtt.recv().await.unwrap();
+        assert_eq!(request.topic.name, "aws/shadow/name/device:child1/get");
+
+        // Deltas already applied before the restart are ignored
+        mqtt.send(mqtt_message(
+            "aws/shadow/name/device:child1/get/accepted",
+            r#"{"version":7,"state":{"desired":{"firmware":"1.3"},"reported":{"firmware":"1.2"},"delta":{"firmware":"1.3"}}}"#,
+        ))
+        .await
+        .unwrap();
+        assert!(mqtt.recv().await.is_none());
+
+        mqtt.send(mqtt_message(
+            "aws/shadow/get/accepted",
+            r#"{"version":3,"state":{"desired":{"interval":60},"delta":{"interval":60}}}"#,
+        ))
+        .await
+        .unwrap();
+        let message = mqtt.recv().await.unwrap();
+        assert_eq!(message.topic.name, "te/device/main///twin/interval");
+        assert_eq!(message.payload_str().unwrap(), "60");
+
+        // Nothing is sent until the bridge reconnects
+        mqtt.send(bridge_health("up")).await.unwrap();
+        assert!(mqtt.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn config_update_delta_triggers_a_config_update_command() {
+        let state_dir = TempDir::new().unwrap();
+        let mut mqtt = spawn_shadow_actor(&state_dir);
+
+        mqtt.send(mqtt_message(
+            "aws/shadow/name/device:child1/update/delta",
+            r#"{"version":9,"state":{"configUpdate":{"type":"collectd","url":"https://example.com/collectd.conf"}}}"#,
+        ))
+        .await
+        .unwrap();
+        assert_message(
+            mqtt.recv().await,
+            "te/device/child1///cmd/config_update/aws-shadow-device_child1-9",
+            json!({
+                "status": "init",
+                "type": "collectd",
+                "remoteUrl": "https://example.com/collectd.conf",
+                "serverUrl": "https://example.com/collectd.conf",
+            }),
+        );
+        assert_message(
+            mqtt.recv().await,
+            "aws/shadow/name/device:child1/update",
+            json!({"state": {"reported": {"configUpdate": {
+                "type": "collectd",
+                "url": "https://example.com/collectd.conf"
+            }}}}),
+        );
+    }
+
+    #[test]
+    fn shadow_names_are_derived_from_entity_topic_ids() {
+        let config = shadow_config(Utf8PathBuf::from("/tmp"));
+        for (entity, shadow) in [
+            ("device/main//", Shadow::Classic),
+            (
+                "device/child1//",
+                Shadow::Named("device:child1".to_string()),
+            ),
+            (
+                "device/main/service/tedge-agent",
+                Shadow::Named("device:main:service:tedge-agent".to_string()),
+            ),
+        ] {
+            let entity: EntityTopicId = entity.parse().unwrap();
+            assert_eq!(config.shadow_of(&entity), Some(shadow.clone()));
+            assert_eq!(config.entity_of(&shadow), Some(entity));
+        }
+
+        let invalid: EntityTopicId = "device/child.1//".parse().unwrap();
+        assert_eq!(config.shadow_of(&invalid), None);
+    }
+
+    fn mqtt_message(topic: &str, payload: &str) -> MqttMessage {
+        MqttMessage::new(&Topic::new_unchecked(topic), payload)
+    }
+
+    fn bridge_health(status: &str) -> MqttMessage {
+        mqtt_message(
+            "te/device/main/service/tedge-mapper-bridge-aws/status/health",
+            &json!({"status": status}).to_string(),
+        )
+    }
+
+    fn assert_message(message: Option<MqttMessage>, topic: &str, expected: Value) {
+        let message = message.expect("a message");
+        assert_eq!(message.topic.name, topic);
+        let payload: Value = serde_json::from_slice(message.payload_bytes()).unwrap();
+        assert_eq!(payload, expected);
+    }
+
+    fn shadow_config(state_dir: Utf8PathBuf) -> AwsShadowConfig {
+        AwsShadowConfig {
+            mqtt_schema: MqttSchema::default(),
+            topic_prefix: "aws".try_into().unwrap(),
+            device_topic_id: EntityTopicId::default_main_device(),
+            bridge_health_topic: Topic::new_unchecked(
+                "te/device/main/service/tedge-mapper-bridge-aws/status/health",
+            ),
+            state_dir,
+        }
+    }
+
+    fn spawn_shadow_actor(state_dir: &TempDir) -> MqttBox {
+        let config = shadow_config(state_dir.path().to_path_buf().try_into().unwrap());
+        let mut mqtt = SimpleMessageBoxBuilder::new("MQTT", 16);
+        let actor = AwsShadowActorBuilder::new(config, &mut mqtt).build();
+        tokio::spawn(actor.run());
+        mqtt.build().with_timeout(TEST_TIMEOUT)
+    }
+}
@@ -0,0 +1,565 @@
+//! Upload files staged on the file transfer service to Azure Blob Storage
+//!
+//! A `file_upload` command is processed along the file upload workflow of Azure IoT Hub:
+//! - a SAS URI is requested to the IoT hub,
+//! - the file is streamed from the file transfer service to the blob storage,
+//! - the IoT hub is notified of the outcome so it can release the associated resources.
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde::Serialize;
+use std::convert::Infallible;
+use tedge_actors::Actor;
+use tedge_actors::Builder;
+use tedge_actors::ChannelError;
+use tedge_actors::ClientMessageBox;
+use tedge_actors::DynSender;
+use tedge_actors::MessageReceiver;
+use tedge_actors::MessageSink;
+use tedge_actors::MessageSource;
+use tedge_actors::NoConfig;
+use tedge_actors::RuntimeError;
+use tedge_actors::RuntimeRequest;
+use tedge_actors::RuntimeRequestSink;
+use tedge_actors::Sender;
+use tedge_actors::Service;
+use tedge_actors::SimpleMessageBox;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::commands::CommandStatus;
+use tedge_api::commands::FileUploadCmd;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::ChannelFilter;
+use tedge_api::mqtt_topics::EntityFilter;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_config::models::TopicPrefix;
+use tedge_http_ext::HttpError;
+use tedge_http_ext::HttpRequest;
+use tedge_http_ext::HttpRequestBuilder;
+use tedge_http_ext::HttpResponseExt;
+use tedge_http_ext::HttpResult;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::TopicFilter;
+use tracing::error;
+use tracing::info;
+
+const IOT_HUB_API_VERSION: &str = "2021-04-12";
+
+/// Configuration of the Azure file upload actor
+#[derive(Clone, Debug)]
+pub struct AzFileUploadConfig {
+    pub mqtt_schema: MqttSchema,
+    pub topic_prefix: TopicPrefix,
+    /// Host name of the IoT hub, as in `tedge config get az.url`
+    pub iot_hub_host: String,
+    pub device_id: String,
+}
+
+/// Response of the IoT hub to a file upload SAS URI request
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileUploadSasUri {
+    correlation_id: String,
+    host_name: String,
+    container_name: String,
+    blob_name: String,
+    sas_token: String,
+}
+
+impl FileUploadSasUri {
+    fn blob_url(&self) -> String {
+        format!(
+            "https://{}/{}/{}",
+            self.host_name, self.container_name, self.blob_name
+        )
+    }
+}
+
+/// Notification sent to the IoT hub when a file upload completes
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileUploadNotification<'a> {
+    correlation_id: &'a str,
+    is_success: bool,
+    status_code: u16,
+    status_description: String,
+}
+
+pub struct AzFileUploadActor {
+    config: AzFileUploadConfig,
+    messages: SimpleMessageBox<MqttMessage, MqttMessage>,
+    local_http: ClientMessageBox<HttpRequest, HttpResult>,
+    cloud_http: ClientMessageBox<HttpRequest, HttpResult>,
+}
+
+#[async_trait]
+impl Actor for AzFileUploadActor {
+    fn name(&self) -> &str {
+        "AzFileUploadActor"
+    }
+
+    async fn run(mut self) -> Result<(), RuntimeError> {
+        while let Some(message) = self.messages.recv().await {
+            let Some(command) = self.parse_init_command(&message) else {
+                continue;
+            };
+
+            let command = command.with_status(CommandStatus::Executing);
+            self.messages
+                .send(command.command_message(&self.config.mqtt_schema))
+                .await?;
+
+            let command = match self.upload(&command).await {
+                Ok(remote_url) => {
+                    info!("File {} uploaded to {remote_url}", command.payload.name);
+                    let mut command = command.with_status(CommandStatus::Successful);
+                    command.payload.remote_url = Some(remote_url);
+                    command
+                }
+                Err(FileUploadError::Channel(err)) => return Err(err.into()),
+                Err(err) => {
+                    error!("Failed to upload {}: {err}", command.payload.name);
+                    command.with_error(err.to_string())
+                }
+            };
+
+            // The command is cleared by the requester
+            self.messages
+                .send(command.command_message(&self.config.mqtt_schema))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl AzFileUploadActor {
+    /// Return the file upload command carried by this message, if to be processed by this mapper
+    fn parse_init_command(&self, message: &MqttMessage) -> Option<FileUploadCmd> {
+        let Ok((
+            target,
+            Channel::Command {
+                operation: OperationType::FileUpload,
+                cmd_id,
+            },
+        )) = self.config.mqtt_schema.entity_channel_of(&message.topic)
+        else {
+            return None;
+        };
+
+        match FileUploadCmd::try_from_bytes(target, cmd_id, message.payload_bytes()) {
+            Ok(Some(command))
+                if command.status() == CommandStatus::Init
+                    && command
+                        .payload
+                        .is_for_cloud(self.config.topic_prefix.as_ref()) =>
+            {
+                Some(command)
+            }
+            Ok(_) => None,
+            Err(err) => {
+                error!("Invalid file upload command: {err}");
+                None
+            }
+        }
+    }
+
+    async fn upload(&mut self, command: &FileUploadCmd) -> Result<String, FileUploadError> {
+        let sas_uri = self.request_sas_uri(&command.payload.name).await?;
+
+        let upload_result = self.transfer(command, &sas_uri).await;
+        let notification = match &upload_result {
+            Ok(()) => FileUploadNotification {
+                correlation_id: &sas_uri.correlation_id,
+                is_success: true,
+                status_code: 200,
+                status_description: "File uploaded".to_string(),
+            },
+            Err(err) => FileUploadNotification {
+                correlation_id: &sas_uri.correlation_id,
+                is_success: false,
+                status_code: 500,
+                status_description: err.to_string(),
+            },
+        };
+        if let Err(err) = self.notify_completion(notification).await {
+            error!("Failed to notify the IoT hub of a file upload completion: {err}");
+        }
+
+        upload_result.map(|()| sas_uri.blob_url())
+    }
+
+    async fn request_sas_uri(
+        &mut self,
+        blob_name: &str,
+    ) -> Result<FileUploadSasUri, FileUploadError> {
+        let url = format!(
+            "https://{}/devices/{}/files?api-version={IOT_HUB_API_VERSION}",
+            self.config.iot_hub_host, self.config.device_id
+        );
+        let request = HttpRequestBuilder::post(url)
+            .json(&serde_json::json!({ "blobName": blob_name }))
+            .build()?;
+        self.cloud_http
+            .await_response(request)
+            .await?
+            .error_for_status()
+            .json()
+            .await
+            .map_err(FileUploadError::FromIotHub)
+    }
+
+    async fn transfer(
+        &mut self,
+        command: &FileUploadCmd,
+        sas_uri: &FileUploadSasUri,
+    ) -> Result<(), FileUploadError> {
+        let request = HttpRequestBuilder::get(&command.payload.tedge_url).build()?;
+        let file = self
+            .local_http
+            .await_response(request)
+            .await?
+            .error_for_status()
+            .map_err(FileUploadError::FromFileTransferService)?;
+
+        let mut request =
+            HttpRequestBuilder::put(format!("{}{}", sas_uri.blob_url(), sas_uri.sas_token))
+                .header("x-ms-blob-type", "BlockBlob")
+                .header("content-type", command.payload.mime_type_or_default());
+        if let Some(length) = file.response.headers().get("content-length") {
+            request = request.header("content-length", length.clone());
+        }
+        let request = request.body(file.response.into_body()).build()?;
+
+        self.cloud_http
+            .await_response(request)
+            .await?
+            .error_for_status()
+            .map_err(FileUploadError::ToBlobStorage)?;
+        Ok(())
+    }
+
+    async fn notify_completion(
+        &mut self,
+        notification: FileUploadNotification<'_>,
+    ) -> Result<(), FileUploadError> {
+        let url = format!(
+            "https://{}/devices/{}/files/notifications?api-version={IOT_HUB_API_VERSION}",
+            self.config.iot_hub_host, self.config.device_id
+        );
+        let request = HttpRequestBuilder::post(url).json(&notification).build()?;
+        self.cloud_http
+            .await_response(request)
+            .await?
+            .error_for_status()
+            .map_err(FileUploadError::FromIotHub)?;
+        Ok(())
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum FileUploadError {
+    #[error(transparent)]
+    Http(#[from] HttpError),
+
+    #[error(transparent)]
+    Channel(#[from] ChannelError),
+
+    #[error("Failed to interact with the IoT hub: {0}")]
+    FromIotHub(HttpError),
+
+    #[error("Failed to get the file from the file transfer service: {0}")]
+    FromFileTransferService(HttpError),
+
+    #[error("Failed to upload the file to the Azure blob storage: {0}")]
+    ToBlobStorage(HttpError),
+}
+
+pub struct AzFileUploadActorBuilder {
+    config: AzFileUploadConfig,
+    box_builder: SimpleMessageBoxBuilder<MqttMessage, MqttMessage>,
+    local_http: ClientMessageBox<HttpRequest, HttpResult>,
+    cloud_http: ClientMessageBox<HttpRequest, HttpResult>,
+}
+
+impl AzFileUploadActorBuilder {
+    /// Create an actor uploading files to Azure
+    ///
+    /// - `local_http` is used to get the files from the file transfer service
+    /// - `cloud_http` is used to interact with the IoT hub and the blob storage,
+    ///   hence must be configured with the device certificate.
+    pub fn new(
+        config: AzFileUploadConfig,
+        mqtt: &mut (impl MessageSource<MqttMessage, TopicFilter> + MessageSink<MqttMessage>),
+        local_http: &mut impl Service<HttpRequest, HttpResult>,
+        cloud_http: &mut impl Service<HttpRequest, HttpResult>,
+    ) -> Self {
+        let mut box_builder = SimpleMessageBoxBuilder::new("AzFileUpload", 16);
+        let topics = config.mqtt_schema.topics(
+            EntityFilter::AnyEntity,
+            ChannelFilter::Command(OperationType::FileUpload),
+        );
+        box_builder.connect_source(topics, mqtt);
+        box_builder.connect_sink(NoConfig, mqtt);
+        AzFileUploadActorBuilder {
+            config,
+            box_builder,
+            local_http: ClientMessageBox::new(local_http),
+            cloud_http: ClientMessageBox::new(cloud_http),
+        }
+    }
+}
+
+impl RuntimeRequestSink for AzFileUploadActorBuilder {
+    fn get_signal_sender(&self) -> DynSender<RuntimeRequest> {
+        self.box_builder.get_signal_sender()
+    }
+}
+
+impl Builder<AzFileUploadActor> for AzFileUploadActorBuilder {
+    type Error = Infallible;
+
+    fn try_build(self) -> Result<AzFileUploadActor, Self::Error> {
+        Ok(self.build())
+    }
+
+    fn build(self) -> AzFileUploadActor {
+        AzFileUploadActor {
+            config: self.config,
+            messages: self.box_builder.build(),
+            local_http: self.local_http,
+            cloud_http: self.cloud_http,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::Duration;
+    use tedge_actors::test_helpers::FakeServerBox;
+    use tedge_actors::test_helpers::FakeServerBoxBuilder;
+    use tedge_actors::test_helpers::MessageReceiverExt;
+    use tedge_actors::test_helpers::TimedMessageBox;
+    use tedge_http_ext::test_helpers::HttpRequestExt;
+    use tedge_http_ext::test_helpers::HttpResponseBuilder;
+    use tedge_mqtt_ext::Topic;
+
+    const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+    type HttpBox = TimedMessageBox<FakeServerBox<HttpRequest, HttpResult>>;
+
+    #[tokio::test]
+    async fn file_is_uploaded_to_the_blob_storage() {
+        let (mut mqtt, mut local_http, mut cloud_http) = spawn_file_upload_actor();
+
+        mqtt.send(file_upload_request(
+            "1234",
+            json!({
+                "status": "init",
+                "tedgeUrl": "http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234",
+                "name": "report.pdf",
+                "mimeType": "application/pdf",
+            }),
+        ))
+        .await
+        .unwrap();
+        assert_command_eq(
+            mqtt.recv().await,
+            json!({
+                "status": "executing",
+                "tedgeUrl": "http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234",
+                "name": "report.pdf",
+                "mimeType": "application/pdf",
+            }),
+        );
+
+        let request = cloud_http.recv().await.unwrap();
+        assert_eq!(
+            request.uri(),
+            "https://my-hub.azure-devices.net/devices/my-device/files?api-version=2021-04-12"
+        );
+        assert_eq!(
+            request.json::<serde_json::Value>().await.unwrap(),
+            json!({"blobName": "report.pdf"})
+        );
+        cloud_http
+            .send(
+                HttpResponseBuilder::new()
+                    .json(&json!({
+                        "correlationId": "some-correlation-id",
+                        "hostName": "my-storage.blob.core.windows.net",
+                        "containerName": "uploads",
+                        "blobName": "my-device/report.pdf",
+                        "sasToken": "?sig=secret"
+                    }))
+                    .build(),
+            )
+            .await
+            .unwrap();
+
+        let request = local_http.recv().await.unwrap();
+        assert_eq!(
+            request.uri(),
+            "http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234"
+        );
+        local_http
+            .send(HttpResponseBuilder::new().json("some content").build())
+            .await
+            .unwrap();
+
+        let request = cloud_http.recv().await.unwrap();
+        assert_eq!(request.method(), "PUT");
+        assert_eq!(
+            request.uri(),
+            "https://my-storage.blob.core.windows.net/uploads/my-device/report.pdf?sig=secret"
+        );
+        assert_eq!(request.headers()["x-ms-blob-type"], "BlockBlob");
+        assert_eq!(request.headers()["content-type"], "application/pdf");
+        cloud_http
+            .send(HttpResponseBuilder::new().status(201).build())
+            .await
+            .unwrap();
+
+        let request = cloud_http.recv().await.unwrap();
+        assert_eq!(
+            request.uri(),
+            "https://my-hub.azure-devices.net/devices/my-device/files/notifications?api-version=2021-04-12"
+        );
+        assert_eq!(
+            request.json::<serde_json::Value>().await.unwrap(),
+            json!({
+                "correlationId": "some-correlation-id",
+                "isSuccess": true,
+                "statusCode": 200,
+                "statusDescription": "File uploaded"
+            })
+        );
+        cloud_http
+            .send(HttpResponseBuilder::new().status(204).build())
+            .await
+            .unwrap();
+
+        assert_command_eq(
+            mqtt.recv().await,
+            json!({
+                "status": "successful",
+                "tedgeUrl": "http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234",
+                "name": "report.pdf",
+                "mimeType": "application/pdf",
+                "remoteUrl": "https://my-storage.blob.core.windows.net/uploads/my-device/report.pdf"
+            }),
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_transfer_is_notified_to_the_iot_hub() {
+        let (mut mqtt, mut local_http, mut cloud_http) = spawn_file_upload_actor();
+
+        mqtt.send(file_upload_request(
+            "1234",
+            json!({
+                "status": "init",
+                "tedgeUrl": "http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234",
+                "name": "report.pdf",
+            }),
+        ))
+        .await
+        .unwrap();
+        mqtt.skip(1).await;
+
+        cloud_http.recv().await.unwrap();
+        cloud_http
+            .send(
+                HttpResponseBuilder::new()
+                    .json(&json!({
+                        "correlationId": "some-correlation-id",
+                        "hostName": "my-storage.blob.core.windows.net",
+                        "containerName": "uploads",
+                        "blobName": "my-device/report.pdf",
+                        "sasToken": "?sig=secret"
+                    }))
+                    .build(),
+            )
+            .await
+            .unwrap();
+
+        local_http.recv().await.unwrap();
+        local_http
+            .send(HttpResponseBuilder::new().status(404).build())
+            .await
+            .unwrap();
+
+        let request = cloud_http.recv().await.unwrap();
+        let notification = request.json::<serde_json::Value>().await.unwrap();
+        assert_eq!(notification["isSuccess"], json!(false));
+        cloud_http
+            .send(HttpResponseBuilder::new().status(204).build())
+            .await
+            .unwrap();
+
+        let response = mqtt.recv().await.unwrap();
+        let response: serde_json::Value = serde_json::from_slice(response.payload_bytes()).unwrap();
+        assert_eq!(response["status"], json!("failed"));
+        assert!(response["reason"]
+            .as_str()
+            .unwrap()
+            .starts_with("Failed to get the file from the file transfer service"));
+    }
+
+    #[tokio::test]
+    async fn file_upload_request_for_another_cloud_is_ignored() {
+        let (mut mqtt, _local_http, _cloud_http) = spawn_file_upload_actor();
+
+        mqtt.send(file_upload_request(
+            "1234",
+            json!({
+                "status": "init",
+                "tedgeUrl": "http://127.0.0.1:8000/te/v1/files/main/file_upload/report.pdf-1234",
+                "name": "report.pdf",
+                "cloud": "c8y",
+            }),
+        ))
+        .await
+        .unwrap();
+
+        assert!(mqtt.recv().await.is_none());
+    }
+
+    fn assert_command_eq(message: Option<MqttMessage>, expected: serde_json::Value) {
+        let message = message.expect("a command state update");
+        assert_eq!(message.topic.name, "te/device/main///cmd/file_upload/1234");
+        let payload: serde_json::Value = serde_json::from_slice(message.payload_bytes()).unwrap();
+        assert_eq!(payload, expected);
+    }
+
+    fn file_upload_request(cmd_id: &str, payload: serde_json::Value) -> MqttMessage {
+        let topic = Topic::new_unchecked(&format!("te/device/main///cmd/file_upload/{cmd_id}"));
+        MqttMessage::new(&topic, payload.to_string()).with_retain()
+    }
+
+    fn spawn_file_upload_actor() -> (
+        TimedMessageBox<SimpleMessageBox<MqttMessage, MqttMessage>>,
+        HttpBox,
+        HttpBox,
+    ) {
+        let config = AzFileUploadConfig {
+            mqtt_schema: MqttSchema::default(),
+            topic_prefix: "az".try_into().unwrap(),
+            iot_hub_host: "my-hub.azure-devices.net".to_string(),
+            device_id: "my-device".to_string(),
+        };
+        let mut mqtt = SimpleMessageBoxBuilder::new("MQTT", 16);
+        let mut local_http = FakeServerBoxBuilder::default();
+        let mut cloud_http = FakeServerBoxBuilder::default();
+        let actor =
+            AzFileUploadActorBuilder::new(config, &mut mqtt, &mut local_http, &mut cloud_http)
+                .build();
+        tokio::spawn(actor.run());
+
+        (
+            mqtt.build().with_timeout(TEST_TIMEOUT),
+            local_http.build().with_timeout(TEST_TIMEOUT),
+            cloud_http.build().with_timeout(TEST_TIMEOUT),
+        )
+    }
+}
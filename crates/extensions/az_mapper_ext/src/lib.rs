@@ -1,3 +1,7 @@
+pub mod file_upload;
+pub mod methods;
+pub mod twin;
+
 use tedge_api::mqtt_topics::MqttSchema;
 use tedge_config::models::timestamp::TimeFormat;
 use tedge_config::models::TopicPrefix;
@@ -0,0 +1,429 @@
+//! Cloud-to-device interactions with the Azure IoT Hub
+//!
+//! - A direct method invoked on Azure is translated into a thin-edge command on the main device,
+//!   the method name being the operation and the method payload the command parameters.
+//!   The final state of the command is returned as the method response.
+//! - A cloud-to-device message is republished, unchanged, on the `<prefix>/c2d` local topic.
+//!
+//! The request id of a direct method is kept in the id of the command (`<prefix>-method-<rid>`),
+//! so the method response can be sent even if the mapper is restarted while the command is executed.
+use async_trait::async_trait;
+use serde_json::json;
+use serde_json::Value;
+use std::convert::Infallible;
+use tedge_actors::Actor;
+use tedge_actors::Builder;
+use tedge_actors::DynSender;
+use tedge_actors::MessageReceiver;
+use tedge_actors::MessageSink;
+use tedge_actors::MessageSource;
+use tedge_actors::NoConfig;
+use tedge_actors::RuntimeError;
+use tedge_actors::RuntimeRequest;
+use tedge_actors::RuntimeRequestSink;
+use tedge_actors::Sender;
+use tedge_actors::SimpleMessageBox;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::ChannelFilter;
+use tedge_api::mqtt_topics::EntityFilter;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_api::workflow::GenericCommandState;
+use tedge_config::models::TopicPrefix;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::QoS;
+use tedge_mqtt_ext::Topic;
+use tedge_mqtt_ext::TopicFilter;
+use tracing::info;
+use tracing::warn;
+
+/// Configuration of the Azure direct methods actor
+#[derive(Clone, Debug)]
+pub struct AzMethodsConfig {
+    pub mqtt_schema: MqttSchema,
+    pub topic_prefix: TopicPrefix,
+    /// The main device, i.e. the device connected to Azure
+    pub device_topic_id: EntityTopicId,
+}
+
+impl AzMethodsConfig {
+    fn method_requests_topics(&self) -> TopicFilter {
+        TopicFilter::new_unchecked(&format!("{}/methods/POST/#", self.topic_prefix))
+    }
+
+    fn method_response_topic(&self, status: u16, rid: &str) -> Topic {
+        Topic::new_unchecked(&format!(
+            "{}/methods/res/{status}/?$rid={rid}",
+            self.topic_prefix
+        ))
+    }
+
+    fn c2d_messages_topics(&self) -> TopicFilter {
+        TopicFilter::new_unchecked(&format!("{}/messages/devicebound/#", self.topic_prefix))
+    }
+
+    fn c2d_local_topic(&self) -> Topic {
+        Topic::new_unchecked(&format!("{}/c2d", self.topic_prefix))
+    }
+
+    fn local_command_topics(&self) -> TopicFilter {
+        self.mqtt_schema.topics(
+            EntityFilter::Entity(&self.device_topic_id),
+            ChannelFilter::AnyCommand,
+        )
+    }
+
+    fn command_id_prefix(&self) -> String {
+        format!("{}-method-", self.topic_prefix)
+    }
+}
+
+pub struct AzMethodsActor {
+    config: AzMethodsConfig,
+    messages: SimpleMessageBox<MqttMessage, MqttMessage>,
+}
+
+#[async_trait]
+impl Actor for AzMethodsActor {
+    fn name(&self) -> &str {
+        "AzMethodsActor"
+    }
+
+    async fn run(mut self) -> Result<(), RuntimeError> {
+        while let Some(message) = self.messages.recv().await {
+            for output in self.process(&message) {
+                self.messages.send(output).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AzMethodsActor {
+    fn process(&self, message: &MqttMessage) -> Vec<MqttMessage> {
+        if self.config.method_requests_topics().accept(message) {
+            return self.process_method_request(message);
+        }
+        if self.config.c2d_messages_topics().accept(message) {
+            return vec![MqttMessage::new(
+                &self.config.c2d_local_topic(),
+                message.payload_bytes().to_vec(),
+            )
+            .with_qos(QoS::AtLeastOnce)];
+        }
+        if self.config.local_command_topics().accept(message) {
+            return self.process_command_update(message);
+        }
+        vec![]
+    }
+
+    /// Translate a direct method invocation into a thin-edge command
+    ///
+    /// e.g. `az/methods/POST/restart/?$rid=1` triggers `te/device/main///cmd/restart/az-method-1`
+    fn process_method_request(&self, message: &MqttMessage) -> Vec<MqttMessage> {
+        let Some((method, rid)) =
+            parse_method_request_topic(&self.config.topic_prefix, &message.topic)
+        else {
+            warn!(
+                "Ignoring invalid direct method request: {}",
+                message.topic.name
+            );
+            return vec![];
+        };
+
+        let mut payload = if message.payload_bytes().is_empty() {
+            Value::Null
+        } else {
+            match serde_json::from_slice(message.payload_bytes()) {
+                Ok(payload) => payload,
+                Err(err) => {
+                    return vec![self.method_response(
+                        400,
+                        &rid,
+                        json!({"reason": format!("Invalid method payload: {err}")}),
+                    )]
+                }
+            }
+        };
+        if payload.is_null() {
+            payload = json!({});
+        }
+        if !payload.is_object() {
+            return vec![self.method_response(
+                400,
+                &rid,
+                json!({"reason": "The method payload must be a JSON object"}),
+            )];
+        }
+
+        let operation = OperationType::from(method.as_str());
+        let topic = self.config.mqtt_schema.topic_for(
+            &self.config.device_topic_id,
+            &Channel::Command {
+                operation,
+                cmd_id: format!("{}{rid}", self.config.command_id_prefix()),
+            },
+        );
+        info!("Direct method {method} invoked from Azure");
+        let command = GenericCommandState::new(topic, "init".to_string(), payload);
+        vec![command.into_message()]
+    }
+
+    /// Return the final state of a command triggered by a direct method as the method response
+    fn process_command_update(&self, message: &MqttMessage) -> Vec<MqttMessage> {
+        let Ok((_, Channel::Command { cmd_id, .. })) =
+            self.config.mqtt_schema.entity_channel_of(&message.topic)
+        else {
+            return vec![];
+        };
+        let Some(rid) = cmd_id.strip_prefix(&self.config.command_id_prefix()) else {
+            return vec![];
+        };
+        if message.payload_bytes().is_empty() {
+            return vec![];
+        }
+        let command = match GenericCommandState::from_command_message(message) {
+            Ok(command) => command,
+            Err(err) => {
+                warn!(
+                    "Ignoring invalid command state on {}: {err}",
+                    message.topic.name
+                );
+                return vec![];
+            }
+        };
+
+        let status = if command.is_successful() {
+            200
+        } else if command.is_failed() {
+            500
+        } else {
+            return vec![];
+        };
+        let response = self.method_response(status, rid, command.payload.clone());
+        vec![response, command.clear().into_message()]
+    }
+
+    fn method_response(&self, status: u16, rid: &str, payload: Value) -> MqttMessage {
+        MqttMessage::new(
+            &self.config.method_response_topic(status, rid),
+            payload.to_string(),
+        )
+        .with_qos(QoS::AtLeastOnce)
+    }
+}
+
+/// Extract the method name and request id of a direct method request topic
+///
+/// e.g. `az/methods/POST/restart/?$rid=1` is a request to the `restart` method with the request id `1`
+fn parse_method_request_topic(prefix: &TopicPrefix, topic: &Topic) -> Option<(String, String)> {
+    let request = topic
+        .name
+        .strip_prefix(&format!("{prefix}/methods/POST/"))?;
+    let (method, properties) = request.split_once('/')?;
+    let rid = properties
+        .trim_start_matches('?')
+        .split('&')
+        .find_map(|property| property.strip_prefix("$rid="))?;
+    let is_valid = |name: &str| {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    };
+    if !is_valid(method) || !is_valid(rid) {
+        return None;
+    }
+    Some((method.to_string(), rid.to_string()))
+}
+
+pub struct AzMethodsActorBuilder {
+    config: AzMethodsConfig,
+    box_builder: SimpleMessageBoxBuilder<MqttMessage, MqttMessage>,
+}
+
+impl AzMethodsActorBuilder {
+    pub fn new(
+        config: AzMethodsConfig,
+        mqtt: &mut (impl MessageSource<MqttMessage, TopicFilter> + MessageSink<MqttMessage>),
+    ) -> Self {
+        let mut box_builder = SimpleMessageBoxBuilder::new("AzMethods", 16);
+        let mut topics = config.method_requests_topics();
+        topics.add_all(config.c2d_messages_topics());
+        topics.add_all(config.local_command_topics());
+        box_builder.connect_source(topics, mqtt);
+        box_builder.connect_sink(NoConfig, mqtt);
+        AzMethodsActorBuilder {
+            config,
+            box_builder,
+        }
+    }
+}
+
+impl RuntimeRequestSink for AzMethodsActorBuilder {
+    fn get_signal_sender(&self) -> DynSender<RuntimeRequest> {
+        self.box_builder.get_signal_sender()
+    }
+}
+
+impl Builder<AzMethodsActor> for AzMethodsActorBuilder {
+    type Error = Infallible;
+
+    fn try_build(self) -> Result<AzMethodsActor, Self::Error> {
+        Ok(self.build())
+    }
+
+    fn build(self) -> AzMethodsActor {
+        AzMethodsActor {
+            config: self.config,
+            messages: self.box_builder.build(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tedge_actors::test_helpers::MessageReceiverExt;
+    use tedge_actors::test_helpers::TimedMessageBox;
+
+    const TEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+    type MqttBox = TimedMessageBox<SimpleMessageBox<MqttMessage, MqttMessage>>;
+
+    #[tokio::test]
+    async fn direct_methods_are_translated_into_commands() {
+        let mut mqtt = spawn_methods_actor();
+
+        mqtt.send(mqtt_message("az/methods/POST/restart/?$rid=1", "null"))
+            .await
+            .unwrap();
+        let command = mqtt.recv().await.expect("a command");
+        assert_eq!(
+            command.topic.name,
+            "te/device/main///cmd/restart/az-method-1"
+        );
+        assert!(command.retain);
+        assert_payload(&command, json!({"status": "init"}));
+
+        mqtt.send(mqtt_message(
+            "az/methods/POST/software_update/?$rid=a2",
+            r#"{"updateList":[]}"#,
+        ))
+        .await
+        .unwrap();
+        let command = mqtt.recv().await.expect("a command");
+        assert_eq!(
+            command.topic.name,
+            "te/device/main///cmd/software_update/az-method-a2"
+        );
+        assert_payload(&command, json!({"status": "init", "updateList": []}));
+    }
+
+    #[tokio::test]
+    async fn final_command_status_is_returned_as_method_response() {
+        let mut mqtt = spawn_methods_actor();
+
+        // Intermediate states are ignored
+        mqtt.send(command_message(
+            "te/device/main///cmd/reboot_app/az-method-7",
+            json!({"status": "executing", "app": "collectd"}),
+        ))
+        .await
+        .unwrap();
+        // As the commands not triggered by a direct method
+        mqtt.send(command_message(
+            "te/device/main///cmd/reboot_app/c8y-mapper-7",
+            json!({"status": "successful", "app": "collectd"}),
+        ))
+        .await
+        .unwrap();
+
+        mqtt.send(command_message(
+            "te/device/main///cmd/reboot_app/az-method-7",
+            json!({"status": "successful", "app": "collectd"}),
+        ))
+        .await
+        .unwrap();
+        let response = mqtt.recv().await.expect("a method response");
+        assert_eq!(response.topic.name, "az/methods/res/200/?$rid=7");
+        assert_payload(
+            &response,
+            json!({"status": "successful", "app": "collectd"}),
+        );
+        let clear = mqtt.recv().await.expect("a clear message");
+        assert_eq!(
+            clear.topic.name,
+            "te/device/main///cmd/reboot_app/az-method-7"
+        );
+        assert!(clear.retain);
+        assert!(clear.payload_bytes().is_empty());
+
+        mqtt.send(command_message(
+            "te/device/main///cmd/restart/az-method-8",
+            json!({"status": "failed", "reason": "Permission denied"}),
+        ))
+        .await
+        .unwrap();
+        let response = mqtt.recv().await.expect("a method response");
+        assert_eq!(response.topic.name, "az/methods/res/500/?$rid=8");
+        assert_payload(
+            &response,
+            json!({"status": "failed", "reason": "Permission denied"}),
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_method_payloads_are_rejected() {
+        let mut mqtt = spawn_methods_actor();
+
+        mqtt.send(mqtt_message("az/methods/POST/restart/?$rid=3", "[1,2]"))
+            .await
+            .unwrap();
+        let response = mqtt.recv().await.expect("a method response");
+        assert_eq!(response.topic.name, "az/methods/res/400/?$rid=3");
+    }
+
+    #[tokio::test]
+    async fn c2d_messages_are_delivered_on_a_local_topic() {
+        let mut mqtt = spawn_methods_actor();
+
+        mqtt.send(mqtt_message(
+            "az/messages/devicebound/%24.mid=123&%24.to=%2Fdevices%2Falpha&iothub-ack=none",
+            "hello",
+        ))
+        .await
+        .unwrap();
+        let message = mqtt.recv().await.expect("a C2D message");
+        assert_eq!(message.topic.name, "az/c2d");
+        assert_eq!(message.payload_str().unwrap(), "hello");
+    }
+
+    fn mqtt_message(topic: &str, payload: &str) -> MqttMessage {
+        MqttMessage::new(&Topic::new_unchecked(topic), payload)
+    }
+
+    fn command_message(topic: &str, payload: Value) -> MqttMessage {
+        mqtt_message(topic, &payload.to_string()).with_retain()
+    }
+
+    fn assert_payload(message: &MqttMessage, expected: Value) {
+        let payload: Value = serde_json::from_slice(message.payload_bytes()).unwrap();
+        assert_eq!(payload, expected);
+    }
+
+    fn spawn_methods_actor() -> MqttBox {
+        let config = AzMethodsConfig {
+            mqtt_schema: MqttSchema::default(),
+            topic_prefix: "az".try_into().unwrap(),
+            device_topic_id: EntityTopicId::default_main_device(),
+        };
+        let mut mqtt = SimpleMessageBoxBuilder::new("MQTT", 16);
+        let actor = AzMethodsActorBuilder::new(config, &mut mqtt).build();
+        tokio::spawn(actor.run());
+        mqtt.build().with_timeout(TEST_TIMEOUT)
+    }
+}
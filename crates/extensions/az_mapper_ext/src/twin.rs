@@ -0,0 +1,648 @@
+//! Synchronization of the thin-edge twin data of the main device with the Azure IoT Hub device twin
+//!
+//! - The twin fragments published on `te/<device>/twin/<key>` are sent to Azure as reported properties.
+//! - The desired properties set on Azure are published on the local twin topics,
+//!   except the `configUpdate` property which triggers a `config_update` command.
+//! - The version of the last desired properties applied on the device is persisted,
+//!   so the same desired properties are not applied twice, even after a reconnect or a restart.
+//! - On each (re)connection of the bridge, the full reported state is sent to Azure
+//!   and the desired properties are fetched to catch up with the changes made while disconnected.
+use async_trait::async_trait;
+use camino::Utf8PathBuf;
+use serde::Deserialize;
+use serde_json::Map;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use tedge_actors::Actor;
+use tedge_actors::Builder;
+use tedge_actors::DynSender;
+use tedge_actors::MessageReceiver;
+use tedge_actors::MessageSink;
+use tedge_actors::MessageSource;
+use tedge_actors::NoConfig;
+use tedge_actors::RuntimeError;
+use tedge_actors::RuntimeRequest;
+use tedge_actors::RuntimeRequestSink;
+use tedge_actors::Sender;
+use tedge_actors::SimpleMessageBox;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::commands::CommandStatus;
+use tedge_api::commands::ConfigUpdateCmd;
+use tedge_api::commands::ConfigUpdateCmdPayload;
+use tedge_api::health::HealthStatus;
+use tedge_api::health::Status;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::ChannelFilter;
+use tedge_api::mqtt_topics::EntityFilter;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_config::models::TopicPrefix;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::QoS;
+use tedge_mqtt_ext::Topic;
+use tedge_mqtt_ext::TopicFilter;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+/// The desired property used to trigger a configuration update
+const CONFIG_UPDATE_PROPERTY: &str = "configUpdate";
+
+/// The file where the version of the last applied desired properties is persisted
+const DESIRED_VERSION_FILE: &str = "twin-desired-version";
+
+/// Configuration of the Azure twin actor
+#[derive(Clone, Debug)]
+pub struct AzTwinConfig {
+    pub mqtt_schema: MqttSchema,
+    pub topic_prefix: TopicPrefix,
+    /// The main device, i.e. the device connected to Azure
+    pub device_topic_id: EntityTopicId,
+    /// The health topic of the bridge, used to detect reconnects
+    pub bridge_health_topic: Topic,
+    /// Where the version of the last applied desired properties is persisted
+    pub state_dir: Utf8PathBuf,
+}
+
+impl AzTwinConfig {
+    fn reported_properties_topic(&self, rid: u64) -> Topic {
+        Topic::new_unchecked(&format!(
+            "{}/twin/PATCH/properties/reported/?$rid={rid}",
+            self.topic_prefix
+        ))
+    }
+
+    fn get_twin_topic(&self, rid: u64) -> Topic {
+        Topic::new_unchecked(&format!("{}/twin/GET/?$rid={rid}", self.topic_prefix))
+    }
+
+    fn twin_response_topics(&self) -> TopicFilter {
+        TopicFilter::new_unchecked(&format!("{}/twin/res/#", self.topic_prefix))
+    }
+
+    fn desired_properties_topics(&self) -> TopicFilter {
+        TopicFilter::new_unchecked(&format!(
+            "{}/twin/PATCH/properties/desired/#",
+            self.topic_prefix
+        ))
+    }
+
+    fn local_twin_topics(&self) -> TopicFilter {
+        self.mqtt_schema.topics(
+            EntityFilter::Entity(&self.device_topic_id),
+            ChannelFilter::EntityTwinData,
+        )
+    }
+}
+
+/// Response of the IoT hub to a twin GET request
+#[derive(Debug, Deserialize)]
+struct TwinDocument {
+    #[serde(default)]
+    desired: Map<String, Value>,
+}
+
+pub struct AzTwinActor {
+    config: AzTwinConfig,
+    messages: SimpleMessageBox<MqttMessage, MqttMessage>,
+    /// The fragments reported to Azure
+    reported: BTreeMap<String, Value>,
+    /// The version of the last desired properties applied on the device
+    desired_version: Option<i64>,
+    /// The request id of the pending twin GET request, if any
+    pending_get: Option<u64>,
+    next_rid: u64,
+    bridge_up: bool,
+}
+
+#[async_trait]
+impl Actor for AzTwinActor {
+    fn name(&self) -> &str {
+        "AzTwinActor"
+    }
+
+    async fn run(mut self) -> Result<(), RuntimeError> {
+        while let Some(message) = self.messages.recv().await {
+            for output in self.process(&message) {
+                self.messages.send(output).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AzTwinActor {
+    fn process(&mut self, message: &MqttMessage) -> Vec<MqttMessage> {
+        if message.topic == self.config.bridge_health_topic {
+            return self.process_bridge_health(message);
+        }
+        if self.config.local_twin_topics().accept(message) {
+            return self.process_local_twin_fragment(message);
+        }
+        if self.config.desired_properties_topics().accept(message) {
+            return self.process_desired_properties_patch(message);
+        }
+        if self.config.twin_response_topics().accept(message) {
+            return self.process_twin_response(message);
+        }
+        vec![]
+    }
+
+    /// On each bridge reconnection, send the full reported state and fetch the desired properties
+    fn process_bridge_health(&mut self, message: &MqttMessage) -> Vec<MqttMessage> {
+        let Ok(health) =
+            HealthStatus::try_from_health_status_message(message, &self.config.mqtt_schema)
+        else {
+            return vec![];
+        };
+        let was_up = self.bridge_up;
+        self.bridge_up = health.status == Status::Up;
+        if was_up || !self.bridge_up {
+            return vec![];
+        }
+
+        info!("Synchronizing the device twin with Azure");
+        let mut messages = vec![];
+        if !self.reported.is_empty() {
+            let reported: Map<String, Value> = self.reported.clone().into_iter().collect();
+            messages.push(self.reported_properties_message(reported));
+        }
+        let rid = self.new_rid();
+        self.pending_get = Some(rid);
+        messages.push(
+            MqttMessage::new(&self.config.get_twin_topic(rid), "").with_qos(QoS::AtLeastOnce),
+        );
+        messages
+    }
+
+    fn process_local_twin_fragment(&mut self, message: &MqttMessage) -> Vec<MqttMessage> {
+        let Ok((_, Channel::EntityTwinData { fragment_key })) =
+            self.config.mqtt_schema.entity_channel_of(&message.topic)
+        else {
+            return vec![];
+        };
+
+        let value = if message.payload_bytes().is_empty() {
+            // Azure removes a reported property when set to null
+            Value::Null
+        } else {
+            match serde_json::from_slice(message.payload_bytes()) {
+                Ok(value) => value,
+                Err(err) => {
+                    warn!(
+                        "Ignoring invalid twin data on {}: {err}",
+                        message.topic.name
+                    );
+                    return vec![];
+                }
+            }
+        };
+
+        if value.is_null() {
+            if self.reported.remove(&fragment_key).is_none() {
+                return vec![];
+            }
+        } else if self.reported.get(&fragment_key) == Some(&value) {
+            return vec![];
+        } else {
+            self.reported.insert(fragment_key.clone(), value.clone());
+        }
+
+        let mut reported = Map::new();
+        reported.insert(fragment_key, value);
+        vec![self.reported_properties_message(reported)]
+    }
+
+    fn process_desired_properties_patch(&mut self, message: &MqttMessage) -> Vec<MqttMessage> {
+        match serde_json::from_slice::<Map<String, Value>>(message.payload_bytes()) {
+            Ok(desired) => self.apply_desired_properties(desired, true),
+            Err(err) => {
+                error!("Invalid desired properties received from Azure: {err}");
+                vec![]
+            }
+        }
+    }
+
+    fn process_twin_response(&mut self, message: &MqttMessage) -> Vec<MqttMessage> {
+        let Some((status, rid)) =
+            parse_twin_response_topic(&self.config.topic_prefix, &message.topic)
+        else {
+            return vec![];
+        };
+        if !(200..300).contains(&status) {
+            error!(
+                "Azure rejected a device twin request with status {status}: {}",
+                message.payload_str().unwrap_or_default()
+            );
+        }
+        if self.pending_get != Some(rid) {
+            // A response to a reported properties update
+            return vec![];
+        }
+        self.pending_get = None;
+        if status != 200 {
+            return vec![];
+        }
+
+        match serde_json::from_slice::<TwinDocument>(message.payload_bytes()) {
+            Ok(twin) => self.apply_desired_properties(twin.desired, false),
+            Err(err) => {
+                error!("Invalid device twin received from Azure: {err}");
+                vec![]
+            }
+        }
+    }
+
+    /// Apply the desired properties if more recent than the last applied version
+    ///
+    /// A patch only carries the properties that changed, a null value meaning a removal,
+    /// while a full twin document carries all the desired properties.
+    fn apply_desired_properties(
+        &mut self,
+        mut desired: Map<String, Value>,
+        is_patch: bool,
+    ) -> Vec<MqttMessage> {
+        let version = desired
+            .remove("$version")
+            .and_then(|version| version.as_i64());
+        if let (Some(version), Some(applied)) = (version, self.desired_version) {
+            if version <= applied {
+                return vec![];
+            }
+        }
+
+        let mut messages = vec![];
+        for (key, value) in desired {
+            if key.starts_with('$') {
+                continue;
+            }
+            if key == CONFIG_UPDATE_PROPERTY {
+                match self.config_update_command(&value, version) {
+                    Some(command) => messages.push(command),
+                    None if value.is_null() => (),
+                    None => {
+                        warn!("Ignoring invalid {CONFIG_UPDATE_PROPERTY} desired property: {value}")
+                    }
+                }
+                continue;
+            }
+            if value.is_null() && !is_patch {
+                continue;
+            }
+            let topic = self.config.mqtt_schema.topic_for(
+                &self.config.device_topic_id,
+                &Channel::EntityTwinData { fragment_key: key },
+            );
+            let payload = if value.is_null() {
+                String::new()
+            } else {
+                value.to_string()
+            };
+            messages.push(
+                MqttMessage::new(&topic, payload)
+                    .with_retain()
+                    .with_qos(QoS::AtLeastOnce),
+            );
+        }
+
+        if let Some(version) = version {
+            self.desired_version = Some(version);
+            persist_desired_version(&self.config.state_dir, version);
+        }
+        messages
+    }
+
+    fn config_update_command(&self, value: &Value, version: Option<i64>) -> Option<MqttMessage> {
+        let config_type = value.get("type")?.as_str()?;
+        let url = value.get("url")?.as_str()?;
+        let command = ConfigUpdateCmd {
+            target: self.config.device_topic_id.clone(),
+            cmd_id: format!(
+                "{}-twin-{}",
+                self.config.topic_prefix,
+                version.unwrap_or_default()
+            ),
+            payload: ConfigUpdateCmdPayload {
+                status: CommandStatus::Init,
+                tedge_url: None,
+                remote_url: url.to_string(),
+                server_url: url.to_string(),
+                config_type: config_type.to_string(),
+                path: None,
+                log_path: None,
+            },
+        };
+        Some(command.command_message(&self.config.mqtt_schema))
+    }
+
+    fn reported_properties_message(&mut self, reported: Map<String, Value>) -> MqttMessage {
+        let rid = self.new_rid();
+        MqttMessage::new(
+            &self.config.reported_properties_topic(rid),
+            Value::Object(reported).to_string(),
+        )
+        .with_qos(QoS::AtLeastOnce)
+    }
+
+    fn new_rid(&mut self) -> u64 {
+        self.next_rid += 1;
+        self.next_rid
+    }
+}
+
+/// Extract the status and request id of a twin response topic
+///
+/// e.g. `az/twin/res/204/?$rid=4&$version=12` is a successful response to the request 4
+fn parse_twin_response_topic(prefix: &TopicPrefix, topic: &Topic) -> Option<(u16, u64)> {
+    let response = topic.name.strip_prefix(&format!("{prefix}/twin/res/"))?;
+    let (status, properties) = response.split_once('/')?;
+    let status = status.parse().ok()?;
+    let rid = properties
+        .trim_start_matches('?')
+        .split('&')
+        .find_map(|property| property.strip_prefix("$rid="))?
+        .parse()
+        .ok()?;
+    Some((status, rid))
+}
+
+fn load_desired_version(state_dir: &Utf8PathBuf) -> Option<i64> {
+    let path = state_dir.join(DESIRED_VERSION_FILE);
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn persist_desired_version(state_dir: &Utf8PathBuf, version: i64) {
+    let path = state_dir.join(DESIRED_VERSION_FILE);
+    let persisted = std::fs::create_dir_all(state_dir)
+        .and_then(|()| std::fs::write(&path, version.to_string()));
+    if let Err(err) = persisted {
+        error!("Failed to persist the device twin version into {path}: {err}");
+    }
+}
+
+pub struct AzTwinActorBuilder {
+    config: AzTwinConfig,
+    box_builder: SimpleMessageBoxBuilder<MqttMessage, MqttMessage>,
+}
+
+impl AzTwinActorBuilder {
+    pub fn new(
+        config: AzTwinConfig,
+        mqtt: &mut (impl MessageSource<MqttMessage, TopicFilter> + MessageSink<MqttMessage>),
+    ) -> Self {
+        let mut box_builder = SimpleMessageBoxBuilder::new("AzTwin", 16);
+        let mut topics = config.local_twin_topics();
+        topics.add_all(config.desired_properties_topics());
+        topics.add_all(config.twin_response_topics());
+        topics.add_all(config.bridge_health_topic.clone().into());
+        box_builder.connect_source(topics, mqtt);
+        box_builder.connect_sink(NoConfig, mqtt);
+        AzTwinActorBuilder {
+            config,
+            box_builder,
+        }
+    }
+}
+
+impl RuntimeRequestSink for AzTwinActorBuilder {
+    fn get_signal_sender(&self) -> DynSender<RuntimeRequest> {
+        self.box_builder.get_signal_sender()
+    }
+}
+
+impl Builder<AzTwinActor> for AzTwinActorBuilder {
+    type Error = Infallible;
+
+    fn try_build(self) -> Result<AzTwinActor, Self::Error> {
+        Ok(self.build())
+    }
+
+    fn build(self) -> AzTwinActor {
+        let desired_version = load_desired_version(&self.config.state_dir);
+        AzTwinActor {
+            config: self.config,
+            messages: self.box_builder.build(),
+            reported: BTreeMap::new(),
+            desired_version,
+            pending_get: None,
+            next_rid: 0,
+            bridge_up: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::Duration;
+    use tedge_actors::test_helpers::MessageReceiverExt;
+    use tedge_actors::test_helpers::TimedMessageBox;
+    use tempfile::TempDir;
+
+    const TEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+    type MqttBox = TimedMessageBox<SimpleMessageBox<MqttMessage, MqttMessage>>;
+
+    #[tokio::test]
+    async fn twin_fragments_are_sent_as_reported_properties() {
+        let state_dir = TempDir::new().unwrap();
+        let mut mqtt = spawn_twin_actor(&state_dir);
+
+        mqtt.send(mqtt_message(
+            "te/device/main///twin/os",
+            r#"{"family":"Debian"}"#,
+        ))
+        .await
+        .unwrap();
+        assert_message(
+            mqtt.recv().await,
+            "az/twin/PATCH/properties/reported/?$rid=1",
+            json!({"os": {"family": "Debian"}}),
+        );
+
+        // Unchanged fragments are not sent again
+        mqtt.send(mqtt_message(
+            "te/device/main///twin/os",
+            r#"{"family":"Debian"}"#,
+        ))
+        .await
+        .unwrap();
+        // Removed fragments are removed from the reported properties
+        mqtt.send(mqtt_message("te/device/main///twin/os", ""))
+            .await
+            .unwrap();
+        assert_message(
+            mqtt.recv().await,
+            "az/twin/PATCH/properties/reported/?$rid=2",
+            json!({"os": null}),
+        );
+    }
+
+    #[tokio::test]
+    async fn desired_properties_are_published_on_local_twin_topics() {
+        let state_dir = TempDir::new().unwrap();
+        let mut mqtt = spawn_twin_actor(&state_dir);
+
+        mqtt.send(mqtt_message(
+            "az/twin/PATCH/properties/desired/?$version=3",
+            r#"{"interval":30,"name":null,"$version":3}"#,
+        ))
+        .await
+        .unwrap();
+        let messages = [mqtt.recv().await.unwrap(), mqtt.recv().await.unwrap()];
+        assert_eq!(messages[0].topic.name, "te/device/main///twin/interval");
+        assert_eq!(messages[0].payload_str().unwrap(), "30");
+        assert!(messages[0].retain);
+        assert_eq!(messages[1].topic.name, "te/device/main///twin/name");
+        assert_eq!(messages[1].payload_str().unwrap(), "");
+
+        // Outdated desired properties are ignored
+        mqtt.send(mqtt_message(
+            "az/twin/PATCH/properties/desired/?$version=2",
+            r#"{"interval":10,"$version":2}"#,
+        ))
+        .await
+        .unwrap();
+        assert!(mqtt.recv().await.is_none());
+
+        assert_eq!(
+            std::fs::read_to_string(state_dir.path().join(DESIRED_VERSION_FILE)).unwrap(),
+            "3"
+        );
+    }
+
+    #[tokio::test]
+    async fn twin_is_synchronized_on_bridge_reconnect() {
+        let state_dir = TempDir::new().unwrap();
+        let mut mqtt = spawn_twin_actor(&state_dir);
+
+        mqtt.send(mqtt_message("te/device/main///twin/os", r#""Debian""#))
+            .await
+            .unwrap();
+        mqtt.skip(1).await;
+
+        mqtt.send(bridge_health("up")).await.unwrap();
+        assert_message(
+            mqtt.recv().await,
+            "az/twin/PATCH/properties/reported/?$rid=2",
+            json!({"os": "Debian"}),
+        );
+        let request = mqtt.recv().await.unwrap();
+        assert_eq!(request.topic.name, "az/twin/GET/?$rid=3");
+
+        // Only the response to the GET request is processed
+        mqtt.send(mqtt_message("az/twin/res/204/?$rid=2&$version=4", ""))
+            .await
+            .unwrap();
+        mqtt.send(mqtt_message(
+            "az/twin/res/200/?$rid=3",
+            r#"{"desired":{"interval":60,"$version":7},"reported":{"os":"Debian","$version":4}}"#,
+        ))
+        .await
+        .unwrap();
+        let message = mqtt.recv().await.unwrap();
+        assert_eq!(message.topic.name, "te/device/main///twin/interval");
+        assert_eq!(message.payload_str().unwrap(), "60");
+
+        // Nothing is sent until the bridge reconnects
+        mqtt.send(bridge_health("up")).await.unwrap();
+        assert!(mqtt.recv().await.is_none());
+        mqtt.send(bridge_health("down")).await.unwrap();
+        mqtt.send(bridge_health("up")).await.unwrap();
+        mqtt.skip(1).await;
+        let request = mqtt.recv().await.unwrap();
+        assert_eq!(request.topic.name, "az/twin/GET/?$rid=5");
+
+        // Desired properties already applied are ignored
+        mqtt.send(mqtt_message(
+            "az/twin/res/200/?$rid=5",
+            r#"{"desired":{"interval":60,"$version":7},"reported":{}}"#,
+        ))
+        .await
+        .unwrap();
+        assert!(mqtt.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn desired_properties_version_is_persisted() {
+        let state_dir = TempDir::new().unwrap();
+        std::fs::write(state_dir.path().join(DESIRED_VERSION_FILE), "12").unwrap();
+        let mut mqtt = spawn_twin_actor(&state_dir);
+
+        mqtt.send(mqtt_message(
+            "az/twin/PATCH/properties/desired/?$version=12",
+            r#"{"interval":30,"$version":12}"#,
+        ))
+        .await
+        .unwrap();
+        assert!(mqtt.recv().await.is_none());
+
+        mqtt.send(mqtt_message(
+            "az/twin/PATCH/properties/desired/?$version=13",
+            r#"{"interval":30,"$version":13}"#,
+        ))
+        .await
+        .unwrap();
+        let message = mqtt.recv().await.unwrap();
+        assert_eq!(message.topic.name, "te/device/main///twin/interval");
+    }
+
+    #[tokio::test]
+    async fn config_update_desired_property_triggers_a_config_update_command() {
+        let state_dir = TempDir::new().unwrap();
+        let mut mqtt = spawn_twin_actor(&state_dir);
+
+        mqtt.send(mqtt_message(
+            "az/twin/PATCH/properties/desired/?$version=5",
+            r#"{"configUpdate":{"type":"tedge.toml","url":"https://example.com/tedge.toml"},"$version":5}"#,
+        ))
+        .await
+        .unwrap();
+        assert_message(
+            mqtt.recv().await,
+            "te/device/main///cmd/config_update/az-twin-5",
+            json!({
+                "status": "init",
+                "type": "tedge.toml",
+                "remoteUrl": "https://example.com/tedge.toml",
+                "serverUrl": "https://example.com/tedge.toml",
+            }),
+        );
+    }
+
+    fn mqtt_message(topic: &str, payload: &str) -> MqttMessage {
+        MqttMessage::new(&Topic::new_unchecked(topic), payload)
+    }
+
+    fn bridge_health(status: &str) -> MqttMessage {
+        mqtt_message(
+            "te/device/main/service/tedge-mapper-bridge-az/status/health",
+            &json!({"status": status}).to_string(),
+        )
+    }
+
+    fn assert_message(message: Option<MqttMessage>, topic: &str, expected: Value) {
+        let message = message.expect("a message");
+        assert_eq!(message.topic.name, topic);
+        let payload: Value = serde_json::from_slice(message.payload_bytes()).unwrap();
+        assert_eq!(payload, expected);
+    }
+
+    fn spawn_twin_actor(state_dir: &TempDir) -> MqttBox {
+        let config = AzTwinConfig {
+            mqtt_schema: MqttSchema::default(),
+            topic_prefix: "az".try_into().unwrap(),
+            device_topic_id: EntityTopicId::default_main_device(),
+            bridge_health_topic: Topic::new_unchecked(
+                "te/device/main/service/tedge-mapper-bridge-az/status/health",
+            ),
+            state_dir: state_dir.path().to_path_buf().try_into().unwrap(),
+        };
+        let mut mqtt = SimpleMessageBoxBuilder::new("MQTT", 16);
+        let actor = AzTwinActorBuilder::new(config, &mut mqtt).build();
+        tokio::spawn(actor.run());
+        mqtt.build().with_timeout(TEST_TIMEOUT)
+    }
+}
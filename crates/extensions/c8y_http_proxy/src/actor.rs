@@ -2,13 +2,20 @@ use crate::messages::C8YRestError;
 use crate::messages::CreateEvent;
 use crate::messages::EventId;
 use crate::messages::SoftwareListResponse;
+use crate::messages::TemplateRegistration;
 use crate::C8YHttpConfig;
 use c8y_api::http_proxy::C8yEndPoint;
 use c8y_api::json_c8y::C8yCreateEvent;
 use c8y_api::json_c8y::C8yEventResponse;
 use c8y_api::json_c8y::C8yManagedObject;
+use c8y_api::json_c8y::C8yMeasurementCollection;
 use c8y_api::json_c8y::InternalIdResponse;
+use c8y_api::smartrest::template_collection::RegisteredSmartRestTemplates;
+use c8y_api::smartrest::template_collection::SmartRestTemplateCollection;
+use c8y_api::smartrest::template_collection::SMARTREST_TEMPLATE_IDENTITY_TYPE;
+use http::StatusCode;
 use serde_json::json;
+use std::collections::HashMap;
 use tedge_actors::ClientMessageBox;
 use tedge_http_ext::HttpRequest;
 use tedge_http_ext::HttpRequestBuilder;
@@ -151,4 +158,180 @@ impl C8YHttpProxyActor {
 
         Ok(())
     }
+
+    pub(crate) async fn register_smartrest_templates(
+        &mut self,
+        collection: &SmartRestTemplateCollection,
+    ) -> Result<TemplateRegistration, C8YRestError> {
+        let (internal_id, registration) = match self
+            .try_get_smartrest_templates_id(&collection.name)
+            .await?
+        {
+            None => {
+                let internal_id = self.create_smartrest_templates(collection).await?;
+                (internal_id, TemplateRegistration::Created)
+            }
+            Some(internal_id) => {
+                let registered = self.get_smartrest_templates(&internal_id).await?;
+                if registered.templates == collection.templates {
+                    return Ok(TemplateRegistration::Unchanged);
+                }
+                self.update_smartrest_templates(&internal_id, collection)
+                    .await?;
+                (internal_id, TemplateRegistration::Updated)
+            }
+        };
+
+        // Check that all the templates are now known by Cumulocity
+        let registered = self.get_smartrest_templates(&internal_id).await?;
+        let missing = collection.templates.missing_from(&registered.templates);
+        if !missing.is_empty() {
+            return Err(C8YRestError::CustomError(format!(
+                "SmartREST templates {} are missing from the {} collection",
+                missing.join(", "),
+                collection.name
+            )));
+        }
+
+        Ok(registration)
+    }
+
+    async fn try_get_smartrest_templates_id(
+        &mut self,
+        collection: &str,
+    ) -> Result<Option<String>, C8YRestError> {
+        let url = self.end_point.proxy_url_for_smartrest_template(collection);
+        let request = HttpRequestBuilder::get(&url).build()?;
+        let http_result = self.http.await_response(request).await?;
+        if matches!(&http_result, Ok(response) if response.status() == StatusCode::NOT_FOUND) {
+            return Ok(None);
+        }
+        let http_response = http_result.error_for_status()?;
+        let internal_id_response: InternalIdResponse = http_response.json().await?;
+        Ok(Some(internal_id_response.id()))
+    }
+
+    async fn get_smartrest_templates(
+        &mut self,
+        internal_id: &str,
+    ) -> Result<RegisteredSmartRestTemplates, C8YRestError> {
+        let url = self.end_point.proxy_url_for_managed_object(internal_id);
+        let request = HttpRequestBuilder::get(&url)
+            .header("Accept", "application/json")
+            .build()?;
+        let http_result = self.http.await_response(request).await?;
+        let http_response = http_result.error_for_status()?;
+        Ok(http_response.json().await?)
+    }
+
+    async fn create_smartrest_templates(
+        &mut self,
+        collection: &SmartRestTemplateCollection,
+    ) -> Result<String, C8YRestError> {
+        let url = self.end_point.proxy_url_for_create_managed_object();
+        let request = HttpRequestBuilder::post(url)
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .json(&collection.managed_object())
+            .build()?;
+        let http_result = self.http.await_response(request).await?;
+        let http_response = http_result.error_for_status()?;
+        let created: RegisteredSmartRestTemplates = http_response.json().await?;
+
+        // The collection is named after its external id
+        let url = self.end_point.proxy_url_for_external_ids(&created.id);
+        let payload = json!({
+            "externalId": collection.name,
+            "type": SMARTREST_TEMPLATE_IDENTITY_TYPE,
+        });
+        let request = HttpRequestBuilder::post(url)
+            .header("Content-Type", "application/json")
+            .json(&payload)
+            .build()?;
+        let http_result = self.http.await_response(request).await?;
+        let _ = http_result.error_for_status()?;
+
+        Ok(created.id)
+    }
+
+    async fn update_smartrest_templates(
+        &mut self,
+        internal_id: &str,
+        collection: &SmartRestTemplateCollection,
+    ) -> Result<(), C8YRestError> {
+        let url = self.end_point.proxy_url_for_managed_object(internal_id);
+        let request = HttpRequestBuilder::put(url)
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .json(&collection.managed_object_update())
+            .build()?;
+        let http_result = self.http.await_response(request).await?;
+        let _ = http_result.error_for_status()?;
+        Ok(())
+    }
+
+    pub(crate) async fn create_measurements(
+        &mut self,
+        mut measurements: Vec<serde_json::Value>,
+    ) -> Result<(), C8YRestError> {
+        // Measurements sent over HTTP must refer to their source by internal id
+        let mut internal_ids: HashMap<String, String> = HashMap::new();
+        for measurement in measurements.iter_mut() {
+            let Some(measurement) = measurement.as_object_mut() else {
+                return Err(C8YRestError::CustomError(
+                    "Not a Cumulocity measurement".to_string(),
+                ));
+            };
+            let Some(external_source) = measurement.remove("externalSource") else {
+                continue;
+            };
+            let Some(external_id) = external_source["externalId"].as_str() else {
+                return Err(C8YRestError::CustomError(format!(
+                    "Invalid measurement source: {external_source}"
+                )));
+            };
+            let internal_id = match internal_ids.get(external_id) {
+                Some(internal_id) => internal_id.clone(),
+                None => {
+                    let internal_id = self.try_get_internal_id(external_id).await?;
+                    internal_ids.insert(external_id.to_string(), internal_id.clone());
+                    internal_id
+                }
+            };
+            measurement.insert(
+                "source".to_string(),
+                json!(C8yManagedObject { id: internal_id }),
+            );
+        }
+
+        let url = self.end_point.proxy_url_for_create_measurements();
+        let request = HttpRequestBuilder::post(url)
+            .header("Accept", "application/json")
+            .header(
+                "Content-Type",
+                "application/vnd.com.nsn.cumulocity.measurementcollection+json",
+            )
+            .json(&C8yMeasurementCollection { measurements })
+            .build()?;
+        let http_result = self.http.await_response(request).await?;
+        let _ = http_result.error_for_status()?;
+        Ok(())
+    }
+
+    pub(crate) async fn update_managed_object(
+        &mut self,
+        device_xid: &str,
+        fragments: &serde_json::Value,
+    ) -> Result<(), C8YRestError> {
+        let internal_id = self.try_get_internal_id(device_xid).await?;
+        let url = self.end_point.proxy_url_for_managed_object(&internal_id);
+        let request = HttpRequestBuilder::put(url)
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .json(fragments)
+            .build()?;
+        let http_result = self.http.await_response(request).await?;
+        let _ = http_result.error_for_status()?;
+        Ok(())
+    }
 }
@@ -3,9 +3,11 @@ use crate::messages::C8YRestError;
 use crate::messages::CreateEvent;
 use crate::messages::EventId;
 use crate::messages::SoftwareListResponse;
+use crate::messages::TemplateRegistration;
 use crate::C8YHttpConfig;
 use c8y_api::http_proxy::InvalidUrl;
 use c8y_api::json_c8y::C8yUpdateSoftwareListResponse;
+use c8y_api::smartrest::template_collection::SmartRestTemplateCollection;
 use reqwest::Url;
 use tedge_actors::ClientMessageBox;
 use tedge_actors::Service;
@@ -90,4 +92,29 @@ impl C8YHttpProxy {
 
         self.c8y.send_software_list_http(request).await
     }
+
+    /// Create or update a SmartREST template collection, checking that all its templates are registered
+    pub async fn register_smartrest_templates(
+        &mut self,
+        collection: &SmartRestTemplateCollection,
+    ) -> Result<TemplateRegistration, C8YRestError> {
+        self.c8y.register_smartrest_templates(collection).await
+    }
+
+    /// Post a collection of measurements, each related to its source by external id
+    pub async fn send_measurements(
+        &mut self,
+        measurements: Vec<serde_json::Value>,
+    ) -> Result<(), C8YRestError> {
+        self.c8y.create_measurements(measurements).await
+    }
+
+    /// Update the fragments of the managed object of a device, given its external id
+    pub async fn update_managed_object(
+        &mut self,
+        device_xid: &str,
+        fragments: &serde_json::Value,
+    ) -> Result<(), C8YRestError> {
+        self.c8y.update_managed_object(device_xid, fragments).await
+    }
 }
@@ -42,6 +42,19 @@ pub(crate) struct SoftwareListResponse {
 
 pub type EventId = String;
 
+/// Outcome of the registration of a SmartREST template collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateRegistration {
+    /// The collection was unknown and has been created
+    Created,
+
+    /// The collection was known but with different templates, which have been updated
+    Updated,
+
+    /// The collection was already registered with the same templates
+    Unchanged,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum C8YConnectionError {
     #[error("The connection has been interrupted before the internal id has been retrieved")]
@@ -1,12 +1,15 @@
 use crate::handle::C8YHttpProxy;
 use crate::messages::CreateEvent;
+use crate::messages::TemplateRegistration;
 use crate::C8YHttpConfig;
 use c8y_api::json_c8y::C8yEventResponse;
 use c8y_api::json_c8y::C8yUpdateSoftwareListResponse;
 use c8y_api::json_c8y::InternalIdResponse;
 use c8y_api::proxy_url::Protocol;
 use c8y_api::proxy_url::ProxyUrlGenerator;
+use c8y_api::smartrest::template_collection::SmartRestTemplateCollection;
 use http::StatusCode;
+use serde_json::json;
 use std::collections::HashMap;
 use tedge_actors::test_helpers::FakeServerBox;
 use tedge_actors::Builder;
@@ -351,6 +354,199 @@ async fn request_internal_id_before_posting_software_list() {
     .await;
 }
 
+#[tokio::test]
+async fn create_unknown_smartrest_template_collection() {
+    let (mut proxy, mut c8y) =
+        spawn_c8y_http_proxy("c8y.tenant.io".into(), "device-001".into()).await;
+    let collection = test_template_collection();
+
+    let registration = tokio::spawn({
+        let collection = collection.clone();
+        async move { proxy.register_smartrest_templates(&collection).await }
+    });
+
+    // The collection is unknown
+    assert_recv(
+        &mut c8y,
+        Some(
+            HttpRequestBuilder::get(
+                "http://localhost:8001/c8y/identity/externalIds/c8y_SmartRest2DeviceIdentifier/tedge-custom",
+            )
+            .build()
+            .unwrap(),
+        ),
+    )
+    .await;
+    let c8y_response = HttpResponseBuilder::new().status(404).build().unwrap();
+    c8y.send(Ok(c8y_response)).await.unwrap();
+
+    // The collection is created and named after its external id
+    assert_recv(
+        &mut c8y,
+        Some(
+            HttpRequestBuilder::post("http://localhost:8001/c8y/inventory/managedObjects")
+                .header("content-type", "application/json")
+                .header("accept", "application/json")
+                .json(&collection.managed_object())
+                .build()
+                .unwrap(),
+        ),
+    )
+    .await;
+    let c8y_response = HttpResponseBuilder::new()
+        .status(201)
+        .json(&json!({"id": "4242", "name": "tedge-custom"}))
+        .build()
+        .unwrap();
+    c8y.send(Ok(c8y_response)).await.unwrap();
+
+    assert_recv(
+        &mut c8y,
+        Some(
+            HttpRequestBuilder::post(
+                "http://localhost:8001/c8y/identity/globalIds/4242/externalIds",
+            )
+            .header("content-type", "application/json")
+            .json(&json!({"externalId": "tedge-custom", "type": "c8y_SmartRest2DeviceIdentifier"}))
+            .build()
+            .unwrap(),
+        ),
+    )
+    .await;
+    let c8y_response = HttpResponseBuilder::new().status(201).build().unwrap();
+    c8y.send(Ok(c8y_response)).await.unwrap();
+
+    // The templates are then checked
+    assert_recv(
+        &mut c8y,
+        Some(
+            HttpRequestBuilder::get("http://localhost:8001/c8y/inventory/managedObjects/4242")
+                .header("accept", "application/json")
+                .build()
+                .unwrap(),
+        ),
+    )
+    .await;
+    let c8y_response = HttpResponseBuilder::new()
+        .status(200)
+        .json(&json!({"id": "4242", "com_cumulocity_model_smartrest_csv_CsvSmartRestTemplate": collection.templates}))
+        .build()
+        .unwrap();
+    c8y.send(Ok(c8y_response)).await.unwrap();
+
+    assert_eq!(
+        registration.await.unwrap().unwrap(),
+        TemplateRegistration::Created
+    );
+}
+
+#[tokio::test]
+async fn smartrest_template_collection_is_not_updated_when_unchanged() {
+    let (mut proxy, mut c8y) =
+        spawn_c8y_http_proxy("c8y.tenant.io".into(), "device-001".into()).await;
+    let collection = test_template_collection();
+
+    let registration = tokio::spawn({
+        let collection = collection.clone();
+        async move { proxy.register_smartrest_templates(&collection).await }
+    });
+
+    let _ = c8y.recv().await;
+    let c8y_response = HttpResponseBuilder::new()
+        .status(200)
+        .json(&InternalIdResponse::new("4242", "tedge-custom"))
+        .build()
+        .unwrap();
+    c8y.send(Ok(c8y_response)).await.unwrap();
+
+    assert_recv(
+        &mut c8y,
+        Some(
+            HttpRequestBuilder::get("http://localhost:8001/c8y/inventory/managedObjects/4242")
+                .header("accept", "application/json")
+                .build()
+                .unwrap(),
+        ),
+    )
+    .await;
+    let c8y_response = HttpResponseBuilder::new()
+        .status(200)
+        .json(&json!({"id": "4242", "com_cumulocity_model_smartrest_csv_CsvSmartRestTemplate": collection.templates}))
+        .build()
+        .unwrap();
+    c8y.send(Ok(c8y_response)).await.unwrap();
+
+    assert_eq!(
+        registration.await.unwrap().unwrap(),
+        TemplateRegistration::Unchanged
+    );
+}
+
+#[tokio::test]
+async fn measurements_sent_over_http_refer_to_their_source_by_internal_id() {
+    let (mut proxy, mut c8y) =
+        spawn_c8y_http_proxy("c8y.tenant.io".into(), "device-001".into()).await;
+
+    let measurements = vec![
+        json!({"type": "vibration", "x": {"x": {"value": 1.0}}, "externalSource": {"externalId": "sensor-1", "type": "c8y_Serial"}}),
+        json!({"type": "vibration", "x": {"x": {"value": 2.0}}, "externalSource": {"externalId": "sensor-1", "type": "c8y_Serial"}}),
+    ];
+    let response = tokio::spawn(async move { proxy.send_measurements(measurements).await });
+
+    // The internal id is requested only once
+    assert_recv(
+        &mut c8y,
+        Some(
+            HttpRequestBuilder::get(
+                "http://localhost:8001/c8y/identity/externalIds/c8y_Serial/sensor-1",
+            )
+            .build()
+            .unwrap(),
+        ),
+    )
+    .await;
+    let c8y_response = HttpResponseBuilder::new()
+        .status(200)
+        .json(&InternalIdResponse::new("1234", "sensor-1"))
+        .build()
+        .unwrap();
+    c8y.send(Ok(c8y_response)).await.unwrap();
+
+    assert_recv(
+        &mut c8y,
+        Some(
+            HttpRequestBuilder::post("http://localhost:8001/c8y/measurement/measurements")
+                .header("accept", "application/json")
+                .header(
+                    "content-type",
+                    "application/vnd.com.nsn.cumulocity.measurementcollection+json",
+                )
+                .json(&json!({"measurements": [
+                    {"type": "vibration", "x": {"x": {"value": 1.0}}, "source": {"id": "1234"}},
+                    {"type": "vibration", "x": {"x": {"value": 2.0}}, "source": {"id": "1234"}},
+                ]}))
+                .build()
+                .unwrap(),
+        ),
+    )
+    .await;
+    let c8y_response = HttpResponseBuilder::new().status(201).build().unwrap();
+    c8y.send(Ok(c8y_response)).await.unwrap();
+
+    response.await.unwrap().unwrap();
+}
+
+fn test_template_collection() -> SmartRestTemplateCollection {
+    serde_json::from_value(json!({
+        "__externalId": "tedge-custom",
+        "com_cumulocity_model_smartrest_csv_CsvSmartRestTemplate": {
+            "requestTemplates": [{"msgId": "100", "api": "INVENTORY", "method": "PUT"}],
+            "responseTemplates": [{"msgId": "dm101", "condition": "c8y_Command"}]
+        }
+    }))
+    .unwrap()
+}
+
 /// Return two handles:
 /// - one `C8YHttpProxy` to send HTTP requests to C8Y
 /// - one `ServerMessageBoxBuilder<HttpRequest,HttpResponse> to fake the behavior of C8Y REST.
@@ -4,6 +4,7 @@ use super::dynamic_discovery::process_inotify_events;
 use crate::entity_cache::UpdateOutcome;
 use crate::mea::entities::C8yEntityBirth;
 use crate::service_monitor::is_c8y_bridge_established;
+use crate::smartrest_templates::SmartRestTemplates;
 use anyhow::anyhow;
 use async_trait::async_trait;
 use c8y_http_proxy::handle::C8YHttpProxy;
@@ -61,6 +62,7 @@ pub struct C8yMapperActor {
     mqtt_publisher: LoggingSender<MqttMessage>,
     bridge_status_messages: SimpleMessageBox<MqttMessage, MqttMessage>,
     message_handlers: HashMap<ChannelFilter, Vec<LoggingSender<MqttMessage>>>,
+    smartrest_templates: Option<SmartRestTemplates>,
 }
 
 #[async_trait]
@@ -83,6 +85,11 @@ impl Actor for C8yMapperActor {
             }
         }
 
+        if let Some(templates) = self.smartrest_templates.take() {
+            // Registered in the background, not to delay the processing of messages
+            tokio::spawn(templates.register(self.converter.http_proxy.clone()));
+        }
+
         let init_messages = self.converter.init_messages();
         for init_message in init_messages.into_iter() {
             self.mqtt_publisher.send(init_message).await?;
@@ -109,6 +116,7 @@ impl C8yMapperActor {
         mqtt_publisher: LoggingSender<MqttMessage>,
         bridge_status_messages: SimpleMessageBox<MqttMessage, MqttMessage>,
         message_handlers: HashMap<ChannelFilter, Vec<LoggingSender<MqttMessage>>>,
+        smartrest_templates: Option<SmartRestTemplates>,
     ) -> Self {
         Self {
             converter,
@@ -116,6 +124,7 @@ impl C8yMapperActor {
             mqtt_publisher,
             bridge_status_messages,
             message_handlers,
+            smartrest_templates,
         }
     }
 
@@ -358,6 +367,7 @@ pub struct C8yMapperBuilder {
     bridge_monitor_builder: SimpleMessageBoxBuilder<MqttMessage, MqttMessage>,
     message_handlers: HashMap<ChannelFilter, Vec<LoggingSender<MqttMessage>>>,
     flow_context: Option<FlowContextHandle>,
+    smartrest_templates: Option<SmartRestTemplates>,
 }
 
 impl C8yMapperBuilder {
@@ -407,6 +417,7 @@ impl C8yMapperBuilder {
             bridge_monitor_builder,
             message_handlers,
             flow_context: None,
+            smartrest_templates: None,
         })
     }
 
@@ -414,6 +425,11 @@ impl C8yMapperBuilder {
         self.flow_context = Some(flow_context);
     }
 
+    /// Register on start the SmartREST template collections defined in the given directory
+    pub fn set_smartrest_templates(&mut self, templates: SmartRestTemplates) {
+        self.smartrest_templates = Some(templates);
+    }
+
     pub async fn init(config: &C8yMapperConfig) -> Result<(), FileError> {
         // Create c8y operations directory
         create_directory_with_defaults(config.ops_dir.as_std_path()).await?;
@@ -472,6 +488,7 @@ impl Builder<C8yMapperActor> for C8yMapperBuilder {
             mqtt_publisher,
             bridge_monitor_box,
             self.message_handlers,
+            self.smartrest_templates,
         ))
     }
 }
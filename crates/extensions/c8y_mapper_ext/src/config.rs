@@ -1,4 +1,6 @@
+use crate::inventory::http_inventory_update_topic_filter;
 use crate::mea::events::EventConverter;
+use crate::mea::measurements::MeasurementConverter;
 use crate::supported_operations::C8yPrefix;
 use crate::supported_operations::Operations;
 use crate::supported_operations::OperationsError;
@@ -10,6 +12,7 @@ use c8y_api::smartrest::topic::C8yTopic;
 use c8y_http_proxy::C8YHttpConfig;
 use camino::Utf8Path;
 use serde_json::Value;
+use std::collections::HashSet;
 use std::ops::Add;
 use std::path::Path;
 use std::sync::Arc;
@@ -78,6 +81,26 @@ pub struct C8yMapperConfig {
 
     pub max_mqtt_payload_size: u32,
     pub alarm_sync_interval: &'static str,
+    pub operation_limits: OperationLimits,
+}
+
+/// Limits on the operations running concurrently on an entity
+#[derive(Clone, Debug)]
+pub struct OperationLimits {
+    /// Maximum number of operations of the same type running concurrently on an entity, 0 for no limit
+    pub max_concurrent: usize,
+
+    /// Operation types that run alone on an entity
+    pub exclusive: HashSet<String>,
+}
+
+impl Default for OperationLimits {
+    fn default() -> Self {
+        OperationLimits {
+            max_concurrent: 1,
+            exclusive: HashSet::from(["restart".to_string()]),
+        }
+    }
 }
 
 impl C8yMapperConfig {
@@ -112,6 +135,7 @@ impl C8yMapperConfig {
         smartrest_child_device_create_with_device_marker: bool,
         max_mqtt_payload_size: u32,
         alarm_interval: &'static str,
+        operation_limits: OperationLimits,
     ) -> Self {
         let ops_dir = config_dir
             .join(SUPPORTED_OPERATIONS_DIRECTORY)
@@ -162,6 +186,7 @@ impl C8yMapperConfig {
 
             max_mqtt_payload_size,
             alarm_sync_interval: alarm_interval,
+            operation_limits,
         }
     }
 
@@ -227,6 +252,17 @@ impl C8yMapperConfig {
             c8y_config.cloud_specific.software_management.with_types;
 
         let auto_log_upload = c8y_config.cloud_specific.operations.auto_log_upload;
+        let operation_limits = OperationLimits {
+            max_concurrent: c8y_config.cloud_specific.operations.max_concurrent as usize,
+            exclusive: c8y_config
+                .cloud_specific
+                .operations
+                .exclusive
+                .0
+                .iter()
+                .cloned()
+                .collect(),
+        };
         let smartrest_use_operation_id = c8y_config.cloud_specific.smartrest.use_operation_id;
         let smartrest_child_device_create_with_device_marker = c8y_config
             .cloud_specific
@@ -290,6 +326,7 @@ impl C8yMapperConfig {
             smartrest_child_device_create_with_device_marker,
             max_mqtt_payload_size,
             alarm_interval,
+            operation_limits,
         ))
     }
 
@@ -303,7 +340,10 @@ impl C8yMapperConfig {
         .try_into()
         .expect("topics that mapper should subscribe to");
 
-        let topic_filter = topic_filter.add(EventConverter::http_event_topic_filter(prefix));
+        let topic_filter = topic_filter
+            .add(EventConverter::http_event_topic_filter(prefix))
+            .add(MeasurementConverter::http_measurements_topic_filter(prefix))
+            .add(http_inventory_update_topic_filter(prefix));
         Ok(topic_filter)
     }
 
@@ -7,11 +7,15 @@ use crate::dynamic_discovery::DiscoverOp;
 use crate::entity_cache::EntityCache;
 use crate::entity_cache::InvalidExternalIdError;
 use crate::entity_cache::UpdateOutcome;
+use crate::entity_cache_log::EntityCacheLog;
 use crate::error::ConversionError;
 use crate::error::MessageConversionError;
+use crate::inventory::http_inventory_update_topic_filter;
 use crate::mea::events::EventConverter;
+use crate::mea::measurements::MeasurementConverter;
 use crate::operations;
 use crate::operations::OperationHandler;
+use crate::operations::OperationQueue;
 use crate::supported_operations::operation::get_child_ops;
 use crate::supported_operations::operation::Operation;
 use crate::supported_operations::operation::ResultFormat;
@@ -20,6 +24,7 @@ use crate::supported_operations::OperationsError;
 use crate::supported_operations::SupportedOperations;
 use anyhow::Context;
 use c8y_api::json_c8y::C8yCreateEvent;
+use c8y_api::json_c8y::C8yMeasurementCollection;
 use c8y_api::json_c8y_deserializer::C8yDeviceControlOperation;
 use c8y_api::json_c8y_deserializer::C8yDeviceControlTopic;
 use c8y_api::json_c8y_deserializer::C8yJsonOverMqttDeserializerError;
@@ -102,6 +107,8 @@ const EARLY_MESSAGE_BUFFER_SIZE: usize = 100;
 #[derive(Debug)]
 pub struct MapperConfig {
     pub http_event_topic: TopicFilter,
+    pub http_measurements_topic: TopicFilter,
+    pub http_inventory_topic: TopicFilter,
     pub errors_topic: Topic,
 }
 
@@ -171,6 +178,8 @@ pub struct CumulocityConverter {
     pub service_type: String,
     pub mqtt_schema: MqttSchema,
     pub(crate) entity_cache: EntityCache,
+    // The persistent log of what has already been sent to the cloud for each entity
+    pub(crate) entity_cache_log: EntityCacheLog,
 
     pub command_id: IdGenerator,
     // Keep active command IDs to avoid creation of multiple commands for an operation
@@ -180,6 +189,7 @@ pub struct CumulocityConverter {
 
     pub supported_operations: SupportedOperations,
     pub operation_handler: OperationHandler,
+    pub(crate) operation_queue: OperationQueue,
 }
 
 impl CumulocityConverter {
@@ -221,8 +231,14 @@ impl CumulocityConverter {
         let mqtt_schema = config.mqtt_schema.clone();
         let http_event_topic =
             EventConverter::http_event_topic_filter(&config.bridge_config.c8y_prefix);
+        let http_measurements_topic =
+            MeasurementConverter::http_measurements_topic_filter(&config.bridge_config.c8y_prefix);
+        let http_inventory_topic =
+            http_inventory_update_topic_filter(&config.bridge_config.c8y_prefix);
         let mapper_config = MapperConfig {
             http_event_topic,
+            http_measurements_topic,
+            http_inventory_topic,
             errors_topic: mqtt_schema.error_topic(),
         };
 
@@ -235,8 +251,11 @@ impl CumulocityConverter {
             Self::validate_external_id,
             EARLY_MESSAGE_BUFFER_SIZE,
         );
+        let entity_cache_log = EntityCacheLog::open(&config.state_dir, config.clean_start);
 
         let command_id = config.id_generator();
+        let operation_queue =
+            OperationQueue::new(mqtt_schema.clone(), config.operation_limits.clone());
 
         let operation_handler = OperationHandler::new(
             &config,
@@ -257,11 +276,13 @@ impl CumulocityConverter {
             service_type,
             mqtt_schema: mqtt_schema.clone(),
             entity_cache,
+            entity_cache_log,
             command_id,
             active_commands: HashMap::new(),
             recently_completed_commands: HashMap::new(),
             active_commands_last_cleared: Instant::now(),
             operation_handler,
+            operation_queue,
         })
     }
 
@@ -284,6 +305,7 @@ impl CumulocityConverter {
         if message.payload().is_empty() {
             // Clear cached entity
             self.entity_cache.delete(&topic_id);
+            self.entity_cache_log.delete(&topic_id);
             return Ok(UpdateOutcome::Deleted);
         }
 
@@ -351,7 +373,15 @@ impl CumulocityConverter {
         };
 
         if let Some(reg_message) = reg_message {
-            messages.push(reg_message);
+            // Skip the registration if already done by a previous run of the mapper
+            let external_id = external_id.clone();
+            if self.entity_cache_log.registration_changed(
+                entity_topic_id,
+                &external_id,
+                &reg_message,
+            ) {
+                messages.push(reg_message);
+            }
         }
 
         for (fragment_key, fragment_value) in input.twin_data.iter() {
@@ -469,6 +499,39 @@ impl CumulocityConverter {
         Ok(())
     }
 
+    async fn post_measurements_over_http(
+        &mut self,
+        message: &MqttMessage,
+    ) -> Result<(), ConversionError> {
+        let collection: C8yMeasurementCollection = serde_json::from_slice(message.payload_bytes())?;
+        self.http_proxy
+            .send_measurements(collection.measurements)
+            .await?;
+        Ok(())
+    }
+
+    async fn update_inventory_over_http(
+        &mut self,
+        message: &MqttMessage,
+    ) -> Result<(), ConversionError> {
+        let fragments: serde_json::Value = serde_json::from_slice(message.payload_bytes())?;
+        let device_id = message
+            .topic
+            .as_ref()
+            .rsplit_once('/')
+            .map(|(_, id)| id.to_string())
+            .unwrap_or_else(|| {
+                self.entity_cache
+                    .main_device_external_id()
+                    .as_ref()
+                    .to_string()
+            });
+        self.http_proxy
+            .update_managed_object(&device_id, &fragments)
+            .await?;
+        Ok(())
+    }
+
     async fn parse_c8y_devicecontrol_topic(
         &mut self,
         message: &MqttMessage,
@@ -586,6 +649,21 @@ impl CumulocityConverter {
                     vec![]
                 }
             }
+            C8yDeviceControlOperation::Command(request) => {
+                if self.has_custom_operation_handler(&device_xid, message, "c8y_Command") {
+                    // A custom c8y_Command operation takes precedence over the builtin command operation
+                    return self
+                        .process_json_custom_operation(
+                            operation_id,
+                            cmd_id,
+                            device_xid,
+                            extras,
+                            message,
+                        )
+                        .await;
+                }
+                self.convert_command_request(device_xid, cmd_id, request)?
+            }
             C8yDeviceControlOperation::Custom => {
                 return self
                     .process_json_custom_operation(
@@ -631,6 +709,22 @@ impl CumulocityConverter {
         Ok(output)
     }
 
+    fn has_custom_operation_handler(
+        &self,
+        device_xid: &str,
+        message: &MqttMessage,
+        fragment: &str,
+    ) -> bool {
+        self.supported_operations
+            .get_operation_handlers(
+                device_xid,
+                &message.topic.name,
+                &self.config.bridge_config.c8y_prefix,
+            )
+            .iter()
+            .any(|(on_fragment, _)| on_fragment == fragment)
+    }
+
     async fn process_json_custom_operation(
         &self,
         operation_id: String,
@@ -1110,12 +1204,22 @@ impl CumulocityConverter {
             Channel::Event { .. } => Ok(vec![]),
             Channel::Alarm { .. } => Ok(vec![]),
 
+            Channel::Command {
+                operation: OperationType::FileUpload,
+                ..
+            } => {
+                // file_upload commands are requested by local components and executed by the mapper
+                let entity = self.operation_entity_target(&source)?;
+                self.operation_handler.handle(entity, message.clone()).await;
+                Ok(vec![])
+            }
+
             Channel::Command { cmd_id, .. } if message.payload_bytes().is_empty() => {
                 // The command has been fully processed
                 self.active_commands.remove(cmd_id);
                 self.recently_completed_commands
                     .insert(cmd_id.to_owned(), Instant::now());
-                Ok(vec![])
+                Ok(self.operation_queue.update(message))
             }
 
             Channel::CommandMetadata { operation } => {
@@ -1147,6 +1251,7 @@ impl CumulocityConverter {
                     OperationType::DeviceProfile => {
                         self.register_device_profile_operation(&source).await
                     }
+                    OperationType::Command => self.register_command_operation(&source).await,
                     OperationType::Custom(command_name) => {
                         self.register_custom_operation(&source, command_name).await
                     }
@@ -1160,16 +1265,11 @@ impl CumulocityConverter {
                 // time to `None` to disable the time-based expiry
                 self.active_commands.insert(cmd_id.clone(), None);
 
-                let entity = self.entity_cache.try_get(&source)?;
-                let entity = operations::EntityTarget {
-                    topic_id: entity.topic_id().clone(),
-                    external_id: entity.external_id.clone(),
-                    smartrest_publish_topic: self
-                        .smartrest_publish_topic_for_entity(entity.topic_id())?,
-                };
-
+                let entity = self.operation_entity_target(&source)?;
                 self.operation_handler.handle(entity, message.clone()).await;
-                Ok(vec![])
+
+                // Start any queued operation for which a slot has been freed
+                Ok(self.operation_queue.update(message))
             }
 
             Channel::Signal { signal_type } => self.process_signal_message(&source, signal_type),
@@ -1180,6 +1280,18 @@ impl CumulocityConverter {
         }
     }
 
+    fn operation_entity_target(
+        &self,
+        source: &EntityTopicId,
+    ) -> Result<operations::EntityTarget, ConversionError> {
+        let entity = self.entity_cache.try_get(source)?;
+        Ok(operations::EntityTarget {
+            topic_id: entity.topic_id().clone(),
+            external_id: entity.external_id.clone(),
+            smartrest_publish_topic: self.smartrest_publish_topic_for_entity(entity.topic_id())?,
+        })
+    }
+
     async fn try_convert_tedge_and_c8y_topics(
         &mut self,
         message: &MqttMessage,
@@ -1236,13 +1348,27 @@ impl CumulocityConverter {
                 self.post_event_over_http(message).await?;
                 Ok(vec![])
             }
+            topic
+                if self
+                    .mapper_config
+                    .http_measurements_topic
+                    .accept_topic(topic) =>
+            {
+                self.post_measurements_over_http(message).await?;
+                Ok(vec![])
+            }
+            topic if self.mapper_config.http_inventory_topic.accept_topic(topic) => {
+                self.update_inventory_over_http(message).await?;
+                Ok(vec![])
+            }
             _ => {
                 error!("Unsupported topic: {}", message.topic.name);
                 Ok(vec![])
             }
         }?;
 
-        Ok(messages)
+        // Hold the new commands that cannot be started yet
+        Ok(self.operation_queue.schedule(messages))
     }
 
     fn try_init_messages(&mut self) -> Result<Vec<MqttMessage>, ConversionError> {
@@ -1372,7 +1498,7 @@ impl CumulocityConverter {
         }
     }
 
-    async fn register_custom_operation(
+    pub(crate) async fn register_custom_operation(
         &mut self,
         target: &EntityTopicId,
         command_name: &str,
@@ -1461,6 +1587,7 @@ pub(crate) mod tests {
     use crate::actor::IdUploadResult;
     use crate::config::BridgeConfig;
     use crate::config::C8yMapperConfig;
+    use crate::config::OperationLimits;
     use crate::entity_cache::InvalidExternalIdError;
     use crate::mea::alarms::AlarmConverter;
     use crate::mea::health::HealthStatusConverter;
@@ -1656,6 +1783,62 @@ pub(crate) mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn only_entity_changes_are_sent_after_a_restart() {
+        let tmp_dir = TempTedgeDir::new();
+        let registration = MqttMessage::new(
+            &Topic::new_unchecked("te/device/child1//"),
+            json!({"@type":"child-device", "name":"child1"}).to_string(),
+        );
+        let os_twin = MqttMessage::new(
+            &Topic::new_unchecked("te/device/child1///twin/os"),
+            json!({"version":"1.0"}).to_string(),
+        );
+        let hw_twin = MqttMessage::new(
+            &Topic::new_unchecked("te/device/child1///twin/hw"),
+            json!({"model":"rpi"}).to_string(),
+        );
+
+        let mut config = c8y_converter_config(&tmp_dir);
+        config.clean_start = false;
+        let (mut converter, _http_proxy) = create_c8y_converter_from_config(config);
+        converter
+            .process_entity_metadata_message(&registration)
+            .await
+            .unwrap();
+        assert_eq!(converter.convert(&registration).await.len(), 1);
+        assert_eq!(converter.convert(&os_twin).await.len(), 1);
+        drop(converter);
+
+        // On restart, the retained messages are received again
+        let mut config = c8y_converter_config(&tmp_dir);
+        config.clean_start = false;
+        let (mut converter, _http_proxy) = create_c8y_converter_from_config(config);
+        converter
+            .process_entity_metadata_message(&registration)
+            .await
+            .unwrap();
+        assert!(converter.convert(&registration).await.is_empty());
+        assert!(converter.convert(&os_twin).await.is_empty());
+        assert_messages_matching(
+            &converter.convert(&hw_twin).await,
+            [(
+                "c8y/inventory/managedObjects/update/test-device:device:child1",
+                json!({"hw":{"model":"rpi"}}).into(),
+            )],
+        );
+        drop(converter);
+
+        // Unless on a clean start
+        let config = c8y_converter_config(&tmp_dir);
+        let (mut converter, _http_proxy) = create_c8y_converter_from_config(config);
+        converter
+            .process_entity_metadata_message(&registration)
+            .await
+            .unwrap();
+        assert_eq!(converter.convert(&registration).await.len(), 1);
+    }
+
     #[tokio::test]
     async fn convert_child_device_registration_control_is_device_fragment() {
         let tmp_dir = TempTedgeDir::new();
@@ -2413,6 +2596,45 @@ pub(crate) mod tests {
         assert_eq!(converter.try_convert(&operation).await.unwrap(), vec![]);
     }
 
+    #[tokio::test]
+    async fn operations_are_queued_while_a_restart_is_running() {
+        let tmp_dir = TempTedgeDir::new();
+        let (mut converter, _http_proxy) = create_c8y_converter(&tmp_dir);
+
+        let restart = MqttMessage::new(&Topic::new_unchecked("c8y/devicecontrol/notifications"), json!(
+            {"id":"1","status":"PENDING","c8y_Restart":{},"externalSource":{"externalId":"test-device","type":"c8y_Serial"}}
+        ).to_string());
+        let log_request = MqttMessage::new(&Topic::new_unchecked("c8y/devicecontrol/notifications"), json!(
+            {"id":"2","status":"PENDING","c8y_LogfileRequest":{"logFile":"syslog","dateFrom":"2023-01-01T00:00:00+0000","dateTo":"2023-01-02T00:00:00+0000","searchText":"","maximumLines":1000},"externalSource":{"externalId":"test-device","type":"c8y_Serial"}}
+        ).to_string());
+
+        let messages = converter.try_convert(&restart).await.unwrap();
+        assert_eq!(
+            messages[0].topic.name,
+            "te/device/main///cmd/restart/c8y-mapper-1"
+        );
+
+        // The log request is kept pending in Cumulocity until the restart completes
+        assert_eq!(converter.try_convert(&log_request).await.unwrap(), vec![]);
+
+        let restarting = MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///cmd/restart/c8y-mapper-1"),
+            json!({"status":"restarting"}).to_string(),
+        );
+        assert_eq!(converter.try_convert(&restarting).await.unwrap(), vec![]);
+
+        let restart_completion = MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///cmd/restart/c8y-mapper-1"),
+            "",
+        );
+        let messages = converter.try_convert(&restart_completion).await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(
+            messages[0].topic.name,
+            "te/device/main///cmd/log_upload/c8y-mapper-2"
+        );
+    }
+
     #[tokio::test]
     async fn custom_operations_are_deduplicated() {
         let tmp_dir = TempTedgeDir::new();
@@ -3138,6 +3360,7 @@ pub(crate) mod tests {
             false,
             16184,
             "3s",
+            OperationLimits::default(),
         )
     }
 
@@ -0,0 +1,378 @@
+//! The entity cache log persists what has already been sent to Cumulocity for each entity,
+//! so that the mapper only sends the changes when restarted.
+//!
+//! The underlying file is a JSON lines file, each line recording the registration of an entity,
+//! the update of one of its twin fragments or its deletion.
+//! Only digests of the messages sent to Cumulocity are persisted,
+//! the entity metadata being rebuilt on start from the retained registration messages.
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Write;
+use tedge_api::entity::EntityExternalId;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_mqtt_ext::MqttMessage;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+const LOG_FILE_NAME: &str = "entity_cache.jsonl";
+const LOG_FORMAT_VERSION: &str = "1.0";
+
+/// Number of log entries, in excess of the live entries, above which the log is compacted
+const COMPACTION_THRESHOLD: usize = 1000;
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "@event", rename_all = "kebab-case")]
+enum LogEntry {
+    Registered {
+        #[serde(rename = "@topic-id")]
+        topic_id: EntityTopicId,
+        #[serde(rename = "@id")]
+        external_id: EntityExternalId,
+        digest: String,
+    },
+    Twin {
+        #[serde(rename = "@topic-id")]
+        topic_id: EntityTopicId,
+        fragment: String,
+        digest: String,
+    },
+    Deleted {
+        #[serde(rename = "@topic-id")]
+        topic_id: EntityTopicId,
+    },
+}
+
+/// The cloud-side state of an entity, as sent by the mapper
+#[derive(Debug, Default)]
+struct CloudEntity {
+    registration: Option<(EntityExternalId, String)>,
+    twin_fragments: HashMap<String, String>,
+}
+
+/// Persisted state of the entities as registered in Cumulocity
+pub(crate) struct EntityCacheLog {
+    path: Utf8PathBuf,
+    entities: HashMap<EntityTopicId, CloudEntity>,
+    writer: Option<BufWriter<File>>,
+    entry_count: usize,
+}
+
+impl EntityCacheLog {
+    /// Open the entity cache log stored in the given directory
+    ///
+    /// On a clean start, the previous log is ignored and the whole state of the entities will be sent again.
+    /// Persistence errors are logged, the mapper then sending the whole state as if on a clean start.
+    pub fn open(state_dir: &Utf8Path, clean_start: bool) -> Self {
+        let path = state_dir.join(LOG_FILE_NAME);
+        let mut log = EntityCacheLog {
+            path,
+            entities: HashMap::new(),
+            writer: None,
+            entry_count: 0,
+        };
+        if !clean_start {
+            log.load();
+        }
+        log.compact();
+        log
+    }
+
+    /// Return true if the registration message has not already been sent to Cumulocity
+    ///
+    /// The message is then assumed sent and recorded in the log.
+    pub fn registration_changed(
+        &mut self,
+        topic_id: &EntityTopicId,
+        external_id: &EntityExternalId,
+        message: &MqttMessage,
+    ) -> bool {
+        let digest = digest([message]);
+        let entity = self.entities.entry(topic_id.clone()).or_default();
+        let registration = Some((external_id.clone(), digest.clone()));
+        if entity.registration == registration {
+            return false;
+        }
+        entity.registration = registration;
+        self.append(LogEntry::Registered {
+            topic_id: topic_id.clone(),
+            external_id: external_id.clone(),
+            digest,
+        });
+        true
+    }
+
+    /// Return true if the messages for a twin fragment have not already been sent to Cumulocity
+    ///
+    /// The messages are then assumed sent and recorded in the log.
+    pub fn twin_fragment_changed(
+        &mut self,
+        topic_id: &EntityTopicId,
+        fragment: &str,
+        messages: &[MqttMessage],
+    ) -> bool {
+        let digest = digest(messages);
+        let entity = self.entities.entry(topic_id.clone()).or_default();
+        if entity.twin_fragments.get(fragment) == Some(&digest) {
+            return false;
+        }
+        entity
+            .twin_fragments
+            .insert(fragment.to_string(), digest.clone());
+        self.append(LogEntry::Twin {
+            topic_id: topic_id.clone(),
+            fragment: fragment.to_string(),
+            digest,
+        });
+        true
+    }
+
+    /// Forget an entity, which will be registered again if re-created
+    pub fn delete(&mut self, topic_id: &EntityTopicId) {
+        if self.entities.remove(topic_id).is_some() {
+            self.append(LogEntry::Deleted {
+                topic_id: topic_id.clone(),
+            });
+        }
+    }
+
+    fn load(&mut self) {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return,
+            Err(err) => {
+                error!("Failed to read the entity cache log {} due to {err}. Ignoring and proceeding...", self.path);
+                return;
+            }
+        };
+
+        info!("Loading the entity cache from {}", self.path);
+        let mut lines = BufReader::new(file).lines();
+        // TODO: Validate if the read version is supported
+        let _version_info = lines.next();
+        for line in lines {
+            let entry = match line {
+                Ok(line) => serde_json::from_str::<LogEntry>(&line),
+                Err(err) => {
+                    error!("Failed to read the entity cache log due to {err}");
+                    return;
+                }
+            };
+            match entry {
+                Ok(entry) => self.apply(entry),
+                Err(err) => warn!("Ignoring entity cache log entry: {err}"),
+            }
+        }
+    }
+
+    fn apply(&mut self, entry: LogEntry) {
+        match entry {
+            LogEntry::Registered {
+                topic_id,
+                external_id,
+                digest,
+            } => {
+                self.entities.entry(topic_id).or_default().registration =
+                    Some((external_id, digest));
+            }
+            LogEntry::Twin {
+                topic_id,
+                fragment,
+                digest,
+            } => {
+                self.entities
+                    .entry(topic_id)
+                    .or_default()
+                    .twin_fragments
+                    .insert(fragment, digest);
+            }
+            LogEntry::Deleted { topic_id } => {
+                self.entities.remove(&topic_id);
+            }
+        }
+    }
+
+    /// The entries that are enough to rebuild the current state
+    fn live_entries(&self) -> Vec<LogEntry> {
+        let mut entries = vec![];
+        for (topic_id, entity) in self.entities.iter() {
+            if let Some((external_id, digest)) = &entity.registration {
+                entries.push(LogEntry::Registered {
+                    topic_id: topic_id.clone(),
+                    external_id: external_id.clone(),
+                    digest: digest.clone(),
+                });
+            }
+            for (fragment, digest) in entity.twin_fragments.iter() {
+                entries.push(LogEntry::Twin {
+                    topic_id: topic_id.clone(),
+                    fragment: fragment.clone(),
+                    digest: digest.clone(),
+                });
+            }
+        }
+        entries
+    }
+
+    /// Rewrite the log with only the entries describing the current state
+    fn compact(&mut self) {
+        let entries = self.live_entries();
+        self.entry_count = entries.len();
+        self.writer = match self.rewrite(&entries) {
+            Ok(writer) => Some(writer),
+            Err(err) => {
+                error!(
+                    "Failed to persist the entity cache log {} due to {err}",
+                    self.path
+                );
+                None
+            }
+        };
+    }
+
+    fn rewrite(&self, entries: &[LogEntry]) -> Result<BufWriter<File>, std::io::Error> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp_path = self.path.with_extension("jsonl.tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            let version_info = json!({ "version": LOG_FORMAT_VERSION }).to_string();
+            writeln!(writer, "{}", version_info)?;
+            for entry in entries {
+                writeln!(writer, "{}", serde_json::to_string(entry)?)?;
+            }
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+        std::fs::rename(&tmp_path, &self.path)?;
+
+        let file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(BufWriter::new(file))
+    }
+
+    fn append(&mut self, entry: LogEntry) {
+        let Some(writer) = self.writer.as_mut() else {
+            return;
+        };
+        if let Err(err) = append_entry(writer, &entry) {
+            error!(
+                "Failed to persist the entity cache log {} due to {err}",
+                self.path
+            );
+            return;
+        }
+
+        self.entry_count += 1;
+        if self.entry_count > self.live_entry_count() + COMPACTION_THRESHOLD {
+            self.compact();
+        }
+    }
+
+    fn live_entry_count(&self) -> usize {
+        self.entities
+            .values()
+            .map(|entity| entity.registration.iter().count() + entity.twin_fragments.len())
+            .sum()
+    }
+}
+
+fn append_entry(writer: &mut BufWriter<File>, entry: &LogEntry) -> Result<(), std::io::Error> {
+    let json_line = serde_json::to_string(entry)?;
+    writeln!(writer, "{}", json_line)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()
+}
+
+/// A digest of the messages sent to Cumulocity, covering topics and payloads
+fn digest<'a>(messages: impl IntoIterator<Item = &'a MqttMessage>) -> String {
+    let mut content = Vec::new();
+    for message in messages {
+        content.extend_from_slice(message.topic.name.as_bytes());
+        content.push(0);
+        content.extend_from_slice(message.payload_bytes());
+        content.push(0);
+    }
+    sha256::digest(content.as_slice())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tedge_mqtt_ext::Topic;
+    use tedge_test_utils::fs::TempTedgeDir;
+
+    #[test]
+    fn only_changes_are_sent_after_a_restart() {
+        let ttd = TempTedgeDir::new();
+        let state_dir = ttd.utf8_path();
+        let child: EntityTopicId = "device/child1//".parse().unwrap();
+        let child_xid: EntityExternalId = "test-device:device:child1".into();
+        let registration = message("c8y/s/us", "101,test-device:device:child1,child1");
+        let os_v1 = message(
+            "c8y/inventory/managedObjects/update/child1",
+            r#"{"os":"v1"}"#,
+        );
+        let os_v2 = message(
+            "c8y/inventory/managedObjects/update/child1",
+            r#"{"os":"v2"}"#,
+        );
+
+        let mut log = EntityCacheLog::open(state_dir, false);
+        assert!(log.registration_changed(&child, &child_xid, &registration));
+        assert!(!log.registration_changed(&child, &child_xid, &registration));
+        assert!(log.twin_fragment_changed(&child, "os", std::slice::from_ref(&os_v1)));
+        drop(log);
+
+        let mut log = EntityCacheLog::open(state_dir, false);
+        assert!(!log.registration_changed(&child, &child_xid, &registration));
+        assert!(!log.twin_fragment_changed(&child, "os", std::slice::from_ref(&os_v1)));
+        assert!(log.twin_fragment_changed(&child, "os", std::slice::from_ref(&os_v2)));
+        log.delete(&child);
+        drop(log);
+
+        let mut log = EntityCacheLog::open(state_dir, false);
+        assert!(log.registration_changed(&child, &child_xid, &registration));
+        assert!(log.twin_fragment_changed(&child, "os", &[os_v2]));
+        drop(log);
+
+        let mut log = EntityCacheLog::open(state_dir, true);
+        assert!(log.registration_changed(&child, &child_xid, &registration));
+    }
+
+    #[test]
+    fn the_log_is_compacted() {
+        let ttd = TempTedgeDir::new();
+        let state_dir = ttd.utf8_path();
+        let main: EntityTopicId = "device/main//".parse().unwrap();
+
+        let mut log = EntityCacheLog::open(state_dir, false);
+        for i in 0..(2 * COMPACTION_THRESHOLD) {
+            let update = message(
+                "c8y/inventory/managedObjects/update/main",
+                &format!(r#"{{"uptime":{i}}}"#),
+            );
+            assert!(log.twin_fragment_changed(&main, "uptime", &[update]));
+        }
+        drop(log);
+
+        let lines = std::fs::read_to_string(state_dir.join(LOG_FILE_NAME))
+            .unwrap()
+            .lines()
+            .count();
+        assert!(lines <= COMPACTION_THRESHOLD + 2, "{lines} lines");
+    }
+
+    fn message(topic: &str, payload: &str) -> MqttMessage {
+        MqttMessage::new(&Topic::new_unchecked(topic), payload)
+    }
+}
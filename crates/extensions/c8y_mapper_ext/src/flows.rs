@@ -26,6 +26,7 @@ impl C8yMapperBuilder {
             mapper_topic_id,
         ));
         flows.register_builtin(crate::mea::measurements::MeasurementConverter::default());
+        flows.register_builtin(crate::mea::supported_series::SupportedSeriesTracker::default());
         flows.register_builtin(crate::mea::events::EventConverter::default());
         flows.register_builtin(crate::mea::alarms::AlarmConverter::default());
         flows.register_builtin(crate::mea::health::HealthStatusConverter::default());
@@ -46,6 +47,10 @@ impl C8yMapperBuilder {
             .persist_builtin_flow("measurements", &self.measurements_flow())
             .await?;
 
+        flows
+            .persist_builtin_flow("supported-series", &self.supported_series_flow())
+            .await?;
+
         flows
             .persist_builtin_flow("events", &self.events_flow())
             .await?;
@@ -101,26 +106,51 @@ topic = "{errors_topic}"
         let topic_prefix = mqtt_schema.root.as_str();
         let errors_topic = mqtt_schema.error_topic();
         let c8y_prefix = &self.config.bridge_config.c8y_prefix;
-        let max_size = self.config.max_mqtt_payload_size;
+        let max_mqtt_payload_size = self.config.max_mqtt_payload_size;
+        let bridge_health_topic = &self.config.bridge_health_topic.name;
         let mut input_topics = self.configured_topics(&format!("{topic_prefix}/+/+/+/+/m/+"));
         if !input_topics.is_empty() {
             input_topics.push(format!("{topic_prefix}/{mapper_topic_id}/status/entities"));
+            input_topics.push(bridge_health_topic.to_string());
         }
 
         format!(
             r#"input.mqtt.topics = {input_topics:?}
 
-config = {{ topic_root = "{topic_prefix}" }}
+config = {{ topic_root = "{topic_prefix}", c8y_prefix = "{c8y_prefix}" }}
 
 steps = [
+    {{ builtin = "update-context", config = {{ topics = ["{bridge_health_topic}"] }} }},
     {{ builtin = "add-timestamp", config = {{ property = "time", format = "unix", reformat = false }} }},
     {{ builtin = "cache-early-messages" }},
-    {{ builtin = "into-c8y-measurements" }},
-    {{ builtin = "limit-payload-size", config = {{ max_size = {max_size} }} }},
+    {{ builtin = "into-c8y-measurements", config = {{ max_mqtt_payload_size = {max_mqtt_payload_size}, bridge_health_topic = "{bridge_health_topic}" }} }},
+]
+
+[output.mqtt]
+
+[errors.mqtt]
+topic = "{errors_topic}"
+"#
+        )
+    }
+
+    fn supported_series_flow(&self) -> String {
+        let mqtt_schema = &self.config.mqtt_schema;
+        let topic_prefix = mqtt_schema.root.as_str();
+        let errors_topic = mqtt_schema.error_topic();
+        let c8y_prefix = &self.config.bridge_config.c8y_prefix;
+        let input_topics = self.configured_topics(&format!("{topic_prefix}/+/+/+/+/m/+"));
+
+        format!(
+            r#"input.mqtt.topics = {input_topics:?}
+
+config = {{ topic_root = "{topic_prefix}", c8y_prefix = "{c8y_prefix}" }}
+
+steps = [
+    {{ builtin = "into-c8y-supported-series", interval = "1s", config = {{ debounce = "10s" }} }},
 ]
 
 [output.mqtt]
-topic = "{c8y_prefix}/measurement/measurements/create"
 
 [errors.mqtt]
 topic = "{errors_topic}"
@@ -181,6 +211,7 @@ config = {{ topic_root = "{topic_prefix}", c8y_prefix = "{c8y_prefix}" }}
 
 steps = [
     {{ builtin = "add-timestamp", config = {{ property = "time", format = "rfc3339", reformat = false }} }},
+    {{ builtin = "alarm-policy" }},
     {{ builtin = "cache-early-messages" }},
     {{ builtin = "into-c8y-alarms", interval = "{alarm_interval}" }},
     {{ builtin = "limit-payload-size", config = {{ max_size = {max_size} }} }},
@@ -242,6 +273,15 @@ mod tests {
         let _flow: FlowConfig = toml::from_str(&flow_specs).unwrap();
     }
 
+    #[tokio::test]
+    async fn check_supported_series_flow() {
+        let tmp_dir = TempTedgeDir::new();
+        let TestHandleBuilder { c8y, .. } = c8y_mapper_builder(&tmp_dir).await;
+        let flow_specs = c8y.supported_series_flow();
+
+        let _flow: FlowConfig = toml::from_str(&flow_specs).unwrap();
+    }
+
     #[tokio::test]
     async fn check_units_flow() {
         let tmp_dir = TempTedgeDir::new();
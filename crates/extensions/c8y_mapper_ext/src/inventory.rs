@@ -10,8 +10,10 @@ use tedge_api::mqtt_topics::EntityTopicId;
 use tedge_config::models::TopicPrefix;
 use tedge_mqtt_ext::MqttMessage;
 use tedge_mqtt_ext::Topic;
+use tedge_mqtt_ext::TopicFilter;
 
 const INVENTORY_MANAGED_OBJECTS_TOPIC: &str = "inventory/managedObjects/update";
+const HTTP_INVENTORY_MANAGED_OBJECTS_TOPIC: &str = "http/inventory/managedObjects/update";
 
 impl CumulocityConverter {
     /// Creates the inventory update message with c8y_Agent fragment
@@ -68,8 +70,19 @@ impl CumulocityConverter {
         mut fragment_key: &str,
         fragment_value: &JsonValue,
     ) -> Result<Vec<MqttMessage>, ConversionError> {
-        if fragment_key == "firmware" {
-            fragment_key = "c8y_Firmware";
+        let twin_fragment = fragment_key;
+        let mut fragment_value = fragment_value;
+        let c8y_network;
+        match fragment_key {
+            "firmware" => fragment_key = "c8y_Firmware",
+            "hardware" => fragment_key = "c8y_Hardware",
+            "mobile" => fragment_key = "c8y_Mobile",
+            "network" => {
+                fragment_key = "c8y_Network";
+                c8y_network = c8y_network_fragment(fragment_value);
+                fragment_value = &c8y_network;
+            }
+            _ => {}
         }
 
         // All services in C8Y must have a fixed `type` fragment called `c8y_Service`.
@@ -81,21 +94,36 @@ impl CumulocityConverter {
 
         let mapped_json = json!({ fragment_key: fragment_value });
         let mapped_message = self.inventory_update_message(source, mapped_json)?;
-        Ok(vec![mapped_message])
+        let messages = vec![mapped_message];
+
+        // Skip the update if already sent by a previous run of the mapper
+        if !self
+            .entity_cache_log
+            .twin_fragment_changed(source, twin_fragment, &messages)
+        {
+            return Ok(vec![]);
+        }
+        Ok(messages)
     }
 
     /// Create a Cumulocity inventory update message from a JSON fragment
+    ///
+    /// If too large to be sent over MQTT, the update is published on a local topic
+    /// from where it is forwarded to Cumulocity over HTTP.
     fn inventory_update_message(
         &self,
         source: &EntityTopicId,
         fragment_value: JsonValue,
     ) -> Result<MqttMessage, ConversionError> {
-        let inventory_update_topic = self.get_inventory_update_topic(source)?;
+        let payload = fragment_value.to_string();
+        let inventory_update_topic = if payload.len() < self.config.max_mqtt_payload_size as usize {
+            self.get_inventory_update_topic(source)?
+        } else {
+            let entity_external_id = self.entity_cache.try_get(source)?.external_id.as_ref();
+            http_inventory_update_topic(&self.config.bridge_config.c8y_prefix, entity_external_id)
+        };
 
-        Ok(MqttMessage::new(
-            &inventory_update_topic,
-            fragment_value.to_string(),
-        ))
+        Ok(MqttMessage::new(&inventory_update_topic, payload))
     }
 
     /// Returns the JSON over MQTT inventory update topic
@@ -110,12 +138,52 @@ impl CumulocityConverter {
     }
 }
 
+/// Complete the `network` twin fragment published by tedge-agent with a `c8y_LAN` fragment
+///
+/// The `c8y_LAN` interface is the first interface that is up with an IPv4 address.
+fn c8y_network_fragment(network: &JsonValue) -> JsonValue {
+    let mut c8y_network = network.clone();
+    let lan = network["interfaces"].as_array().and_then(|interfaces| {
+        interfaces.iter().find_map(|interface| {
+            if interface["state"] != "up" {
+                return None;
+            }
+            let (ip, prefix) = interface["ipv4"].get(0)?.as_str()?.split_once('/')?;
+            let prefix = prefix.parse::<u32>().ok().filter(|prefix| *prefix <= 32)?;
+            let netmask = std::net::Ipv4Addr::from(u32::MAX.checked_shl(32 - prefix).unwrap_or(0));
+            Some(json!({
+                "name": interface["name"],
+                "mac": interface["mac"],
+                "ip": ip,
+                "netmask": netmask.to_string(),
+                "enabled": 1,
+            }))
+        })
+    });
+    if let (Some(lan), Some(c8y_network)) = (lan, c8y_network.as_object_mut()) {
+        c8y_network.insert("c8y_LAN".to_string(), lan);
+    }
+    c8y_network
+}
+
 pub fn inventory_update_topic(prefix: &TopicPrefix, external_id: &str) -> Topic {
     Topic::new_unchecked(&format!(
         "{prefix}/{INVENTORY_MANAGED_OBJECTS_TOPIC}/{external_id}",
     ))
 }
 
+fn http_inventory_update_topic(prefix: &TopicPrefix, external_id: &str) -> Topic {
+    Topic::new_unchecked(&format!(
+        "{prefix}/{HTTP_INVENTORY_MANAGED_OBJECTS_TOPIC}/{external_id}",
+    ))
+}
+
+pub fn http_inventory_update_topic_filter(prefix: &TopicPrefix) -> TopicFilter {
+    TopicFilter::new_unchecked(&format!(
+        "{prefix}/{HTTP_INVENTORY_MANAGED_OBJECTS_TOPIC}/+"
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::converter::tests::create_c8y_converter;
@@ -155,6 +223,24 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn oversized_twin_data_is_sent_over_http() {
+        let tmp_dir = TempTedgeDir::new();
+        let (mut converter, _http_proxy) = create_c8y_converter(&tmp_dir);
+
+        let twin_message = MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///twin/large"),
+            json!({ "text": "x".repeat(20 * 1024) }).to_string(),
+        );
+        let inventory_messages = converter.convert(&twin_message).await;
+
+        assert_eq!(inventory_messages.len(), 1);
+        assert_eq!(
+            inventory_messages[0].topic.name,
+            "c8y/http/inventory/managedObjects/update/test-device"
+        );
+    }
+
     #[tokio::test]
     async fn convert_entity_twin_data_string_value() {
         let tmp_dir = TempTedgeDir::new();
@@ -458,6 +544,59 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn convert_device_inventory_fragments() {
+        let tmp_dir = TempTedgeDir::new();
+        let (mut converter, _http_proxy) = create_c8y_converter(&tmp_dir);
+
+        let hardware = MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///twin/hardware"),
+            r#"{"model":"Raspberry Pi 4 Model B Rev 1.1","serialNumber":"10000000d8e3f1d2","revision":"c03111"}"#,
+        );
+        assert_messages_matching(
+            &converter.convert(&hardware).await,
+            [(
+                "c8y/inventory/managedObjects/update/test-device",
+                json!({"c8y_Hardware":{"model":"Raspberry Pi 4 Model B Rev 1.1","serialNumber":"10000000d8e3f1d2","revision":"c03111"}}).into(),
+            )],
+        );
+
+        let network = MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///twin/network"),
+            json!({"interfaces":[
+                {"name":"wlan0","mac":"dc:a6:32:01:02:04","state":"down","ipv4":[],"ipv6":[]},
+                {"name":"eth0","mac":"dc:a6:32:01:02:03","state":"up","ipv4":["192.168.1.12/24"],"ipv6":["fe80::1/64"]},
+            ]})
+            .to_string(),
+        );
+        assert_messages_matching(
+            &converter.convert(&network).await,
+            [(
+                "c8y/inventory/managedObjects/update/test-device",
+                json!({"c8y_Network":{
+                    "c8y_LAN":{"name":"eth0","mac":"dc:a6:32:01:02:03","ip":"192.168.1.12","netmask":"255.255.255.0","enabled":1},
+                    "interfaces":[
+                        {"name":"wlan0","mac":"dc:a6:32:01:02:04","state":"down","ipv4":[],"ipv6":[]},
+                        {"name":"eth0","mac":"dc:a6:32:01:02:03","state":"up","ipv4":["192.168.1.12/24"],"ipv6":["fe80::1/64"]},
+                    ],
+                }})
+                .into(),
+            )],
+        );
+
+        let mobile = MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///twin/mobile"),
+            r#"{"imei":"356938035643809","operator":"ACME"}"#,
+        );
+        assert_messages_matching(
+            &converter.convert(&mobile).await,
+            [(
+                "c8y/inventory/managedObjects/update/test-device",
+                json!({"c8y_Mobile":{"imei":"356938035643809","operator":"ACME"}}).into(),
+            )],
+        );
+    }
+
     #[tokio::test]
     async fn convert_service_type() {
         let tmp_dir = TempTedgeDir::new();
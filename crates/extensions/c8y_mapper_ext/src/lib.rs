@@ -6,6 +6,7 @@ pub mod config;
 pub mod converter;
 pub mod dynamic_discovery;
 pub mod entity_cache;
+mod entity_cache_log;
 pub mod error;
 pub mod flows;
 mod fragments;
@@ -16,6 +17,7 @@ mod operations;
 mod serializer;
 pub mod service_monitor;
 mod signals;
+pub mod smartrest_templates;
 mod supported_operations;
 
 #[cfg(test)]
@@ -2,18 +2,53 @@ use crate::entity_cache::CloudEntityMetadata;
 use crate::json;
 use crate::mea::get_entity_metadata;
 use crate::mea::get_measurement_units;
+use serde_json::json;
+use std::collections::VecDeque;
 use std::time::SystemTime;
 use tedge_api::mqtt_topics::Channel;
 use tedge_api::mqtt_topics::MqttSchema;
+use tedge_config::models::TopicPrefix;
 use tedge_flows::ConfigError;
 use tedge_flows::FlowContextHandle;
 use tedge_flows::FlowError;
 use tedge_flows::JsonValue;
 use tedge_flows::Message;
+use tedge_mqtt_ext::TopicFilter;
 
-#[derive(Clone, Default)]
+const C8Y_JSON_MQTT_MEASUREMENTS_TOPIC: &str = "measurement/measurements/create";
+const C8Y_JSON_HTTP_MEASUREMENTS_TOPIC: &str = "http/measurements/create";
+
+/// Maximum number of measurements sent in a single HTTP request
+const MAX_HTTP_BATCH_SIZE: usize = 500;
+
+/// Maximum number of measurements kept in memory while the bridge is down
+const MAX_PENDING_MEASUREMENTS: usize = 10_000;
+
+#[derive(Clone)]
 pub struct MeasurementConverter {
     mqtt_schema: MqttSchema,
+    c8y_prefix: TopicPrefix,
+    max_mqtt_payload_size: Option<usize>,
+
+    /// Health topic of the Cumulocity bridge, as stored in the flow context
+    ///
+    /// When set, measurements are batched and sent over HTTP while the bridge is down.
+    bridge_health_topic: Option<String>,
+
+    /// Measurements waiting to be sent over HTTP
+    pending: VecDeque<serde_json::Value>,
+}
+
+impl Default for MeasurementConverter {
+    fn default() -> Self {
+        MeasurementConverter {
+            mqtt_schema: MqttSchema::default(),
+            c8y_prefix: TopicPrefix::try_new("c8y").unwrap(),
+            max_mqtt_payload_size: None,
+            bridge_health_topic: None,
+            pending: VecDeque::new(),
+        }
+    }
 }
 
 impl tedge_flows::Transformer for MeasurementConverter {
@@ -25,6 +60,18 @@ impl tedge_flows::Transformer for MeasurementConverter {
         if let Some(root) = config.string_property("topic_root") {
             self.mqtt_schema = MqttSchema::with_root(root.to_string())
         }
+        if let Some(c8y_prefix) = config.string_property("c8y_prefix") {
+            self.c8y_prefix = TopicPrefix::try_new(c8y_prefix).map_err(|err| {
+                ConfigError::IncorrectSetting(format!("Not a valid c8y topic prefix: {}", err))
+            })?;
+        }
+        self.max_mqtt_payload_size = config
+            .number_property("max_mqtt_payload_size")
+            .and_then(|n| n.as_u64())
+            .map(|n| n as usize);
+        self.bridge_health_topic = config
+            .string_property("bridge_health_topic")
+            .map(|topic| topic.to_string());
         Ok(())
     }
 
@@ -45,8 +92,21 @@ impl tedge_flows::Transformer for MeasurementConverter {
                     return Ok(vec![]);
                 };
 
-                let measurement = self.convert(context, entity, &measurement_type, payload)?;
-                Ok(vec![measurement])
+                let measurement =
+                    self.convert(context, entity.clone(), &measurement_type, payload)?;
+                if self.can_send_over_mqtt(&measurement) && !self.is_bridge_down(context) {
+                    return Ok(vec![measurement]);
+                }
+
+                // The measurement must be sent over HTTP
+                // Actually this converter forwards this measurement over MQTT to the c8y converter which does the HTTP request
+                let http_measurement = Self::http_measurement(&entity, &measurement)?;
+                if self.is_bridge_down(context) {
+                    self.push_pending(http_measurement);
+                    Ok(vec![])
+                } else {
+                    Ok(vec![self.http_measurements_message(vec![http_measurement])])
+                }
             }
             _ => Err(FlowError::UnsupportedMessage(format!(
                 "Not a measurement topic: {}",
@@ -54,6 +114,24 @@ impl tedge_flows::Transformer for MeasurementConverter {
             ))),
         }
     }
+
+    fn is_periodic(&self) -> bool {
+        self.bridge_health_topic.is_some()
+    }
+
+    fn on_interval(
+        &mut self,
+        _timestamp: SystemTime,
+        _context: &FlowContextHandle,
+    ) -> Result<Vec<Message>, FlowError> {
+        let mut messages = vec![];
+        while !self.pending.is_empty() {
+            let batch_size = self.pending.len().min(MAX_HTTP_BATCH_SIZE);
+            let batch = self.pending.drain(..batch_size).collect();
+            messages.push(self.http_measurements_message(batch));
+        }
+        Ok(messages)
+    }
 }
 
 impl MeasurementConverter {
@@ -78,8 +156,154 @@ impl MeasurementConverter {
             ));
         };
         Ok(Message::new(
-            "c8y/measurement/measurements/create",
+            format!("{}/{C8Y_JSON_MQTT_MEASUREMENTS_TOPIC}", self.c8y_prefix),
             c8y_json_payload,
         ))
     }
+
+    pub fn http_measurements_topic_filter(c8y_prefix: &TopicPrefix) -> TopicFilter {
+        TopicFilter::new_unchecked(&format!("{c8y_prefix}/{C8Y_JSON_HTTP_MEASUREMENTS_TOPIC}"))
+    }
+
+    /// A measurement collection to be posted over HTTP
+    fn http_measurements_message(&self, measurements: Vec<serde_json::Value>) -> Message {
+        let topic = format!("{}/{C8Y_JSON_HTTP_MEASUREMENTS_TOPIC}", self.c8y_prefix);
+        let payload = json!({ "measurements": measurements });
+        Message::new(topic, payload.to_string())
+    }
+
+    /// Add the external id of the source to a measurement, the source being implicit over MQTT for the main device
+    fn http_measurement(
+        entity: &CloudEntityMetadata,
+        measurement: &Message,
+    ) -> Result<serde_json::Value, FlowError> {
+        let mut measurement: serde_json::Value = serde_json::from_slice(&measurement.payload)
+            .map_err(|err| {
+                FlowError::UnsupportedMessage(format!("Not a Cumulocity measurement: {err}"))
+            })?;
+        if let Some(measurement) = measurement.as_object_mut() {
+            measurement.insert(
+                "externalSource".to_string(),
+                json!({"externalId": entity.external_id.as_ref(), "type": "c8y_Serial"}),
+            );
+        }
+        Ok(measurement)
+    }
+
+    fn push_pending(&mut self, measurement: serde_json::Value) {
+        if self.pending.len() >= MAX_PENDING_MEASUREMENTS {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(measurement);
+    }
+
+    fn can_send_over_mqtt(&self, message: &Message) -> bool {
+        let Some(max_size) = self.max_mqtt_payload_size else {
+            return true;
+        };
+        message.payload.len() < max_size
+    }
+
+    /// Check the bridge health status, as published by the builtin bridge (`{"status":"up"}`)
+    /// or the mosquitto bridge (`1`)
+    ///
+    /// The bridge is assumed up when its status is unknown.
+    fn is_bridge_down(&self, context: &FlowContextHandle) -> bool {
+        let Some(topic) = &self.bridge_health_topic else {
+            return false;
+        };
+        match context.get_value(topic) {
+            JsonValue::Null => false,
+            JsonValue::Number(status) => status.as_u64() != Some(1),
+            status => status.string_property("status") != Some("up"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tedge_api::entity::EntityMetadata;
+    use tedge_flows::Transformer;
+
+    const BRIDGE_HEALTH: &str = "te/device/main/service/tedge-mapper-bridge-c8y/status/health";
+
+    #[test]
+    fn oversized_measurements_are_sent_over_http() {
+        let mut converter = MeasurementConverter::default();
+        converter
+            .set_config(JsonValue::from(json!({"max_mqtt_payload_size": 100})))
+            .unwrap();
+        let context = FlowContextHandle::default();
+        register_main_device(&context);
+
+        let small = converter.on_message(SystemTime::now(), &measurement("x", 1), &context);
+        assert_eq!(
+            small.unwrap()[0].topic,
+            "c8y/measurement/measurements/create"
+        );
+
+        let large = converter.on_message(SystemTime::now(), &measurement("x", 20), &context);
+        let large = large.unwrap();
+        assert_eq!(large[0].topic, "c8y/http/measurements/create");
+        let payload: serde_json::Value = serde_json::from_slice(&large[0].payload).unwrap();
+        assert_eq!(
+            payload["measurements"][0]["externalSource"],
+            json!({"externalId": "test-device", "type": "c8y_Serial"})
+        );
+        assert_eq!(payload["measurements"][0]["x19"]["x19"]["value"], 19.0);
+    }
+
+    #[test]
+    fn measurements_are_batched_over_http_while_the_bridge_is_down() {
+        let mut converter = MeasurementConverter::default();
+        converter
+            .set_config(JsonValue::from(
+                json!({"bridge_health_topic": BRIDGE_HEALTH}),
+            ))
+            .unwrap();
+        assert!(converter.is_periodic());
+        let context = FlowContextHandle::default();
+        register_main_device(&context);
+
+        context.set_value(BRIDGE_HEALTH, JsonValue::from(json!({"status": "down"})));
+        for i in 1..=3 {
+            let messages = converter.on_message(SystemTime::now(), &measurement("x", i), &context);
+            assert_eq!(messages.unwrap(), vec![]);
+        }
+
+        let messages = converter.on_interval(SystemTime::now(), &context).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].topic, "c8y/http/measurements/create");
+        let payload: serde_json::Value = serde_json::from_slice(&messages[0].payload).unwrap();
+        assert_eq!(payload["measurements"].as_array().unwrap().len(), 3);
+        assert_eq!(
+            converter.on_interval(SystemTime::now(), &context).unwrap(),
+            vec![]
+        );
+
+        // The mosquitto bridge uses a different health status format
+        context.set_value(BRIDGE_HEALTH, JsonValue::from(json!(1)));
+        let messages = converter.on_message(SystemTime::now(), &measurement("x", 1), &context);
+        assert_eq!(
+            messages.unwrap()[0].topic,
+            "c8y/measurement/measurements/create"
+        );
+    }
+
+    fn measurement(prefix: &str, series: usize) -> Message {
+        let payload: serde_json::Map<String, serde_json::Value> = (0..series)
+            .map(|i| (format!("{prefix}{i}"), json!(i as f64)))
+            .collect();
+        Message::new(
+            "te/device/main///m/",
+            serde_json::Value::Object(payload).to_string(),
+        )
+    }
+
+    fn register_main_device(context: &FlowContextHandle) {
+        let entity =
+            CloudEntityMetadata::new("test-device".into(), EntityMetadata::main_device(None));
+        context.set_value("device/main//", JsonValue::from_value(entity).unwrap());
+    }
 }
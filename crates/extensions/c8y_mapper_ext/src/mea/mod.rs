@@ -9,6 +9,7 @@ pub mod events;
 pub mod health;
 pub mod measurements;
 pub mod message_cache;
+pub mod supported_series;
 
 fn get_entity_metadata(context: &FlowContextHandle, entity: &str) -> Option<CloudEntityMetadata> {
     let json = context.get_value(entity);
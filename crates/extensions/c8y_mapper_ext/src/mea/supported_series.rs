@@ -0,0 +1,250 @@
+use crate::inventory::inventory_update_topic;
+use crate::mea::get_entity_metadata;
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::SystemTime;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_config::models::TopicPrefix;
+use tedge_flows::ConfigError;
+use tedge_flows::FlowContextHandle;
+use tedge_flows::FlowError;
+use tedge_flows::JsonValue;
+use tedge_flows::Message;
+
+/// Track the measurement series produced by each entity,
+/// to publish the `c8y_SupportedMeasurements` and `c8y_SupportedSeries` fragments when they change
+///
+/// The inventory update is debounced, being published only once no new series has been observed
+/// for an entity during the `debounce` period.
+#[derive(Clone)]
+pub struct SupportedSeriesTracker {
+    mqtt_schema: MqttSchema,
+    c8y_prefix: TopicPrefix,
+    debounce: Duration,
+    entities: HashMap<String, EntitySeries>,
+}
+
+#[derive(Clone, Default)]
+struct EntitySeries {
+    /// The measurement fragments and series as `<fragment>.<series>`
+    series: BTreeSet<(String, String)>,
+
+    /// When a series not yet published has been observed for the last time
+    updated_at: Option<SystemTime>,
+}
+
+impl Default for SupportedSeriesTracker {
+    fn default() -> Self {
+        SupportedSeriesTracker {
+            mqtt_schema: MqttSchema::default(),
+            c8y_prefix: TopicPrefix::try_new("c8y").unwrap(),
+            debounce: Duration::from_secs(10),
+            entities: HashMap::new(),
+        }
+    }
+}
+
+impl tedge_flows::Transformer for SupportedSeriesTracker {
+    fn name(&self) -> &str {
+        "into-c8y-supported-series"
+    }
+
+    fn set_config(&mut self, config: JsonValue) -> Result<(), ConfigError> {
+        if let Some(root) = config.string_property("topic_root") {
+            self.mqtt_schema = MqttSchema::with_root(root.to_string())
+        }
+        if let Some(c8y_prefix) = config.string_property("c8y_prefix") {
+            self.c8y_prefix = TopicPrefix::try_new(c8y_prefix).map_err(|err| {
+                ConfigError::IncorrectSetting(format!("Not a valid c8y topic prefix: {}", err))
+            })?;
+        }
+        if let Some(debounce) = config.string_property("debounce") {
+            self.debounce = humantime::parse_duration(debounce).map_err(|err| {
+                ConfigError::IncorrectSetting(format!("Not a valid debounce duration: {}", err))
+            })?;
+        }
+        Ok(())
+    }
+
+    fn on_message(
+        &mut self,
+        timestamp: SystemTime,
+        message: &Message,
+        _context: &FlowContextHandle,
+    ) -> Result<Vec<Message>, FlowError> {
+        let Ok((entity_id, Channel::Measurement { .. })) =
+            self.mqtt_schema.entity_channel_of(&message.topic)
+        else {
+            return Err(FlowError::UnsupportedMessage(format!(
+                "Not a measurement topic: {}",
+                message.topic
+            )));
+        };
+        let Ok(serde_json::Value::Object(measurement)) =
+            serde_json::from_slice::<serde_json::Value>(&message.payload)
+        else {
+            return Err(FlowError::UnsupportedMessage(
+                "Not a thin-edge measurement".to_string(),
+            ));
+        };
+
+        let entity = self.entities.entry(entity_id.to_string()).or_default();
+        for series in measurement_series(&measurement) {
+            if entity.series.insert(series) {
+                entity.updated_at = Some(timestamp);
+            }
+        }
+        Ok(vec![])
+    }
+
+    fn is_periodic(&self) -> bool {
+        true
+    }
+
+    fn on_interval(
+        &mut self,
+        timestamp: SystemTime,
+        context: &FlowContextHandle,
+    ) -> Result<Vec<Message>, FlowError> {
+        let mut updates = vec![];
+        for (entity_id, entity) in self.entities.iter_mut() {
+            let Some(updated_at) = entity.updated_at else {
+                continue;
+            };
+            if timestamp.duration_since(updated_at).unwrap_or_default() < self.debounce {
+                continue;
+            }
+            // The update is postponed till the entity is registered
+            let Some(metadata) = get_entity_metadata(context, entity_id) else {
+                continue;
+            };
+
+            entity.updated_at = None;
+            let measurements: BTreeSet<&str> = entity
+                .series
+                .iter()
+                .map(|(fragment, _)| fragment.as_str())
+                .collect();
+            let series: Vec<String> = entity
+                .series
+                .iter()
+                .map(|(fragment, series)| format!("{fragment}.{series}"))
+                .collect();
+            let topic = inventory_update_topic(&self.c8y_prefix, metadata.external_id.as_ref());
+            let payload = json!({
+                "c8y_SupportedMeasurements": measurements,
+                "c8y_SupportedSeries": series,
+            });
+            updates.push(Message::new(topic.name, payload.to_string()));
+        }
+        Ok(updates)
+    }
+}
+
+/// The fragments and series of a thin-edge measurement, as named by the measurement converter
+fn measurement_series(
+    measurement: &serde_json::Map<String, serde_json::Value>,
+) -> Vec<(String, String)> {
+    let mut series = vec![];
+    for (key, value) in measurement {
+        match value {
+            serde_json::Value::Number(_) if key != "time" => {
+                series.push((key.clone(), key.clone()));
+            }
+            serde_json::Value::Object(group) => {
+                for (name, value) in group {
+                    if value.is_number() {
+                        series.push((key.clone(), name.clone()));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    series
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tedge_api::entity::EntityMetadata;
+    use tedge_flows::Transformer;
+
+    #[test]
+    fn supported_series_are_published_once_stable() {
+        let mut tracker = SupportedSeriesTracker::default();
+        let context = FlowContextHandle::default();
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1763050414);
+        register_main_device(&context);
+
+        measure(&mut tracker, &context, t0, r#"{"temperature": 21.3}"#);
+        let t1 = t0 + Duration::from_secs(5);
+        measure(
+            &mut tracker,
+            &context,
+            t1,
+            r#"{"time": 1763050419, "temperature": 21.4, "env": {"humidity": 43, "label": "room"}}"#,
+        );
+
+        // Debounced till no new series is observed
+        let t2 = t1 + Duration::from_secs(5);
+        assert_eq!(tracker.on_interval(t2, &context).unwrap(), vec![]);
+        let t3 = t1 + Duration::from_secs(10);
+        let updates = tracker.on_interval(t3, &context).unwrap();
+        assert_eq!(
+            updates,
+            vec![Message::new(
+                "c8y/inventory/managedObjects/update/test-device",
+                json!({
+                    "c8y_SupportedMeasurements": ["env", "temperature"],
+                    "c8y_SupportedSeries": ["env.humidity", "temperature.temperature"],
+                })
+                .to_string()
+            )]
+        );
+
+        // Nothing is published till a new series is observed
+        measure(&mut tracker, &context, t3, r#"{"temperature": 21.5}"#);
+        let t4 = t3 + Duration::from_secs(20);
+        assert_eq!(tracker.on_interval(t4, &context).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn supported_series_are_published_once_the_entity_is_registered() {
+        let mut tracker = SupportedSeriesTracker::default();
+        let context = FlowContextHandle::default();
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1763050414);
+
+        measure(&mut tracker, &context, t0, r#"{"pressure": 1013}"#);
+        let t1 = t0 + Duration::from_secs(20);
+        assert_eq!(tracker.on_interval(t1, &context).unwrap(), vec![]);
+
+        register_main_device(&context);
+        let t2 = t1 + Duration::from_secs(1);
+        assert_eq!(tracker.on_interval(t2, &context).unwrap().len(), 1);
+    }
+
+    fn measure(
+        tracker: &mut SupportedSeriesTracker,
+        context: &FlowContextHandle,
+        timestamp: SystemTime,
+        payload: &str,
+    ) {
+        let message = Message::new("te/device/main///m/environment", payload);
+        assert_eq!(
+            tracker.on_message(timestamp, &message, context).unwrap(),
+            vec![]
+        );
+    }
+
+    fn register_main_device(context: &FlowContextHandle) {
+        let entity = crate::entity_cache::CloudEntityMetadata::new(
+            "test-device".into(),
+            EntityMetadata::main_device(None),
+        );
+        context.set_value("device/main//", JsonValue::from_value(entity).unwrap());
+    }
+}
@@ -1,5 +1,6 @@
 //! Converting Cumulocity Smartrest operation messages into local thin-edge operation messages.
 use crate::supported_operations::operation::Operation;
+use c8y_api::json_c8y_deserializer::C8yCommand;
 use c8y_api::json_c8y_deserializer::C8yDeviceProfile;
 use c8y_api::json_c8y_deserializer::C8yDownloadConfigFile;
 use c8y_api::json_c8y_deserializer::C8yFirmware;
@@ -17,6 +18,7 @@ use tedge_api::commands::ConfigUpdateCmdPayload;
 use tedge_api::commands::FirmwareUpdateCmdPayload;
 use tedge_api::commands::LogMetadata;
 use tedge_api::commands::LogUploadCmdPayload;
+use tedge_api::commands::ShellCommandCmdPayload;
 use tedge_api::device_profile::ConfigPayload;
 use tedge_api::device_profile::DeviceProfileCmdPayload;
 use tedge_api::entity::EntityExternalId;
@@ -150,6 +152,72 @@ impl CumulocityConverter {
         ])
     }
 
+    /// Convert c8y_Command operation to a ThinEdge command command
+    pub fn convert_command_request(
+        &self,
+        device_xid: String,
+        cmd_id: String,
+        command_request: C8yCommand,
+    ) -> Result<Vec<MqttMessage>, CumulocityMapperError> {
+        let target = self
+            .entity_cache
+            .try_get_by_external_id(&device_xid.into())?;
+
+        let channel = Channel::Command {
+            operation: OperationType::Command,
+            cmd_id: cmd_id.clone(),
+        };
+        let topic = self.mqtt_schema.topic_for(target.topic_id(), &channel);
+
+        let tedge_url = format!(
+            "http://{}/te/v1/files/{}/command/{}",
+            &self.config.tedge_http_host,
+            target.external_id.as_ref(),
+            cmd_id
+        );
+
+        let request = ShellCommandCmdPayload {
+            status: CommandStatus::Init,
+            command: command_request.text,
+            tedge_url: Some(tedge_url),
+            exit_code: None,
+            output_truncated: false,
+            log_path: None,
+        };
+
+        // Command messages must be retained
+        Ok(vec![
+            MqttMessage::new(&topic, request.to_json()).with_retain()
+        ])
+    }
+
+    /// Converts a command metadata message to supported operation "c8y_Command"
+    ///
+    /// Unless the `command` workflow is bound to a custom operation handler.
+    pub async fn register_command_operation(
+        &mut self,
+        topic_id: &EntityTopicId,
+    ) -> Result<Vec<MqttMessage>, ConversionError> {
+        let command_name = OperationType::Command.to_string();
+        if self
+            .supported_operations
+            .get_operation_name_by_workflow_operation(&command_name)
+            .is_some()
+        {
+            return self
+                .register_custom_operation(topic_id, &command_name)
+                .await;
+        }
+
+        match self.register_operation(topic_id, "c8y_Command").await {
+            Err(err) => {
+                error!("Failed to register `command` operation for {topic_id} due to: {err}");
+                Ok(vec![])
+            }
+            Ok(messages) => Ok(messages),
+        }
+    }
+
     /// Converts a log_upload metadata message to
     /// - supported operation "c8y_LogfileRequest"
     /// - supported log types
@@ -16,6 +16,7 @@ use tedge_actors::LoggingSender;
 use tedge_api::mqtt_topics::Channel;
 use tedge_api::mqtt_topics::ChannelFilter;
 use tedge_api::mqtt_topics::EntityFilter;
+use tedge_api::mqtt_topics::OperationType;
 use tedge_api::workflow::GenericCommandState;
 use tedge_mqtt_ext::MqttMessage;
 use tracing::debug;
@@ -59,6 +60,7 @@ impl OperationHandler {
                 capabilities: c8y_mapper_config.capabilities,
                 auto_log_upload: c8y_mapper_config.auto_log_upload,
                 tedge_http_host: c8y_mapper_config.tedge_http_host.clone(),
+                c8y_prefix: c8y_mapper_config.bridge_config.c8y_prefix.clone(),
                 tmp_dir: c8y_mapper_config.tmp_dir.clone(),
                 mqtt_schema: c8y_mapper_config.mqtt_schema.clone(),
                 mqtt_publisher: mqtt_publisher.clone(),
@@ -125,7 +127,10 @@ impl OperationHandler {
             return;
         }
 
-        if !self.context.command_id.is_generator_of(&cmd_id) {
+        // file_upload commands are requested by local components, not by c8y
+        if !self.context.command_id.is_generator_of(&cmd_id)
+            && operation != OperationType::FileUpload
+        {
             return;
         }
 
@@ -216,7 +221,6 @@ impl OperationHandler {
         use tedge_api::mqtt_topics::ChannelFilter::Command;
         use tedge_api::mqtt_topics::ChannelFilter::CommandMetadata;
         use tedge_api::mqtt_topics::EntityFilter::AnyEntity;
-        use tedge_api::mqtt_topics::OperationType;
 
         let mut topics = vec![];
 
@@ -0,0 +1,262 @@
+use super::error::OperationError;
+use super::EntityTarget;
+use super::OperationContext;
+use super::OperationOutcome;
+use anyhow::Context;
+use c8y_api::smartrest::smartrest_serializer::CumulocitySupportedOperations;
+use c8y_api::smartrest::smartrest_serializer::TextOrCsv;
+use tedge_api::commands::CommandStatus;
+use tedge_api::commands::ShellCommandCmd;
+use tedge_downloader_ext::DownloadRequest;
+use tedge_mqtt_ext::MqttMessage;
+
+impl OperationContext {
+    /// Address a received command command. If its status is
+    /// - "executing", it converts the message to SmartREST "Executing".
+    /// - "successful", it downloads the command output from the file transfer service
+    ///   and converts the message to SmartREST "Successful" with this output as result.
+    /// - "failed", it converts the message to SmartREST "Failed".
+    pub async fn handle_command_state_change(
+        &self,
+        target: &EntityTarget,
+        cmd_id: &str,
+        message: &MqttMessage,
+    ) -> Result<OperationOutcome, OperationError> {
+        let command = match ShellCommandCmd::try_from_bytes(
+            target.topic_id.clone(),
+            cmd_id.into(),
+            message.payload_bytes(),
+        )
+        .context("Could not parse command as a command command")?
+        {
+            Some(command) => command,
+            None => {
+                // The command has been fully processed
+                return Ok(OperationOutcome::Ignored);
+            }
+        };
+
+        match command.status() {
+            CommandStatus::Executing => Ok(OperationOutcome::Executing {
+                extra_messages: vec![],
+            }),
+            CommandStatus::Successful => {
+                let output = match &command.payload.tedge_url {
+                    Some(tedge_url) => self.download_command_output(cmd_id, tedge_url).await?,
+                    None => String::new(),
+                };
+
+                let smartrest_response = self
+                    .try_get_smartrest_successful_status_payload_with_args(
+                        CumulocitySupportedOperations::C8yCommand,
+                        cmd_id,
+                        TextOrCsv::Text(output),
+                    );
+                let c8y_notification =
+                    MqttMessage::new(&target.smartrest_publish_topic, smartrest_response);
+
+                Ok(OperationOutcome::Finished {
+                    messages: vec![c8y_notification],
+                })
+            }
+            CommandStatus::Failed { reason } => Err(anyhow::anyhow!(reason).into()),
+            _ => {
+                // Do nothing as other components might handle those states
+                Ok(OperationOutcome::Ignored)
+            }
+        }
+    }
+
+    async fn download_command_output(
+        &self,
+        cmd_id: &str,
+        tedge_url: &str,
+    ) -> Result<String, OperationError> {
+        let destination_dir = tempfile::tempdir_in(self.tmp_dir.as_std_path())
+            .context("Failed to create a temporary directory")?;
+        let destination_path = destination_dir.path().join(cmd_id);
+
+        let download_request = DownloadRequest::new(tedge_url, &destination_path);
+        let (_, download_result) = self
+            .downloader
+            .clone()
+            .await_response((cmd_id.into(), download_request))
+            .await
+            .context("Unexpected ChannelError")?;
+
+        let download_response = download_result.context(
+            "tedge-mapper-c8y failed to download command output from file transfer service",
+        )?;
+
+        let output = tokio::fs::read(&download_response.file_path)
+            .await
+            .context("Could not read the command output")?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::*;
+    use c8y_api::json_c8y_deserializer::C8yDeviceControlTopic;
+    use serde_json::json;
+    use std::time::Duration;
+    use tedge_actors::test_helpers::MessageReceiverExt;
+    use tedge_actors::MessageReceiver;
+    use tedge_actors::Sender;
+    use tedge_downloader_ext::DownloadResponse;
+    use tedge_mqtt_ext::test_helpers::assert_received_contains_str;
+    use tedge_mqtt_ext::test_helpers::assert_received_includes_json;
+    use tedge_mqtt_ext::MqttMessage;
+    use tedge_mqtt_ext::Topic;
+    use tedge_test_utils::fs::TempTedgeDir;
+
+    const TEST_TIMEOUT_MS: Duration = Duration::from_millis(3000);
+
+    #[tokio::test]
+    async fn mapper_converts_c8y_command_to_command_cmd() {
+        let ttd = TempTedgeDir::new();
+        let test_handle = spawn_c8y_mapper_actor(&ttd, true).await;
+
+        let TestHandle { mqtt, .. } = test_handle;
+        let mut mqtt = mqtt.with_timeout(TEST_TIMEOUT_MS);
+
+        skip_init_messages(&mut mqtt).await;
+
+        mqtt.send(MqttMessage::new(
+            &C8yDeviceControlTopic::topic(&"c8y".try_into().unwrap()),
+            json!({
+                "id": "123456",
+                "c8y_Command": {
+                    "text": "df -h /"
+                },
+                "externalSource": {
+                    "externalId": "test-device",
+                    "type": "c8y_Serial"
+                 }
+            })
+            .to_string(),
+        ))
+        .await
+        .expect("Send failed");
+
+        assert_received_includes_json(
+            &mut mqtt,
+            [(
+                "te/device/main///cmd/command/c8y-mapper-123456",
+                json!({
+                    "status": "init",
+                    "command": "df -h /",
+                    "tedgeUrl": "http://localhost:8888/te/v1/files/test-device/command/c8y-mapper-123456",
+                }),
+            )],
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn mapper_registers_c8y_command_operation() {
+        let ttd = TempTedgeDir::new();
+        let test_handle = spawn_c8y_mapper_actor(&ttd, true).await;
+
+        let TestHandle { mqtt, .. } = test_handle;
+        let mut mqtt = mqtt.with_timeout(TEST_TIMEOUT_MS);
+
+        skip_init_messages(&mut mqtt).await;
+
+        mqtt.send(
+            MqttMessage::new(&Topic::new_unchecked("te/device/main///cmd/command"), "{}")
+                .with_retain(),
+        )
+        .await
+        .expect("Send failed");
+
+        assert_received_contains_str(&mut mqtt, [("c8y/s/us", "114,c8y_Command")]).await;
+    }
+
+    #[tokio::test]
+    async fn handle_command_successful_cmd_with_its_output() {
+        let ttd = TempTedgeDir::new();
+        let test_handle = spawn_c8y_mapper_actor(&ttd, true).await;
+        let TestHandle { mqtt, dl, .. } = test_handle;
+
+        let mut mqtt = mqtt.with_timeout(TEST_TIMEOUT_MS);
+        let mut dl = dl.with_timeout(TEST_TIMEOUT_MS);
+        skip_init_messages(&mut mqtt).await;
+
+        mqtt.send(MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///cmd/command/c8y-mapper-1234"),
+            json!({
+                "status": "successful",
+                "command": "uptime",
+                "tedgeUrl": "http://localhost:8888/te/v1/files/test-device/command/c8y-mapper-1234",
+                "exitCode": 0
+            })
+            .to_string(),
+        ))
+        .await
+        .expect("Send failed");
+
+        // The command output is downloaded from the file transfer service
+        let download_request = dl.recv().await.expect("timeout");
+        assert_eq!(download_request.0, "c8y-mapper-1234");
+        assert_eq!(
+            download_request.1.url,
+            "http://localhost:8888/te/v1/files/test-device/command/c8y-mapper-1234"
+        );
+        std::fs::write(
+            &download_request.1.file_path,
+            "up 2 days, load average: 0.15",
+        )
+        .unwrap();
+        dl.send((
+            download_request.0,
+            Ok(DownloadResponse {
+                url: download_request.1.url,
+                file_path: download_request.1.file_path,
+            }),
+        ))
+        .await
+        .unwrap();
+
+        assert_received_contains_str(
+            &mut mqtt,
+            [(
+                "c8y/s/us",
+                "503,c8y_Command,\"up 2 days, load average: 0.15\"",
+            )],
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn handle_command_failed_cmd() {
+        let ttd = TempTedgeDir::new();
+        let test_handle = spawn_c8y_mapper_actor(&ttd, true).await;
+        let TestHandle { mqtt, .. } = test_handle;
+
+        let mut mqtt = mqtt.with_timeout(TEST_TIMEOUT_MS);
+        skip_init_messages(&mut mqtt).await;
+
+        mqtt.send(MqttMessage::new(
+            &Topic::new_unchecked("te/device/main///cmd/command/c8y-mapper-1234"),
+            json!({
+                "status": "failed",
+                "reason": "Command not allowed by agent.command.allowed: rm -rf /",
+                "command": "rm -rf /",
+            })
+            .to_string(),
+        ))
+        .await
+        .expect("Send failed");
+
+        assert_received_contains_str(
+            &mut mqtt,
+            [(
+                "c8y/s/us",
+                "502,c8y_Command,Command not allowed by agent.command.allowed: rm -rf /",
+            )],
+        )
+        .await;
+    }
+}
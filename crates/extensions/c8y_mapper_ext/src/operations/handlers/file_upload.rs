@@ -0,0 +1,252 @@
+use super::EntityTarget;
+use super::OperationContext;
+use super::OperationOutcome;
+use anyhow::Context;
+use camino::Utf8PathBuf;
+use tedge_actors::Sender;
+use tedge_api::commands::CommandStatus;
+use tedge_api::commands::FileUploadCmd;
+use tedge_downloader_ext::DownloadRequest;
+use tedge_mqtt_ext::MqttMessage;
+use tracing::error;
+use tracing::info;
+
+const DEFAULT_EVENT_TYPE: &str = "tedge_UploadedFile";
+
+impl OperationContext {
+    /// Address a file_upload command requested by a local component.
+    ///
+    /// Unlike the other operations, this command is not triggered by Cumulocity but by the device,
+    /// hence it's the mapper that executes the command, when "init",
+    /// attaching the file staged on the file transfer service to a new event.
+    /// The outcome is only reported locally, setting the `remoteUrl` of the command on success.
+    pub async fn handle_file_upload_state_change(
+        &self,
+        target: &EntityTarget,
+        cmd_id: &str,
+        message: &MqttMessage,
+    ) -> anyhow::Result<OperationOutcome> {
+        let Some(command) = FileUploadCmd::try_from_bytes(
+            target.topic_id.clone(),
+            cmd_id.into(),
+            message.payload_bytes(),
+        )
+        .context("Could not parse command as a file upload command")?
+        else {
+            // The command has been fully processed
+            return Ok(OperationOutcome::Ignored);
+        };
+
+        if command.status() != CommandStatus::Init
+            || !command.payload.is_for_cloud(self.c8y_prefix.as_ref())
+        {
+            return Ok(OperationOutcome::Ignored);
+        }
+
+        let mut mqtt_publisher = self.mqtt_publisher.clone();
+        let command = command.with_status(CommandStatus::Executing);
+        mqtt_publisher
+            .send(command.command_message(&self.mqtt_schema))
+            .await?;
+
+        let command = match self.upload_file_to_event(target, cmd_id, &command).await {
+            Ok(remote_url) => {
+                info!("File {} uploaded to {remote_url}", command.payload.name);
+                let mut command = command.with_status(CommandStatus::Successful);
+                command.payload.remote_url = Some(remote_url);
+                command
+            }
+            Err(err) => {
+                error!("Failed to upload {}: {err:#}", command.payload.name);
+                command.with_error(format!("{err:#}"))
+            }
+        };
+        mqtt_publisher
+            .send(command.command_message(&self.mqtt_schema))
+            .await?;
+
+        // The command is cleared by the requester
+        Ok(OperationOutcome::Ignored)
+    }
+
+    async fn upload_file_to_event(
+        &self,
+        target: &EntityTarget,
+        cmd_id: &str,
+        command: &FileUploadCmd,
+    ) -> anyhow::Result<String> {
+        let destination_dir = tempfile::tempdir_in(self.tmp_dir.as_std_path())
+            .context("Failed to create a temporary directory")?;
+        let destination_path = destination_dir.path().join(cmd_id);
+
+        let download_request = DownloadRequest::new(&command.payload.tedge_url, &destination_path);
+        let (_, download_result) = self
+            .downloader
+            .clone()
+            .await_response((cmd_id.into(), download_request))
+            .await
+            .context("Unexpected ChannelError")?;
+        let download_response = download_result
+            .context("tedge-mapper-c8y failed to download the file from file transfer service")?;
+        let file_path = Utf8PathBuf::try_from(download_response.file_path)
+            .map_err(|e| e.into_io_error())
+            .context("Could not parse file path as Utf-8")?;
+
+        let mime_type = command
+            .payload
+            .mime_type
+            .as_ref()
+            .map(|mime_type| mime_type.parse())
+            .transpose()
+            .context("Invalid MIME type")?;
+        let event_type = command
+            .payload
+            .file_type
+            .clone()
+            .unwrap_or_else(|| DEFAULT_EVENT_TYPE.to_string());
+        let (binary_url, upload_result) = self
+            .upload_file(
+                &target.external_id,
+                &file_path,
+                Some(command.payload.name.clone()),
+                mime_type,
+                cmd_id,
+                event_type,
+                Some(format!("Uploaded file: {}", command.payload.name)),
+            )
+            .await
+            .context("Could not upload the file to C8y")?;
+        upload_result.context("Could not upload the file to C8y")?;
+
+        Ok(binary_url.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::*;
+    use serde_json::json;
+    use std::time::Duration;
+    use tedge_actors::test_helpers::MessageReceiverExt;
+    use tedge_actors::MessageReceiver;
+    use tedge_actors::Sender;
+    use tedge_downloader_ext::DownloadResponse;
+    use tedge_mqtt_ext::test_helpers::assert_received_includes_json;
+    use tedge_mqtt_ext::MqttMessage;
+    use tedge_mqtt_ext::Topic;
+    use tedge_test_utils::fs::TempTedgeDir;
+    use tedge_uploader_ext::UploadResponse;
+
+    const TEST_TIMEOUT_MS: Duration = Duration::from_millis(3000);
+
+    #[tokio::test]
+    async fn file_upload_request_is_attached_to_a_new_event() {
+        let ttd = TempTedgeDir::new();
+        let test_handle = spawn_c8y_mapper_actor(&ttd, true).await;
+        let TestHandle {
+            mqtt, http, ul, dl, ..
+        } = test_handle;
+        spawn_dummy_c8y_http_proxy(http);
+
+        let mut mqtt = mqtt.with_timeout(TEST_TIMEOUT_MS);
+        let mut ul = ul.with_timeout(TEST_TIMEOUT_MS);
+        let mut dl = dl.with_timeout(TEST_TIMEOUT_MS);
+        skip_init_messages(&mut mqtt).await;
+
+        let tedge_url = "http://localhost:8888/te/v1/files/main/file_upload/report.pdf-1234";
+        mqtt.send(
+            MqttMessage::new(
+                &Topic::new_unchecked("te/device/main///cmd/file_upload/tedge-upload-1234"),
+                json!({
+                    "status": "init",
+                    "tedgeUrl": tedge_url,
+                    "name": "report.pdf",
+                    "mimeType": "application/pdf",
+                })
+                .to_string(),
+            )
+            .with_retain(),
+        )
+        .await
+        .expect("Send failed");
+
+        assert_received_includes_json(
+            &mut mqtt,
+            [(
+                "te/device/main///cmd/file_upload/tedge-upload-1234",
+                json!({ "status": "executing" }),
+            )],
+        )
+        .await;
+
+        // The file is downloaded from the file transfer service
+        let download_request = dl.recv().await.expect("timeout");
+        assert_eq!(download_request.1.url, tedge_url);
+        dl.send((
+            download_request.0,
+            Ok(DownloadResponse {
+                url: download_request.1.url,
+                file_path: download_request.1.file_path,
+            }),
+        ))
+        .await
+        .unwrap();
+
+        // Then attached to an event
+        let upload_request = ul.recv().await.expect("timeout");
+        assert_eq!(
+            upload_request.1.url,
+            "http://127.0.0.1:8001/c8y/event/events/dummy-event-id-1234/binaries"
+        );
+        ul.send((
+            upload_request.0,
+            Ok(UploadResponse {
+                url: upload_request.1.url,
+                file_path: upload_request.1.file_path,
+            }),
+        ))
+        .await
+        .unwrap();
+
+        assert_received_includes_json(
+            &mut mqtt,
+            [(
+                "te/device/main///cmd/file_upload/tedge-upload-1234",
+                json!({
+                    "status": "successful",
+                    "remoteUrl": "https://test.c8y.io/event/events/dummy-event-id-1234/binaries"
+                }),
+            )],
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn file_upload_request_for_another_cloud_is_ignored() {
+        let ttd = TempTedgeDir::new();
+        let test_handle = spawn_c8y_mapper_actor(&ttd, true).await;
+        let TestHandle { mqtt, dl, .. } = test_handle;
+
+        let mut mqtt = mqtt.with_timeout(TEST_TIMEOUT_MS);
+        let mut dl = dl.with_timeout(Duration::from_millis(200));
+        skip_init_messages(&mut mqtt).await;
+
+        mqtt.send(
+            MqttMessage::new(
+                &Topic::new_unchecked("te/device/main///cmd/file_upload/tedge-upload-1234"),
+                json!({
+                    "status": "init",
+                    "tedgeUrl": "http://localhost:8888/te/v1/files/main/file_upload/report.pdf-1234",
+                    "name": "report.pdf",
+                    "cloud": "az",
+                })
+                .to_string(),
+            )
+            .with_retain(),
+        )
+        .await
+        .expect("Send failed");
+
+        assert!(dl.recv().await.is_none());
+    }
+}
@@ -1,9 +1,11 @@
 //! Handling of different types of thin-edge.io operations.
 
+mod command;
 mod config_snapshot;
 mod config_update;
 mod custom_operation;
 mod device_profile;
+mod file_upload;
 mod firmware_update;
 mod log_upload;
 mod restart;
@@ -45,6 +47,7 @@ use tedge_api::mqtt_topics::OperationType;
 use tedge_api::workflow::GenericCommandState;
 use tedge_config::models::AutoLogUpload;
 use tedge_config::models::SoftwareManagementApiFlag;
+use tedge_config::models::TopicPrefix;
 use tedge_mqtt_ext::MqttMessage;
 use tedge_mqtt_ext::QoS;
 use tedge_mqtt_ext::Topic;
@@ -56,6 +59,7 @@ pub(super) struct OperationContext {
     pub(super) capabilities: Capabilities,
     pub(super) auto_log_upload: AutoLogUpload,
     pub(super) tedge_http_host: Arc<str>,
+    pub(super) c8y_prefix: TopicPrefix,
     pub(super) tmp_dir: Arc<Utf8Path>,
     pub(super) mqtt_schema: MqttSchema,
     pub(super) software_management_api: SoftwareManagementApiFlag,
@@ -148,7 +152,16 @@ impl OperationContext {
                 self.handle_device_profile_state_change(&entity, &cmd_id, &message)
                     .await
             }
-            OperationType::Custom(_) => {
+            OperationType::FileUpload => self
+                .handle_file_upload_state_change(&entity, &cmd_id, &message)
+                .await
+                .map_err(OperationError::from),
+            OperationType::Command if !self.is_custom_operation(&command) => {
+                self.handle_command_state_change(&entity, &cmd_id, &message)
+                    .await
+            }
+            // A custom operation can be bound to a user-defined `command` workflow
+            OperationType::Command | OperationType::Custom(_) => {
                 let (outcome, maybe_c8y_operation) = self
                     .handle_custom_operation_state_change(&entity, &cmd_id, &message)
                     .await;
@@ -279,6 +292,15 @@ impl OperationContext {
         OperationOutcome::Finished { messages }
     }
 
+    /// Check if a command has been created from a custom operation handler
+    fn is_custom_operation(&self, command: &GenericCommandState) -> bool {
+        let mapper_id = self.command_id.prefix();
+        command
+            .payload
+            .pointer(&format!("/{mapper_id}/on_fragment"))
+            .is_some()
+    }
+
     fn get_operation_id(&self, cmd_id: &str) -> Option<String> {
         self.command_id
             .get_value(cmd_id)
@@ -333,6 +355,9 @@ fn to_c8y_operation(operation_type: &OperationType) -> Option<CumulocitySupporte
         OperationType::FirmwareUpdate => Some(CumulocitySupportedOperations::C8yFirmware),
         OperationType::SoftwareUpdate => Some(CumulocitySupportedOperations::C8ySoftwareUpdate),
         OperationType::DeviceProfile => Some(CumulocitySupportedOperations::C8yDeviceProfile),
+        OperationType::Command => Some(CumulocitySupportedOperations::C8yCommand),
+        // requested by the device, not by c8y
+        OperationType::FileUpload => None,
         // Cannot convert custom operation name systematically
         OperationType::Custom(_) => None,
         // software list is not an c8y, only a fragment, but is a local operation that is spawned as
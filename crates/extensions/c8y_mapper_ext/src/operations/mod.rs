@@ -12,6 +12,7 @@
 //!   handle different operations ([`handler`])
 //! - conversion from C8y operation messages into thin-edge operation messages ([`convert`])
 //! - implementations of operations ([`handlers`])
+//! - queuing of the operations exceeding the concurrency limits ([`queue`])
 //!
 //! thin-edge.io operations reference:
 //! https://thin-edge.github.io/thin-edge.io/operate/c8y/supported-operations/
@@ -25,4 +26,7 @@ pub use handler::OperationHandler;
 mod handlers;
 pub use handlers::EntityTarget;
 
+mod queue;
+pub(crate) use queue::OperationQueue;
+
 mod upload;
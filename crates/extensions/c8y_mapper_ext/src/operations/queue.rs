@@ -0,0 +1,266 @@
+use crate::config::OperationLimits;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_api::workflow::GenericCommandState;
+use tedge_mqtt_ext::MqttMessage;
+use tracing::info;
+
+/// Queues the commands created by the mapper from Cumulocity operations,
+/// so that no more operations run concurrently on an entity than allowed by the [`OperationLimits`].
+///
+/// A queued command is only published to the local MQTT broker once it can be started,
+/// the Cumulocity operation being kept PENDING until then.
+/// The commands of an entity are started in order, except that an operation waiting for a running operation
+/// of the same type doesn't delay the operations of other types.
+/// An exclusive operation waits for all the running operations of the entity to complete,
+/// and delays any subsequent operation on that entity until done.
+pub(crate) struct OperationQueue {
+    mqtt_schema: MqttSchema,
+    limits: OperationLimits,
+
+    /// The running commands of each entity, indexed by command topic
+    running: HashMap<EntityTopicId, HashMap<String, OperationType>>,
+
+    /// The commands of each entity waiting to be started
+    queued: HashMap<EntityTopicId, VecDeque<QueuedCommand>>,
+}
+
+struct QueuedCommand {
+    operation: OperationType,
+    message: MqttMessage,
+}
+
+impl OperationQueue {
+    pub fn new(mqtt_schema: MqttSchema, limits: OperationLimits) -> Self {
+        OperationQueue {
+            mqtt_schema,
+            limits,
+            running: HashMap::new(),
+            queued: HashMap::new(),
+        }
+    }
+
+    /// Return the messages that can be published right now,
+    /// queuing the new commands that cannot be started yet.
+    pub fn schedule(&mut self, messages: Vec<MqttMessage>) -> Vec<MqttMessage> {
+        let mut ready = vec![];
+        for message in messages {
+            let Some((entity, operation)) = self.new_command(&message) else {
+                ready.push(message);
+                continue;
+            };
+
+            self.queued
+                .entry(entity.clone())
+                .or_default()
+                .push_back(QueuedCommand {
+                    operation: operation.clone(),
+                    message,
+                });
+            let started = self.release(&entity);
+            if started.is_empty() {
+                info!(
+                    "Queuing {operation} operation on {entity} until running operations complete"
+                );
+            }
+            ready.extend(started);
+        }
+        ready
+    }
+
+    /// Update the state of a command from a command message received on the local MQTT broker
+    ///
+    /// Return the queued commands that can be started,
+    /// if the command is completed and frees a slot.
+    pub fn update(&mut self, message: &MqttMessage) -> Vec<MqttMessage> {
+        let Ok((entity, Channel::Command { operation, .. })) =
+            self.mqtt_schema.entity_channel_of(&message.topic)
+        else {
+            return vec![];
+        };
+        let Ok(state) = GenericCommandState::from_command_message(message) else {
+            return vec![];
+        };
+
+        let topic = &message.topic.name;
+        if !state.is_cleared() && !state.is_finished() {
+            // Also track the commands started before a restart of the mapper
+            self.running
+                .entry(entity)
+                .or_default()
+                .entry(topic.clone())
+                .or_insert(operation);
+            return vec![];
+        }
+
+        let Some(running) = self.running.get_mut(&entity) else {
+            return vec![];
+        };
+        if running.remove(topic).is_none() {
+            return vec![];
+        }
+        if running.is_empty() {
+            self.running.remove(&entity);
+        }
+        self.release(&entity)
+    }
+
+    /// Start the queued commands of an entity that are no more blocked by running commands
+    fn release(&mut self, entity: &EntityTopicId) -> Vec<MqttMessage> {
+        let Some(mut queue) = self.queued.remove(entity) else {
+            return vec![];
+        };
+
+        let mut ready = vec![];
+        let mut still_queued = VecDeque::new();
+        while let Some(command) = queue.pop_front() {
+            if self.can_start(entity, &command.operation) {
+                self.start(entity, &command.operation, &command.message);
+                ready.push(command.message);
+            } else {
+                let exclusive = self.is_exclusive(&command.operation);
+                still_queued.push_back(command);
+                if exclusive {
+                    // Nothing can be started before this exclusive operation
+                    break;
+                }
+            }
+        }
+        still_queued.extend(queue);
+        if !still_queued.is_empty() {
+            self.queued.insert(entity.clone(), still_queued);
+        }
+        ready
+    }
+
+    fn can_start(&self, entity: &EntityTopicId, operation: &OperationType) -> bool {
+        let Some(running) = self.running.get(entity) else {
+            return true;
+        };
+        if self.is_exclusive(operation) {
+            return running.is_empty();
+        }
+        if running.values().any(|op| self.is_exclusive(op)) {
+            return false;
+        }
+        let max_concurrent = self.limits.max_concurrent;
+        max_concurrent == 0
+            || running.values().filter(|op| *op == operation).count() < max_concurrent
+    }
+
+    fn start(&mut self, entity: &EntityTopicId, operation: &OperationType, message: &MqttMessage) {
+        self.running
+            .entry(entity.clone())
+            .or_default()
+            .insert(message.topic.name.clone(), operation.clone());
+    }
+
+    fn is_exclusive(&self, operation: &OperationType) -> bool {
+        self.limits.exclusive.contains(&operation.to_string())
+    }
+
+    /// Check if a message is a new command, returning its target entity and operation type
+    fn new_command(&self, message: &MqttMessage) -> Option<(EntityTopicId, OperationType)> {
+        let (entity, channel) = self.mqtt_schema.entity_channel_of(&message.topic).ok()?;
+        let Channel::Command { operation, .. } = channel else {
+            return None;
+        };
+        let state = GenericCommandState::from_command_message(message).ok()?;
+        (state.status == "init").then_some((entity, operation))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use tedge_mqtt_ext::Topic;
+
+    #[test]
+    fn operations_of_the_same_type_are_queued() {
+        let mut queue = OperationQueue::new(MqttSchema::default(), OperationLimits::default());
+
+        let update_1 = command("device/main//", "software_update", "c8y-1", "init");
+        let update_2 = command("device/main//", "software_update", "c8y-2", "init");
+        let log_1 = command("device/main//", "log_upload", "c8y-3", "init");
+        let child_update = command("device/child//", "software_update", "c8y-4", "init");
+        assert_eq!(
+            queue.schedule(vec![
+                update_1.clone(),
+                update_2.clone(),
+                log_1.clone(),
+                child_update.clone()
+            ]),
+            vec![update_1, log_1, child_update]
+        );
+
+        // Nothing is released until the running operation completes
+        let executing = command("device/main//", "software_update", "c8y-1", "executing");
+        assert!(queue.update(&executing).is_empty());
+        let successful = command("device/main//", "software_update", "c8y-1", "successful");
+        assert_eq!(queue.update(&successful), vec![update_2]);
+        assert!(queue
+            .update(&clear("device/main//", "software_update", "c8y-1"))
+            .is_empty());
+    }
+
+    #[test]
+    fn exclusive_operations_run_alone() {
+        let mut queue = OperationQueue::new(MqttSchema::default(), OperationLimits::default());
+
+        let log_1 = command("device/main//", "log_upload", "c8y-1", "init");
+        let restart = command("device/main//", "restart", "c8y-2", "init");
+        let config = command("device/main//", "config_snapshot", "c8y-3", "init");
+        assert_eq!(
+            queue.schedule(vec![log_1.clone(), restart.clone(), config.clone()]),
+            vec![log_1]
+        );
+
+        // The restart waits for the log upload, and the config snapshot for the restart
+        let log_done = clear("device/main//", "log_upload", "c8y-1");
+        assert_eq!(queue.update(&log_done), vec![restart]);
+        let restart_done = command("device/main//", "restart", "c8y-2", "failed");
+        assert_eq!(queue.update(&restart_done), vec![config]);
+    }
+
+    #[test]
+    fn commands_started_before_a_restart_are_tracked() {
+        let limits = OperationLimits {
+            max_concurrent: 2,
+            exclusive: HashSet::new(),
+        };
+        let mut queue = OperationQueue::new(MqttSchema::default(), limits);
+
+        let retained_1 = command("device/main//", "firmware_update", "c8y-1", "executing");
+        let retained_2 = command("device/main//", "firmware_update", "c8y-2", "scheduled");
+        assert!(queue.update(&retained_1).is_empty());
+        assert!(queue.update(&retained_2).is_empty());
+
+        let new = command("device/main//", "firmware_update", "c8y-3", "init");
+        assert!(queue.schedule(vec![new.clone()]).is_empty());
+        assert_eq!(
+            queue.update(&clear("device/main//", "firmware_update", "c8y-2")),
+            vec![new]
+        );
+    }
+
+    fn command(entity: &str, operation: &str, cmd_id: &str, status: &str) -> MqttMessage {
+        MqttMessage::new(
+            &Topic::new_unchecked(&format!("te/{entity}/cmd/{operation}/{cmd_id}")),
+            format!(r#"{{"status":"{status}"}}"#),
+        )
+        .with_retain()
+    }
+
+    fn clear(entity: &str, operation: &str, cmd_id: &str) -> MqttMessage {
+        MqttMessage::new(
+            &Topic::new_unchecked(&format!("te/{entity}/cmd/{operation}/{cmd_id}")),
+            "",
+        )
+        .with_retain()
+    }
+}
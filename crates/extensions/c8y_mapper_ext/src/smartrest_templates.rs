@@ -0,0 +1,124 @@
+use c8y_api::smartrest::template_collection::SmartRestTemplateCollection;
+use c8y_http_proxy::handle::C8YHttpProxy;
+use c8y_http_proxy::messages::TemplateRegistration;
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+use tedge_config::models::TemplatesSet;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+/// SmartREST 2.0 template collections to be registered in Cumulocity on start
+///
+/// Each `*.json` file of the templates directory defines a collection,
+/// using the format of Cumulocity when a collection is exported.
+pub struct SmartRestTemplates {
+    /// Directory of the template definition files
+    pub templates_dir: Utf8PathBuf,
+
+    /// The collections the mapper is subscribed to, as set by `c8y.smartrest.templates`
+    pub subscribed: TemplatesSet,
+}
+
+impl SmartRestTemplates {
+    /// Create or update the template collections in Cumulocity
+    ///
+    /// Errors are logged, a failure to register a collection not preventing the registration of the others.
+    pub async fn register(self, mut http_proxy: C8YHttpProxy) {
+        for (path, collection) in load_template_collections(&self.templates_dir).await {
+            let collection = match collection {
+                Ok(collection) => collection,
+                Err(err) => {
+                    error!("Ignoring SmartREST templates defined by {path}: {err}");
+                    continue;
+                }
+            };
+
+            let name = &collection.name;
+            match http_proxy.register_smartrest_templates(&collection).await {
+                Ok(TemplateRegistration::Created) => {
+                    info!("SmartREST template collection {name} created from {path}")
+                }
+                Ok(TemplateRegistration::Updated) => {
+                    info!("SmartREST template collection {name} updated from {path}")
+                }
+                Ok(TemplateRegistration::Unchanged) => {
+                    info!("SmartREST template collection {name} is up to date")
+                }
+                Err(err) => {
+                    error!("Fail to register the SmartREST templates defined by {path}: {err}");
+                    continue;
+                }
+            }
+
+            if !self.subscribed.0.contains(name) {
+                warn!("The mapper is not subscribed to the SmartREST template collection {name}: add it to c8y.smartrest.templates");
+            }
+        }
+    }
+}
+
+/// Load the template collections defined in a directory, sorted by file name
+async fn load_template_collections(
+    templates_dir: &Utf8Path,
+) -> Vec<(Utf8PathBuf, Result<SmartRestTemplateCollection, String>)> {
+    let Ok(entries) = templates_dir.read_dir_utf8() else {
+        return vec![];
+    };
+
+    let mut paths: Vec<Utf8PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.into_path())
+        .filter(|path| path.extension() == Some("json"))
+        .collect();
+    paths.sort();
+
+    let mut collections = vec![];
+    for path in paths {
+        let collection = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str(&content).map_err(|err| err.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+        collections.push((path, collection));
+    }
+    collections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tedge_test_utils::fs::TempTedgeDir;
+
+    #[tokio::test]
+    async fn load_template_collections_from_json_files() {
+        let ttd = TempTedgeDir::new();
+        ttd.file("b-custom.json").with_raw_content(
+            r#"{
+                "__externalId": "b-custom",
+                "com_cumulocity_model_smartrest_csv_CsvSmartRestTemplate": {
+                    "requestTemplates": [{"msgId": "100"}]
+                }
+            }"#,
+        );
+        ttd.file("a-broken.json").with_raw_content("{");
+        ttd.file("README.md")
+            .with_raw_content("Not a template collection");
+
+        let dir = Utf8Path::from_path(ttd.path()).unwrap();
+        let collections = load_template_collections(dir).await;
+
+        assert_eq!(collections.len(), 2);
+        assert_eq!(collections[0].0, dir.join("a-broken.json"));
+        assert!(collections[0].1.is_err());
+        assert_eq!(collections[1].0, dir.join("b-custom.json"));
+        assert_eq!(collections[1].1.as_ref().unwrap().name, "b-custom");
+    }
+
+    #[tokio::test]
+    async fn no_template_collections_when_the_directory_is_missing() {
+        let ttd = TempTedgeDir::new();
+        let dir = Utf8Path::from_path(ttd.path()).unwrap().join("smartrest");
+
+        assert!(load_template_collections(&dir).await.is_empty());
+    }
+}
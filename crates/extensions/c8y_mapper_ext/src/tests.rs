@@ -6,6 +6,7 @@ use crate::actor::IdUploadRequest;
 use crate::actor::IdUploadResult;
 use crate::availability::AvailabilityBuilder;
 use crate::config::BridgeConfig;
+use crate::config::OperationLimits;
 use crate::operations::OperationHandler;
 use crate::Capabilities;
 use c8y_api::json_c8y::C8yEventResponse;
@@ -3370,6 +3371,7 @@ pub(crate) fn test_mapper_config(tmp_dir: &TempTedgeDir) -> C8yMapperConfig {
         false,
         C8Y_MQTT_PAYLOAD_LIMIT,
         "100ms",
+        OperationLimits::default(),
     )
 }
 
@@ -0,0 +1,315 @@
+use crate::templates::entity_from_topic_level;
+use crate::templates::entity_topic_level;
+use crate::templates::TopicTemplate;
+use serde_json::Value;
+use std::time::SystemTime;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_flows::ConfigError;
+use tedge_flows::FlowContextHandle;
+use tedge_flows::FlowError;
+use tedge_flows::JsonValue;
+use tedge_flows::Message;
+use tedge_flows::Transport;
+use tedge_mqtt_ext::QoS;
+
+/// Turn the commands received from the remote broker into thin-edge commands
+///
+/// A command received on `<topic_prefix>/<command template>` is published,
+/// as is but with an `init` status if none is given,
+/// on `<topic_root>/<entity>/cmd/<operation>/<topic_prefix>-<cmd_id>`.
+#[derive(Clone, Default)]
+pub struct FromMqttCloudCommand {
+    topic_root: String,
+    topic_prefix: String,
+    template: Option<TopicTemplate>,
+}
+
+impl tedge_flows::Transformer for FromMqttCloudCommand {
+    fn name(&self) -> &str {
+        "from-mqtt-cloud-command"
+    }
+
+    fn set_config(&mut self, config: JsonValue) -> Result<(), ConfigError> {
+        let (topic_root, topic_prefix, template) = command_config(self.name(), &config)?;
+        self.topic_root = topic_root;
+        self.topic_prefix = topic_prefix;
+        self.template = Some(template);
+        Ok(())
+    }
+
+    fn on_message(
+        &mut self,
+        _timestamp: SystemTime,
+        message: &Message,
+        _context: &FlowContextHandle,
+    ) -> Result<Vec<Message>, FlowError> {
+        let Some(template) = &self.template else {
+            return Ok(vec![]);
+        };
+        let Some(remote_topic) = message
+            .topic
+            .strip_prefix(&self.topic_prefix)
+            .and_then(|topic| topic.strip_prefix('/'))
+        else {
+            return Ok(vec![]);
+        };
+        let Some(values) = template.capture(remote_topic) else {
+            return Ok(vec![]);
+        };
+        let (Some(entity), Some(operation), Some(cmd_id)) = (
+            values.get("entity"),
+            values.get("operation"),
+            values.get("cmd_id"),
+        ) else {
+            return Ok(vec![]);
+        };
+        if message.payload.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let entity = entity_from_topic_level(entity).ok_or_else(|| {
+            FlowError::UnsupportedMessage(format!("Unknown entity {entity:?} in {remote_topic}"))
+        })?;
+        let mut command: serde_json::Map<String, Value> = serde_json::from_slice(&message.payload)
+            .map_err(|err| {
+                FlowError::UnsupportedMessage(format!("Invalid command payload: {err}"))
+            })?;
+        command
+            .entry("status")
+            .or_insert_with(|| Value::String("init".to_string()));
+
+        let topic = format!(
+            "{}/{entity}/cmd/{operation}/{}-{cmd_id}",
+            self.topic_root, self.topic_prefix
+        );
+        let payload = Value::Object(command).to_string();
+        Ok(vec![retained(topic, payload)])
+    }
+}
+
+/// Report to the remote broker the status of the commands received from there
+///
+/// Each update of a command is published on `<topic_prefix>/<command template>/status`,
+/// and the command is cleared once successful or failed.
+#[derive(Clone, Default)]
+pub struct IntoMqttCloudCommandStatus {
+    topic_prefix: String,
+    template: Option<TopicTemplate>,
+}
+
+impl tedge_flows::Transformer for IntoMqttCloudCommandStatus {
+    fn name(&self) -> &str {
+        "into-mqtt-cloud-command-status"
+    }
+
+    fn set_config(&mut self, config: JsonValue) -> Result<(), ConfigError> {
+        let (_, topic_prefix, template) = command_config(self.name(), &config)?;
+        self.topic_prefix = topic_prefix;
+        self.template = Some(template.with_suffix("status"));
+        Ok(())
+    }
+
+    fn on_message(
+        &mut self,
+        _timestamp: SystemTime,
+        message: &Message,
+        _context: &FlowContextHandle,
+    ) -> Result<Vec<Message>, FlowError> {
+        let Some(template) = &self.template else {
+            return Ok(vec![]);
+        };
+        let levels: Vec<_> = message.topic.split('/').collect();
+        let [_, a, b, c, d, "cmd", operation, cmd_id] = levels[..] else {
+            return Ok(vec![]);
+        };
+        let Some(cmd_id) = cmd_id
+            .strip_prefix(&self.topic_prefix)
+            .and_then(|id| id.strip_prefix('-'))
+        else {
+            return Ok(vec![]);
+        };
+        if message.payload.is_empty() {
+            return Ok(vec![]);
+        }
+        let Ok(entity) = format!("{a}/{b}/{c}/{d}").parse::<EntityTopicId>() else {
+            return Ok(vec![]);
+        };
+
+        let status = serde_json::from_slice::<Value>(&message.payload)
+            .ok()
+            .and_then(|command| command.get("status")?.as_str().map(str::to_owned));
+        let entity = entity_topic_level(&entity);
+        let topic = template.render(&[
+            ("entity", &entity),
+            ("operation", operation),
+            ("cmd_id", cmd_id),
+        ]);
+        let topic = format!("{}/{topic}", self.topic_prefix);
+        let mut messages = vec![Message {
+            topic,
+            payload: message.payload.clone(),
+            timestamp: None,
+            transport: Some(Transport::Mqtt {
+                qos: QoS::AtLeastOnce,
+                retain: false,
+            }),
+        }];
+        if matches!(status.as_deref(), Some("successful") | Some("failed")) {
+            messages.push(retained(message.topic.clone(), ""));
+        }
+        Ok(messages)
+    }
+}
+
+fn command_config(
+    step: &str,
+    config: &JsonValue,
+) -> Result<(String, String, TopicTemplate), ConfigError> {
+    let property = |name: &str| {
+        config.string_property(name).ok_or_else(|| {
+            ConfigError::IncorrectSetting(format!("No {name} configured for {step} step"))
+        })
+    };
+    let topic_root = property("topic_root")?.to_owned();
+    let topic_prefix = property("topic_prefix")?.to_owned();
+    let template = TopicTemplate::try_new(property("topic")?)
+        .map_err(|err| ConfigError::IncorrectSetting(err.to_string()))?;
+    template
+        .require_placeholders(&["entity", "operation", "cmd_id"])
+        .map_err(|err| ConfigError::IncorrectSetting(err.to_string()))?;
+    Ok((topic_root, topic_prefix, template))
+}
+
+fn retained(topic: String, payload: impl Into<Vec<u8>>) -> Message {
+    Message {
+        topic,
+        payload: payload.into(),
+        timestamp: None,
+        transport: Some(Transport::Mqtt {
+            qos: QoS::AtLeastOnce,
+            retain: true,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tedge_flows::Transformer;
+
+    fn config() -> JsonValue {
+        JsonValue::from_value(json!({
+            "topic_root": "te",
+            "topic_prefix": "mqtt",
+            "topic": "tedge/rpi4/{entity}/cmd/{operation}/{cmd_id}",
+        }))
+        .unwrap()
+    }
+
+    fn process(
+        transformer: &mut impl Transformer,
+        topic: &str,
+        payload: &str,
+    ) -> Vec<(String, Value, bool)> {
+        let message = Message::new(topic, payload);
+        transformer
+            .on_message(SystemTime::now(), &message, &FlowContextHandle::default())
+            .unwrap()
+            .into_iter()
+            .map(|message| {
+                let payload = serde_json::from_slice(&message.payload).unwrap_or(Value::Null);
+                let retain =
+                    matches!(message.transport, Some(Transport::Mqtt { retain, .. }) if retain);
+                (message.topic, payload, retain)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn remote_commands_are_published_as_thin_edge_commands() {
+        let mut transformer = FromMqttCloudCommand::default();
+        transformer.set_config(config()).unwrap();
+
+        let output = process(
+            &mut transformer,
+            "mqtt/tedge/rpi4/device:child1/cmd/software_update/42",
+            r#"{"updateList": []}"#,
+        );
+
+        assert_eq!(
+            output,
+            vec![(
+                "te/device/child1///cmd/software_update/mqtt-42".to_string(),
+                json!({"status": "init", "updateList": []}),
+                true
+            )]
+        );
+    }
+
+    #[test]
+    fn remote_commands_with_invalid_payload_are_rejected() {
+        let mut transformer = FromMqttCloudCommand::default();
+        transformer.set_config(config()).unwrap();
+
+        let message = Message::new("mqtt/tedge/rpi4/device:main/cmd/restart/1", "not json");
+        let result =
+            transformer.on_message(SystemTime::now(), &message, &FlowContextHandle::default());
+
+        assert!(matches!(result, Err(FlowError::UnsupportedMessage(_))));
+    }
+
+    #[test]
+    fn command_status_is_reported_and_final_commands_cleared() {
+        let mut transformer = IntoMqttCloudCommandStatus::default();
+        transformer.set_config(config()).unwrap();
+
+        let output = process(
+            &mut transformer,
+            "te/device/main///cmd/restart/mqtt-1",
+            r#"{"status": "executing"}"#,
+        );
+        assert_eq!(
+            output,
+            vec![(
+                "mqtt/tedge/rpi4/device:main/cmd/restart/1/status".to_string(),
+                json!({"status": "executing"}),
+                false
+            )]
+        );
+
+        let output = process(
+            &mut transformer,
+            "te/device/main///cmd/restart/mqtt-1",
+            r#"{"status": "successful"}"#,
+        );
+        assert_eq!(
+            output,
+            vec![
+                (
+                    "mqtt/tedge/rpi4/device:main/cmd/restart/1/status".to_string(),
+                    json!({"status": "successful"}),
+                    false
+                ),
+                (
+                    "te/device/main///cmd/restart/mqtt-1".to_string(),
+                    Value::Null,
+                    true
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn commands_not_received_from_the_remote_broker_are_ignored() {
+        let mut transformer = IntoMqttCloudCommandStatus::default();
+        transformer.set_config(config()).unwrap();
+
+        let output = process(
+            &mut transformer,
+            "te/device/main///cmd/restart/c8y-mapper-1",
+            r#"{"status": "executing"}"#,
+        );
+        assert!(output.is_empty());
+    }
+}
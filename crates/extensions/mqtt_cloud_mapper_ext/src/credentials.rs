@@ -0,0 +1,64 @@
+use camino::Utf8Path;
+use camino::Utf8PathBuf;
+
+/// The credential file representation. e.g.:
+/// ```toml
+/// [mqtt_cloud]
+/// username = "octocat"
+/// password = "abcd1234"
+/// ```
+#[derive(Debug, serde::Deserialize)]
+struct Credentials {
+    mqtt_cloud: BasicCredentials,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct BasicCredentials {
+    username: String,
+    password: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CredentialsFileError {
+    #[error("Failed to read the basic auth credentials file. file={0}: {1}")]
+    ReadCredentialsFailed(Utf8PathBuf, #[source] std::io::Error),
+
+    #[error("Error while parsing credentials file: '{0}': {1}.")]
+    TomlError(Utf8PathBuf, #[source] toml::de::Error),
+}
+
+/// Read the username and password used to connect the remote MQTT broker
+pub fn read_mqtt_cloud_credentials(
+    credentials_path: &Utf8Path,
+) -> Result<(String, String), CredentialsFileError> {
+    let contents = std::fs::read_to_string(credentials_path)
+        .map_err(|e| CredentialsFileError::ReadCredentialsFailed(credentials_path.into(), e))?;
+    let credentials: Credentials = toml::from_str(&contents)
+        .map_err(|e| CredentialsFileError::TomlError(credentials_path.into(), e))?;
+    let BasicCredentials { username, password } = credentials.mqtt_cloud;
+
+    Ok((username, password))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_credentials_from_mqtt_cloud_table() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = Utf8Path::from_path(dir.path())
+            .unwrap()
+            .join("credentials.toml");
+        std::fs::write(
+            &path,
+            "[mqtt_cloud]\nusername = \"octocat\"\npassword = \"abcd1234\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_mqtt_cloud_credentials(&path).unwrap(),
+            ("octocat".to_string(), "abcd1234".to_string())
+        );
+    }
+}
@@ -0,0 +1,295 @@
+mod commands;
+mod credentials;
+mod telemetry;
+mod templates;
+
+pub use commands::FromMqttCloudCommand;
+pub use commands::IntoMqttCloudCommandStatus;
+pub use credentials::read_mqtt_cloud_credentials;
+pub use credentials::CredentialsFileError;
+pub use telemetry::SetMqttCloudTopic;
+pub use templates::InvalidTopicTemplate;
+pub use templates::MqttCloudTopics;
+pub use templates::TopicTemplate;
+
+use camino::Utf8Path;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_config::models::TopicPrefix;
+use tedge_flows::ConnectedFlowRegistry;
+use tedge_flows::FlowRegistryExt;
+use tedge_flows::UpdateFlowRegistryError;
+use tedge_mqtt_ext::Topic;
+use tedge_utils::timestamp::TimeFormat;
+
+pub struct MqttCloudConverter {
+    topic_root: String,
+    topic_prefix: TopicPrefix,
+    errors_topic: Topic,
+    input_topics: Vec<String>,
+    size_threshold: usize,
+    add_timestamp: bool,
+    time_format: TimeFormat,
+    topics: MqttCloudTopics,
+}
+
+impl MqttCloudConverter {
+    pub fn new(
+        add_timestamp: bool,
+        mqtt_schema: &MqttSchema,
+        time_format: TimeFormat,
+        topic_prefix: TopicPrefix,
+        max_payload_size: u32,
+        input_topics: Vec<String>,
+        topics: MqttCloudTopics,
+    ) -> Self {
+        MqttCloudConverter {
+            topic_root: mqtt_schema.root.clone(),
+            topic_prefix,
+            errors_topic: mqtt_schema.error_topic(),
+            input_topics,
+            size_threshold: max_payload_size as usize,
+            add_timestamp,
+            time_format,
+            topics,
+        }
+    }
+
+    pub async fn flow_registry(
+        &self,
+        flows_dir: impl AsRef<Utf8Path>,
+    ) -> Result<ConnectedFlowRegistry, UpdateFlowRegistryError> {
+        let mut flows = ConnectedFlowRegistry::new(flows_dir);
+        flows.register_builtin(SetMqttCloudTopic::default());
+        flows.register_builtin(FromMqttCloudCommand::default());
+        flows.register_builtin(IntoMqttCloudCommandStatus::default());
+        self.persist_builtin_flows(&mut flows).await?;
+        Ok(flows)
+    }
+
+    async fn persist_builtin_flows(
+        &self,
+        flows: &mut ConnectedFlowRegistry,
+    ) -> Result<(), UpdateFlowRegistryError> {
+        let (twin_topics, telemetry_topics): (Vec<_>, Vec<_>) = self
+            .input_topics
+            .iter()
+            .partition(|topic| topic.split('/').nth(5) == Some("twin"));
+        flows
+            .persist_builtin_flow("mea", &self.telemetry_flow(&telemetry_topics, true))
+            .await?;
+        flows
+            .persist_builtin_flow("twin", &self.telemetry_flow(&twin_topics, false))
+            .await?;
+        flows
+            .persist_builtin_flow("commands", &self.commands_flow())
+            .await?;
+        flows
+            .persist_builtin_flow("command-status", &self.command_status_flow())
+            .await
+    }
+
+    fn telemetry_flow(&self, input_topics: &[&String], with_timestamp: bool) -> String {
+        let timestamp_step = if self.add_timestamp && with_timestamp {
+            format!(
+                r#"{{ builtin = "add-timestamp", config = {{ property = "time", format = "{time_format}", reformat = true }} }},"#,
+                time_format = self.time_format,
+            )
+        } else {
+            "".to_string()
+        };
+
+        format!(
+            r#"
+input.mqtt.topics = {input_topics:?}
+
+steps = [
+    {{ builtin = "skip-mosquitto-health-status" }},
+    {timestamp_step}
+    {{ builtin = "limit-payload-size", config = {{ max_size = {max_size} }} }},
+    {{ builtin = "set-mqtt-cloud-topic", config = {{ measurements = "{prefix}/{measurements}", events = "{prefix}/{events}", alarms = "{prefix}/{alarms}", twin = "{prefix}/{twin}" }} }},
+]
+
+errors.mqtt.topic = "{errors_topic}"
+"#,
+            prefix = self.topic_prefix,
+            max_size = self.size_threshold,
+            measurements = self.topics.measurements.as_str(),
+            events = self.topics.events.as_str(),
+            alarms = self.topics.alarms.as_str(),
+            twin = self.topics.twin.as_str(),
+            errors_topic = self.errors_topic,
+        )
+    }
+
+    fn commands_flow(&self) -> String {
+        let input_topics: Vec<_> = self
+            .topics
+            .incoming_filters()
+            .into_iter()
+            .map(|filter| format!("{}/{filter}", self.topic_prefix))
+            .collect();
+        format!(
+            r#"
+input.mqtt.topics = {input_topics:?}
+
+steps = [
+    {{ builtin = "from-mqtt-cloud-command", config = {{ topic_root = "{topic_root}", topic_prefix = "{prefix}", topic = "{commands}" }} }},
+]
+
+errors.mqtt.topic = "{errors_topic}"
+"#,
+            topic_root = self.topic_root,
+            prefix = self.topic_prefix,
+            commands = self.topics.commands.as_str(),
+            errors_topic = self.errors_topic,
+        )
+    }
+
+    fn command_status_flow(&self) -> String {
+        format!(
+            r#"
+input.mqtt.topics = ["{topic_root}/+/+/+/+/cmd/+/+"]
+
+steps = [
+    {{ builtin = "into-mqtt-cloud-command-status", config = {{ topic_root = "{topic_root}", topic_prefix = "{prefix}", topic = "{commands}" }} }},
+]
+
+errors.mqtt.topic = "{errors_topic}"
+"#,
+            topic_root = self.topic_root,
+            prefix = self.topic_prefix,
+            commands = self.topics.commands.as_str(),
+            errors_topic = self.errors_topic,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use serde_json::Value;
+    use std::time::SystemTime;
+    use tedge_config::tedge_toml::mapper_config::MqttCloudTopicTemplates;
+    use tedge_flows::FlowResult;
+    use tedge_flows::Message;
+    use tedge_flows::MessageProcessor;
+    use tedge_flows::SourceTag;
+
+    struct MqttCloudFlows {
+        runtime: MessageProcessor<ConnectedFlowRegistry>,
+        _flows_dir: tempfile::TempDir,
+    }
+
+    impl MqttCloudFlows {
+        async fn new(add_timestamp: bool) -> Self {
+            let templates = MqttCloudTopicTemplates {
+                measurements: "tedge/{device_id}/{entity}/m/{type}".to_string(),
+                events: "tedge/{device_id}/{entity}/e/{type}".to_string(),
+                alarms: "tedge/{device_id}/{entity}/a/{type}".to_string(),
+                twin: "tedge/{device_id}/{entity}/twin/{type}".to_string(),
+                commands: "tedge/{device_id}/{entity}/cmd/{operation}/{cmd_id}".to_string(),
+            };
+            let converter = MqttCloudConverter::new(
+                add_timestamp,
+                &MqttSchema::default(),
+                TimeFormat::Rfc3339,
+                TopicPrefix::try_from("mqtt").unwrap(),
+                1024,
+                vec![
+                    "te/+/+/+/+/m/+".to_string(),
+                    "te/+/+/+/+/twin/+".to_string(),
+                ],
+                MqttCloudTopics::try_new(&templates, "rpi4").unwrap(),
+            );
+            let flows_dir = tempfile::TempDir::new().unwrap();
+            let flows_path = Utf8Path::from_path(flows_dir.path()).unwrap();
+            let flows = converter.flow_registry(flows_path).await.unwrap();
+            let mut runtime = MessageProcessor::try_new(flows).await.unwrap();
+            runtime.load_all_flows().await;
+            MqttCloudFlows {
+                runtime,
+                _flows_dir: flows_dir,
+            }
+        }
+
+        async fn process(&mut self, topic: &str, payload: &str) -> Vec<(String, Value)> {
+            let message = Message::new(topic, payload);
+            let mut output = vec![];
+            for result in self
+                .runtime
+                .on_message(SystemTime::UNIX_EPOCH, &SourceTag::Mqtt, &message)
+                .await
+            {
+                match result {
+                    FlowResult::Ok { messages, .. } => output.extend(messages),
+                    FlowResult::Err { error, .. } => panic!("{error}"),
+                }
+            }
+            output
+                .into_iter()
+                .map(|message| {
+                    let payload = serde_json::from_slice(&message.payload).unwrap_or(Value::Null);
+                    (message.topic, payload)
+                })
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn measurements_are_timestamped_but_not_twin_data() {
+        let mut flows = MqttCloudFlows::new(true).await;
+
+        let output = flows
+            .process("te/device/main///m/environment", r#"{"temperature": 21.3}"#)
+            .await;
+        assert_eq!(
+            output,
+            vec![(
+                "mqtt/tedge/rpi4/device:main/m/environment".to_string(),
+                json!({"temperature": 21.3, "time": "1970-01-01T00:00:00Z"})
+            )]
+        );
+
+        let output = flows
+            .process("te/device/child1///twin/firmware", r#"{"version": "1.0"}"#)
+            .await;
+        assert_eq!(
+            output,
+            vec![(
+                "mqtt/tedge/rpi4/device:child1/twin/firmware".to_string(),
+                json!({"version": "1.0"})
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn remote_commands_are_forwarded_to_thin_edge() {
+        let mut flows = MqttCloudFlows::new(false).await;
+
+        let output = flows
+            .process("mqtt/tedge/rpi4/device:main/cmd/restart/7", "{}")
+            .await;
+        assert_eq!(
+            output,
+            vec![(
+                "te/device/main///cmd/restart/mqtt-7".to_string(),
+                json!({"status": "init"})
+            )]
+        );
+
+        let output = flows
+            .process(
+                "te/device/main///cmd/restart/mqtt-7",
+                r#"{"status": "restarting"}"#,
+            )
+            .await;
+        assert_eq!(
+            output,
+            vec![(
+                "mqtt/tedge/rpi4/device:main/cmd/restart/7/status".to_string(),
+                json!({"status": "restarting"})
+            )]
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use crate::templates::entity_topic_level;
+use crate::templates::TopicTemplate;
+use std::time::SystemTime;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_flows::ConfigError;
+use tedge_flows::FlowContextHandle;
+use tedge_flows::FlowError;
+use tedge_flows::JsonValue;
+use tedge_flows::Message;
+
+/// Publish measurements, events, alarms and twin data on the remote topics given by templates
+#[derive(Clone, Default)]
+pub struct SetMqttCloudTopic {
+    measurements: Option<TopicTemplate>,
+    events: Option<TopicTemplate>,
+    alarms: Option<TopicTemplate>,
+    twin: Option<TopicTemplate>,
+}
+
+impl tedge_flows::Transformer for SetMqttCloudTopic {
+    fn name(&self) -> &str {
+        "set-mqtt-cloud-topic"
+    }
+
+    fn set_config(&mut self, config: JsonValue) -> Result<(), ConfigError> {
+        let template = |property: &str| {
+            config
+                .string_property(property)
+                .map(TopicTemplate::try_new)
+                .transpose()
+                .map_err(|err| ConfigError::IncorrectSetting(err.to_string()))
+        };
+        self.measurements = template("measurements")?;
+        self.events = template("events")?;
+        self.alarms = template("alarms")?;
+        self.twin = template("twin")?;
+        Ok(())
+    }
+
+    fn on_message(
+        &mut self,
+        _timestamp: SystemTime,
+        message: &Message,
+        _context: &FlowContextHandle,
+    ) -> Result<Vec<Message>, FlowError> {
+        let levels: Vec<_> = message.topic.split('/').collect();
+        let [_, a, b, c, d, channel, ty] = levels[..] else {
+            return Ok(vec![]);
+        };
+        let template = match channel {
+            "m" => &self.measurements,
+            "e" => &self.events,
+            "a" => &self.alarms,
+            "twin" => &self.twin,
+            _ => return Ok(vec![]),
+        };
+        let Some(template) = template else {
+            return Ok(vec![]);
+        };
+        let Ok(entity) = format!("{a}/{b}/{c}/{d}").parse::<EntityTopicId>() else {
+            return Ok(vec![]);
+        };
+
+        let topic = template.render(&[("entity", &entity_topic_level(&entity)), ("type", ty)]);
+        let mut mapped_message = message.clone();
+        mapped_message.topic = topic;
+        Ok(vec![mapped_message])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tedge_flows::Transformer;
+
+    fn transformer() -> SetMqttCloudTopic {
+        let mut transformer = SetMqttCloudTopic::default();
+        transformer
+            .set_config(
+                JsonValue::from_value(json!({
+                    "measurements": "tedge/rpi4/{entity}/m/{type}",
+                    "events": "tedge/rpi4/{entity}/e/{type}",
+                    "twin": "tedge/rpi4/{entity}/twin/{type}",
+                }))
+                .unwrap(),
+            )
+            .unwrap();
+        transformer
+    }
+
+    fn map(transformer: &mut SetMqttCloudTopic, topic: &str) -> Vec<String> {
+        let message = Message::new(topic, r#"{"temperature": 21.3}"#);
+        transformer
+            .on_message(SystemTime::now(), &message, &FlowContextHandle::default())
+            .unwrap()
+            .into_iter()
+            .map(|message| message.topic)
+            .collect()
+    }
+
+    #[test]
+    fn telemetry_is_published_on_configured_topics() {
+        let mut transformer = transformer();
+
+        assert_eq!(
+            map(&mut transformer, "te/device/main///m/environment"),
+            vec!["tedge/rpi4/device:main/m/environment"]
+        );
+        assert_eq!(
+            map(&mut transformer, "te/device/child1///e/login"),
+            vec!["tedge/rpi4/device:child1/e/login"]
+        );
+        assert_eq!(
+            map(&mut transformer, "te/device/main/service/app/twin/config"),
+            vec!["tedge/rpi4/device:main:service:app/twin/config"]
+        );
+    }
+
+    #[test]
+    fn messages_without_configured_topic_are_ignored() {
+        let mut transformer = transformer();
+
+        assert!(map(&mut transformer, "te/device/main///a/high_temperature").is_empty());
+        assert!(map(&mut transformer, "te/device/main///cmd/restart/1").is_empty());
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_config::tedge_toml::mapper_config::MqttCloudTopicTemplates;
+
+/// A remote topic, with `{placeholder}`s replaced by message specific values
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TopicTemplate {
+    template: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Invalid topic template {template:?}: {reason}")]
+pub struct InvalidTopicTemplate {
+    template: String,
+    reason: String,
+}
+
+impl TopicTemplate {
+    pub fn try_new(template: &str) -> Result<Self, InvalidTopicTemplate> {
+        let invalid = |reason: &str| InvalidTopicTemplate {
+            template: template.to_string(),
+            reason: reason.to_string(),
+        };
+        if template.is_empty() {
+            return Err(invalid("a topic cannot be empty"));
+        }
+        if template.contains(['+', '#']) {
+            return Err(invalid("a topic cannot contain MQTT wildcards"));
+        }
+        if template.starts_with('$') {
+            return Err(invalid("a topic cannot start with '$'"));
+        }
+        Ok(TopicTemplate {
+            template: template.to_string(),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Return a copy of this template with an additional topic level
+    pub fn with_suffix(&self, suffix: &str) -> Self {
+        TopicTemplate {
+            template: format!("{}/{suffix}", self.template),
+        }
+    }
+
+    /// Replace the placeholders by the given values
+    pub fn render(&self, values: &[(&str, &str)]) -> String {
+        values
+            .iter()
+            .fold(self.template.clone(), |topic, (placeholder, value)| {
+                topic.replace(&format!("{{{placeholder}}}"), value)
+            })
+    }
+
+    /// The topic filter matching all the topics rendered by this template
+    ///
+    /// Any topic level with a placeholder is replaced by a `+` wildcard.
+    pub fn topic_filter(&self) -> String {
+        self.template
+            .split('/')
+            .map(|level| if level.contains('{') { "+" } else { level })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Check that each of the given placeholders is used as a full topic level
+    pub fn require_placeholders(&self, placeholders: &[&str]) -> Result<(), InvalidTopicTemplate> {
+        for placeholder in placeholders {
+            let level = format!("{{{placeholder}}}");
+            if !self.template.split('/').any(|l| l == level) {
+                return Err(InvalidTopicTemplate {
+                    template: self.template.clone(),
+                    reason: format!("the {level} placeholder must be used as a full topic level"),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Extract the placeholder values from a topic matching this template
+    pub fn capture<'a>(&'a self, topic: &'a str) -> Option<HashMap<&'a str, &'a str>> {
+        let template_levels: Vec<_> = self.template.split('/').collect();
+        let topic_levels: Vec<_> = topic.split('/').collect();
+        if template_levels.len() != topic_levels.len() {
+            return None;
+        }
+
+        let mut values = HashMap::new();
+        for (template_level, topic_level) in template_levels.into_iter().zip(topic_levels) {
+            match template_level
+                .strip_prefix('{')
+                .and_then(|l| l.strip_suffix('}'))
+            {
+                Some(placeholder) => {
+                    values.insert(placeholder, topic_level);
+                }
+                None if template_level == topic_level => (),
+                None => return None,
+            }
+        }
+        Some(values)
+    }
+}
+
+/// The remote topics used to exchange messages with the MQTT broker
+#[derive(Clone, Debug)]
+pub struct MqttCloudTopics {
+    pub measurements: TopicTemplate,
+    pub events: TopicTemplate,
+    pub alarms: TopicTemplate,
+    pub twin: TopicTemplate,
+    pub commands: TopicTemplate,
+}
+
+impl MqttCloudTopics {
+    /// Build the remote topics from the configured templates
+    ///
+    /// The `{device_id}` placeholder is replaced once for all by the given device id.
+    pub fn try_new(
+        templates: &MqttCloudTopicTemplates,
+        device_id: &str,
+    ) -> Result<Self, InvalidTopicTemplate> {
+        let template =
+            |template: &str| TopicTemplate::try_new(&template.replace("{device_id}", device_id));
+        let commands = template(&templates.commands)?;
+        commands.require_placeholders(&["entity", "operation", "cmd_id"])?;
+        Ok(MqttCloudTopics {
+            measurements: template(&templates.measurements)?,
+            events: template(&templates.events)?,
+            alarms: template(&templates.alarms)?,
+            twin: template(&templates.twin)?,
+            commands,
+        })
+    }
+
+    /// The remote topic on which the status of a command is published
+    pub fn command_status(&self) -> TopicTemplate {
+        self.commands.with_suffix("status")
+    }
+
+    /// The remote topic filters of the messages forwarded to the remote broker
+    pub fn outgoing_filters(&self) -> Vec<String> {
+        let mut filters = Vec::new();
+        for template in [
+            &self.measurements,
+            &self.events,
+            &self.alarms,
+            &self.twin,
+            &self.command_status(),
+        ] {
+            let filter = template.topic_filter();
+            if !filters.contains(&filter) {
+                filters.push(filter);
+            }
+        }
+        filters
+    }
+
+    /// The remote topic filters of the messages received from the remote broker
+    pub fn incoming_filters(&self) -> Vec<String> {
+        vec![self.commands.topic_filter()]
+    }
+}
+
+/// Encode an entity topic id as a single topic level, e.g. `device:child1`
+pub fn entity_topic_level(entity: &EntityTopicId) -> String {
+    entity
+        .as_str()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Decode an entity topic id encoded as a single topic level
+pub fn entity_from_topic_level(level: &str) -> Option<EntityTopicId> {
+    let mut segments: Vec<_> = level.split(':').collect();
+    if segments.len() > 4 {
+        return None;
+    }
+    segments.resize(4, "");
+    segments.join("/").parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_topic_template() {
+        let template = TopicTemplate::try_new("tedge/rpi4/{entity}/m/{type}").unwrap();
+
+        assert_eq!(
+            template.render(&[("entity", "device:child1"), ("type", "environment")]),
+            "tedge/rpi4/device:child1/m/environment"
+        );
+        assert_eq!(template.topic_filter(), "tedge/rpi4/+/m/+");
+    }
+
+    #[test]
+    fn capture_placeholder_values() {
+        let template =
+            TopicTemplate::try_new("devices/d1/{entity}/cmd/{operation}/{cmd_id}").unwrap();
+
+        let values = template
+            .capture("devices/d1/device:main/cmd/restart/1234")
+            .unwrap();
+        assert_eq!(values.get("entity"), Some(&"device:main"));
+        assert_eq!(values.get("operation"), Some(&"restart"));
+        assert_eq!(values.get("cmd_id"), Some(&"1234"));
+
+        assert!(template
+            .capture("devices/d2/device:main/cmd/restart/1234")
+            .is_none());
+        assert!(template
+            .capture("devices/d1/device:main/cmd/restart/1234/status")
+            .is_none());
+    }
+
+    #[test]
+    fn command_templates_must_have_full_level_placeholders() {
+        let templates = MqttCloudTopicTemplates {
+            measurements: "m".to_string(),
+            events: "e".to_string(),
+            alarms: "a".to_string(),
+            twin: "twin".to_string(),
+            commands: "cmd/{entity}/{operation}-{cmd_id}".to_string(),
+        };
+
+        let err = MqttCloudTopics::try_new(&templates, "d1").unwrap_err();
+        assert!(err.to_string().contains("{operation}"), "{err}");
+    }
+
+    #[test]
+    fn entity_topic_level_round_trip() {
+        for entity in [
+            "device/main//",
+            "device/child1//",
+            "device/main/service/tedge-agent",
+        ] {
+            let entity: EntityTopicId = entity.parse().unwrap();
+            let level = entity_topic_level(&entity);
+            assert!(!level.contains('/'));
+            assert_eq!(entity_from_topic_level(&level), Some(entity));
+        }
+    }
+}
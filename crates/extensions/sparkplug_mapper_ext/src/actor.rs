@@ -0,0 +1,966 @@
+//! Publication of the thin-edge entities as a Sparkplug B edge node and devices
+//!
+//! - The main device is the edge node and each other entity a Sparkplug device,
+//!   named after its entity topic id with `:` as separator (e.g. `device:child1`).
+//! - An NBIRTH is published on start, declaring the `bdSeq` of the session and the node metrics.
+//!   A DBIRTH is published for each child device registered, and for any entity publishing measurements.
+//! - The measurements are published as NDATA or DDATA metrics, named `<type>/<name>`.
+//!   A measurement introducing a new metric triggers a rebirth of the node or device.
+//! - The NDEATH is the last will of the connection to the Sparkplug broker.
+//!   On each reconnection, a rebirth request is sent to the edge node itself,
+//!   so the births are published again.
+//! - NCMD and DCMD metric writes are executed as thin-edge commands:
+//!   `Node Control/Reboot` and `Device Control/Reboot` as `restart` commands,
+//!   and any other metric write as a `metric_write` command.
+use crate::payload::Metric;
+use crate::payload::MetricValue;
+use crate::payload::Payload;
+use crate::topics::MessageType;
+use crate::topics::SparkplugTopic;
+use crate::topics::SPARKPLUG_NAMESPACE;
+use async_trait::async_trait;
+use camino::Utf8Path;
+use serde_json::json;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::convert::Infallible;
+use std::time::SystemTime;
+use tedge_actors::Actor;
+use tedge_actors::Builder;
+use tedge_actors::DynSender;
+use tedge_actors::MessageReceiver;
+use tedge_actors::MessageSink;
+use tedge_actors::MessageSource;
+use tedge_actors::NoConfig;
+use tedge_actors::RuntimeError;
+use tedge_actors::RuntimeRequest;
+use tedge_actors::RuntimeRequestSink;
+use tedge_actors::Sender;
+use tedge_actors::SimpleMessageBox;
+use tedge_actors::SimpleMessageBoxBuilder;
+use tedge_api::entity::EntityType;
+use tedge_api::entity_store::EntityRegistrationMessage;
+use tedge_api::measurement::parse_str;
+use tedge_api::measurement::MeasurementVisitor;
+use tedge_api::mqtt_topics::Channel;
+use tedge_api::mqtt_topics::ChannelFilter;
+use tedge_api::mqtt_topics::EntityFilter;
+use tedge_api::mqtt_topics::EntityTopicId;
+use tedge_api::mqtt_topics::MqttSchema;
+use tedge_api::mqtt_topics::OperationType;
+use tedge_api::workflow::GenericCommandState;
+use tedge_mqtt_ext::MqttMessage;
+use tedge_mqtt_ext::QoS;
+use tedge_mqtt_ext::Topic;
+use tedge_mqtt_ext::TopicFilter;
+use time::OffsetDateTime;
+use tracing::error;
+use tracing::warn;
+
+const BD_SEQ_METRIC: &str = "bdSeq";
+const REBIRTH_METRIC: &str = "Node Control/Rebirth";
+const NODE_REBOOT_METRIC: &str = "Node Control/Reboot";
+const DEVICE_REBOOT_METRIC: &str = "Device Control/Reboot";
+
+/// The operation of the commands triggered by metric writes
+const METRIC_WRITE_OPERATION: &str = "metric_write";
+
+/// The prefix of the ids of the commands triggered by Sparkplug commands
+const COMMAND_ID_PREFIX: &str = "sparkplug-";
+
+/// The file where the birth-death sequence number of the last session is persisted
+const BD_SEQ_FILE: &str = "bdseq";
+
+/// Configuration of the Sparkplug actor
+#[derive(Clone, Debug)]
+pub struct SparkplugConfig {
+    pub mqtt_schema: MqttSchema,
+    /// The main device, published as the Sparkplug edge node
+    pub device_topic_id: EntityTopicId,
+    pub group_id: String,
+    pub edge_node_id: String,
+    /// The birth-death sequence number of the session, as returned by [next_bd_seq]
+    pub bd_seq: u64,
+}
+
+impl SparkplugConfig {
+    fn sparkplug_topic(&self, message_type: MessageType, device_id: Option<&str>) -> Topic {
+        SparkplugTopic {
+            group_id: self.group_id.clone(),
+            message_type,
+            edge_node_id: self.edge_node_id.clone(),
+            device_id: device_id.map(|id| id.to_string()),
+        }
+        .to_topic()
+    }
+
+    fn local_topics(&self) -> TopicFilter {
+        let mut topics = TopicFilter::empty();
+        for channel in [
+            ChannelFilter::EntityMetadata,
+            ChannelFilter::Measurement,
+            ChannelFilter::CommandMetadata(OperationType::Restart),
+            ChannelFilter::AnyCommand,
+        ] {
+            topics.add_all(self.mqtt_schema.topics(EntityFilter::AnyEntity, channel));
+        }
+        topics
+    }
+
+    fn sparkplug_command_topics(&self) -> TopicFilter {
+        let mut topics = TopicFilter::empty();
+        topics.add_all(self.sparkplug_topic(MessageType::NCmd, None).into());
+        topics.add_all(self.sparkplug_topic(MessageType::DCmd, Some("+")).into());
+        topics
+    }
+
+    /// The NDEATH message, to be registered as the last will of the connection to the Sparkplug broker
+    pub fn death_certificate(&self) -> MqttMessage {
+        let payload = Payload {
+            timestamp: Some(now_millis()),
+            metrics: vec![Metric::new(BD_SEQ_METRIC, MetricValue::UInt(self.bd_seq))],
+            seq: None,
+        };
+        MqttMessage::new(
+            &self.sparkplug_topic(MessageType::NDeath, None),
+            payload.encode(),
+        )
+        .with_qos(QoS::AtLeastOnce)
+    }
+
+    /// The NCMD requesting the edge node to publish its births again
+    ///
+    /// This message is published on each reconnection to the Sparkplug broker.
+    pub fn rebirth_request(&self) -> MqttMessage {
+        let payload = Payload {
+            timestamp: Some(now_millis()),
+            metrics: vec![Metric::new(REBIRTH_METRIC, MetricValue::Boolean(true))],
+            seq: None,
+        };
+        MqttMessage::new(
+            &self.sparkplug_topic(MessageType::NCmd, None),
+            payload.encode(),
+        )
+        .with_qos(QoS::AtMostOnce)
+    }
+}
+
+/// Increment the birth-death sequence number persisted in the given directory
+///
+/// Returns the sequence number of the new session, starting with 0.
+pub fn next_bd_seq(state_dir: &Utf8Path) -> u64 {
+    let path = state_dir.join(BD_SEQ_FILE);
+    let bd_seq = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|previous| previous.trim().parse::<u64>().ok())
+        .map_or(0, |previous| (previous + 1) % 256);
+    let persisted =
+        std::fs::create_dir_all(state_dir).and_then(|()| std::fs::write(&path, bd_seq.to_string()));
+    if let Err(err) = persisted {
+        error!("Failed to persist the Sparkplug bdSeq into {path}: {err}");
+    }
+    bd_seq
+}
+
+/// The Sparkplug device id of an entity, i.e. its topic id with `:` as separator
+fn sparkplug_device_id(entity: &EntityTopicId) -> String {
+    entity.as_str().trim_end_matches('/').replace('/', ":")
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+pub struct SparkplugActor {
+    config: SparkplugConfig,
+    messages: SimpleMessageBox<MqttMessage, MqttMessage>,
+    sparkplug: DynSender<MqttMessage>,
+    /// The latest value of each metric of the edge node
+    node_metrics: BTreeMap<String, MetricValue>,
+    /// The latest value of each metric of the Sparkplug devices
+    devices: BTreeMap<EntityTopicId, BTreeMap<String, MetricValue>>,
+    /// The entities supporting the `restart` operation
+    restartable: BTreeSet<EntityTopicId>,
+    seq: u64,
+}
+
+#[async_trait]
+impl Actor for SparkplugActor {
+    fn name(&self) -> &str {
+        "SparkplugActor"
+    }
+
+    async fn run(mut self) -> Result<(), RuntimeError> {
+        for birth in self.node_birth() {
+            self.publish(birth).await?;
+        }
+        while let Some(message) = self.messages.recv().await {
+            for output in self.process(&message) {
+                self.publish(output).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SparkplugActor {
+    /// Publish a message either on the Sparkplug broker or on the local broker
+    async fn publish(&mut self, message: MqttMessage) -> Result<(), RuntimeError> {
+        if message.topic.name.starts_with(SPARKPLUG_NAMESPACE) {
+            self.sparkplug.send(message).await?;
+        } else {
+            self.messages.send(message).await?;
+        }
+        Ok(())
+    }
+
+    fn process(&mut self, message: &MqttMessage) -> Vec<MqttMessage> {
+        if let Some(topic) = SparkplugTopic::parse(&message.topic.name) {
+            return self.process_sparkplug_command(topic, message);
+        }
+        let Ok((entity, channel)) = self.config.mqtt_schema.entity_channel_of(&message.topic)
+        else {
+            return vec![];
+        };
+        match channel {
+            Channel::EntityMetadata => self.process_registration(entity, message),
+            Channel::Measurement { measurement_type } => {
+                self.process_measurement(entity, &measurement_type, message)
+            }
+            Channel::CommandMetadata {
+                operation: OperationType::Restart,
+            } => self.process_restart_capability(entity, message),
+            Channel::Command { cmd_id, .. } => self.process_command_update(&cmd_id, message),
+            _ => vec![],
+        }
+    }
+
+    fn process_registration(
+        &mut self,
+        entity: EntityTopicId,
+        message: &MqttMessage,
+    ) -> Vec<MqttMessage> {
+        if entity == self.config.device_topic_id {
+            return vec![];
+        }
+        if message.payload_bytes().is_empty() {
+            self.restartable.remove(&entity);
+            if self.devices.remove(&entity).is_none() {
+                return vec![];
+            }
+            let device_id = sparkplug_device_id(&entity);
+            return vec![self.sparkplug_message(MessageType::DDeath, Some(&device_id), vec![])];
+        }
+
+        match EntityRegistrationMessage::try_from(entity.clone(), message.payload_bytes()) {
+            Ok(registration)
+                if registration.r#type == EntityType::ChildDevice
+                    && !self.devices.contains_key(&entity) =>
+            {
+                self.devices.insert(entity.clone(), BTreeMap::new());
+                vec![self.device_birth(&entity)]
+            }
+            Ok(_) => vec![],
+            Err(err) => {
+                warn!(
+                    "Ignoring invalid registration message on {}: {err}",
+                    message.topic.name
+                );
+                vec![]
+            }
+        }
+    }
+
+    fn process_measurement(
+        &mut self,
+        entity: EntityTopicId,
+        measurement_type: &str,
+        message: &MqttMessage,
+    ) -> Vec<MqttMessage> {
+        let mut collector = MetricsCollector::new(measurement_type);
+        let parsed = message
+            .payload_str()
+            .map_err(|err| err.to_string())
+            .and_then(|payload| parse_str(payload, &mut collector).map_err(|err| err.to_string()));
+        if let Err(err) = parsed {
+            warn!(
+                "Ignoring invalid measurement on {}: {err}",
+                message.topic.name
+            );
+            return vec![];
+        }
+        let timestamp = collector.timestamp.unwrap_or_else(now_millis);
+        let metrics: Vec<Metric> = collector
+            .metrics
+            .into_iter()
+            .map(|(name, value)| Metric::new(name, value).with_timestamp(timestamp))
+            .collect();
+        if metrics.is_empty() {
+            return vec![];
+        }
+
+        if entity == self.config.device_topic_id {
+            if update_metrics(&mut self.node_metrics, &metrics) {
+                return self.node_birth();
+            }
+            return vec![self.sparkplug_message(MessageType::NData, None, metrics)];
+        }
+
+        let is_new_device = !self.devices.contains_key(&entity);
+        let device_metrics = self.devices.entry(entity.clone()).or_default();
+        if update_metrics(device_metrics, &metrics) || is_new_device {
+            return vec![self.device_birth(&entity)];
+        }
+        let device_id = sparkplug_device_id(&entity);
+        vec![self.sparkplug_message(MessageType::DData, Some(&device_id), metrics)]
+    }
+
+    fn process_restart_capability(
+        &mut self,
+        entity: EntityTopicId,
+        message: &MqttMessage,
+    ) -> Vec<MqttMessage> {
+        let updated = if message.payload_bytes().is_empty() {
+            self.restartable.remove(&entity)
+        } else {
+            self.restartable.insert(entity.clone())
+        };
+        if !updated {
+            return vec![];
+        }
+        // The reboot metric being added or removed, the node or device has to be reborn
+        if entity == self.config.device_topic_id {
+            self.node_birth()
+        } else if self.devices.contains_key(&entity) {
+            vec![self.device_birth(&entity)]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Clear the commands triggered by Sparkplug commands, once successful or failed
+    fn process_command_update(&mut self, cmd_id: &str, message: &MqttMessage) -> Vec<MqttMessage> {
+        if !cmd_id.starts_with(COMMAND_ID_PREFIX) || message.payload_bytes().is_empty() {
+            return vec![];
+        }
+        match GenericCommandState::from_command_message(message) {
+            Ok(command) if command.is_finished() => vec![command.clear().into_message()],
+            Ok(_) => vec![],
+            Err(err) => {
+                warn!(
+                    "Ignoring invalid command state on {}: {err}",
+                    message.topic.name
+                );
+                vec![]
+            }
+        }
+    }
+
+    fn process_sparkplug_command(
+        &mut self,
+        topic: SparkplugTopic,
+        message: &MqttMessage,
+    ) -> Vec<MqttMessage> {
+        if topic.group_id != self.config.group_id || topic.edge_node_id != self.config.edge_node_id
+        {
+            return vec![];
+        }
+        let (entity, reboot_metric) = match (topic.message_type, &topic.device_id) {
+            (MessageType::NCmd, None) => (self.config.device_topic_id.clone(), NODE_REBOOT_METRIC),
+            (MessageType::DCmd, Some(device_id)) => match self.entity_of(device_id) {
+                Some(entity) => (entity, DEVICE_REBOOT_METRIC),
+                None => {
+                    warn!("Ignoring DCMD sent to the unknown Sparkplug device {device_id}");
+                    return vec![];
+                }
+            },
+            _ => return vec![],
+        };
+        let payload = match Payload::decode(message.payload_bytes()) {
+            Ok(payload) => payload,
+            Err(err) => {
+                warn!(
+                    "Ignoring invalid Sparkplug payload on {}: {err}",
+                    message.topic.name
+                );
+                return vec![];
+            }
+        };
+
+        let timestamp = payload.timestamp.unwrap_or_else(now_millis);
+        let mut messages = vec![];
+        for (index, metric) in payload.metrics.into_iter().enumerate() {
+            let cmd_id = format!("{COMMAND_ID_PREFIX}{timestamp}-{index}");
+            let name = metric.name.as_str();
+            if name == REBIRTH_METRIC && topic.message_type == MessageType::NCmd {
+                if metric.value == MetricValue::Boolean(true) {
+                    messages.extend(self.node_birth());
+                }
+            } else if name == reboot_metric {
+                if metric.value == MetricValue::Boolean(true) {
+                    messages.push(self.command_message(
+                        &entity,
+                        OperationType::Restart,
+                        cmd_id,
+                        json!({}),
+                    ));
+                }
+            } else {
+                match metric.value.to_json() {
+                    Some(value) if !name.is_empty() => messages.push(self.command_message(
+                        &entity,
+                        OperationType::from(METRIC_WRITE_OPERATION),
+                        cmd_id,
+                        json!({"metric": name, "value": value}),
+                    )),
+                    _ => warn!("Ignoring unsupported write of the metric {name:?} on {topic}"),
+                }
+            }
+        }
+        messages
+    }
+
+    fn entity_of(&self, device_id: &str) -> Option<EntityTopicId> {
+        self.devices
+            .keys()
+            .find(|entity| sparkplug_device_id(entity) == device_id)
+            .cloned()
+    }
+
+    /// The NBIRTH of the edge node, followed by the DBIRTH of all the devices
+    fn node_birth(&mut self) -> Vec<MqttMessage> {
+        self.seq = 0;
+        let mut metrics = vec![
+            Metric::new(BD_SEQ_METRIC, MetricValue::UInt(self.config.bd_seq)),
+            Metric::new(REBIRTH_METRIC, MetricValue::Boolean(false)),
+        ];
+        if self.restartable.contains(&self.config.device_topic_id) {
+            metrics.push(Metric::new(NODE_REBOOT_METRIC, MetricValue::Boolean(false)));
+        }
+        metrics.extend(
+            self.node_metrics
+                .iter()
+                .map(|(name, value)| Metric::new(name, value.clone())),
+        );
+
+        let mut messages = vec![self.sparkplug_message(MessageType::NBirth, None, metrics)];
+        let devices: Vec<EntityTopicId> = self.devices.keys().cloned().collect();
+        for entity in devices {
+            messages.push(self.device_birth(&entity));
+        }
+        messages
+    }
+
+    fn device_birth(&mut self, entity: &EntityTopicId) -> MqttMessage {
+        let mut metrics = vec![];
+        if self.restartable.contains(entity) {
+            metrics.push(Metric::new(
+                DEVICE_REBOOT_METRIC,
+                MetricValue::Boolean(false),
+            ));
+        }
+        if let Some(device_metrics) = self.devices.get(entity) {
+            metrics.extend(
+                device_metrics
+                    .iter()
+                    .map(|(name, value)| Metric::new(name, value.clone())),
+            );
+        }
+        let device_id = sparkplug_device_id(entity);
+        self.sparkplug_message(MessageType::DBirth, Some(&device_id), metrics)
+    }
+
+    fn sparkplug_message(
+        &mut self,
+        message_type: MessageType,
+        device_id: Option<&str>,
+        metrics: Vec<Metric>,
+    ) -> MqttMessage {
+        let payload = Payload {
+            timestamp: Some(now_millis()),
+            metrics,
+            seq: Some(self.seq),
+        };
+        self.seq = (self.seq + 1) % 256;
+        let topic = self.config.sparkplug_topic(message_type, device_id);
+        MqttMessage::new(&topic, payload.encode()).with_qos(QoS::AtMostOnce)
+    }
+
+    fn command_message(
+        &self,
+        entity: &EntityTopicId,
+        operation: OperationType,
+        cmd_id: String,
+        payload: Value,
+    ) -> MqttMessage {
+        let topic = self
+            .config
+            .mqtt_schema
+            .topic_for(entity, &Channel::Command { operation, cmd_id });
+        GenericCommandState::new(topic, "init".to_string(), payload).into_message()
+    }
+}
+
+/// Update the latest values of a set of metrics
+///
+/// Returns `true` if a metric is new or has changed of data type, requiring a rebirth.
+fn update_metrics(known: &mut BTreeMap<String, MetricValue>, metrics: &[Metric]) -> bool {
+    let mut rebirth = false;
+    for metric in metrics {
+        let previous = known.insert(metric.name.clone(), metric.value.clone());
+        rebirth |= previous.is_none_or(|previous| {
+            std::mem::discriminant(&previous) != std::mem::discriminant(&metric.value)
+        });
+    }
+    rebirth
+}
+
+/// Collect the metrics of a thin-edge measurement
+///
+/// The metrics are named after the measurement type, the group if any, and the measurement name,
+/// e.g. `environment/temperature` or `environment/coordinates/x`.
+struct MetricsCollector {
+    prefix: String,
+    group: Option<String>,
+    timestamp: Option<u64>,
+    metrics: Vec<(String, MetricValue)>,
+}
+
+impl MetricsCollector {
+    fn new(measurement_type: &str) -> Self {
+        MetricsCollector {
+            prefix: measurement_type.to_string(),
+            group: None,
+            timestamp: None,
+            metrics: vec![],
+        }
+    }
+
+    fn add_metric(&mut self, name: &str, value: MetricValue) {
+        let name = [
+            Some(self.prefix.as_str()),
+            self.group.as_deref(),
+            Some(name),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|level| !level.is_empty())
+        .collect::<Vec<_>>()
+        .join("/");
+        self.metrics.push((name, value));
+    }
+}
+
+impl MeasurementVisitor for MetricsCollector {
+    type Error = Infallible;
+
+    fn visit_timestamp(&mut self, value: OffsetDateTime) -> Result<(), Self::Error> {
+        self.timestamp = Some((value.unix_timestamp_nanos() / 1_000_000) as u64);
+        Ok(())
+    }
+
+    fn visit_measurement(&mut self, name: &str, value: f64) -> Result<(), Self::Error> {
+        self.add_metric(name, MetricValue::Double(value));
+        Ok(())
+    }
+
+    fn visit_text_property(&mut self, name: &str, value: &str) -> Result<(), Self::Error> {
+        self.add_metric(name, MetricValue::String(value.to_string()));
+        Ok(())
+    }
+
+    fn visit_json_property(&mut self, _name: &str, _value: Value) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn visit_start_group(&mut self, group: &str) -> Result<(), Self::Error> {
+        self.group = Some(group.to_string());
+        Ok(())
+    }
+
+    fn visit_end_group(&mut self) -> Result<(), Self::Error> {
+        self.group = None;
+        Ok(())
+    }
+}
+
+pub struct SparkplugActorBuilder {
+    config: SparkplugConfig,
+    box_builder: SimpleMessageBoxBuilder<MqttMessage, MqttMessage>,
+    sparkplug_sender: DynSender<MqttMessage>,
+}
+
+impl SparkplugActorBuilder {
+    /// Connect the Sparkplug actor to the local MQTT broker and to the Sparkplug MQTT broker
+    pub fn new(
+        config: SparkplugConfig,
+        mqtt: &mut (impl MessageSource<MqttMessage, TopicFilter> + MessageSink<MqttMessage>),
+        sparkplug_mqtt: &mut (impl MessageSource<MqttMessage, TopicFilter> + MessageSink<MqttMessage>),
+    ) -> Self {
+        let mut box_builder = SimpleMessageBoxBuilder::new("Sparkplug", 16);
+        box_builder.connect_source(config.local_topics(), mqtt);
+        box_builder.connect_sink(NoConfig, mqtt);
+        box_builder.connect_source(config.sparkplug_command_topics(), sparkplug_mqtt);
+        let sparkplug_sender = sparkplug_mqtt.get_sender();
+        SparkplugActorBuilder {
+            config,
+            box_builder,
+            sparkplug_sender,
+        }
+    }
+}
+
+impl RuntimeRequestSink for SparkplugActorBuilder {
+    fn get_signal_sender(&self) -> DynSender<RuntimeRequest> {
+        self.box_builder.get_signal_sender()
+    }
+}
+
+impl Builder<SparkplugActor> for SparkplugActorBuilder {
+    type Error = Infallible;
+
+    fn try_build(self) -> Result<SparkplugActor, Self::Error> {
+        Ok(self.build())
+    }
+
+    fn build(self) -> SparkplugActor {
+        SparkplugActor {
+            config: self.config,
+            messages: self.box_builder.build(),
+            sparkplug: self.sparkplug_sender,
+            node_metrics: BTreeMap::new(),
+            devices: BTreeMap::new(),
+            restartable: BTreeSet::new(),
+            seq: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tedge_actors::test_helpers::MessageReceiverExt;
+    use tedge_actors::test_helpers::TimedMessageBox;
+    use tempfile::TempDir;
+
+    const TEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+    type MqttBox = TimedMessageBox<SimpleMessageBox<MqttMessage, MqttMessage>>;
+
+    #[tokio::test]
+    async fn the_edge_node_is_born_on_start() {
+        let (_local, mut sparkplug) = spawn_sparkplug_actor();
+
+        let (topic, payload) = next_sparkplug_message(&mut sparkplug).await;
+        assert_eq!(topic, "spBv1.0/plant-1/NBIRTH/gateway");
+        assert_eq!(payload.seq, Some(0));
+        assert_eq!(
+            metric_values(&payload),
+            vec![
+                ("bdSeq", MetricValue::UInt(3)),
+                ("Node Control/Rebirth", MetricValue::Boolean(false)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn child_devices_are_born_on_registration_and_die_on_deregistration() {
+        let (mut local, mut sparkplug) = spawn_sparkplug_actor();
+        next_sparkplug_message(&mut sparkplug).await;
+
+        local
+            .send(mqtt_message(
+                "te/device/child1//",
+                r#"{"@type":"child-device"}"#,
+            ))
+            .await
+            .unwrap();
+        let (topic, payload) = next_sparkplug_message(&mut sparkplug).await;
+        assert_eq!(topic, "spBv1.0/plant-1/DBIRTH/gateway/device:child1");
+        assert_eq!(payload.seq, Some(1));
+
+        // Services are only born when publishing measurements
+        local
+            .send(mqtt_message(
+                "te/device/main/service/tedge-agent",
+                r#"{"@type":"service"}"#,
+            ))
+            .await
+            .unwrap();
+        local
+            .send(mqtt_message("te/device/child1//", ""))
+            .await
+            .unwrap();
+        let (topic, payload) = next_sparkplug_message(&mut sparkplug).await;
+        assert_eq!(topic, "spBv1.0/plant-1/DDEATH/gateway/device:child1");
+        assert_eq!(payload.seq, Some(2));
+    }
+
+    #[tokio::test]
+    async fn measurements_are_published_as_metrics() {
+        let (mut local, mut sparkplug) = spawn_sparkplug_actor();
+        next_sparkplug_message(&mut sparkplug).await;
+
+        // A new metric of the edge node triggers a rebirth
+        local
+            .send(mqtt_message(
+                "te/device/main///m/environment",
+                r#"{"temperature": 21.3, "time": "2025-01-21T09:10:11.123Z"}"#,
+            ))
+            .await
+            .unwrap();
+        let (topic, payload) = next_sparkplug_message(&mut sparkplug).await;
+        assert_eq!(topic, "spBv1.0/plant-1/NBIRTH/gateway");
+        assert_eq!(payload.seq, Some(0));
+        assert!(metric_values(&payload)
+            .contains(&("environment/temperature", MetricValue::Double(21.3))));
+
+        local
+            .send(mqtt_message(
+                "te/device/main///m/environment",
+                r#"{"temperature": 22.5, "time": "2025-01-21T09:10:11.123Z"}"#,
+            ))
+            .await
+            .unwrap();
+        let (topic, payload) = next_sparkplug_message(&mut sparkplug).await;
+        assert_eq!(topic, "spBv1.0/plant-1/NDATA/gateway");
+        assert_eq!(payload.seq, Some(1));
+        assert_eq!(
+            payload.metrics,
+            vec![
+                Metric::new("environment/temperature", MetricValue::Double(22.5))
+                    .with_timestamp(1737450611123)
+            ]
+        );
+
+        // The first measurements of an entity trigger its birth
+        local
+            .send(mqtt_message(
+                "te/device/child1///m/",
+                r#"{"pressure": {"inlet": 1.2}, "mode": "auto"}"#,
+            ))
+            .await
+            .unwrap();
+        let (topic, payload) = next_sparkplug_message(&mut sparkplug).await;
+        assert_eq!(topic, "spBv1.0/plant-1/DBIRTH/gateway/device:child1");
+        assert_eq!(
+            metric_values(&payload),
+            vec![
+                ("mode", MetricValue::String("auto".to_string())),
+                ("pressure/inlet", MetricValue::Double(1.2)),
+            ]
+        );
+
+        local
+            .send(mqtt_message(
+                "te/device/child1///m/",
+                r#"{"pressure": {"inlet": 1.4}}"#,
+            ))
+            .await
+            .unwrap();
+        let (topic, payload) = next_sparkplug_message(&mut sparkplug).await;
+        assert_eq!(topic, "spBv1.0/plant-1/DDATA/gateway/device:child1");
+        assert_eq!(payload.seq, Some(3));
+        assert_eq!(
+            metric_values(&payload),
+            vec![("pressure/inlet", MetricValue::Double(1.4))]
+        );
+    }
+
+    #[tokio::test]
+    async fn all_births_are_published_again_on_rebirth_request() {
+        let (mut local, mut sparkplug) = spawn_sparkplug_actor();
+        next_sparkplug_message(&mut sparkplug).await;
+        local
+            .send(mqtt_message(
+                "te/device/child1//",
+                r#"{"@type":"child-device"}"#,
+            ))
+            .await
+            .unwrap();
+        next_sparkplug_message(&mut sparkplug).await;
+
+        sparkplug
+            .send(test_config().rebirth_request())
+            .await
+            .unwrap();
+
+        let (topic, payload) = next_sparkplug_message(&mut sparkplug).await;
+        assert_eq!(topic, "spBv1.0/plant-1/NBIRTH/gateway");
+        assert_eq!(payload.seq, Some(0));
+        let (topic, payload) = next_sparkplug_message(&mut sparkplug).await;
+        assert_eq!(topic, "spBv1.0/plant-1/DBIRTH/gateway/device:child1");
+        assert_eq!(payload.seq, Some(1));
+    }
+
+    #[tokio::test]
+    async fn metric_writes_are_executed_as_commands() {
+        let (mut local, mut sparkplug) = spawn_sparkplug_actor();
+        next_sparkplug_message(&mut sparkplug).await;
+        local
+            .send(mqtt_message(
+                "te/device/child1//",
+                r#"{"@type":"child-device"}"#,
+            ))
+            .await
+            .unwrap();
+        next_sparkplug_message(&mut sparkplug).await;
+
+        // The reboot metric is declared once the restart operation is supported
+        local
+            .send(mqtt_message("te/device/child1///cmd/restart", "{}"))
+            .await
+            .unwrap();
+        let (topic, payload) = next_sparkplug_message(&mut sparkplug).await;
+        assert_eq!(topic, "spBv1.0/plant-1/DBIRTH/gateway/device:child1");
+        assert_eq!(
+            metric_values(&payload),
+            vec![("Device Control/Reboot", MetricValue::Boolean(false))]
+        );
+
+        sparkplug
+            .send(sparkplug_command(
+                "spBv1.0/plant-1/DCMD/gateway/device:child1",
+                vec![
+                    Metric::new("Device Control/Reboot", MetricValue::Boolean(true)),
+                    Metric::new("setpoint", MetricValue::Int(-5)),
+                ],
+            ))
+            .await
+            .unwrap();
+        let restart = local.recv().await.expect("a restart command");
+        assert_eq!(
+            restart.topic.name,
+            "te/device/child1///cmd/restart/sparkplug-1737450611123-0"
+        );
+        assert!(restart.retain);
+        assert_eq!(json_payload(&restart), json!({"status": "init"}));
+        let write = local.recv().await.expect("a metric_write command");
+        assert_eq!(
+            write.topic.name,
+            "te/device/child1///cmd/metric_write/sparkplug-1737450611123-1"
+        );
+        assert_eq!(
+            json_payload(&write),
+            json!({"status": "init", "metric": "setpoint", "value": -5})
+        );
+
+        // Commands are cleared once completed
+        local
+            .send(
+                mqtt_message(
+                    "te/device/child1///cmd/metric_write/sparkplug-1737450611123-1",
+                    r#"{"status":"successful","metric":"setpoint","value":-5}"#,
+                )
+                .with_retain(),
+            )
+            .await
+            .unwrap();
+        let clear = local.recv().await.expect("a clear message");
+        assert_eq!(
+            clear.topic.name,
+            "te/device/child1///cmd/metric_write/sparkplug-1737450611123-1"
+        );
+        assert!(clear.retain);
+        assert!(clear.payload_bytes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn commands_to_unknown_devices_are_ignored() {
+        let (mut local, mut sparkplug) = spawn_sparkplug_actor();
+        next_sparkplug_message(&mut sparkplug).await;
+
+        sparkplug
+            .send(sparkplug_command(
+                "spBv1.0/plant-1/DCMD/gateway/device:unknown",
+                vec![Metric::new(
+                    "Device Control/Reboot",
+                    MetricValue::Boolean(true),
+                )],
+            ))
+            .await
+            .unwrap();
+        assert!(local.recv().await.is_none());
+    }
+
+    #[test]
+    fn bd_seq_is_incremented_on_each_session() {
+        let state_dir = TempDir::new().unwrap();
+        let state_dir = Utf8Path::from_path(state_dir.path()).unwrap();
+
+        assert_eq!(next_bd_seq(state_dir), 0);
+        assert_eq!(next_bd_seq(state_dir), 1);
+
+        std::fs::write(state_dir.join(BD_SEQ_FILE), "255").unwrap();
+        assert_eq!(next_bd_seq(state_dir), 0);
+    }
+
+    #[test]
+    fn the_death_certificate_carries_the_bd_seq() {
+        let death = test_config().death_certificate();
+        assert_eq!(death.topic.name, "spBv1.0/plant-1/NDEATH/gateway");
+        let payload = Payload::decode(death.payload_bytes()).unwrap();
+        assert_eq!(payload.seq, None);
+        assert_eq!(
+            metric_values(&payload),
+            vec![("bdSeq", MetricValue::UInt(3))]
+        );
+    }
+
+    fn test_config() -> SparkplugConfig {
+        SparkplugConfig {
+            mqtt_schema: MqttSchema::default(),
+            device_topic_id: EntityTopicId::default_main_device(),
+            group_id: "plant-1".to_string(),
+            edge_node_id: "gateway".to_string(),
+            bd_seq: 3,
+        }
+    }
+
+    fn spawn_sparkplug_actor() -> (MqttBox, MqttBox) {
+        let mut local = SimpleMessageBoxBuilder::new("MQTT", 16);
+        let mut sparkplug = SimpleMessageBoxBuilder::new("Sparkplug MQTT", 16);
+        let actor = SparkplugActorBuilder::new(test_config(), &mut local, &mut sparkplug).build();
+        tokio::spawn(actor.run());
+        (
+            local.build().with_timeout(TEST_TIMEOUT),
+            sparkplug.build().with_timeout(TEST_TIMEOUT),
+        )
+    }
+
+    fn mqtt_message(topic: &str, payload: &str) -> MqttMessage {
+        MqttMessage::new(&Topic::new_unchecked(topic), payload)
+    }
+
+    fn sparkplug_command(topic: &str, metrics: Vec<Metric>) -> MqttMessage {
+        let payload = Payload {
+            timestamp: Some(1737450611123),
+            metrics,
+            seq: Some(0),
+        };
+        MqttMessage::new(&Topic::new_unchecked(topic), payload.encode())
+    }
+
+    async fn next_sparkplug_message(sparkplug: &mut MqttBox) -> (String, Payload) {
+        let message = sparkplug.recv().await.expect("a Sparkplug message");
+        let payload = Payload::decode(message.payload_bytes()).expect("a Sparkplug payload");
+        (message.topic.name, payload)
+    }
+
+    fn metric_values(payload: &Payload) -> Vec<(&str, MetricValue)> {
+        payload
+            .metrics
+            .iter()
+            .map(|metric| (metric.name.as_str(), metric.value.clone()))
+            .collect()
+    }
+
+    fn json_payload(message: &MqttMessage) -> Value {
+        serde_json::from_slice(message.payload_bytes()).unwrap()
+    }
+}
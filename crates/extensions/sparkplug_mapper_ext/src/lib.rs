@@ -0,0 +1,8 @@
+mod actor;
+pub mod payload;
+pub mod topics;
+
+pub use actor::next_bd_seq;
+pub use actor::SparkplugActor;
+pub use actor::SparkplugActorBuilder;
+pub use actor::SparkplugConfig;
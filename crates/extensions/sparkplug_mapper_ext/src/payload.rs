@@ -0,0 +1,445 @@
+//! Encoding and decoding of Sparkplug B payloads
+//!
+//! A Sparkplug B payload is a protobuf message, as defined by the `sparkplug_b.proto` file
+//! of the Sparkplug specification. Only the subset used by thin-edge is supported:
+//! the metrics carrying a scalar value. Properties, metadata, datasets and templates
+//! are skipped when decoding a payload.
+
+/// A Sparkplug B payload
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Payload {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: Option<u64>,
+    pub metrics: Vec<Metric>,
+    /// The message sequence number, absent from NDEATH and STATE messages
+    pub seq: Option<u64>,
+}
+
+/// A Sparkplug B metric
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metric {
+    /// The metric name, with `/` as folder separator
+    pub name: String,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: Option<u64>,
+    pub value: MetricValue,
+}
+
+/// The value of a metric, along its Sparkplug data type
+#[derive(Clone, Debug, PartialEq)]
+pub enum MetricValue {
+    Int(i64),
+    UInt(u64),
+    Float(f32),
+    Double(f64),
+    Boolean(bool),
+    String(String),
+    Null,
+    /// A value of a data type not supported by thin-edge
+    Unsupported(u32),
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum PayloadError {
+    #[error("Truncated Sparkplug payload")]
+    Truncated,
+
+    #[error("Invalid varint in Sparkplug payload")]
+    InvalidVarint,
+
+    #[error("Unsupported protobuf wire type {0} in Sparkplug payload")]
+    UnsupportedWireType(u64),
+
+    #[error("Invalid UTF-8 string in Sparkplug payload")]
+    InvalidString,
+}
+
+/// Sparkplug data types, as defined by the `DataType` enum of `sparkplug_b.proto`
+mod data_type {
+    pub const UNKNOWN: u32 = 0;
+    pub const INT8: u32 = 1;
+    pub const INT16: u32 = 2;
+    pub const INT32: u32 = 3;
+    pub const INT64: u32 = 4;
+    pub const UINT8: u32 = 5;
+    pub const UINT16: u32 = 6;
+    pub const UINT32: u32 = 7;
+    pub const UINT64: u32 = 8;
+    pub const FLOAT: u32 = 9;
+    pub const DOUBLE: u32 = 10;
+    pub const BOOLEAN: u32 = 11;
+    pub const STRING: u32 = 12;
+    pub const DATETIME: u32 = 13;
+    pub const TEXT: u32 = 14;
+    pub const UUID: u32 = 15;
+}
+
+const VARINT: u64 = 0;
+const FIXED64: u64 = 1;
+const LEN: u64 = 2;
+const FIXED32: u64 = 5;
+
+impl Payload {
+    /// Encode the payload, the timestamp being the last field
+    ///
+    /// The MQTT layer removing any trailing null char from the payloads,
+    /// the encoded payload must not end with a zero byte, as it would with a `seq` of 0
+    /// or a metric value of 0. Hence, the fields are not encoded in the order of their numbers,
+    /// as protobuf allows, and the timestamp is put last.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        if let Some(seq) = self.seq {
+            put_varint_field(&mut buf, 3, seq);
+        }
+        for metric in &self.metrics {
+            put_bytes_field(&mut buf, 2, &metric.encode());
+        }
+        if let Some(timestamp) = self.timestamp {
+            put_varint_field(&mut buf, 1, timestamp);
+        }
+        buf
+    }
+
+    /// Decode a payload received over MQTT
+    ///
+    /// A truncated payload is decoded again with a trailing zero byte,
+    /// as the MQTT layer removes the trailing null char of the payloads.
+    pub fn decode(bytes: &[u8]) -> Result<Self, PayloadError> {
+        match Payload::decode_bytes(bytes) {
+            Err(PayloadError::Truncated) => {
+                let mut bytes = bytes.to_vec();
+                bytes.push(0);
+                Payload::decode_bytes(&bytes)
+            }
+            result => result,
+        }
+    }
+
+    fn decode_bytes(bytes: &[u8]) -> Result<Self, PayloadError> {
+        let mut payload = Payload::default();
+        let mut decoder = Decoder { bytes };
+        while let Some((field, value)) = decoder.next_field()? {
+            match (field, value) {
+                (1, Field::Varint(timestamp)) => payload.timestamp = Some(timestamp),
+                (2, Field::Bytes(metric)) => payload.metrics.push(Metric::decode(metric)?),
+                (3, Field::Varint(seq)) => payload.seq = Some(seq),
+                _ => {}
+            }
+        }
+        Ok(payload)
+    }
+}
+
+impl Metric {
+    pub fn new(name: impl Into<String>, value: MetricValue) -> Self {
+        Metric {
+            name: name.into(),
+            timestamp: None,
+            value,
+        }
+    }
+
+    pub fn with_timestamp(self, timestamp: u64) -> Self {
+        Metric {
+            timestamp: Some(timestamp),
+            ..self
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        put_bytes_field(&mut buf, 1, self.name.as_bytes());
+        if let Some(timestamp) = self.timestamp {
+            put_varint_field(&mut buf, 3, timestamp);
+        }
+        put_varint_field(&mut buf, 4, self.value.data_type() as u64);
+        match &self.value {
+            MetricValue::Int(value) => put_varint_field(&mut buf, 11, *value as u64),
+            MetricValue::UInt(value) => put_varint_field(&mut buf, 11, *value),
+            MetricValue::Float(value) => {
+                put_key(&mut buf, 12, FIXED32);
+                buf.extend_from_slice(&value.to_le_bytes());
+            }
+            MetricValue::Double(value) => {
+                put_key(&mut buf, 13, FIXED64);
+                buf.extend_from_slice(&value.to_le_bytes());
+            }
+            MetricValue::Boolean(value) => put_varint_field(&mut buf, 14, *value as u64),
+            MetricValue::String(value) => put_bytes_field(&mut buf, 15, value.as_bytes()),
+            MetricValue::Null | MetricValue::Unsupported(_) => put_varint_field(&mut buf, 7, 1),
+        }
+        buf
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, PayloadError> {
+        let mut name = String::new();
+        let mut timestamp = None;
+        let mut data_type = data_type::UNKNOWN;
+        let mut is_null = false;
+        let mut value = None;
+
+        let mut decoder = Decoder { bytes };
+        while let Some((field, field_value)) = decoder.next_field()? {
+            match (field, field_value) {
+                (1, Field::Bytes(bytes)) => name = decode_string(bytes)?,
+                (3, Field::Varint(millis)) => timestamp = Some(millis),
+                (4, Field::Varint(code)) => data_type = code as u32,
+                (7, Field::Varint(flag)) => is_null = flag != 0,
+                (10..=15, field_value) => value = Some(field_value),
+                _ => {}
+            }
+        }
+
+        let value = match value {
+            Some(value) if !is_null => MetricValue::decode(data_type, value)?,
+            _ => MetricValue::Null,
+        };
+        Ok(Metric {
+            name,
+            timestamp,
+            value,
+        })
+    }
+}
+
+impl MetricValue {
+    /// The Sparkplug data type used to publish this value
+    fn data_type(&self) -> u32 {
+        match self {
+            MetricValue::Int(_) => data_type::INT64,
+            MetricValue::UInt(_) => data_type::UINT64,
+            MetricValue::Float(_) => data_type::FLOAT,
+            MetricValue::Double(_) => data_type::DOUBLE,
+            MetricValue::Boolean(_) => data_type::BOOLEAN,
+            MetricValue::String(_) => data_type::STRING,
+            MetricValue::Null => data_type::UNKNOWN,
+            MetricValue::Unsupported(data_type) => *data_type,
+        }
+    }
+
+    /// Interpret the value field of a metric according to its data type
+    ///
+    /// Signed integers are encoded as the two's complement of the value,
+    /// in a 32-bit field for the 8-, 16- and 32-bit data types.
+    fn decode(data_type: u32, value: Field) -> Result<Self, PayloadError> {
+        Ok(match (data_type, value) {
+            (data_type::INT8, Field::Varint(v)) => MetricValue::Int(v as u8 as i8 as i64),
+            (data_type::INT16, Field::Varint(v)) => MetricValue::Int(v as u16 as i16 as i64),
+            (data_type::INT32, Field::Varint(v)) => MetricValue::Int(v as u32 as i32 as i64),
+            (data_type::INT64, Field::Varint(v)) => MetricValue::Int(v as i64),
+            (data_type::UINT8, Field::Varint(v)) => MetricValue::UInt(v as u8 as u64),
+            (data_type::UINT16, Field::Varint(v)) => MetricValue::UInt(v as u16 as u64),
+            (data_type::UINT32, Field::Varint(v)) => MetricValue::UInt(v as u32 as u64),
+            (data_type::UINT64 | data_type::DATETIME, Field::Varint(v)) => MetricValue::UInt(v),
+            (data_type::FLOAT, Field::Fixed32(v)) => MetricValue::Float(f32::from_bits(v)),
+            (data_type::DOUBLE, Field::Fixed64(v)) => MetricValue::Double(f64::from_bits(v)),
+            (data_type::BOOLEAN, Field::Varint(v)) => MetricValue::Boolean(v != 0),
+            (data_type::STRING | data_type::TEXT | data_type::UUID, Field::Bytes(bytes)) => {
+                MetricValue::String(decode_string(bytes)?)
+            }
+            (data_type, _) => MetricValue::Unsupported(data_type),
+        })
+    }
+
+    /// The JSON representation of this value, `None` for unsupported values
+    pub fn to_json(&self) -> Option<serde_json::Value> {
+        Some(match self {
+            MetricValue::Int(value) => (*value).into(),
+            MetricValue::UInt(value) => (*value).into(),
+            MetricValue::Float(value) => (*value).into(),
+            MetricValue::Double(value) => (*value).into(),
+            MetricValue::Boolean(value) => (*value).into(),
+            MetricValue::String(value) => value.clone().into(),
+            MetricValue::Null => serde_json::Value::Null,
+            MetricValue::Unsupported(_) => return None,
+        })
+    }
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn put_key(buf: &mut Vec<u8>, field: u64, wire_type: u64) {
+    put_varint(buf, (field << 3) | wire_type)
+}
+
+fn put_varint_field(buf: &mut Vec<u8>, field: u64, value: u64) {
+    put_key(buf, field, VARINT);
+    put_varint(buf, value);
+}
+
+fn put_bytes_field(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    put_key(buf, field, LEN);
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn decode_string(bytes: &[u8]) -> Result<String, PayloadError> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| PayloadError::InvalidString)
+}
+
+/// The value of a protobuf field, as read from the wire
+#[derive(Clone, Copy, Debug)]
+enum Field<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn next_field(&mut self) -> Result<Option<(u64, Field<'a>)>, PayloadError> {
+        if self.bytes.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let field = match key & 0x07 {
+            VARINT => Field::Varint(self.varint()?),
+            FIXED64 => Field::Fixed64(u64::from_le_bytes(self.array()?)),
+            LEN => {
+                let len = self.varint()? as usize;
+                Field::Bytes(self.take(len)?)
+            }
+            FIXED32 => Field::Fixed32(u32::from_le_bytes(self.array()?)),
+            wire_type => return Err(PayloadError::UnsupportedWireType(wire_type)),
+        };
+        Ok(Some((key >> 3, field)))
+    }
+
+    fn varint(&mut self) -> Result<u64, PayloadError> {
+        let mut value = 0u64;
+        for (i, byte) in self.bytes.iter().enumerate().take(10) {
+            value |= ((byte & 0x7f) as u64) << (7 * i);
+            if byte & 0x80 == 0 {
+                self.bytes = &self.bytes[i + 1..];
+                return Ok(value);
+            }
+        }
+        if self.bytes.len() < 10 {
+            Err(PayloadError::Truncated)
+        } else {
+            Err(PayloadError::InvalidVarint)
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PayloadError> {
+        if self.bytes.len() < len {
+            return Err(PayloadError::Truncated);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], PayloadError> {
+        let bytes = self.take(N)?;
+        Ok(bytes.try_into().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding_follows_the_protobuf_wire_format() {
+        let payload = Payload {
+            timestamp: Some(1),
+            metrics: vec![Metric::new("a", MetricValue::Boolean(true))],
+            seq: Some(0),
+        };
+
+        assert_eq!(
+            payload.encode(),
+            vec![
+                0x18, 0x00, // seq = 0
+                0x12, 0x07, // a metric of 7 bytes
+                0x0a, 0x01, b'a', // name = "a"
+                0x20, 0x0b, // datatype = Boolean
+                0x70, 0x01, // boolean_value = true
+                0x08, 0x01, // timestamp = 1
+            ]
+        );
+    }
+
+    #[test]
+    fn encoded_payloads_can_be_decoded() {
+        let payload = Payload {
+            timestamp: Some(1737450611123),
+            metrics: vec![
+                Metric::new("bdSeq", MetricValue::UInt(3)),
+                Metric::new("environment/temperature", MetricValue::Double(21.3))
+                    .with_timestamp(1737450611000),
+                Metric::new("offset", MetricValue::Int(-42)),
+                Metric::new("ratio", MetricValue::Float(0.5)),
+                Metric::new("firmware", MetricValue::String("1.0".to_string())),
+                Metric::new("unset", MetricValue::Null),
+            ],
+            seq: Some(255),
+        };
+
+        assert_eq!(Payload::decode(&payload.encode()), Ok(payload));
+    }
+
+    #[test]
+    fn small_signed_integers_are_decoded_from_their_twos_complement() {
+        let mut metric = vec![0x0a, 0x01, b'x', 0x20, 0x03, 0x50];
+        put_varint(&mut metric, u32::MAX as u64);
+        let mut bytes = vec![];
+        put_bytes_field(&mut bytes, 2, &metric);
+
+        let payload = Payload::decode(&bytes).unwrap();
+        assert_eq!(
+            payload.metrics,
+            vec![Metric::new("x", MetricValue::Int(-1))]
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_skipped() {
+        let mut bytes = vec![];
+        put_bytes_field(&mut bytes, 4, b"some-uuid");
+        put_key(&mut bytes, 6, FIXED64);
+        bytes.extend_from_slice(&[0; 8]);
+        put_varint_field(&mut bytes, 3, 7);
+
+        let payload = Payload::decode(&bytes).unwrap();
+        assert_eq!(payload.seq, Some(7));
+        assert!(payload.metrics.is_empty());
+    }
+
+    #[test]
+    fn a_trailing_zero_byte_removed_by_the_mqtt_layer_is_restored() {
+        // timestamp = 1, then a metric with a boolean_value of false
+        let bytes = [
+            0x08, 0x01, 0x12, 0x07, 0x0a, 0x01, b'a', 0x20, 0x0b, 0x70, 0x00,
+        ];
+
+        let payload = Payload::decode(&bytes[..bytes.len() - 1]).unwrap();
+        assert_eq!(
+            payload.metrics,
+            vec![Metric::new("a", MetricValue::Boolean(false))]
+        );
+    }
+
+    #[test]
+    fn truncated_payloads_are_rejected() {
+        let payload = Payload {
+            timestamp: Some(1),
+            metrics: vec![Metric::new("a", MetricValue::Boolean(true))],
+            seq: Some(0),
+        };
+        let bytes = payload.encode();
+
+        assert_eq!(Payload::decode(&bytes[..6]), Err(PayloadError::Truncated));
+    }
+}
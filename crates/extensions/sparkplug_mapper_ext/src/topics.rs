@@ -0,0 +1,138 @@
+use std::fmt;
+use std::str::FromStr;
+use tedge_mqtt_ext::Topic;
+
+/// The namespace of all the Sparkplug B topics
+pub const SPARKPLUG_NAMESPACE: &str = "spBv1.0";
+
+/// The Sparkplug message types exchanged by an edge node
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageType {
+    NBirth,
+    NDeath,
+    NData,
+    NCmd,
+    DBirth,
+    DDeath,
+    DData,
+    DCmd,
+}
+
+impl MessageType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageType::NBirth => "NBIRTH",
+            MessageType::NDeath => "NDEATH",
+            MessageType::NData => "NDATA",
+            MessageType::NCmd => "NCMD",
+            MessageType::DBirth => "DBIRTH",
+            MessageType::DDeath => "DDEATH",
+            MessageType::DData => "DDATA",
+            MessageType::DCmd => "DCMD",
+        }
+    }
+}
+
+impl FromStr for MessageType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "NBIRTH" => MessageType::NBirth,
+            "NDEATH" => MessageType::NDeath,
+            "NDATA" => MessageType::NData,
+            "NCMD" => MessageType::NCmd,
+            "DBIRTH" => MessageType::DBirth,
+            "DDEATH" => MessageType::DDeath,
+            "DDATA" => MessageType::DData,
+            "DCMD" => MessageType::DCmd,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// A Sparkplug topic: `spBv1.0/<group_id>/<message_type>/<edge_node_id>[/<device_id>]`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SparkplugTopic {
+    pub group_id: String,
+    pub message_type: MessageType,
+    pub edge_node_id: String,
+    pub device_id: Option<String>,
+}
+
+impl SparkplugTopic {
+    /// Parse a Sparkplug topic, returning `None` for any other topic
+    pub fn parse(topic: &str) -> Option<Self> {
+        let mut levels = topic.split('/');
+        if levels.next()? != SPARKPLUG_NAMESPACE {
+            return None;
+        }
+        let group_id = levels.next()?.to_string();
+        let message_type = levels.next()?.parse().ok()?;
+        let edge_node_id = levels.next()?.to_string();
+        let device_id = levels.next().map(|id| id.to_string());
+        if levels.next().is_some() {
+            return None;
+        }
+        Some(SparkplugTopic {
+            group_id,
+            message_type,
+            edge_node_id,
+            device_id,
+        })
+    }
+
+    pub fn to_topic(&self) -> Topic {
+        Topic::new_unchecked(&self.to_string())
+    }
+}
+
+impl fmt::Display for SparkplugTopic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{SPARKPLUG_NAMESPACE}/{}/{}/{}",
+            self.group_id,
+            self.message_type.as_str(),
+            self.edge_node_id
+        )?;
+        if let Some(device_id) = &self.device_id {
+            write!(f, "/{device_id}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_node_and_device_topics() {
+        assert_eq!(
+            SparkplugTopic::parse("spBv1.0/plant-1/NCMD/gateway"),
+            Some(SparkplugTopic {
+                group_id: "plant-1".to_string(),
+                message_type: MessageType::NCmd,
+                edge_node_id: "gateway".to_string(),
+                device_id: None,
+            })
+        );
+        assert_eq!(
+            SparkplugTopic::parse("spBv1.0/plant-1/DCMD/gateway/device:child1")
+                .map(|topic| topic.to_string()),
+            Some("spBv1.0/plant-1/DCMD/gateway/device:child1".to_string())
+        );
+    }
+
+    #[test]
+    fn reject_non_sparkplug_topics() {
+        assert_eq!(SparkplugTopic::parse("te/device/main///m/"), None);
+        assert_eq!(SparkplugTopic::parse("spBv1.0/STATE/host"), None);
+        assert_eq!(SparkplugTopic::parse("spBv1.0/g/XCMD/node"), None);
+        assert_eq!(
+            SparkplugTopic::parse("spBv1.0/g/DCMD/node/device/extra"),
+            None
+        );
+    }
+}
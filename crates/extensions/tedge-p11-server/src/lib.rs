@@ -55,3 +55,53 @@ pub enum CryptokiConfig {
         pin: Option<SecretString>,
     },
 }
+
+impl CryptokiConfig {
+    /// Select the key to be used, and possibly the PIN to access it
+    pub fn with_key(self, key_uri: Arc<str>, key_pin: Option<SecretString>) -> Self {
+        match self {
+            CryptokiConfig::Direct(config) => CryptokiConfig::Direct(CryptokiConfigDirect {
+                uri: Some(key_uri),
+                pin: key_pin.unwrap_or(config.pin),
+                ..config
+            }),
+            CryptokiConfig::SocketService {
+                socket_path, pin, ..
+            } => CryptokiConfig::SocketService {
+                socket_path,
+                uri: Some(key_uri),
+                pin: key_pin.or(pin),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selecting_a_key_keeps_the_configured_pin_unless_overridden() {
+        let config = CryptokiConfig::SocketService {
+            socket_path: "/run/tedge-p11-server/tedge-p11-server.sock".into(),
+            uri: None,
+            pin: Some(SecretString::new("1234".to_string())),
+        };
+
+        let CryptokiConfig::SocketService { uri, pin, .. } =
+            config.clone().with_key("pkcs11:object=tedge".into(), None)
+        else {
+            panic!("expected a socket service config");
+        };
+        assert_eq!(uri.as_deref(), Some("pkcs11:object=tedge"));
+        assert_eq!(pin, Some(SecretString::new("1234".to_string())));
+
+        let CryptokiConfig::SocketService { pin, .. } = config.with_key(
+            "pkcs11:object=tedge".into(),
+            Some(SecretString::new("5678".to_string())),
+        ) else {
+            panic!("expected a socket service config");
+        };
+        assert_eq!(pin, Some(SecretString::new("5678".to_string())));
+    }
+}
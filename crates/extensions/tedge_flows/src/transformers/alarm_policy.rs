@@ -0,0 +1,463 @@
+use crate::config::ConfigError;
+use crate::js_value::JsonValue;
+use crate::transformers::Transformer;
+use crate::FlowContextHandle;
+use crate::FlowError;
+use crate::Message;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::time::Duration;
+use std::time::SystemTime;
+use time::OffsetDateTime;
+use time::Time;
+
+/// Apply a policy on alarms before they are forwarded to the cloud
+///
+/// The policy is configured by default for all the alarms,
+/// and can be refined per entity and alarm type using rules:
+///
+/// ```toml
+/// { builtin = "alarm-policy", config = {
+///     hold_off = "10s",
+///     min_clear = "1m",
+///     flapping = { threshold = 6, window = "10m" },
+///     rules = [
+///         { entity = "device/main//", alarm_type = "temperature_high", hold_off = "5m" },
+///         { alarm_type = "door_open", maintenance = [{ from = "22:00", to = "06:00" }] },
+///     ]
+/// } }
+/// ```
+///
+/// - `hold_off`: an alarm is only raised if not cleared within that delay
+/// - `min_clear`: an alarm is only cleared if not raised again within that delay
+/// - `deduplicate`: an alarm raised again with the same severity and text is ignored
+/// - `flapping`: an alarm raised and cleared `threshold` times within `window`
+///   is converted into a single flapping alarm, which is resolved once the alarm is stable for `window`
+/// - `maintenance`: daily time ranges (UTC) during which alarms are not raised
+///
+/// Messages that are not alarms, as well as alarms with no applicable policy, are passed unchanged.
+#[derive(Clone)]
+pub struct AlarmPolicy {
+    topic_root: String,
+    config: PolicyConfig,
+    alarms: HashMap<String, AlarmState>,
+}
+
+impl Default for AlarmPolicy {
+    fn default() -> Self {
+        AlarmPolicy {
+            topic_root: "te".to_string(),
+            config: PolicyConfig::default(),
+            alarms: HashMap::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct PolicyConfig {
+    #[serde(flatten)]
+    defaults: PolicySettings,
+
+    #[serde(default)]
+    rules: Vec<PolicyRule>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct PolicySettings {
+    #[serde(default, deserialize_with = "parse_optional_human_duration")]
+    hold_off: Option<Duration>,
+
+    #[serde(default, deserialize_with = "parse_optional_human_duration")]
+    min_clear: Option<Duration>,
+
+    deduplicate: Option<bool>,
+
+    flapping: Option<FlappingDetection>,
+
+    maintenance: Option<Vec<MaintenanceWindow>>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct PolicyRule {
+    /// The entity topic id of the alarm source, e.g. `device/child01//`. All entities if not set.
+    entity: Option<String>,
+
+    /// The alarm type. All types if not set.
+    alarm_type: Option<String>,
+
+    #[serde(flatten)]
+    settings: PolicySettings,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct FlappingDetection {
+    threshold: usize,
+
+    #[serde(deserialize_with = "parse_human_duration")]
+    window: Duration,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct MaintenanceWindow {
+    #[serde(deserialize_with = "parse_time_of_day")]
+    from: Time,
+
+    #[serde(deserialize_with = "parse_time_of_day")]
+    to: Time,
+}
+
+#[derive(Clone)]
+struct AlarmState {
+    entity: String,
+    alarm_type: String,
+
+    /// The latest alarm message received for this alarm: either a raise or a clear
+    actual: Message,
+
+    /// When the alarm has been raised or cleared for the last time
+    since: SystemTime,
+
+    /// The alarm as known by the cloud
+    forwarded: Forwarded,
+
+    /// The latest raise message, used to build the flapping alarm
+    last_raise: Option<Message>,
+
+    /// Recent raise/clear transitions, used to detect flapping alarms
+    transitions: VecDeque<SystemTime>,
+    flapping: bool,
+}
+
+#[derive(Clone, PartialEq)]
+enum Forwarded {
+    Unknown,
+    Cleared,
+    Raised(Vec<u8>),
+}
+
+impl Transformer for AlarmPolicy {
+    fn name(&self) -> &str {
+        "alarm-policy"
+    }
+
+    fn set_config(&mut self, config: JsonValue) -> Result<(), ConfigError> {
+        if let Some(root) = config.string_property("topic_root") {
+            self.topic_root = root.to_string();
+        }
+        self.config = config
+            .into_value()
+            .map_err(|err| ConfigError::IncorrectSetting(format!("Invalid alarm policy: {err}")))?;
+        Ok(())
+    }
+
+    fn on_message(
+        &mut self,
+        timestamp: SystemTime,
+        message: &Message,
+        _context: &FlowContextHandle,
+    ) -> Result<Vec<Message>, FlowError> {
+        let Some((entity, alarm_type)) = self.alarm_source(&message.topic) else {
+            return Ok(vec![message.clone()]);
+        };
+        let settings = self.config.settings_for(entity, alarm_type);
+        if settings.is_empty() && !self.alarms.contains_key(&message.topic) {
+            return Ok(vec![message.clone()]);
+        }
+
+        let (entity, alarm_type) = (entity.to_string(), alarm_type.to_string());
+        let state = self
+            .alarms
+            .entry(message.topic.clone())
+            .or_insert_with(|| AlarmState::new(entity, alarm_type, message, timestamp));
+
+        let mut messages: Vec<Message> = state
+            .update(message, timestamp, &settings)
+            .into_iter()
+            .collect();
+        messages.extend(state.reconcile(timestamp, &settings));
+        if state.is_settled() {
+            self.alarms.remove(&message.topic);
+        }
+        Ok(messages)
+    }
+
+    fn is_periodic(&self) -> bool {
+        true
+    }
+
+    fn on_interval(
+        &mut self,
+        timestamp: SystemTime,
+        _context: &FlowContextHandle,
+    ) -> Result<Vec<Message>, FlowError> {
+        let mut messages = vec![];
+        for state in self.alarms.values_mut() {
+            let settings = self.config.settings_for(&state.entity, &state.alarm_type);
+            messages.extend(state.reconcile(timestamp, &settings));
+        }
+        self.alarms.retain(|_, state| !state.is_settled());
+        Ok(messages)
+    }
+}
+
+impl AlarmPolicy {
+    /// Extract the entity topic id and the alarm type from an alarm topic
+    fn alarm_source<'a>(&self, topic: &'a str) -> Option<(&'a str, &'a str)> {
+        let path = topic.strip_prefix(&self.topic_root)?.strip_prefix('/')?;
+        let mut parts = path.splitn(6, '/');
+        let entity_len = (0..4)
+            .map(|_| parts.next().map(str::len))
+            .sum::<Option<usize>>()?;
+        let entity = &path[..entity_len + 3];
+        match (parts.next(), parts.next()) {
+            (Some("a"), Some(alarm_type)) => Some((entity, alarm_type)),
+            _ => None,
+        }
+    }
+}
+
+impl PolicyConfig {
+    /// The settings of the first matching rule, completed by the default settings
+    fn settings_for(&self, entity: &str, alarm_type: &str) -> PolicySettings {
+        let rule = self.rules.iter().find(|rule| {
+            rule.entity.as_ref().is_none_or(|e| e == entity)
+                && rule.alarm_type.as_ref().is_none_or(|t| t == alarm_type)
+        });
+        match rule {
+            None => self.defaults.clone(),
+            Some(rule) => {
+                let rule = rule.settings.clone();
+                let defaults = self.defaults.clone();
+                PolicySettings {
+                    hold_off: rule.hold_off.or(defaults.hold_off),
+                    min_clear: rule.min_clear.or(defaults.min_clear),
+                    deduplicate: rule.deduplicate.or(defaults.deduplicate),
+                    flapping: rule.flapping.or(defaults.flapping),
+                    maintenance: rule.maintenance.or(defaults.maintenance),
+                }
+            }
+        }
+    }
+}
+
+impl PolicySettings {
+    fn is_empty(&self) -> bool {
+        self.hold_off.is_none_or(|d| d.is_zero())
+            && self.min_clear.is_none_or(|d| d.is_zero())
+            && !self.deduplicate.unwrap_or(false)
+            && self.flapping.is_none()
+            && self.maintenance.as_ref().is_none_or(|m| m.is_empty())
+    }
+
+    fn in_maintenance(&self, now: SystemTime) -> bool {
+        let time_of_day = OffsetDateTime::from(now).time();
+        self.maintenance
+            .iter()
+            .flatten()
+            .any(|window| window.contains(time_of_day))
+    }
+}
+
+impl MaintenanceWindow {
+    fn contains(&self, time: Time) -> bool {
+        if self.from <= self.to {
+            self.from <= time && time < self.to
+        } else {
+            self.from <= time || time < self.to
+        }
+    }
+}
+
+impl AlarmState {
+    fn new(entity: String, alarm_type: String, message: &Message, now: SystemTime) -> Self {
+        AlarmState {
+            entity,
+            alarm_type,
+            actual: message.clone(),
+            since: now,
+            // An alarm first seen raised is held off as not yet known by the cloud,
+            // while an alarm first seen cleared might have been raised before a restart
+            forwarded: if Self::is_raised(message) {
+                Forwarded::Cleared
+            } else {
+                Forwarded::Unknown
+            },
+            last_raise: None,
+            transitions: VecDeque::new(),
+            flapping: false,
+        }
+    }
+
+    fn is_raised(message: &Message) -> bool {
+        !message.payload.is_empty()
+    }
+
+    /// Update the state with a new alarm message,
+    /// returning the flapping alarm when this update makes the alarm flapping
+    fn update(
+        &mut self,
+        message: &Message,
+        now: SystemTime,
+        settings: &PolicySettings,
+    ) -> Option<Message> {
+        let raised = Self::is_raised(message);
+        if raised != Self::is_raised(&self.actual) {
+            self.since = now;
+            if settings.flapping.is_some() {
+                self.transitions.push_back(now);
+            }
+        }
+        if raised {
+            self.last_raise = Some(message.clone());
+        }
+        self.actual = message.clone();
+
+        let flapping = settings.flapping.as_ref()?;
+        self.forget_transitions_before(now, flapping.window);
+        if self.flapping || self.transitions.len() < flapping.threshold {
+            return None;
+        }
+
+        self.flapping = true;
+        let alarm = self.flapping_alarm(flapping);
+        self.forwarded = Forwarded::Raised(alarm.payload.clone());
+        Some(alarm)
+    }
+
+    /// Return the message to be forwarded, if any, for the cloud to be in sync with the alarm
+    fn reconcile(&mut self, now: SystemTime, settings: &PolicySettings) -> Option<Message> {
+        let raised = Self::is_raised(&self.actual);
+
+        if settings.in_maintenance(now) {
+            // Alarms are not raised during maintenance, but are still cleared
+            if raised || self.forwarded == Forwarded::Cleared {
+                return None;
+            }
+            self.forwarded = Forwarded::Cleared;
+            return Some(self.actual.clone());
+        }
+
+        if self.flapping {
+            if let Some(flapping) = &settings.flapping {
+                self.forget_transitions_before(now, flapping.window);
+                if !self.transitions.is_empty() {
+                    return None;
+                }
+            }
+            self.flapping = false;
+            self.transitions.clear();
+        }
+
+        let elapsed = now.duration_since(self.since).unwrap_or_default();
+        let forward = match &self.forwarded {
+            Forwarded::Raised(payload) if raised => {
+                payload != &self.actual.payload
+                    && !(settings.deduplicate.unwrap_or(false)
+                        && same_alarm(payload, &self.actual.payload))
+            }
+            Forwarded::Unknown | Forwarded::Cleared if raised => {
+                elapsed >= settings.hold_off.unwrap_or_default()
+            }
+            Forwarded::Raised(_) => elapsed >= settings.min_clear.unwrap_or_default(),
+            Forwarded::Unknown => true,
+            Forwarded::Cleared => false,
+        };
+        if !forward {
+            return None;
+        }
+
+        self.forwarded = if raised {
+            Forwarded::Raised(self.actual.payload.clone())
+        } else {
+            Forwarded::Cleared
+        };
+        Some(self.actual.clone())
+    }
+
+    /// An alarm that is cleared in the cloud and stable doesn't need to be tracked anymore
+    fn is_settled(&self) -> bool {
+        !Self::is_raised(&self.actual)
+            && self.forwarded == Forwarded::Cleared
+            && !self.flapping
+            && self.transitions.is_empty()
+    }
+
+    fn forget_transitions_before(&mut self, now: SystemTime, window: Duration) {
+        while let Some(transition) = self.transitions.front() {
+            if now.duration_since(*transition).unwrap_or_default() < window {
+                break;
+            }
+            self.transitions.pop_front();
+        }
+    }
+
+    fn flapping_alarm(&self, flapping: &FlappingDetection) -> Message {
+        let mut alarm = self
+            .last_raise
+            .clone()
+            .unwrap_or_else(|| self.actual.clone());
+        let mut payload = match serde_json::from_slice(&alarm.payload) {
+            Ok(serde_json::Value::Object(payload)) => payload,
+            _ => serde_json::Map::new(),
+        };
+        payload.entry("severity").or_insert_with(|| "major".into());
+        payload.insert(
+            "text".to_string(),
+            format!(
+                "Alarm {} is flapping: raised and cleared {} times within {}",
+                self.alarm_type,
+                self.transitions.len(),
+                humantime::format_duration(flapping.window)
+            )
+            .into(),
+        );
+        payload.insert("flapping".to_string(), true.into());
+        alarm.payload = serde_json::Value::Object(payload).to_string().into_bytes();
+        alarm
+    }
+}
+
+/// Two alarms are the same if they only differ by their time
+fn same_alarm(left: &[u8], right: &[u8]) -> bool {
+    let without_time = |payload: &[u8]| match serde_json::from_slice(payload) {
+        Ok(serde_json::Value::Object(mut alarm)) => {
+            alarm.remove("time");
+            Some(alarm)
+        }
+        _ => None,
+    };
+    match (without_time(left), without_time(right)) {
+        (Some(left), Some(right)) => left == right,
+        _ => left == right,
+    }
+}
+
+fn parse_human_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    humantime::parse_duration(&value).map_err(|_| serde::de::Error::custom("Invalid duration"))
+}
+
+fn parse_optional_human_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    parse_human_duration(deserializer).map(Some)
+}
+
+fn parse_time_of_day<'de, D>(deserializer: D) -> Result<Time, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    let invalid = || serde::de::Error::custom(format!("Invalid time of day: {value}"));
+    let mut parts = value.split(':').map(|part| part.parse::<u8>());
+    let (Some(Ok(hour)), Some(Ok(minute)), None) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    Time::from_hms(hour, minute, 0).map_err(|_| invalid())
+}
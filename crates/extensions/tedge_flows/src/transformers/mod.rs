@@ -8,6 +8,7 @@ use std::collections::HashMap;
 use std::time::SystemTime;
 
 mod add_timestamp;
+mod alarm_policy;
 mod ignore_topics;
 mod limit_payload_size;
 mod set_topic;
@@ -59,6 +60,7 @@ impl Default for BuiltinTransformers {
             transformers: HashMap::default(),
         };
         transformers.register(add_timestamp::AddTimestamp::default());
+        transformers.register(alarm_policy::AlarmPolicy::default());
         transformers.register(limit_payload_size::LimitPayloadSize::default());
         transformers.register(ignore_topics::IgnoreTopics::default());
         transformers.register(set_topic::SetTopic::default());
@@ -191,6 +193,165 @@ config = { topics = ["units/#"] }
         );
     }
 
+    #[tokio::test]
+    async fn holding_off_alarms_cleared_soon_after_being_raised() {
+        let step = r#"
+builtin = "alarm-policy"
+config = { hold_off = "10s" }
+"#;
+        let transformers = BuiltinTransformers::new();
+        let (runtime, mut step) = step_instance(&transformers, step).await;
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1763050414);
+        let raise = Message::new("te/device/main///a/high_temp", r#"{"severity":"major"}"#);
+        let clear = Message::new("te/device/main///a/high_temp", "");
+
+        // An alarm raised and cleared within the hold-off delay is ignored
+        assert_eq!(step.on_message(&runtime, t0, &raise).await.unwrap(), vec![]);
+        let t1 = t0 + Duration::from_secs(5);
+        assert_eq!(step.on_message(&runtime, t1, &clear).await.unwrap(), vec![]);
+        let t2 = t1 + Duration::from_secs(20);
+        assert_eq!(step.on_interval(&runtime, t2).await.unwrap(), vec![]);
+
+        // An alarm still raised after the hold-off delay is forwarded
+        assert_eq!(step.on_message(&runtime, t2, &raise).await.unwrap(), vec![]);
+        let t3 = t2 + Duration::from_secs(5);
+        assert_eq!(step.on_interval(&runtime, t3).await.unwrap(), vec![]);
+        let t4 = t2 + Duration::from_secs(10);
+        assert_eq!(
+            step.on_interval(&runtime, t4).await.unwrap(),
+            vec![raise.clone()]
+        );
+
+        // Other messages are passed unchanged
+        let measurement = Message::new("te/device/main///m/", r#"{"temperature":42}"#);
+        assert_eq!(
+            step.on_message(&runtime, t4, &measurement).await.unwrap(),
+            vec![measurement]
+        );
+    }
+
+    #[tokio::test]
+    async fn ignoring_clears_followed_by_a_raise_within_the_minimum_clear_duration() {
+        let step = r#"
+builtin = "alarm-policy"
+config = { min_clear = "1m", deduplicate = true }
+"#;
+        let transformers = BuiltinTransformers::new();
+        let (runtime, mut step) = step_instance(&transformers, step).await;
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1763050414);
+        let raise = Message::new("te/device/main///a/high_temp", r#"{"severity":"major"}"#);
+        let clear = Message::new("te/device/main///a/high_temp", "");
+
+        assert_eq!(
+            step.on_message(&runtime, t0, &raise).await.unwrap(),
+            vec![raise.clone()]
+        );
+        let t1 = t0 + Duration::from_secs(10);
+        assert_eq!(step.on_message(&runtime, t1, &clear).await.unwrap(), vec![]);
+        let t2 = t1 + Duration::from_secs(10);
+        let raise_again = Message::new(
+            "te/device/main///a/high_temp",
+            r#"{"severity":"major","time":1763050434}"#,
+        );
+        assert_eq!(
+            step.on_message(&runtime, t2, &raise_again).await.unwrap(),
+            vec![]
+        );
+
+        // The clear is forwarded only once the alarm stayed cleared for the minimum duration
+        let t3 = t2 + Duration::from_secs(10);
+        assert_eq!(step.on_message(&runtime, t3, &clear).await.unwrap(), vec![]);
+        let t4 = t3 + Duration::from_secs(60);
+        assert_eq!(
+            step.on_interval(&runtime, t4).await.unwrap(),
+            vec![clear.clone()]
+        );
+    }
+
+    #[tokio::test]
+    async fn converting_flapping_alarms_into_a_single_alarm() {
+        let step = r#"
+builtin = "alarm-policy"
+config = { rules = [{ alarm_type = "door_open", flapping = { threshold = 4, window = "10m" } }] }
+"#;
+        let transformers = BuiltinTransformers::new();
+        let (runtime, mut step) = step_instance(&transformers, step).await;
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1763050414);
+        let raise = Message::new("te/device/main///a/door_open", r#"{"text":"Door open"}"#);
+        let clear = Message::new("te/device/main///a/door_open", "");
+
+        assert_eq!(
+            step.on_message(&runtime, t0, &raise).await.unwrap(),
+            vec![raise.clone()]
+        );
+        let mut t = t0;
+        for message in [&clear, &raise, &clear] {
+            t += Duration::from_secs(1);
+            assert_eq!(
+                step.on_message(&runtime, t, message).await.unwrap(),
+                vec![message.clone()]
+            );
+        }
+
+        // The alarm is now flapping
+        t += Duration::from_secs(1);
+        let flapping = step.on_message(&runtime, t, &raise).await.unwrap();
+        assert_eq!(flapping.len(), 1);
+        let payload: serde_json::Value = serde_json::from_slice(&flapping[0].payload).unwrap();
+        assert_eq!(flapping[0].topic, "te/device/main///a/door_open");
+        assert_eq!(payload["flapping"], json!(true));
+        assert_eq!(payload["severity"], json!("major"));
+        assert_eq!(
+            payload["text"],
+            json!("Alarm door_open is flapping: raised and cleared 4 times within 10m")
+        );
+
+        // Further changes are ignored
+        for message in [&clear, &raise, &clear] {
+            t += Duration::from_secs(1);
+            assert_eq!(step.on_message(&runtime, t, message).await.unwrap(), vec![]);
+        }
+
+        // Once stable, the actual alarm state is forwarded
+        t += Duration::from_secs(600);
+        assert_eq!(step.on_interval(&runtime, t).await.unwrap(), vec![clear]);
+
+        // Alarms of other types are passed unchanged
+        let other = Message::new("te/device/main///a/high_temp", r#"{"text":"Too hot"}"#);
+        assert_eq!(
+            step.on_message(&runtime, t, &other).await.unwrap(),
+            vec![other]
+        );
+    }
+
+    #[tokio::test]
+    async fn suppressing_alarms_during_maintenance() {
+        let step = r#"
+builtin = "alarm-policy"
+config = { rules = [{ entity = "device/child//", maintenance = [{ from = "23:00", to = "01:00" }] }] }
+"#;
+        let transformers = BuiltinTransformers::new();
+        let (runtime, mut step) = step_instance(&transformers, step).await;
+        // 2025-11-13T23:30:00Z
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1763076600);
+        let raise = Message::new("te/device/child///a/offline", r#"{"text":"Offline"}"#);
+
+        assert_eq!(step.on_message(&runtime, t0, &raise).await.unwrap(), vec![]);
+
+        // The alarm is raised if still active at the end of the maintenance window
+        let t1 = t0 + Duration::from_secs(5399);
+        assert_eq!(step.on_interval(&runtime, t1).await.unwrap(), vec![]);
+        let t2 = t1 + Duration::from_secs(1);
+        assert_eq!(step.on_interval(&runtime, t2).await.unwrap(), vec![raise]);
+
+        // Alarms of other entities are passed unchanged
+        let other = Message::new("te/device/main///a/offline", r#"{"text":"Offline"}"#);
+        assert_eq!(
+            step.on_message(&runtime, t0, &other).await.unwrap(),
+            vec![other]
+        );
+    }
+
     async fn step_instance(
         transformers: &BuiltinTransformers,
         config: &str,
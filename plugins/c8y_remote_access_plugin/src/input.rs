@@ -9,6 +9,7 @@ use std::io::BufRead;
 use tedge_config::cli::CommonArgs;
 use tedge_config::tedge_toml::ProfileName;
 use tedge_config::Path;
+use tedge_remote_access::TunnelRequest;
 
 use crate::csv::deserialize_csv_record;
 use crate::UNIX_SOCKFILE;
@@ -22,7 +23,7 @@ pub struct RemoteAccessConnect {
 }
 
 #[derive(Parser, Debug)]
-#[clap(group(ArgGroup::new("install").args(&["init", "cleanup", "connect_string", "child"])))]
+#[clap(group(ArgGroup::new("install").args(&["init", "cleanup", "connect_string", "child", "serve"])))]
 #[clap(
 name = clap::crate_name!(),
 version = clap::crate_version!(),
@@ -49,6 +50,11 @@ pub struct C8yRemoteAccessPluginOpt {
     // Use "-" to read the value from stdin.
     child: Option<String>,
 
+    #[arg(long)]
+    /// Run as a long-lived service, opening the remote access sessions requested on the Unix socket
+    /// and running them concurrently
+    serve: bool,
+
     /// The user who will own the directories created by --init
     #[arg(long, requires("init"), default_value = "tedge")]
     user: Option<String>,
@@ -69,6 +75,7 @@ pub struct C8yRemoteAccessPluginOpt {
 pub enum Command {
     Init(String, String),
     Cleanup,
+    Serve,
     SpawnChild(String),
     TryConnectUnixSocket(String),
     Connect((RemoteAccessConnect, Option<ProfileName>)),
@@ -89,6 +96,7 @@ impl TryFrom<C8yRemoteAccessPluginOpt> for Command {
                 ..
             } => Ok(Command::Init(user, group)),
             C8yRemoteAccessPluginOpt { cleanup: true, .. } => Ok(Command::Cleanup),
+            C8yRemoteAccessPluginOpt { serve: true, .. } => Ok(Command::Serve),
             C8yRemoteAccessPluginOpt {
                 connect_string: Some(message),
                 ..
@@ -112,7 +120,7 @@ impl TryFrom<C8yRemoteAccessPluginOpt> for Command {
 }
 
 impl RemoteAccessConnect {
-    fn deserialize_smartrest(
+    pub(crate) fn deserialize_smartrest(
         message: &str,
         mut stdin: impl BufRead,
     ) -> miette::Result<(Self, Option<ProfileName>)> {
@@ -149,6 +157,19 @@ impl RemoteAccessConnect {
     pub fn key(&self) -> &str {
         &self.key
     }
+
+    /// The tunnel requested by this message, the connection key identifying the session
+    ///
+    /// Cumulocity doesn't tell which user opened the session,
+    /// hence the initiator is only known as the cloud itself.
+    pub fn tunnel_request(&self) -> TunnelRequest {
+        TunnelRequest {
+            session_id: self.key.clone(),
+            initiator: "c8y".to_string(),
+            host: self.host.clone(),
+            port: self.port,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -164,6 +185,7 @@ mod tests {
     #[case::init_and_cleanup(&["--init", "--cleanup"])]
     #[case::init_and_command_string(&["--init", "530,jrh-rc-test0,127.0.0.1,22,cd8fc847-f4f2-4712-8dd7-31496aef0a7d"])]
     #[case::cleanup_and_command_string(&["--cleanup", "530,jrh-rc-test0,127.0.0.1,22,cd8fc847-f4f2-4712-8dd7-31496aef0a7d"])]
+    #[case::serve_and_command_string(&["--serve", "530,jrh-rc-test0,127.0.0.1,22,cd8fc847-f4f2-4712-8dd7-31496aef0a7d"])]
     #[case::cleanup_and_child_string(&["--cleanup", "--child", "530,jrh-rc-test0,127.0.0.1,22,cd8fc847-f4f2-4712-8dd7-31496aef0a7d"])]
     fn arguments_are_mutually_exclusive(#[case] arguments: &[&str]) {
         try_parse_arguments(arguments).unwrap_err();
@@ -172,6 +194,7 @@ mod tests {
     #[rstest]
     #[case::init("--init", Command::Init("tedge".to_string(), "tedge".to_string()))]
     #[case::cleanup("--cleanup", Command::Cleanup)]
+    #[case::serve("--serve", Command::Serve)]
     fn parses_lifecycle_flags(#[case] argument: &str, #[case] expected: Command) {
         assert_eq!(try_parse_arguments(&[argument]).unwrap(), expected);
     }
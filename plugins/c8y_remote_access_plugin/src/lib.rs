@@ -7,24 +7,34 @@ use miette::miette;
 use miette::Context;
 use miette::IntoDiagnostic;
 use std::io;
+use std::os::unix::fs::PermissionsExt;
 use std::process::Stdio;
+use std::sync::Arc;
 use tedge_config::log_init;
 use tedge_config::tedge_toml::mapper_config::C8yMapperConfig;
 use tedge_config::TEdgeConfig;
+use tedge_remote_access::AuditLog;
+use tedge_remote_access::SessionEnd;
+use tedge_remote_access::SessionStats;
+use tedge_remote_access::TunnelPolicy;
+use tedge_remote_access::TunnelService;
 use tedge_utils::file::change_user_and_group;
 use tedge_utils::file::create_directory_with_user_group;
 use tedge_utils::file::create_file_with_user_group;
 use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncReadExt;
 use tokio::io::AsyncWriteExt;
 use tokio::io::BufReader;
+use tokio::net::UnixListener;
 use tokio::net::UnixStream;
+use tokio::task::JoinHandle;
 use url::Url;
 
 use crate::auth::Auth;
 pub use crate::input::C8yRemoteAccessPluginOpt;
 use crate::input::Command;
 use crate::input::RemoteAccessConnect;
-use crate::proxy::WebsocketSocketProxy;
+use crate::proxy::connect_websocket;
 
 mod auth;
 mod csv;
@@ -65,6 +75,7 @@ pub async fn run(opt: C8yRemoteAccessPluginOpt) -> miette::Result<()> {
             remove_supported_operation(tedge_config.root_dir());
             Ok(())
         }
+        Command::Serve => serve(tedge_config).await,
         Command::Connect((command, p)) => {
             let c8y_config = tedge_config.mapper_config(&p).map_err(|e| miette!("{e}"))?;
             proxy(command, &tedge_config, &c8y_config).await
@@ -312,6 +323,25 @@ async fn proxy(
     config: &TEdgeConfig,
     c8y_config: &C8yMapperConfig,
 ) -> miette::Result<()> {
+    let service = TunnelService::new(
+        TunnelPolicy::from_tedge_config(config),
+        AuditLog::from_tedge_config(config),
+    );
+    let session = open_session(&service, &command, config, c8y_config).await?;
+    println!("{SUCCESS_MESSAGE}");
+
+    let _ = session.await;
+    println!("STOPPING");
+    Ok(())
+}
+
+/// Open a remote access session, the session running in the background once connected
+async fn open_session(
+    service: &TunnelService,
+    command: &RemoteAccessConnect,
+    config: &TEdgeConfig,
+    c8y_config: &C8yMapperConfig,
+) -> miette::Result<JoinHandle<(SessionEnd, SessionStats)>> {
     let host = c8y_config
         .cloud_specific
         .http
@@ -323,17 +353,80 @@ async fn proxy(
         .await.context("Failed when requesting JWT from Cumulocity or invalid username/password credentials are given")?;
     let client_config = config.cloud_client_tls_config();
 
-    let proxy = WebsocketSocketProxy::connect(
-        &url,
-        command.target_address(),
-        auth,
-        Some(client_config),
-        &config.proxy,
-    )
-    .await?;
+    eprintln!("Connecting to {}", command.target_address());
+    let websocket = connect_websocket(&url, auth, Some(client_config), &config.proxy);
+    service
+        .open(command.tunnel_request(), websocket)
+        .await
+        .into_diagnostic()
+}
 
-    proxy.run().await;
-    Ok(())
+/// Serve the remote access requests received on the Unix socket, running the sessions concurrently
+async fn serve(config: TEdgeConfig) -> miette::Result<()> {
+    let service = TunnelService::new(
+        TunnelPolicy::from_tedge_config(&config),
+        AuditLog::from_tedge_config(&config),
+    );
+    let config = Arc::new(config);
+
+    // A socket left over by a previous instance prevents binding
+    let _ = std::fs::remove_file(UNIX_SOCKFILE);
+    let listener = UnixListener::bind(UNIX_SOCKFILE)
+        .into_diagnostic()
+        .with_context(|| format!("Binding Unix socket {UNIX_SOCKFILE}"))?;
+    std::fs::set_permissions(UNIX_SOCKFILE, std::fs::Permissions::from_mode(0o666))
+        .into_diagnostic()
+        .with_context(|| format!("Setting permissions of Unix socket {UNIX_SOCKFILE}"))?;
+    eprintln!("sock: Listening for remote access requests on {UNIX_SOCKFILE}");
+
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .into_diagnostic()
+            .context("sock: Accepting connection")?;
+        let service = service.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_request(stream, &service, &config).await {
+                eprintln!("sock: {err:?}");
+            }
+        });
+    }
+}
+
+/// Open the session requested on a Unix socket connection,
+/// replying with the same responses as a child process would print
+async fn handle_request(
+    mut stream: UnixStream,
+    service: &TunnelService,
+    config: &TEdgeConfig,
+) -> miette::Result<()> {
+    let mut request = String::new();
+    stream
+        .read_to_string(&mut request)
+        .await
+        .into_diagnostic()
+        .context("sock: Could not read from socket")?;
+
+    let result = async {
+        let (command, profile) =
+            RemoteAccessConnect::deserialize_smartrest("-", std::io::Cursor::new(request))?;
+        let c8y_config = config.mapper_config(&profile).map_err(|e| miette!("{e}"))?;
+        open_session(service, &command, config, &c8y_config).await
+    }
+    .await;
+
+    let response = match &result {
+        Ok(_) => SUCCESS_MESSAGE,
+        Err(_) => "STOPPING",
+    };
+    stream
+        .write_all(format!("{response}\n").as_bytes())
+        .await
+        .into_diagnostic()
+        .context("sock: Could not write to socket")?;
+    result.map(|_session| ())
 }
 
 fn supported_operation_path(config_dir: &Utf8Path) -> Utf8PathBuf {
@@ -4,14 +4,9 @@ use async_http_proxy::http_connect_tokio;
 use async_http_proxy::http_connect_tokio_with_basic_auth;
 use async_tungstenite::tokio::ClientStream;
 use base64::prelude::*;
-use futures::future::join;
-use futures::future::select;
-use futures_util::io::AsyncReadExt;
-use futures_util::io::AsyncWriteExt;
 use http::HeaderValue;
 use miette::miette;
 use miette::Context;
-use miette::Diagnostic;
 use miette::IntoDiagnostic;
 use rand::RngCore;
 use rustls::ClientConfig;
@@ -20,66 +15,26 @@ use std::sync::Arc;
 use tedge_config::all_or_nothing;
 use tedge_config::models::proxy_scheme::ProxyScheme;
 use tedge_config::tedge_toml::TEdgeConfigReaderProxy;
-use thiserror::Error;
 use tokio::io::AsyncRead;
 use tokio::io::AsyncWrite;
 use tokio::net::TcpStream;
-use tokio::net::ToSocketAddrs;
 use tokio_rustls::client::TlsStream;
 use tokio_rustls::TlsConnector;
 use url::Url;
 use ws_stream_tungstenite::WsStream;
 
-use crate::SUCCESS_MESSAGE;
-
-/// This proxy creates a TCP connection to a local socket and creates a websocket. Cumulocity cloud will initiate a
-/// connection to the websocket. Any data received from the socket is sent out via the websocket and any data received
-/// from the websocket is sent to the local socket.
-pub struct WebsocketSocketProxy {
-    socket: TcpStream,
-    websocket: Websocket,
-}
-
-#[derive(Diagnostic, Error, Debug)]
-#[error("Failed to connect to TCP socket")]
-struct SocketError(#[from] std::io::Error);
-
-impl WebsocketSocketProxy {
-    pub async fn connect<SA: ToSocketAddrs + std::fmt::Debug>(
-        url: &Url,
-        socket: SA,
-        auth: Auth,
-        config: Option<ClientConfig>,
-        proxy: &TEdgeConfigReaderProxy,
-    ) -> miette::Result<Self> {
-        let socket_future = TcpStream::connect(socket);
-        let websocket_future = Websocket::new(url, auth.authorization_header(), config, proxy);
-
-        match join(socket_future, websocket_future).await {
-            (Err(socket_error), _) => Err(SocketError(socket_error))?,
-            (_, Err(websocket_error)) => Err(websocket_error),
-            (Ok(socket), Ok(websocket)) => {
-                println!("{SUCCESS_MESSAGE}");
-                Ok(WebsocketSocketProxy { socket, websocket })
-            }
-        }
-    }
-
-    pub async fn run(mut self) {
-        let (mut ws_reader, mut ws_writer) = self.websocket.socket.split();
-        let (mut reader, mut writer) = self.socket.split();
-        let (mut reader, mut writer) = (reader.compat_mut(), writer.compat_mut());
-        let incoming = futures_util::io::copy(&mut ws_reader, &mut writer);
-        let outgoing = futures_util::io::copy(&mut reader, &mut ws_writer);
-        {
-            futures::pin_mut!(incoming);
-            futures::pin_mut!(outgoing);
-
-            select(incoming, outgoing).await;
-        }
-        println!("STOPPING");
-        let _ = join(ws_writer.close(), writer.close()).await;
-    }
+/// Open the websocket Cumulocity connects to for a remote access session
+///
+/// The returned stream forwards to the websocket any data written to it,
+/// and returns the data received from the websocket.
+pub async fn connect_websocket(
+    url: &Url,
+    auth: Auth,
+    config: Option<ClientConfig>,
+    proxy: &TEdgeConfigReaderProxy,
+) -> miette::Result<impl AsyncRead + AsyncWrite + Unpin + Send + 'static> {
+    let websocket = Websocket::new(url, auth.authorization_header(), config, proxy).await?;
+    Ok(websocket.socket.compat())
 }
 
 struct Websocket {
@@ -242,8 +197,15 @@ mod tests {
     use http::StatusCode;
     use sha1::Digest;
     use tedge_config::TEdgeConfig;
+    use tedge_remote_access::AuditLog;
+    use tedge_remote_access::SessionEnd;
+    use tedge_remote_access::SessionStats;
+    use tedge_remote_access::TunnelPolicy;
+    use tedge_remote_access::TunnelRequest;
+    use tedge_remote_access::TunnelService;
     use tokio::io::AsyncReadExt;
     use tokio::io::AsyncWriteExt;
+    use tokio::task::JoinHandle;
 
     use super::*;
 
@@ -332,18 +294,9 @@ mod tests {
         tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
         let target = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
         let target_port = target.local_addr().unwrap().port();
-        let tedge_config = TEdgeConfig::load_toml_str("");
-        let proxy = WebsocketSocketProxy::connect(
-            &format!("ws://127.0.0.1:{axum_port}/ws").parse().unwrap(),
-            format!("127.0.0.1:{target_port}"),
-            Auth::test_value(HeaderValue::from_static("AUTHORIZATION HEADER")),
-            None,
-            &tedge_config.proxy,
-        )
-        .await
-        .unwrap();
+        let session = open_session(axum_port, target_port).await;
 
-        proxy.run().await;
+        session.await.unwrap();
     }
 
     #[tokio::test]
@@ -385,25 +338,41 @@ mod tests {
             data.read_to_string(&mut incoming).await.unwrap();
             assert_eq!(incoming, "ws->tcp");
         });
-        let tedge_config = TEdgeConfig::load_toml_str("");
 
         tokio::time::timeout(Duration::from_secs(5), async move {
-            let proxy = WebsocketSocketProxy::connect(
-                &format!("ws://127.0.0.1:{axum_port}/ws").parse().unwrap(),
-                format!("127.0.0.1:{target_port}"),
-                Auth::test_value(HeaderValue::from_static("AUTHORIZATION HEADER")),
-                None,
-                &tedge_config.proxy,
-            )
-            .await
-            .unwrap();
-            proxy.run().await;
+            let session = open_session(axum_port, target_port).await;
+            session.await.unwrap();
             assert_bidirectional_comms.await.unwrap();
         })
         .await
         .unwrap();
     }
 
+    async fn open_session(
+        axum_port: u16,
+        target_port: u16,
+    ) -> JoinHandle<(SessionEnd, SessionStats)> {
+        let tedge_config = TEdgeConfig::load_toml_str("");
+        let service = TunnelService::new(
+            TunnelPolicy::from_tedge_config(&tedge_config),
+            AuditLog::disabled(),
+        );
+        let url = format!("ws://127.0.0.1:{axum_port}/ws").parse().unwrap();
+        let websocket = connect_websocket(
+            &url,
+            Auth::test_value(HeaderValue::from_static("AUTHORIZATION HEADER")),
+            None,
+            &tedge_config.proxy,
+        );
+        let request = TunnelRequest {
+            session_id: "test-session".to_string(),
+            initiator: "test".to_string(),
+            host: "127.0.0.1".to_string(),
+            port: target_port,
+        };
+        service.open(request, websocket).await.unwrap()
+    }
+
     fn sign(key: &[u8]) -> HeaderValue {
         let mut sha1 = sha1::Sha1::default();
         sha1.update(key);